@@ -1,5 +1,36 @@
 use std::collections::HashMap;
 
+use crate::editor::Edge;
+use crate::syntax::Language;
+
+/// How a language is indented: with tabs, or `width` spaces per level.
+/// `width` is also how many columns a tab takes on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Indent {
+    pub use_tabs: bool,
+    pub width: usize,
+}
+
+impl Indent {
+    /// `tabs` or how many spaces, for the status line and `:set indent`
+    pub fn name(&self) -> String {
+        if self.use_tabs {
+            "tabs".to_string()
+        } else {
+            format!("spaces:{}", self.width)
+        }
+    }
+
+    /// One level of indentation
+    pub fn unit(&self) -> String {
+        if self.use_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.width)
+        }
+    }
+}
+
 /// Editor settings that can be customized via Rhai config
 #[derive(Debug, Clone)]
 pub struct Settings {
@@ -9,17 +40,59 @@ pub struct Settings {
     pub relative_line_numbers: bool,
     pub tab_width: usize,
     pub show_whitespace: bool,
+    pub cursor_line: bool,           // Highlight the line the cursor is on
+    pub color_column: usize,         // 1-based column to mark as a ruler, 0 for none
+    pub pane_status: bool,           // Name unfocused split panes on their last row
+    pub open_binary: bool,           // Show binary files' contents instead of a placeholder
+    pub large_file_size: usize, // Files this many bytes or bigger aren't highlighted (0: no limit)
+    pub auto_install_grammars: bool, // Install a missing grammar when a file needs it
+    pub mouse: bool,            // Click to focus and place the cursor, wheel to scroll
+    pub git_signs: bool,        // Mark lines changed since git HEAD in the sign column
+    pub auto_reload: bool,      // Reload files changed outside lark that have no unsaved changes
+    pub scroll_indicator: bool, // Show Top/Bot/All or how far down the view is in the status line
+    pub scroll_off: usize,      // Lines kept on screen above and below the cursor
+    pub side_scroll_off: usize, // Columns kept on screen either side of the cursor
 
     // Editing
     pub auto_indent: bool,
-    pub insert_spaces: bool, // Use spaces instead of tabs
+    pub auto_pairs: bool,       // Close brackets and quotes as they're typed
+    pub insert_spaces: bool,    // Use spaces instead of tabs
+    pub system_clipboard: bool, // Plain yanks and pastes use the system clipboard ("+)
+    pub smart_home: bool,       // 0 goes to the first non-blank, and to column 0 from there
+    pub ignore_case: bool,      // Searches match either case
+    pub smart_case: bool,       // Searches match either case unless the pattern has capitals
+
+    // Indentation per language, overriding the builtin ones and tab_width/insert_spaces
+    pub indents: HashMap<Language, Indent>,
+    pub detect_indent: bool, // Follow the indentation files already use when opening them
+
+    // Comment tokens for gc, overriding each language's own ("//", "<!-- -->")
+    pub comment_tokens: HashMap<Language, String>,
+
+    // Formatting: command lines that read source on stdin and write it to stdout
+    pub formatters: HashMap<Language, String>,
+    pub format_on_save: bool, // Run the buffer's formatter before :w
+    pub trim_trailing_whitespace: bool, // Strip spaces and tabs at line ends on :w
+    pub ensure_final_newline: bool, // End the file with a line break on :w
 
     // File browser
-    pub file_browser_width: u16,
+    pub file_browser_edge: Edge, // Side of the screen it opens along
+    pub file_browser_ratio: f32, // Share of the screen's width (or height, at the bottom) it takes
     pub show_hidden_files: bool,
 
+    // Finders
+    pub preview_command: String, // Shows {file} at {line} ("" for bat, or head without it)
+    pub finder_from_cwd: bool,   // Search the working directory, not the project root
+
+    // Key sequences
+    pub leader: char,    // Starts <leader> sequences (<leader>ff, ...)
+    pub timeout_ms: u64, // How long a partly typed sequence waits for its next key
+
     // Custom keybinds: key sequence -> action name
     pub keybinds: HashMap<String, String>,
+
+    // Command aliases: command name -> builtin command it runs
+    pub command_aliases: HashMap<String, String>,
 }
 
 impl Default for Settings {
@@ -30,14 +103,50 @@ impl Default for Settings {
             relative_line_numbers: true,
             tab_width: 4,
             show_whitespace: false,
+            cursor_line: false,
+            color_column: 0,
+            pane_status: true,
+            open_binary: false,
+            large_file_size: 10 * 1024 * 1024,
+            auto_install_grammars: false,
+            mouse: true,
+            git_signs: true,
+            auto_reload: true,
+            scroll_indicator: true,
+            scroll_off: 0,
+            side_scroll_off: 5,
 
             auto_indent: true,
+            auto_pairs: true,
             insert_spaces: true,
+            system_clipboard: false,
+            smart_home: false,
+            ignore_case: false,
+            smart_case: false,
+
+            indents: HashMap::new(),
+            detect_indent: true,
 
-            file_browser_width: 30,
+            comment_tokens: HashMap::new(),
+
+            formatters: HashMap::new(),
+            format_on_save: false,
+            trim_trailing_whitespace: false,
+            ensure_final_newline: false,
+
+            file_browser_edge: Edge::Left,
+            file_browser_ratio: 0.2,
             show_hidden_files: false,
 
+            preview_command: String::new(),
+            finder_from_cwd: false,
+
+            leader: ' ',
+            timeout_ms: 1000,
+
             keybinds: HashMap::new(),
+
+            command_aliases: HashMap::new(),
         }
     }
 }
@@ -46,4 +155,67 @@ impl Settings {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// How `language` is indented: its `indents` entry, else the convention
+    /// for languages that have a strong one, else tab_width and insert_spaces
+    pub fn indent(&self, language: Language) -> Indent {
+        if let Some(indent) = self.indents.get(&language) {
+            return *indent;
+        }
+        match language {
+            Language::Go => Indent {
+                use_tabs: true,
+                width: self.tab_width,
+            },
+            Language::Python => Indent {
+                use_tabs: false,
+                width: 4,
+            },
+            Language::Ruby | Language::Yaml => Indent {
+                use_tabs: false,
+                width: 2,
+            },
+            _ => Indent {
+                use_tabs: !self.insert_spaces,
+                width: self.tab_width,
+            },
+        }
+    }
+
+    /// The comment token `gc` uses for `language`, if it has one
+    pub fn comment_token(&self, language: Language) -> Option<String> {
+        match self.comment_tokens.get(&language) {
+            Some(token) => Some(token.clone()),
+            None => language.comment_token().map(str::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages_indent_by_their_override_then_convention_then_the_globals() {
+        let mut settings = Settings {
+            tab_width: 8,
+            ..Settings::default()
+        };
+        assert_eq!(settings.indent(Language::Go).unit(), "\t");
+        assert_eq!(settings.indent(Language::Go).width, 8);
+        assert_eq!(settings.indent(Language::Python).unit(), "    ");
+        assert_eq!(settings.indent(Language::Rust).unit(), " ".repeat(8));
+
+        settings.insert_spaces = false;
+        assert_eq!(settings.indent(Language::Rust).unit(), "\t");
+        settings.indents.insert(
+            Language::Python,
+            Indent {
+                use_tabs: true,
+                width: 3,
+            },
+        );
+        assert_eq!(settings.indent(Language::Python).unit(), "\t");
+        assert_eq!(settings.indent(Language::Python).width, 3);
+    }
 }
@@ -1,10 +1,116 @@
 use ropey::Rope;
-use std::{fs::File, io, path::PathBuf};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+use super::word_completion;
+
+/// Number of terminal cells a character occupies (CJK and emoji take 2)
+pub fn char_width(ch: char) -> usize {
+    // Control characters report no width; treat them as a single cell
+    ch.width().unwrap_or(1)
+}
+
+/// When the file at `path` was last modified, if it can be found out
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Cells a character takes when it starts at display column `at`; a tab
+/// reaches the next multiple of `tab_width`
+pub fn cell_width(ch: char, at: usize, tab_width: usize) -> usize {
+    if ch == '\t' {
+        let tab_width = tab_width.max(1);
+        tab_width - at % tab_width
+    } else {
+        char_width(ch)
+    }
+}
+
+/// Line ending style of a file; text is always stored with `\n` in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// Detect the style from the first line break in `text`
+    pub fn detect(text: &str) -> Self {
+        match text.find('\n') {
+            Some(i) if i > 0 && text.as_bytes()[i - 1] == b'\r' => LineEnding::Crlf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    /// Parse a `fileformat` name (`unix` or `dos`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unix" => Some(LineEnding::Lf),
+            "dos" => Some(LineEnding::Crlf),
+            _ => None,
+        }
+    }
+
+    /// The `fileformat` name, as used by `:set ff`
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "unix",
+            LineEnding::Crlf => "dos",
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// How a file is indented, as detected from its text or set with `:set indent=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces(usize), // Per level
+}
+
+impl IndentStyle {
+    /// Parse a `:set indent=` value: `tabs`, or a number of spaces
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tabs" => Some(IndentStyle::Tabs),
+            _ => match name.parse::<usize>() {
+                Ok(width @ 1..=16) => Some(IndentStyle::Spaces(width)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// How many indented lines `detect_indent` looks at
+const INDENT_SAMPLE_LINES: usize = 100;
+
+/// How much of a file is checked for NUL bytes when deciding if it's binary
+const BINARY_SNIFF_LEN: usize = 8000;
 
 pub struct Buffer {
     text: Rope,
     filepath: Option<PathBuf>,
+    scratch_name: Option<String>, // Shown instead of "[No Name]" until saved
+    binary: bool,                 // Loaded from a file that isn't UTF-8 text
+    read_only: bool,
     dirty: bool,
+    line_ending: LineEnding,
+    modified: Option<SystemTime>, // The file's mtime when last read or written
+    words: Option<Vec<String>>,   // For insert completion; dropped when the text changes
+    indent_style: Option<IndentStyle>, // Overrides the configured indentation when set
 }
 
 impl Buffer {
@@ -12,16 +118,58 @@ impl Buffer {
         Self {
             text: Rope::new(),
             filepath: None,
+            scratch_name: None,
+            binary: false,
+            read_only: false,
             dirty: false,
+            line_ending: LineEnding::Lf,
+            modified: None,
+            words: None,
+            indent_style: None,
         }
     }
 
-    pub fn from_file(path: PathBuf) -> Self {
-        let text = Rope::from_reader(File::open(&path).unwrap()).unwrap();
+    /// Load a file. Binary files (NUL bytes near the start, or not UTF-8) are
+    /// loaded lossily and marked read-only, since writing them back would
+    /// corrupt them.
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        let modified = modified_time(&path);
+        let bytes = fs::read(&path)?;
+        let binary = bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+            || std::str::from_utf8(&bytes).is_err();
+        let mut buffer = Self::from_raw(&String::from_utf8_lossy(&bytes));
+        buffer.filepath = Some(path);
+        buffer.binary = binary;
+        buffer.read_only = binary;
+        buffer.modified = modified;
+        Ok(buffer)
+    }
+
+    /// A buffer with no file holding `raw` (e.g. piped-in text), shown as
+    /// `name` until it's written somewhere
+    pub fn scratch(name: &str, raw: &str) -> Self {
+        let mut buffer = Self::from_raw(raw);
+        buffer.scratch_name = Some(name.to_string());
+        buffer
+    }
+
+    fn from_raw(raw: &str) -> Self {
+        let line_ending = LineEnding::detect(raw);
+        let text = match line_ending {
+            LineEnding::Crlf => Rope::from_str(&raw.replace("\r\n", "\n")),
+            LineEnding::Lf => Rope::from_str(raw),
+        };
         Self {
             text,
-            filepath: Some(path),
+            filepath: None,
+            scratch_name: None,
+            binary: false,
+            read_only: false,
             dirty: false,
+            line_ending,
+            modified: None,
+            words: None,
+            indent_style: None,
         }
     }
 
@@ -31,32 +179,198 @@ impl Buffer {
         Self {
             text: Rope::from_str(s),
             filepath: None,
+            scratch_name: None,
+            binary: false,
+            read_only: false,
             dirty: false,
+            line_ending: LineEnding::Lf,
+            modified: None,
+            words: None,
+            indent_style: None,
         }
     }
 
+    /// Save to `path` from now on (the text isn't written until `save`)
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.filepath = Some(path);
+    }
+
     pub fn path(&self) -> Option<&PathBuf> {
         self.filepath.as_ref()
     }
 
+    /// The file name, for tabs and status lines
+    pub fn display_name(&self) -> String {
+        match (&self.filepath, &self.scratch_name) {
+            (Some(path), _) => path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "[No Name]".to_string()),
+            (None, Some(name)) => name.clone(),
+            (None, None) => "[No Name]".to_string(),
+        }
+    }
+
+    /// Write the whole buffer to `path`, which it's then saved to from now on.
+    /// Binary buffers were loaded lossily, so they're never written anywhere.
+    pub fn save_as(&mut self, path: PathBuf) -> io::Result<()> {
+        if self.binary {
+            return Err(io::Error::other("Binary files can't be written"));
+        }
+        self.set_path(path);
+        self.dirty = true;
+        self.save()
+    }
+
+    /// Write the whole buffer to `path`, leaving its own file and modified flag alone
+    pub fn write_copy(&self, path: &Path) -> io::Result<()> {
+        if self.binary {
+            return Err(io::Error::other("Binary files can't be written"));
+        }
+        self.write_file(path)
+    }
+
     /// Get the full text as a String (for syntax highlighting)
     pub fn text(&self) -> String {
         self.text.to_string()
     }
 
-    pub fn save(&self) -> io::Result<()> {
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Change the line ending used when saving (marks the buffer modified)
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.line_ending != line_ending {
+            self.line_ending = line_ending;
+            self.dirty = true;
+        }
+    }
+
+    /// The indentation detected in the file or set for it, if any
+    pub fn indent_style(&self) -> Option<IndentStyle> {
+        self.indent_style
+    }
+
+    pub fn set_indent_style(&mut self, indent_style: Option<IndentStyle>) {
+        self.indent_style = indent_style;
+    }
+
+    /// Guess the indentation from the first indented lines: tabs if most
+    /// start with one, else the most common step in spaces between a line
+    /// and a more indented one after it. None without enough to go on.
+    pub fn detect_indent(&self) -> Option<IndentStyle> {
+        let mut tabs = 0;
+        let mut spaced = 0;
+        let mut steps = [0usize; 9]; // By width, for steps of 2 to 8 spaces
+        let mut previous = 0; // Spaces before the last non-blank line
+        for line in self.text.lines() {
+            let mut chars = line.chars().peekable();
+            let mut spaces: usize = 0;
+            while chars.next_if_eq(&' ').is_some() {
+                spaces += 1;
+            }
+            match chars.next() {
+                None | Some('\n' | '\r') => continue, // Blank
+                Some('\t') if spaces == 0 => tabs += 1,
+                Some(_) if spaces > 0 => {
+                    spaced += 1;
+                    if let Some(step) = spaces.checked_sub(previous)
+                        && (2..=8).contains(&step)
+                    {
+                        steps[step] += 1;
+                    }
+                }
+                Some(_) => {}
+            }
+            previous = spaces;
+            if tabs + spaced >= INDENT_SAMPLE_LINES {
+                break;
+            }
+        }
+
+        if tabs > 0 && tabs >= spaced {
+            return Some(IndentStyle::Tabs);
+        }
+        // The smaller step wins a tie
+        let width = (2..=8).rev().max_by_key(|&w| steps[w])?;
+        (steps[width] > 0).then_some(IndentStyle::Spaces(width))
+    }
+
+    /// Whether the file was binary when loaded (see `load`)
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    /// Whether editing commands are refused
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Whether the buffer has changes that haven't been written
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
         if !self.dirty {
             return Ok(());
         }
         if let Some(path) = &self.filepath {
-            let mut file = File::create(path)?;
-            self.text.write_to(&mut file)?;
+            self.write_file(path)?;
+            self.modified = modified_time(path);
+            self.dirty = false;
             Ok(())
         } else {
             Err(io::Error::new(io::ErrorKind::Other, "No file path"))
         }
     }
 
+    /// Whether the file has been written by something else since the buffer
+    /// last read or wrote it. A file that's gone hasn't changed.
+    pub fn changed_on_disk(&self) -> bool {
+        match (&self.filepath, self.modified) {
+            (Some(path), Some(seen)) => modified_time(path).is_some_and(|now| now != seen),
+            _ => false,
+        }
+    }
+
+    /// Take the file as it is on disk as seen, so `changed_on_disk` is false
+    /// until it's written again
+    pub fn acknowledge_disk_change(&mut self) {
+        if let Some(path) = &self.filepath {
+            self.modified = modified_time(path);
+        }
+    }
+
+    fn write_file(&self, path: &Path) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        match self.line_ending {
+            LineEnding::Lf => self.text.write_to(&mut file)?,
+            LineEnding::Crlf => {
+                for chunk in self.text.chunks() {
+                    let chunk = chunk.replace('\n', self.line_ending.as_str());
+                    file.write_all(chunk.as_bytes())?;
+                }
+            }
+        }
+        file.flush()
+    }
+
+    /// The text in pieces, without building one `String`
+    pub fn chunks(&self) -> ropey::iter::Chunks<'_> {
+        self.text.chunks()
+    }
+
+    /// Size of the text in bytes (with `\n` line breaks)
+    pub fn len_bytes(&self) -> usize {
+        self.text.len_bytes()
+    }
+
     pub fn line_count(&self) -> usize {
         self.text.len_lines()
     }
@@ -88,16 +402,250 @@ impl Buffer {
         Some(rope_line.char(col))
     }
 
+    /// The char columns where a line's grapheme clusters (what's drawn as one
+    /// character, e.g. a letter and its combining accents or a flag) start,
+    /// and the line length after the last
+    fn grapheme_boundaries(&self, line: usize) -> Vec<usize> {
+        let text: String = self
+            .text
+            .line(line)
+            .chars()
+            .take(self.line_len(line))
+            .collect();
+        let mut boundaries: Vec<usize> = Vec::new();
+        let mut col = 0;
+        for grapheme in text.graphemes(true) {
+            boundaries.push(col);
+            col += grapheme.chars().count();
+        }
+        boundaries.push(col);
+        boundaries
+    }
+
+    /// The first column of the grapheme cluster char `col` is part of
+    pub fn grapheme_start(&self, line: usize, col: usize) -> usize {
+        if line >= self.line_count() || col == 0 {
+            return col;
+        }
+        // Two ASCII chars other than \r\n are always separate clusters
+        let before = self.char_at(line, col - 1);
+        let at = self.char_at(line, col);
+        if before.is_some_and(|c| c.is_ascii() && c != '\r') && at.is_none_or(|c| c.is_ascii()) {
+            return col;
+        }
+        if col >= self.line_len(line) {
+            return col;
+        }
+        let boundaries = self.grapheme_boundaries(line);
+        boundaries
+            .into_iter()
+            .rev()
+            .find(|&b| b <= col)
+            .unwrap_or(0)
+    }
+
+    /// The column after the grapheme cluster at `col`, at most the line length
+    pub fn next_grapheme(&self, line: usize, col: usize) -> usize {
+        if line >= self.line_count() {
+            return col;
+        }
+        let boundaries = self.grapheme_boundaries(line);
+        let line_len = boundaries.last().copied().unwrap_or(0);
+        boundaries
+            .into_iter()
+            .find(|&b| b > col)
+            .unwrap_or(line_len)
+    }
+
+    /// The first column of the grapheme cluster before `col`, or 0
+    pub fn prev_grapheme(&self, line: usize, col: usize) -> usize {
+        if line >= self.line_count() {
+            return col.saturating_sub(1);
+        }
+        let boundaries = self.grapheme_boundaries(line);
+        boundaries.into_iter().rev().find(|&b| b < col).unwrap_or(0)
+    }
+
+    /// Display column (terminal cells from the start of the line) of char `col`
+    pub fn display_col(&self, line: usize, col: usize, tab_width: usize) -> usize {
+        if line >= self.line_count() {
+            return 0;
+        }
+        let end = col.min(self.line_len(line));
+        self.text
+            .line(line)
+            .chars()
+            .take(end)
+            .fold(0, |cells, ch| cells + cell_width(ch, cells, tab_width))
+    }
+
+    /// The char column drawn over display cell `cells` of a line (the inverse of
+    /// `display_col`), or the line length when the cell is past its end
+    pub fn col_at_display(&self, line: usize, cells: usize, tab_width: usize) -> usize {
+        if line >= self.line_count() {
+            return 0;
+        }
+        let line_len = self.line_len(line);
+        let mut end = 0;
+        for (col, ch) in self.text.line(line).chars().take(line_len).enumerate() {
+            end += cell_width(ch, end, tab_width);
+            if cells < end {
+                return col;
+            }
+        }
+        line_len
+    }
+
+    /// Display width in terminal cells of the chars in `start..end` on a line
+    pub fn display_width(&self, line: usize, start: usize, end: usize, tab_width: usize) -> usize {
+        let end_col = self.display_col(line, end, tab_width);
+        end_col.saturating_sub(self.display_col(line, start, tab_width))
+    }
+
     /// Convert (line, col) to a char index in the rope
     fn line_col_to_char(&self, line: usize, col: usize) -> usize {
         self.text.line_to_char(line) + col
     }
 
+    /// Convert (line, col) to a byte offset in the text (used for syntax edits)
+    pub fn byte_offset(&self, line: usize, col: usize) -> usize {
+        self.text.char_to_byte(self.line_col_to_char(line, col))
+    }
+
+    /// Find the bracket matching the one under (or next after) the cursor on its line
+    ///
+    /// Scans the text with a nesting counter for the same bracket pair, so
+    /// brackets inside strings or comments are counted too.
+    pub fn matching_bracket(&self, line: usize, col: usize) -> Option<(usize, usize)> {
+        if line >= self.line_count() {
+            return None;
+        }
+        let line_len = self.line_len(line);
+        let (start_col, ch) = (col..line_len)
+            .map(|c| (c, self.text.line(line).char(c)))
+            .find(|(_, ch)| "(){}[]".contains(*ch))?;
+
+        let (open, close, forward) = match ch {
+            '(' => ('(', ')', true),
+            '[' => ('[', ']', true),
+            '{' => ('{', '}', true),
+            ')' => ('(', ')', false),
+            ']' => ('[', ']', false),
+            '}' => ('{', '}', false),
+            _ => return None,
+        };
+
+        let start = self.line_col_to_char(line, start_col);
+        let mut depth = 0usize;
+        let found = if forward {
+            self.text.chars_at(start).enumerate().find_map(|(i, c)| {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(start + i);
+                    }
+                }
+                None
+            })
+        } else {
+            let mut chars = self.text.chars_at(start + 1);
+            let mut idx = start + 1;
+            std::iter::from_fn(|| {
+                let c = chars.prev()?;
+                idx -= 1;
+                Some((idx, c))
+            })
+            .find_map(|(i, c)| {
+                if c == close {
+                    depth += 1;
+                } else if c == open {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+                None
+            })
+        }?;
+
+        let found_line = self.text.char_to_line(found);
+        Some((found_line, found - self.text.line_to_char(found_line)))
+    }
+
+    /// The blank line after the paragraph at or below `line` (`}`), or the last line.
+    /// Blank lines are empty ones; a line of spaces is part of a paragraph.
+    pub fn paragraph_forward(&self, line: usize) -> usize {
+        let last = self.line_count() - 1;
+        let mut line = line.min(last);
+        while line < last && self.line_len(line) == 0 {
+            line += 1;
+        }
+        while line < last && self.line_len(line) > 0 {
+            line += 1;
+        }
+        line
+    }
+
+    /// The blank line before the paragraph at or above `line` (`{`), or the first line
+    pub fn paragraph_backward(&self, line: usize) -> usize {
+        let mut line = line.min(self.line_count() - 1);
+        while line > 0 && self.line_len(line) == 0 {
+            line -= 1;
+        }
+        while line > 0 && self.line_len(line) > 0 {
+            line -= 1;
+        }
+        line
+    }
+
+    /// Delete the chars between two (line, col) positions, returning the removed text
+    pub fn delete_range(&mut self, start: (usize, usize), end: (usize, usize)) -> String {
+        let from = self.line_col_to_char(start.0, start.1);
+        let to = self
+            .line_col_to_char(end.0, end.1)
+            .min(self.text.len_chars());
+        if from >= to {
+            return String::new();
+        }
+        let removed = self.text.slice(from..to).to_string();
+        self.text.remove(from..to);
+        self.dirty = true;
+        self.words = None;
+        removed
+    }
+
+    /// The distinct words in the buffer, sorted, for insert completion
+    pub fn words(&mut self) -> &[String] {
+        self.words
+            .get_or_insert_with(|| word_completion::words(self.text.chars()))
+    }
+
+    /// Insert text at the given position
+    pub fn insert_text(&mut self, line: usize, col: usize, text: &str) {
+        let idx = self.line_col_to_char(line, col);
+        self.text.insert(idx, text);
+        self.dirty = true;
+        self.words = None;
+    }
+
+    /// Column of the first non-whitespace char on a line (the line length if blank)
+    pub fn first_non_blank(&self, line: usize) -> usize {
+        let len = self.line_len(line);
+        self.line(line)
+            .chars()
+            .take(len)
+            .position(|c| !c.is_whitespace())
+            .unwrap_or(len)
+    }
+
     /// Insert a character at the given position
     pub fn insert_char(&mut self, line: usize, col: usize, ch: char) {
         let idx = self.line_col_to_char(line, col);
         self.text.insert_char(idx, ch);
         self.dirty = true;
+        self.words = None;
     }
 
     /// Delete the character at the given position
@@ -106,6 +654,7 @@ impl Buffer {
         if idx < self.text.len_chars() {
             self.text.remove(idx..idx + 1);
             self.dirty = true;
+            self.words = None;
         }
     }
 
@@ -120,6 +669,7 @@ impl Buffer {
             if idx > 0 {
                 self.text.remove(idx - 1..idx);
                 self.dirty = true;
+                self.words = None;
                 return true;
             }
             false
@@ -142,10 +692,62 @@ mod tests {
         Buffer {
             text: Rope::from_str(s),
             filepath: None,
+            scratch_name: None,
+            binary: false,
+            read_only: false,
             dirty: false,
+            line_ending: LineEnding::Lf,
+            modified: None,
+            words: None,
+            indent_style: None,
         }
     }
 
+    #[test]
+    fn binary_files_are_read_only() {
+        let dir = std::env::temp_dir().join(format!("lark-binary-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("nul.bin"), b"ELF\0\0\x01").unwrap();
+        std::fs::write(dir.join("latin1.txt"), b"caf\xe9\n").unwrap();
+        std::fs::write(dir.join("text.txt"), "café\n").unwrap();
+
+        let nul = Buffer::load(dir.join("nul.bin")).unwrap();
+        let latin1 = Buffer::load(dir.join("latin1.txt")).unwrap();
+        let text = Buffer::load(dir.join("text.txt")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(nul.is_binary() && nul.is_read_only());
+        assert!(latin1.is_binary() && latin1.is_read_only());
+        assert!(!text.is_binary() && !text.is_read_only());
+    }
+
+    #[test]
+    fn binary_buffers_are_not_saved_elsewhere() {
+        let path = std::env::temp_dir().join(format!("lark-binary-copy-{}", std::process::id()));
+        let mut buffer = Buffer::from_raw("data\n");
+        buffer.binary = true;
+
+        assert!(buffer.write_copy(&path).is_err());
+        assert!(buffer.save_as(path.clone()).is_err());
+        assert!(buffer.path().is_none());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn scratch_buffer_is_named_until_saved() {
+        let path = std::env::temp_dir().join(format!("lark-scratch-{}.txt", std::process::id()));
+        let mut buffer = Buffer::scratch("[stdin]", "piped\r\ntext\r\n");
+        assert_eq!(buffer.display_name(), "[stdin]");
+        assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+
+        // Unmodified text is still written when given a path
+        buffer.save_as(path.clone()).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, "piped\r\ntext\r\n");
+        assert!(buffer.display_name().starts_with("lark-scratch-"));
+    }
+
     #[test]
     fn new_buffer_is_empty() {
         let buf = Buffer::new();
@@ -173,6 +775,125 @@ mod tests {
         assert_eq!(buf.line_len(1), 5); // "world" (no trailing \n)
     }
 
+    #[test]
+    fn display_width_counts_wide_chars_as_two_cells() {
+        let buf = buffer_from_str("a日本b\n");
+        assert_eq!(buf.display_width(0, 0, 4, 4), 6);
+        assert_eq!(buf.display_width(0, 1, 3, 4), 4);
+        assert_eq!(buf.display_width(0, 3, 10, 4), 1); // clamped to line length
+    }
+
+    #[test]
+    fn tabs_expand_to_the_next_tab_stop() {
+        let buf = buffer_from_str("\tab\tc\n");
+        assert_eq!(buf.display_col(0, 1, 4), 4);
+        assert_eq!(buf.display_col(0, 4, 4), 8); // `ab` then a 2-cell tab
+        assert_eq!(buf.display_width(0, 3, 4, 8), 6); // cells 10..16
+    }
+
+    #[test]
+    fn col_at_display_finds_the_char_covering_a_cell() {
+        let buf = buffer_from_str("\ta日b\n");
+        assert_eq!(buf.col_at_display(0, 0, 4), 0);
+        assert_eq!(buf.col_at_display(0, 3, 4), 0); // inside the tab
+        assert_eq!(buf.col_at_display(0, 4, 4), 1);
+        assert_eq!(buf.col_at_display(0, 6, 4), 2); // right half of 日
+        assert_eq!(buf.col_at_display(0, 7, 4), 3);
+        assert_eq!(buf.col_at_display(0, 20, 4), 4); // past the end
+    }
+
+    #[test]
+    fn crlf_files_round_trip() {
+        let path = std::env::temp_dir().join(format!("lark-crlf-{}.txt", std::process::id()));
+        std::fs::write(&path, "one\r\ntwo\r\n").unwrap();
+
+        let mut buf = Buffer::load(path.clone()).unwrap();
+        assert_eq!(buf.line_ending(), LineEnding::Crlf);
+        assert_eq!(buf.line_len(0), 3); // no stray \r in memory
+        assert_eq!(buf.line(1).to_string(), "two\n");
+
+        buf.insert_newline(1, 3);
+        buf.insert_char(2, 0, 'x');
+        buf.save().unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved, "one\r\ntwo\r\nx\r\n");
+    }
+
+    #[test]
+    fn line_ending_detects_first_break() {
+        assert_eq!(LineEnding::detect("a\r\nb\n"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect("a\nb\r\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("no breaks"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn indentation_is_detected_from_the_first_indented_lines() {
+        let tabs = "fn main() {\n\tif x {\n\t\ty();\n\t}\n}\n";
+        assert_eq!(
+            buffer_from_str(tabs).detect_indent(),
+            Some(IndentStyle::Tabs)
+        );
+
+        let two = "a:\n  b:\n    c: 1\n\n    d: 2\n  e: 3\nf:\n  g: 4\n";
+        assert_eq!(
+            buffer_from_str(two).detect_indent(),
+            Some(IndentStyle::Spaces(2))
+        );
+
+        // Continuation lines and block comments don't outvote the indents
+        let four =
+            "/*\n * doc\n */\nfn f() {\n    g(a,\n      b);\n    if x {\n        y();\n    }\n}\n";
+        assert_eq!(
+            buffer_from_str(four).detect_indent(),
+            Some(IndentStyle::Spaces(4))
+        );
+
+        assert_eq!(buffer_from_str("flat\ntext\n").detect_indent(), None);
+        assert_eq!(IndentStyle::from_name("tabs"), Some(IndentStyle::Tabs));
+        assert_eq!(IndentStyle::from_name("3"), Some(IndentStyle::Spaces(3)));
+        assert_eq!(IndentStyle::from_name("0"), None);
+    }
+
+    #[test]
+    fn matching_bracket_handles_nesting_across_lines() {
+        let buf = buffer_from_str("fn f() {\n    if (a[0]) {}\n}\n");
+
+        assert_eq!(buf.matching_bracket(0, 7), Some((2, 0))); // { -> }
+        assert_eq!(buf.matching_bracket(2, 0), Some((0, 7))); // } -> {
+        assert_eq!(buf.matching_bracket(1, 7), Some((1, 12))); // ( -> ) past a[0]
+        assert_eq!(buf.matching_bracket(1, 11), Some((1, 9))); // ] -> [
+        // Not on a bracket: use the next one on the line
+        assert_eq!(buf.matching_bracket(0, 0), Some((0, 5)));
+    }
+
+    #[test]
+    fn paragraphs_are_separated_by_empty_lines() {
+        let buf = buffer_from_str("a\nb\n\n\nc\n  \nd\n");
+        let forward: Vec<_> = (0..8).map(|l| buf.paragraph_forward(l)).collect();
+        assert_eq!(forward, [2, 2, 7, 7, 7, 7, 7, 7]);
+        let backward: Vec<_> = (0..8).map(|l| buf.paragraph_backward(l)).collect();
+        assert_eq!(backward, [0, 0, 0, 0, 3, 3, 3, 3]);
+
+        // Without a trailing newline the last line is the end
+        let buf = buffer_from_str("a\n\nb\nc");
+        assert_eq!(buf.paragraph_forward(0), 1);
+        assert_eq!(buf.paragraph_forward(1), 3);
+        assert_eq!(buf.paragraph_forward(3), 3);
+        assert_eq!(buf.paragraph_backward(3), 1);
+    }
+
+    #[test]
+    fn matching_bracket_returns_none_when_unbalanced_or_absent() {
+        let buf = buffer_from_str("((a)\nb]\n");
+
+        assert_eq!(buf.matching_bracket(0, 0), None); // outer ( never closed
+        assert_eq!(buf.matching_bracket(0, 1), Some((0, 3)));
+        assert_eq!(buf.matching_bracket(1, 0), None); // next bracket is a stray ]
+        assert_eq!(buf.matching_bracket(0, 4), None); // past the last bracket
+    }
+
     #[test]
     fn line_len_handles_empty_lines() {
         let buf = buffer_from_str("hello\n\nworld");
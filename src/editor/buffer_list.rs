@@ -0,0 +1,196 @@
+//! Open-buffer registry
+//!
+//! Panes own the buffer they display. When a pane switches to another file or
+//! is closed, its buffer (with cursor and highlighting) is parked here so it
+//! can be brought back later with `:b` or `Ctrl-^` without losing edits.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::fold::Folds;
+use super::marks::Marks;
+use super::{Buffer, Cursor, SignKind};
+use crate::syntax::{Highlighter, Language};
+
+/// A buffer together with the view state a pane needs to display it
+pub struct BufferView {
+    pub buffer: Buffer,
+    pub cursor: Cursor,
+    pub scroll_offset: usize,
+    pub scroll_col: usize,
+    pub highlighter: Highlighter,
+    pub language: Language,
+    pub folds: Folds,
+    pub marks: Marks,
+    pub signs: HashMap<usize, SignKind>,
+    pub large_file: bool, // Too big to highlight, see `open`
+}
+
+impl BufferView {
+    /// Load a file from disk and set up its syntax highlighting, unless it's
+    /// at least `large_file_size` bytes (0 for no limit)
+    pub fn open(path: PathBuf, large_file_size: usize) -> io::Result<Self> {
+        let buffer = Buffer::load(path.clone())?;
+        let large_file = large_file_size > 0 && buffer.len_bytes() >= large_file_size;
+        let mut highlighter = Highlighter::new();
+        let language = Language::from_path(&path);
+        if !large_file && highlighter.set_language(language) {
+            highlighter.parse(&buffer.text());
+        }
+
+        Ok(Self {
+            buffer,
+            cursor: Cursor::new(),
+            scroll_offset: 0,
+            scroll_col: 0,
+            highlighter,
+            language,
+            folds: Folds::new(),
+            marks: Marks::new(),
+            signs: HashMap::new(),
+            large_file,
+        })
+    }
+}
+
+/// Buffers that are open but not shown in any pane, keyed by path
+#[derive(Default)]
+pub struct BufferList {
+    hidden: HashMap<PathBuf, BufferView>,
+    order: Vec<PathBuf>, // Every path opened this session, for stable `:ls` numbers
+}
+
+impl BufferList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note that a path has been opened (gives it a buffer number)
+    pub fn register(&mut self, path: &Path) {
+        if !self.order.iter().any(|p| p == path) {
+            self.order.push(path.to_path_buf());
+        }
+    }
+
+    /// Park a buffer that's no longer displayed; unnamed buffers are dropped.
+    /// A parked buffer with unsaved changes is never replaced.
+    pub fn park(&mut self, view: BufferView) {
+        let Some(path) = view.buffer.path().cloned() else {
+            return;
+        };
+        self.register(&path);
+        if self
+            .hidden
+            .get(&path)
+            .is_some_and(|kept| kept.buffer.is_dirty())
+        {
+            return;
+        }
+        self.hidden.insert(path, view);
+    }
+
+    /// Take a parked buffer back out to display it
+    pub fn take(&mut self, path: &Path) -> Option<BufferView> {
+        self.hidden.remove(path)
+    }
+
+    /// A parked buffer, if this path isn't displayed anywhere
+    pub fn hidden(&self, path: &Path) -> Option<&BufferView> {
+        self.hidden.get(path)
+    }
+
+    /// All known paths with their 1-based buffer numbers
+    pub fn numbered(&self) -> impl Iterator<Item = (usize, &PathBuf)> {
+        self.order.iter().enumerate().map(|(i, p)| (i + 1, p))
+    }
+
+    /// Whether any parked buffer has unsaved changes
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.hidden.values().any(|v| v.buffer.is_dirty())
+    }
+
+    /// Resolve `:b` arguments: a buffer number or a unique part of a path
+    pub fn find(&self, query: &str) -> Result<PathBuf, String> {
+        if let Ok(n) = query.parse::<usize>() {
+            return self
+                .order
+                .get(n.wrapping_sub(1))
+                .cloned()
+                .ok_or_else(|| format!("No buffer {}", n));
+        }
+
+        let matches: Vec<&PathBuf> = self
+            .order
+            .iter()
+            .filter(|p| p.to_string_lossy().contains(query))
+            .collect();
+        match matches.as_slice() {
+            [path] => Ok((*path).clone()),
+            [] => Err(format!("No matching buffer for {}", query)),
+            _ => Err(format!("More than one match for {}", query)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view_for(path: &str) -> BufferView {
+        let mut buffer = Buffer::from_text("text\n");
+        buffer.set_path(PathBuf::from(path));
+        BufferView {
+            buffer,
+            cursor: Cursor::new(),
+            scroll_offset: 0,
+            scroll_col: 0,
+            highlighter: Highlighter::new(),
+            language: Language::Unknown,
+            folds: Folds::new(),
+            marks: Marks::new(),
+            signs: HashMap::new(),
+            large_file: false,
+        }
+    }
+
+    #[test]
+    fn park_and_take_round_trip() {
+        let mut list = BufferList::new();
+        let mut view = view_for("src/main.rs");
+        view.cursor.line = 3;
+        list.park(view);
+
+        assert!(list.hidden(Path::new("src/main.rs")).is_some());
+        let view = list.take(Path::new("src/main.rs")).unwrap();
+        assert_eq!(view.cursor.line, 3);
+        assert!(list.hidden(Path::new("src/main.rs")).is_none());
+    }
+
+    #[test]
+    fn parking_never_replaces_unsaved_changes() {
+        let mut list = BufferList::new();
+        let mut edited = view_for("src/main.rs");
+        edited.buffer.insert_char(0, 0, 'x');
+        list.park(edited);
+        list.park(view_for("src/main.rs"));
+
+        let kept = list.hidden(Path::new("src/main.rs")).unwrap();
+        assert_eq!(kept.buffer.line(0).to_string(), "xtext\n");
+        assert!(list.has_unsaved_changes());
+    }
+
+    #[test]
+    fn find_by_number_or_unique_name() {
+        let mut list = BufferList::new();
+        list.register(Path::new("src/main.rs"));
+        list.register(Path::new("src/lib.rs"));
+        list.register(Path::new("src/main.rs")); // keeps its number
+
+        assert_eq!(list.find("2"), Ok(PathBuf::from("src/lib.rs")));
+        assert_eq!(list.find("main"), Ok(PathBuf::from("src/main.rs")));
+        assert!(list.find("src").is_err()); // ambiguous
+        assert!(list.find("3").is_err());
+        assert!(list.find("0").is_err());
+    }
+}
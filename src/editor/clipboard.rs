@@ -0,0 +1,140 @@
+//! The system clipboard, read and written through the `"+` and `"*` registers
+//!
+//! Support is behind the `clipboard` cargo feature. Without it, or where
+//! there's no clipboard to talk to (a headless Linux box with no X11 or
+//! Wayland server), the registers keep their text inside the editor and the
+//! reason is reported once.
+
+pub struct Clipboard {
+    #[cfg(feature = "clipboard")]
+    system: Option<arboard::Clipboard>, // Connected on first use; kept so X11 can serve our text
+    unavailable: bool,
+    warning: Option<String>, // Why the clipboard can't be used, until taken
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "clipboard")]
+            system: None,
+            unavailable: false,
+            warning: None,
+        }
+    }
+
+    /// The clipboard's text, or None if it can't be read
+    pub fn get(&mut self) -> Option<String> {
+        let result = self.connect()?.get_text().map_err(|e| e.to_string());
+        self.check(result)
+    }
+
+    /// Put `text` on the clipboard, returning whether that worked
+    pub fn set(&mut self, text: &str) -> bool {
+        let Some(system) = self.connect() else {
+            return false;
+        };
+        let result = system.set_text(text).map_err(|e| e.to_string());
+        self.check(result).is_some()
+    }
+
+    /// Why the clipboard can't be used, the first time it fails
+    pub fn take_warning(&mut self) -> Option<String> {
+        self.warning.take()
+    }
+
+    fn check<T>(&mut self, result: Result<T, String>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.fail(e);
+                None
+            }
+        }
+    }
+
+    /// Stop using the clipboard for the rest of the session
+    fn fail(&mut self, reason: String) {
+        if !self.unavailable {
+            self.unavailable = true;
+            self.warning = Some(format!(
+                "Clipboard unavailable ({}); using an internal register",
+                reason
+            ));
+        }
+    }
+
+    #[cfg(feature = "clipboard")]
+    fn connect(&mut self) -> Option<&mut arboard::Clipboard> {
+        if self.unavailable {
+            return None;
+        }
+        if self.system.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(system) => self.system = Some(system),
+                Err(e) => {
+                    self.fail(e.to_string());
+                    return None;
+                }
+            }
+        }
+        self.system.as_mut()
+    }
+
+    #[cfg(not(feature = "clipboard"))]
+    fn connect(&mut self) -> Option<&mut NoClipboard> {
+        self.fail("built without the clipboard feature".to_string());
+        None
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+impl Clipboard {
+    /// One that has already failed, so tests never touch the real clipboard
+    pub fn failed(reason: &str) -> Self {
+        let mut clipboard = Self::new();
+        clipboard.fail(reason.to_string());
+        clipboard
+    }
+}
+
+/// Stands in for the system clipboard in builds without it; never connected
+#[cfg(not(feature = "clipboard"))]
+pub struct NoClipboard;
+
+#[cfg(not(feature = "clipboard"))]
+impl NoClipboard {
+    fn get_text(&mut self) -> Result<String, String> {
+        Err(String::new())
+    }
+
+    fn set_text(&mut self, _text: &str) -> Result<(), String> {
+        Err(String::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_failed_clipboard_is_left_alone_and_warns_once() {
+        let mut clipboard = Clipboard::failed("no display");
+        assert!(!clipboard.set("text"));
+        assert_eq!(clipboard.get(), None);
+        assert_eq!(
+            clipboard.take_warning().as_deref(),
+            Some("Clipboard unavailable (no display); using an internal register")
+        );
+
+        // Later failures don't warn again
+        clipboard.fail("still no display".to_string());
+        assert_eq!(clipboard.get(), None);
+        assert_eq!(clipboard.take_warning(), None);
+    }
+}
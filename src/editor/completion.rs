@@ -0,0 +1,217 @@
+//! Tab completion on the `:` command line
+//!
+//! What's completed depends on the command typed so far: command names for
+//! the first word, then theme names after `:theme`, grammar names after
+//! `:TSInstall` and `:TSUninstall`, and file paths after commands that open
+//! or write a file. Repeated Tabs cycle through the matches.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::syntax::Language;
+
+/// Commands offered when completing the first word, by their full names
+const COMMANDS: &[&str] = &[
+    "TSDebug",
+    "TSInstall",
+    "TSList",
+    "TSStatus",
+    "TSUninstall",
+    "TSUpdate",
+    "TrimWhitespace",
+    "buffer",
+    "buffers",
+    "close",
+    "config",
+    "edit",
+    "format",
+    "log",
+    "messages",
+    "nohlsearch",
+    "only",
+    "quit",
+    "quitall",
+    "recent",
+    "saveas",
+    "set",
+    "source",
+    "split",
+    "symbols",
+    "syntax",
+    "theme",
+    "themes",
+    "verbose",
+    "view",
+    "vsplit",
+    "wq",
+    "write",
+];
+
+/// Commands whose argument is a file path
+const PATH_COMMANDS: &[&str] = &[
+    "e", "edit", "view", "vie", "w", "write", "w!", "write!", "saveas", "sav", "saveas!", "sav!",
+    "vs", "vsplit", "sp", "split",
+];
+
+/// Matches for the word before the cursor, while Tab cycles through them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub start: usize, // Byte offset in the command line of the word being completed
+    pub candidates: Vec<String>,
+    pub selected: usize,
+}
+
+impl Completion {
+    /// The matches for the end of `line` (the text before the cursor), or
+    /// None if there are none. `commands` are extra names to offer, e.g.
+    /// ones defined in the config script.
+    pub fn new(line: &str, commands: &[String]) -> Option<Self> {
+        let start = line.rfind(' ').map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let command = line.split(' ').next().unwrap_or("");
+
+        let mut candidates: Vec<String> = if start == 0 {
+            let names = COMMANDS.iter().map(|c| c.to_string());
+            names.chain(commands.iter().cloned()).collect()
+        } else if start != command.len() + 1 {
+            Vec::new() // Only a command's first argument is completed
+        } else if command == "theme" {
+            crate::theme::list_themes()
+        } else if command == "TSInstall" || command == "TSUninstall" {
+            let languages = Language::all_installable().into_iter();
+            let mut names: Vec<_> = languages
+                .filter_map(|l| l.grammar_name().map(String::from))
+                .collect();
+            if command == "TSInstall" {
+                names.push("all".to_string());
+            }
+            names
+        } else if PATH_COMMANDS.contains(&command) {
+            paths(word)
+        } else {
+            Vec::new()
+        };
+        candidates.retain(|c| c.starts_with(word));
+        candidates.sort();
+        candidates.dedup();
+
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(Self {
+            start,
+            candidates,
+            selected: 0,
+        })
+    }
+
+    /// The match to put in the line
+    pub fn current(&self) -> &str {
+        &self.candidates[self.selected]
+    }
+
+    /// Move to the next match, or the previous one if `!forward`
+    pub fn step(&mut self, forward: bool) {
+        let count = self.candidates.len();
+        self.selected = if forward {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+}
+
+/// Entries of the directory `partial` is in that it could be the start of,
+/// as `partial` would be completed (directories end in `/`). Hidden files are
+/// left out unless the name being typed starts with a dot.
+fn paths(partial: &str) -> Vec<String> {
+    let (dir, name) = match partial.rfind('/') {
+        Some(i) => partial.split_at(i + 1),
+        None => ("", partial),
+    };
+    let listed = if dir.is_empty() {
+        PathBuf::from(".")
+    } else if let Some(rest) = dir.strip_prefix("~/") {
+        match dirs::home_dir() {
+            Some(home) => home.join(rest),
+            None => return Vec::new(),
+        }
+    } else {
+        PathBuf::from(dir)
+    };
+
+    let Ok(entries) = fs::read_dir(&listed) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if !file_name.starts_with(name)
+                || (file_name.starts_with('.') && !name.starts_with('.'))
+            {
+                return None;
+            }
+            let slash = if is_dir(&entry.path()) { "/" } else { "" };
+            Some(format!("{}{}{}", dir, file_name, slash))
+        })
+        .collect()
+}
+
+/// Whether `path` is a directory, following symlinks
+fn is_dir(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(line: &str) -> Vec<String> {
+        Completion::new(line, &["Greet".to_string()])
+            .map(|c| c.candidates)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn the_command_decides_what_is_completed() {
+        assert_eq!(candidates("the"), ["theme", "themes"]);
+        assert_eq!(candidates("Gr"), ["Greet"]);
+        assert_eq!(candidates("TSIn"), ["TSInstall"]);
+        assert!(candidates("theme ").contains(&"gruvbox-dark".to_string()));
+        assert!(candidates("TSInstall r").contains(&"rust".to_string()));
+        assert!(candidates("theme gruvbox-dark x").is_empty());
+        assert!(candidates("messages ").is_empty());
+
+        let dir = std::env::temp_dir().join(format!("lark-complete-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("sample.txt"), "").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        let prefix = format!("e {}/", dir.display());
+        let all = candidates(&prefix);
+        let dotted = candidates(&format!("{}.", prefix));
+        let s = candidates(&format!("{}s", prefix));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dir = dir.display();
+        assert_eq!(
+            all,
+            [format!("{}/sample.txt", dir), format!("{}/src/", dir)]
+        );
+        assert_eq!(dotted, [format!("{}/.hidden", dir)]);
+        assert_eq!(s.len(), 2);
+    }
+
+    #[test]
+    fn tab_cycles_both_ways() {
+        let mut completion = Completion::new("the", &[]).unwrap();
+        assert_eq!(completion.start, 0);
+        assert_eq!(completion.current(), "theme");
+        completion.step(true);
+        assert_eq!(completion.current(), "themes");
+        completion.step(true);
+        assert_eq!(completion.current(), "theme");
+        completion.step(false);
+        assert_eq!(completion.current(), "themes");
+    }
+}
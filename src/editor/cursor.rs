@@ -1,3 +1,5 @@
+use super::Buffer;
+
 #[derive(Debug, Clone, Default)]
 pub struct Cursor {
     pub line: usize,
@@ -9,15 +11,15 @@ impl Cursor {
         Self { line: 0, col: 0 }
     }
 
-    pub fn move_left(&mut self) {
-        if self.col > 0 {
-            self.col -= 1;
-        }
+    /// Step back over one grapheme cluster (a char with its accents, or a
+    /// joined emoji), stopping at the start of the line
+    pub fn move_left(&mut self, buffer: &Buffer) {
+        self.col = buffer.prev_grapheme(self.line, self.col);
     }
 
-    pub fn move_right(&mut self, line_len: usize) {
-        self.col += 1;
-        self.col = self.col.clamp(0, line_len);
+    /// Step over one grapheme cluster, stopping at the end of the line
+    pub fn move_right(&mut self, buffer: &Buffer) {
+        self.col = buffer.next_grapheme(self.line, self.col);
     }
 
     pub fn move_up(&mut self) {
@@ -43,36 +45,59 @@ mod tests {
         assert_eq!(cursor.col, 0);
     }
 
+    fn ten_chars() -> Buffer {
+        Buffer::from_text("0123456789\n")
+    }
+
     #[test]
     fn move_left_decrements_col() {
         let mut cursor = Cursor { line: 0, col: 5 };
-        cursor.move_left();
+        cursor.move_left(&ten_chars());
         assert_eq!(cursor.col, 4);
     }
 
     #[test]
     fn move_left_stops_at_zero() {
         let mut cursor = Cursor::new();
-        cursor.move_left();
+        cursor.move_left(&ten_chars());
         assert_eq!(cursor.col, 0);
     }
 
     #[test]
     fn move_right_increments_col() {
         let mut cursor = Cursor::new();
-        cursor.move_right(10);
+        cursor.move_right(&ten_chars());
         assert_eq!(cursor.col, 1);
     }
 
     #[test]
     fn move_right_clamps_to_line_len() {
         let mut cursor = Cursor { line: 0, col: 9 };
-        cursor.move_right(10);
+        cursor.move_right(&ten_chars());
         assert_eq!(cursor.col, 10);
-        cursor.move_right(10);
+        cursor.move_right(&ten_chars());
         assert_eq!(cursor.col, 10); // stays at max
     }
 
+    #[test]
+    fn left_and_right_step_over_whole_graphemes() {
+        // e + combining acute, a flag (two regional indicators), and a family
+        // joined with zero-width joiners
+        let buffer = Buffer::from_text(
+            "e\u{301}\u{1F1EB}\u{1F1F7}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}x\n",
+        );
+        let mut cursor = Cursor::new();
+        let mut cols = Vec::new();
+        for _ in 0..4 {
+            cursor.move_right(&buffer);
+            cols.push(cursor.col);
+        }
+        assert_eq!(cols, [2, 4, 9, 10]);
+        cursor.move_left(&buffer);
+        cursor.move_left(&buffer);
+        assert_eq!(cursor.col, 4);
+    }
+
     #[test]
     fn move_up_decrements_line() {
         let mut cursor = Cursor { line: 5, col: 0 };
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct FileEntry {
@@ -13,17 +13,26 @@ pub struct FileEntry {
 pub struct FileBrowser {
     pub entries: Vec<FileEntry>,
     pub selected: usize,
+    pub scroll_offset: usize, // First entry shown
+    viewport_height: usize,   // Entries that fit in the pane, from the last adjust_scroll
     pub root_dir: PathBuf,
+    pub show_hidden: bool, // Show dotfiles
     expanded: HashSet<PathBuf>,
 }
 
 impl FileBrowser {
     pub fn new() -> Self {
-        let root_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        Self::with_root(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+    }
+
+    pub fn with_root(root_dir: PathBuf) -> Self {
         let mut browser = Self {
             entries: Vec::new(),
             selected: 0,
+            scroll_offset: 0,
+            viewport_height: usize::MAX,
             root_dir,
+            show_hidden: false,
             expanded: HashSet::new(),
         };
         browser.refresh();
@@ -36,6 +45,7 @@ impl FileBrowser {
         if self.selected >= self.entries.len() {
             self.selected = self.entries.len().saturating_sub(1);
         }
+        self.scroll_offset = self.scroll_offset.min(self.selected);
     }
 
     fn build_tree(&mut self, dir: &PathBuf, depth: usize) {
@@ -53,10 +63,10 @@ impl FileBrowser {
                         depth,
                     }
                 })
-                .filter(|e| !e.name.starts_with('.'))
+                .filter(|e| self.show_hidden || !e.name.starts_with('.'))
                 .collect();
 
-            // Sort: directories first, then alphabetically
+            // Sort: directories first, then alphabetically (dotfiles included)
             entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
                 (true, false) => std::cmp::Ordering::Less,
                 (false, true) => std::cmp::Ordering::Greater,
@@ -81,12 +91,25 @@ impl FileBrowser {
         if self.selected > 0 {
             self.selected -= 1;
         }
+        self.adjust_scroll(self.viewport_height);
     }
 
     pub fn move_down(&mut self) {
         if self.selected + 1 < self.entries.len() {
             self.selected += 1;
         }
+        self.adjust_scroll(self.viewport_height);
+    }
+
+    /// Scroll so the selected entry is within a viewport of `viewport_height` entries
+    pub fn adjust_scroll(&mut self, viewport_height: usize) {
+        self.viewport_height = viewport_height.max(1);
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        }
+        if self.selected >= self.scroll_offset + self.viewport_height {
+            self.scroll_offset = self.selected - self.viewport_height + 1;
+        }
     }
 
     /// Toggle directory expansion or return file path
@@ -113,6 +136,110 @@ impl FileBrowser {
     pub fn is_expanded(&self, path: &PathBuf) -> bool {
         self.expanded.contains(path)
     }
+
+    /// Show or hide dotfiles, rebuilding the tree if that changes anything
+    pub fn set_show_hidden(&mut self, show: bool) {
+        if self.show_hidden != show {
+            self.show_hidden = show;
+            let selected = self.selected_entry().map(|e| e.path.clone());
+            self.refresh();
+            // Keep the same entry selected if it's still listed
+            if let Some(i) = selected.and_then(|p| self.entries.iter().position(|e| e.path == p)) {
+                self.selected = i;
+            }
+        }
+    }
+
+    pub fn selected_entry(&self) -> Option<&FileEntry> {
+        self.entries.get(self.selected)
+    }
+
+    // File operations
+
+    /// Directory new entries go in: the selected directory itself, or the
+    /// directory containing the selected file
+    fn target_dir(&self) -> PathBuf {
+        match self.selected_entry() {
+            Some(entry) if entry.is_dir => entry.path.clone(),
+            Some(entry) => entry
+                .path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| self.root_dir.clone()),
+            None => self.root_dir.clone(),
+        }
+    }
+
+    /// Create an empty file
+    pub fn create_file(&mut self, name: &str) -> Result<PathBuf, String> {
+        check_name(name)?;
+        let path = self.target_dir().join(name);
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| format!("Cannot create {}: {}", name, e))?;
+        self.reveal(&path);
+        Ok(path)
+    }
+
+    /// Create a directory
+    pub fn create_dir(&mut self, name: &str) -> Result<PathBuf, String> {
+        check_name(name)?;
+        let path = self.target_dir().join(name);
+        if path.exists() {
+            return Err(format!("Cannot create {}: already exists", name));
+        }
+        fs::create_dir(&path).map_err(|e| format!("Cannot create {}: {}", name, e))?;
+        self.reveal(&path);
+        Ok(path)
+    }
+
+    /// Rename the selected entry within its directory
+    pub fn rename_selected(&mut self, new_name: &str) -> Result<PathBuf, String> {
+        check_name(new_name)?;
+        let entry = self.selected_entry().ok_or("Nothing selected")?.clone();
+        let parent = entry.path.parent().unwrap_or(&self.root_dir);
+        let new_path = parent.join(new_name);
+        if new_path.exists() {
+            return Err(format!("Cannot rename to {}: already exists", new_name));
+        }
+        fs::rename(&entry.path, &new_path)
+            .map_err(|e| format!("Cannot rename {}: {}", entry.name, e))?;
+        if self.expanded.remove(&entry.path) {
+            self.expanded.insert(new_path.clone());
+        }
+        self.reveal(&new_path);
+        Ok(new_path)
+    }
+
+    /// Delete the selected entry (directories recursively)
+    pub fn delete_selected(&mut self) -> Result<PathBuf, String> {
+        let entry = self.selected_entry().ok_or("Nothing selected")?.clone();
+        let result = if entry.is_dir {
+            fs::remove_dir_all(&entry.path)
+        } else {
+            fs::remove_file(&entry.path)
+        };
+        result.map_err(|e| format!("Cannot delete {}: {}", entry.name, e))?;
+        self.expanded.remove(&entry.path);
+        self.refresh();
+        Ok(entry.path)
+    }
+
+    /// Refresh, expanding the directories leading to `path` and selecting it
+    fn reveal(&mut self, path: &Path) {
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&self.root_dir) || dir == self.root_dir {
+                break;
+            }
+            self.expanded.insert(dir.to_path_buf());
+        }
+        self.refresh();
+        if let Some(i) = self.entries.iter().position(|e| e.path == path) {
+            self.selected = i;
+        }
+    }
 }
 
 impl Default for FileBrowser {
@@ -120,3 +247,134 @@ impl Default for FileBrowser {
         Self::new()
     }
 }
+
+/// Names typed into the browser's prompts stay in the directory they're
+/// typed for: no separators, `.`/`..` or absolute paths
+fn check_name(name: &str) -> Result<(), String> {
+    let single = Path::new(name).components().count() == 1;
+    let special = matches!(name, "" | "." | "..");
+    if special || !single || name.contains(['/', std::path::MAIN_SEPARATOR]) {
+        return Err(format!("Invalid name: {} (no paths)", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lark-fb-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn create_rename_and_delete_entries() {
+        let root = temp_root("ops");
+        let mut browser = FileBrowser::with_root(root.clone());
+
+        let dir = browser.create_dir("src").unwrap();
+        assert_eq!(browser.selected_entry().unwrap().path, dir);
+
+        // With a directory selected, new files go inside it
+        let file = browser.create_file("main.rs").unwrap();
+        assert_eq!(file, root.join("src/main.rs"));
+        assert_eq!(browser.selected_entry().unwrap().path, file);
+        assert!(browser.create_file("main.rs").is_err()); // name collision from the file's dir
+
+        let renamed = browser.rename_selected("lib.rs").unwrap();
+        assert!(renamed.exists() && !file.exists());
+
+        browser.delete_selected().unwrap();
+        assert!(!renamed.exists());
+        assert!(dir.exists());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn names_cannot_leave_the_directory() {
+        let root = temp_root("names");
+        let mut browser = FileBrowser::with_root(root.join("inner"));
+        fs::create_dir_all(root.join("inner")).unwrap();
+        fs::write(root.join("inner/file.txt"), "").unwrap();
+        browser.refresh();
+        let outside = root.join("outside");
+
+        for name in ["../outside", "sub/file", "..", ".", ""] {
+            assert!(browser.create_file(name).is_err(), "{}", name);
+            assert!(browser.create_dir(name).is_err(), "{}", name);
+            assert!(browser.rename_selected(name).is_err(), "{}", name);
+        }
+        let absolute = outside.to_string_lossy();
+        assert!(browser.create_file(&absolute).is_err());
+        assert!(browser.create_dir(&absolute).is_err());
+        let left = outside.exists() || root.join("inner/sub").exists();
+        fs::remove_dir_all(root).unwrap();
+
+        assert!(!left);
+    }
+
+    #[test]
+    fn scrolling_keeps_selection_visible() {
+        let root = temp_root("scroll");
+        for i in 0..10 {
+            fs::write(root.join(format!("file{}.txt", i)), "").unwrap();
+        }
+        let mut browser = FileBrowser::with_root(root.clone());
+        browser.adjust_scroll(4);
+
+        for _ in 0..5 {
+            browser.move_down();
+        }
+        assert_eq!(browser.selected, 5);
+        assert_eq!(browser.scroll_offset, 2);
+
+        for _ in 0..4 {
+            browser.move_up();
+        }
+        assert_eq!(browser.selected, 1);
+        assert_eq!(browser.scroll_offset, 1);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn hidden_files_can_be_toggled() {
+        let root = temp_root("hidden");
+        fs::create_dir_all(root.join(".config")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join(".env"), "").unwrap();
+        fs::write(root.join("main.rs"), "").unwrap();
+        let mut browser = FileBrowser::with_root(root.clone());
+
+        let names = |b: &FileBrowser| b.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&browser), ["src", "main.rs"]);
+
+        browser.move_down();
+        browser.set_show_hidden(true);
+        assert_eq!(names(&browser), [".config", "src", ".env", "main.rs"]);
+        assert_eq!(browser.selected_entry().unwrap().name, "main.rs");
+
+        browser.set_show_hidden(false);
+        assert_eq!(names(&browser), ["src", "main.rs"]);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn rename_refuses_to_overwrite() {
+        let root = temp_root("rename");
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        let mut browser = FileBrowser::with_root(root.clone());
+
+        assert_eq!(browser.selected_entry().unwrap().name, "a.txt");
+        assert!(browser.rename_selected("b.txt").is_err());
+        assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "b");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+//! Code folding
+//!
+//! A pane keeps the folds that are currently closed. Foldable ranges come from
+//! the parse tree when a grammar is loaded and from indentation otherwise; a
+//! closed fold shows its first line and hides the rest.
+
+use super::Buffer;
+
+/// A range of lines that can be folded; `start` stays visible when closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize, // Last hidden line (inclusive)
+}
+
+impl Fold {
+    pub fn contains(&self, line: usize) -> bool {
+        line >= self.start && line <= self.end
+    }
+
+    /// Number of lines hidden when the fold is closed
+    pub fn hidden_lines(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// The closed folds of a pane
+#[derive(Debug, Clone, Default)]
+pub struct Folds {
+    closed: Vec<Fold>,
+}
+
+impl Folds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.closed.is_empty()
+    }
+
+    /// The outermost closed fold containing `line`
+    pub fn closed_at(&self, line: usize) -> Option<Fold> {
+        self.closed
+            .iter()
+            .filter(|f| f.contains(line))
+            .min_by_key(|f| (f.start, usize::MAX - f.end))
+            .copied()
+    }
+
+    /// Whether `line` is hidden inside a closed fold
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.closed_at(line).is_some_and(|f| f.start != line)
+    }
+
+    /// The first line of the closed fold hiding `line`, or `line` itself
+    pub fn visible_line(&self, line: usize) -> usize {
+        match self.closed_at(line) {
+            Some(fold) => fold.start,
+            None => line,
+        }
+    }
+
+    /// The next visible line after `line`, if there is one before `line_count`
+    pub fn next_visible(&self, line: usize, line_count: usize) -> Option<usize> {
+        let last = self.closed_at(line).map_or(line, |f| f.end);
+        (last + 1 < line_count).then_some(last + 1)
+    }
+
+    /// The visible line before `line`, if any
+    pub fn prev_visible(&self, line: usize) -> Option<usize> {
+        let first = self.visible_line(line);
+        (first > 0).then(|| self.visible_line(first - 1))
+    }
+
+    /// Number of screen rows from visible line `from` down to `to`
+    pub fn rows_between(&self, from: usize, to: usize) -> usize {
+        let (mut line, mut rows) = (self.visible_line(from), 0);
+        let to = self.visible_line(to);
+        while line < to {
+            match self.next_visible(line, usize::MAX) {
+                Some(next) => line = next,
+                None => break,
+            }
+            rows += 1;
+        }
+        rows
+    }
+
+    pub fn close(&mut self, fold: Fold) {
+        if !self.closed.contains(&fold) {
+            self.closed.push(fold);
+        }
+    }
+
+    /// Open the outermost closed fold containing `line`; returns false if there was none
+    pub fn open_at(&mut self, line: usize) -> bool {
+        match self.closed_at(line) {
+            Some(fold) => {
+                self.closed.retain(|f| *f != fold);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn open_all(&mut self) {
+        self.closed.clear();
+    }
+
+    /// Keep folds in place after lines `start..=old_end` became `start..=new_end`.
+    /// Folds whose hidden lines the edit touched are opened.
+    pub fn shift(&mut self, start: usize, old_end: usize, new_end: usize) {
+        self.closed.retain_mut(|fold| {
+            if old_end < fold.start {
+                fold.start = fold.start + new_end - old_end;
+                fold.end = fold.end + new_end - old_end;
+                true
+            } else {
+                // Edits within the visible first line leave the fold alone
+                start > fold.end || (start == fold.start && old_end == start && new_end == start)
+            }
+        });
+    }
+}
+
+/// Foldable ranges from indentation, for buffers without a grammar: a line
+/// folds the lines after it that are indented deeper (or blank)
+pub fn indent_ranges(buffer: &Buffer) -> Vec<Fold> {
+    let line_count = buffer.line_count();
+    let is_blank = |line: usize| buffer.first_non_blank(line) >= buffer.line_len(line);
+    // Only relative indentation matters, so any consistent tab width will do
+    let indent = |line: usize| buffer.display_col(line, buffer.first_non_blank(line), 8);
+
+    let mut ranges = Vec::new();
+    for start in 0..line_count {
+        if is_blank(start) {
+            continue;
+        }
+        let base = indent(start);
+        let mut end = start;
+        for line in start + 1..line_count {
+            if is_blank(line) {
+                continue;
+            }
+            if indent(line) <= base {
+                break;
+            }
+            end = line;
+        }
+        if end > start {
+            ranges.push(Fold { start, end });
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indent_ranges_nest_and_skip_blank_lines() {
+        let buffer = Buffer::from_text("a:\n  b:\n    c\n\n  d\ne\n");
+        assert_eq!(
+            indent_ranges(&buffer),
+            vec![Fold { start: 0, end: 4 }, Fold { start: 1, end: 2 }]
+        );
+    }
+
+    #[test]
+    fn closed_folds_hide_lines_and_shift_with_edits() {
+        let mut folds = Folds::new();
+        folds.close(Fold { start: 2, end: 5 });
+        folds.close(Fold { start: 3, end: 4 });
+
+        assert!(!folds.is_hidden(2));
+        assert!(folds.is_hidden(3) && folds.is_hidden(5));
+        assert_eq!(folds.next_visible(2, 10), Some(6));
+        assert_eq!(folds.prev_visible(6), Some(2));
+        assert_eq!(folds.rows_between(0, 7), 4); // 0, 1, 2 (folded), 6, 7
+
+        // Two lines inserted above move the folds down
+        folds.shift(0, 0, 2);
+        assert_eq!(folds.closed_at(5), Some(Fold { start: 4, end: 7 }));
+
+        // Editing inside the outer fold opens both
+        folds.shift(6, 6, 6);
+        assert!(folds.is_empty());
+    }
+}
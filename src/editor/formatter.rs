@@ -0,0 +1,61 @@
+//! External formatters
+//!
+//! A formatter is a shell-free command line (e.g. `rustfmt --edition 2024`)
+//! that reads source on stdin and writes the formatted source to stdout.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Pipe `input` through `command`, returning its stdout, or its stderr on failure
+pub fn run(command: &str, input: &str) -> Result<String, String> {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        return Err("Formatter command is empty".to_string());
+    };
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    // Write from another thread so a formatter that streams output can't fill
+    // its stdout pipe while we're still blocked writing stdin
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("{} error: {}", program, e))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if stderr.is_empty() {
+            format!("{} exited with {}", program, output.status)
+        } else {
+            stderr
+        });
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("{} wrote invalid UTF-8", program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipes_text_through_the_command() {
+        assert_eq!(run("tr a-z A-Z", "hello\n").unwrap(), "HELLO\n");
+    }
+
+    #[test]
+    fn reports_failures() {
+        assert!(run("sh -c false", "x").unwrap_err().contains("exited with"));
+        assert!(run("lark-no-such-formatter", "x").is_err());
+        assert!(run("  ", "x").is_err());
+    }
+}
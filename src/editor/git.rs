@@ -0,0 +1,171 @@
+//! Git change markers: the lines of a file added, changed or deleted since
+//! HEAD, shown in the sign column
+//!
+//! Worked out from `git diff -U0` when a file is opened and each time it's
+//! saved. Files outside a repository (or without git installed) get no signs,
+//! and untracked files are all added.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use super::SignKind;
+
+/// Signs for the file at `path` as it is on disk, by 0-based line
+pub fn signs(path: &Path) -> HashMap<usize, SignKind> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return HashMap::new();
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let git = |args: &[&str]| -> Option<Output> {
+        Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .arg("--")
+            .arg(name)
+            .output()
+            .ok()
+    };
+
+    let tracked = git(&["ls-files", "--error-unmatch"]).is_some_and(|o| o.status.success());
+    if tracked {
+        // Fails only before the first commit, when everything is new
+        if let Some(output) = git(&["diff", "--no-color", "-U0", "HEAD"])
+            && output.status.success()
+        {
+            return parse_diff(&String::from_utf8_lossy(&output.stdout));
+        }
+    } else {
+        // Lists nothing for ignored files, and fails outside a repository
+        let untracked = git(&["ls-files", "--others", "--exclude-standard"]);
+        if !untracked.is_some_and(|o| o.status.success() && !o.stdout.is_empty()) {
+            return HashMap::new();
+        }
+    }
+
+    let text = std::fs::read(path).unwrap_or_default();
+    let lines = text.iter().filter(|&&b| b == b'\n').count()
+        + usize::from(!text.is_empty() && !text.ends_with(b"\n"));
+    (0..lines).map(|line| (line, SignKind::Added)).collect()
+}
+
+/// Signs from the hunk headers (`@@ -old,count +new,count @@`) of a `-U0` diff.
+/// Lines replacing old ones are changed and any extra are added; a deletion
+/// marks the line above it.
+fn parse_diff(diff: &str) -> HashMap<usize, SignKind> {
+    // A range without a count is one line long
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+
+    let mut signs = HashMap::new();
+    for header in diff.lines().filter_map(|l| l.strip_prefix("@@ -")) {
+        let Some((old, rest)) = header.split_once(" +") else {
+            continue;
+        };
+        let Some((new, _)) = rest.split_once(" @@") else {
+            continue;
+        };
+        let (Some((_, old_count)), Some((start, new_count))) = (range(old), range(new)) else {
+            continue;
+        };
+
+        if new_count == 0 {
+            signs.insert(start.saturating_sub(1), SignKind::Removed);
+            continue;
+        }
+        for i in 0..new_count {
+            let kind = if i < old_count {
+                SignKind::Changed
+            } else {
+                SignKind::Added
+            };
+            signs.insert(start - 1 + i, kind);
+        }
+    }
+    signs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunks_become_signs() {
+        let diff = "diff --git a/f b/f\n\
+                    --- a/f\n\
+                    +++ b/f\n\
+                    @@ -2 +2 @@ fn main() {\n\
+                    -old\n\
+                    +new\n\
+                    @@ -5,0 +6,2 @@\n\
+                    +one\n\
+                    +two\n\
+                    @@ -9,2 +11,0 @@\n\
+                    -gone\n\
+                    -gone\n\
+                    @@ -20,1 +18,3 @@\n";
+        let signs = parse_diff(diff);
+
+        let mut lines: Vec<_> = signs.into_iter().collect();
+        lines.sort_by_key(|&(line, _)| line);
+        assert_eq!(
+            lines,
+            [
+                (1, SignKind::Changed),
+                (5, SignKind::Added),
+                (6, SignKind::Added),
+                (10, SignKind::Removed),
+                (17, SignKind::Changed),
+                (18, SignKind::Added),
+                (19, SignKind::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn files_in_a_repository_are_diffed_against_head() {
+        let dir = std::env::temp_dir().join(format!("lark-git-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(["-c", "user.name=lark", "-c", "user.email=lark@example.com"])
+                .args(["-c", "commit.gpgsign=false"])
+                .args(args)
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        };
+        let file = dir.join("file.txt");
+        std::fs::write(&file, "a\nb\nc\n").unwrap();
+        let outside = signs(&file);
+
+        if !git(&["init", "-q"]) {
+            std::fs::remove_dir_all(&dir).unwrap();
+            return; // No git to test with
+        }
+        let untracked = signs(&file);
+        git(&["add", "file.txt"]);
+        git(&["commit", "-q", "-m", "first"]);
+        std::fs::write(&file, "a\nB\nc\nd\n").unwrap();
+        let changed = signs(&file);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(outside.is_empty());
+        assert_eq!(untracked.len(), 3);
+        assert!(untracked.values().all(|&kind| kind == SignKind::Added));
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed.get(&1), Some(&SignKind::Changed));
+        assert_eq!(changed.get(&3), Some(&SignKind::Added));
+    }
+}
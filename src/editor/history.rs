@@ -0,0 +1,135 @@
+//! Command-line history, persisted to `~/.config/lark/history`
+//!
+//! The file holds one command per line, oldest first. Up and Down step
+//! through the commands that start with what was typed before the first Up,
+//! as in Vim.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Most commands remembered
+const MAX_HISTORY: usize = 500;
+
+/// Commands run from the `:` line
+#[derive(Debug, Default)]
+pub struct CommandHistory {
+    store: Option<PathBuf>, // Where the history is saved; None keeps it in memory
+    entries: Vec<String>,
+    position: Option<usize>, // The entry being shown while stepping through
+    draft: String,           // What was typed before stepping started
+}
+
+impl CommandHistory {
+    /// The history saved in `~/.config/lark/history`
+    pub fn load() -> Self {
+        match dirs::home_dir() {
+            Some(home) => Self::from_file(home.join(".config").join("lark").join("history")),
+            None => Self::default(),
+        }
+    }
+
+    /// The history saved in `store`, which is created on the first `add`
+    pub fn from_file(store: PathBuf) -> Self {
+        let entries = fs::read_to_string(&store)
+            .map(|content| content.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+        Self {
+            store: Some(store),
+            entries,
+            ..Self::default()
+        }
+    }
+
+    /// Remember `command` as the most recent one and save the history
+    pub fn add(&mut self, command: &str) {
+        self.reset();
+        let command = command.trim();
+        if command.is_empty() || command.contains('\n') {
+            return;
+        }
+        self.entries.retain(|c| c != command);
+        self.entries.push(command.to_string());
+        let excess = self.entries.len().saturating_sub(MAX_HISTORY);
+        self.entries.drain(..excess);
+        self.save();
+    }
+
+    /// The next older command starting with the text typed before stepping
+    /// began, or None when there are no more
+    pub fn older(&mut self, typed: &str) -> Option<&str> {
+        let end = match self.position {
+            Some(position) => position,
+            None => {
+                self.draft = typed.to_string();
+                self.entries.len()
+            }
+        };
+        let found = self.entries[..end]
+            .iter()
+            .rposition(|c| c.starts_with(&self.draft))?;
+        self.position = Some(found);
+        Some(&self.entries[found])
+    }
+
+    /// The next newer matching command, or what was typed once past the newest.
+    /// None when not stepping through the history.
+    pub fn newer(&mut self) -> Option<&str> {
+        let position = self.position?;
+        let found = self.entries[position + 1..]
+            .iter()
+            .position(|c| c.starts_with(&self.draft))
+            .map(|i| position + 1 + i);
+        self.position = found;
+        match found {
+            Some(found) => Some(&self.entries[found]),
+            None => Some(&self.draft),
+        }
+    }
+
+    /// Stop stepping through the history, e.g. when the line is edited
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.draft.clear();
+    }
+
+    fn save(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        if let Some(dir) = store.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let content: String = self.entries.iter().map(|c| format!("{}\n", c)).collect();
+        let _ = fs::write(store, content);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stepping_matches_the_typed_prefix_and_history_persists() {
+        let dir = std::env::temp_dir().join(format!("lark-history-{}", std::process::id()));
+        let store = dir.join("history");
+
+        let mut history = CommandHistory::from_file(store.clone());
+        for command in ["w", "set number", "vs", "set wrap", "vs"] {
+            history.add(command);
+        }
+        let mut history = CommandHistory::from_file(store);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(history.entries, ["w", "set number", "set wrap", "vs"]);
+
+        assert_eq!(history.older("set"), Some("set wrap"));
+        assert_eq!(history.older("ignored"), Some("set number"));
+        assert_eq!(history.older(""), None);
+        assert_eq!(history.newer(), Some("set wrap"));
+        assert_eq!(history.newer(), Some("set"));
+        assert_eq!(history.newer(), None);
+
+        assert_eq!(history.older(""), Some("vs"));
+        history.reset();
+        assert_eq!(history.older(""), Some("vs"));
+    }
+}
@@ -1,5 +1,9 @@
 use super::pane::PaneId;
 
+/// How far a split may be resized towards either side
+const MIN_RATIO: f32 = 0.1;
+const MAX_RATIO: f32 = 0.9;
+
 /// Direction of a split
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SplitDirection {
@@ -7,6 +11,14 @@ pub enum SplitDirection {
     Vertical,   // panes side by side
 }
 
+/// An edge of the screen a pane can be added along the whole of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Left,
+    Right,
+    Bottom,
+}
+
 /// A rectangle representing a pane's screen area
 #[derive(Debug, Clone, Copy)]
 pub struct Rect {
@@ -55,6 +67,11 @@ impl Rect {
         );
         (left, right)
     }
+
+    /// Whether the cell at (x, y) is inside the rect
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
 }
 
 /// A node in the layout tree
@@ -105,6 +122,85 @@ impl LayoutNode {
         }
     }
 
+    /// Whether the pane is anywhere in this subtree
+    pub fn contains(&self, target_id: PaneId) -> bool {
+        match self {
+            LayoutNode::Pane(id) => *id == target_id,
+            LayoutNode::Split { first, second, .. } => {
+                first.contains(target_id) || second.contains(target_id)
+            }
+        }
+    }
+
+    /// Grow the pane's side of the innermost `direction` split enclosing it
+    /// by `delta` (negative to shrink), returning false if no such split
+    /// encloses it
+    pub fn resize_pane(
+        &mut self,
+        target_id: PaneId,
+        direction: SplitDirection,
+        delta: f32,
+    ) -> bool {
+        let LayoutNode::Split {
+            direction: split_direction,
+            ratio,
+            first,
+            second,
+        } = self
+        else {
+            return false;
+        };
+
+        let in_first = first.contains(target_id);
+        if !in_first && !second.contains(target_id) {
+            return false;
+        }
+        let child = if in_first { first } else { second };
+        if child.resize_pane(target_id, direction, delta) {
+            return true;
+        }
+        if *split_direction != direction {
+            return false;
+        }
+
+        // The ratio is the first child's share, so the second grows as it falls
+        let delta = if in_first { delta } else { -delta };
+        *ratio = (*ratio + delta).clamp(MIN_RATIO, MAX_RATIO);
+        true
+    }
+
+    /// Exchange the positions of two panes, leaving the tree's shape alone
+    pub fn swap_panes(&mut self, a: PaneId, b: PaneId) {
+        match self {
+            LayoutNode::Pane(id) if *id == a => *id = b,
+            LayoutNode::Pane(id) if *id == b => *id = a,
+            LayoutNode::Pane(_) => {}
+            LayoutNode::Split { first, second, .. } => {
+                first.swap_panes(a, b);
+                second.swap_panes(a, b);
+            }
+        }
+    }
+
+    /// Swap the children of the innermost split enclosing the pane, skipping
+    /// any split that holds `pinned`; returns false if there was none to rotate
+    pub fn rotate_split(&mut self, target_id: PaneId, pinned: Option<PaneId>) -> bool {
+        if !self.contains(target_id) {
+            return false;
+        }
+        let LayoutNode::Split { first, second, .. } = self else {
+            return false;
+        };
+        if first.rotate_split(target_id, pinned) || second.rotate_split(target_id, pinned) {
+            return true;
+        }
+        if pinned.is_some_and(|p| first.contains(p) || second.contains(p)) {
+            return false;
+        }
+        std::mem::swap(first, second);
+        true
+    }
+
     /// Remove a pane from the layout, returning the new root if it was removed
     pub fn remove_pane(self, target_id: PaneId) -> Option<LayoutNode> {
         match self {
@@ -287,7 +383,7 @@ impl Layout {
         }
     }
 
-    /// Add a pane to the left side of the entire layout
+    /// Add a pane to the left side of the entire layout, taking `ratio` of its width
     pub fn add_left_pane(&mut self, new_pane_id: PaneId, ratio: f32) {
         let old_root = std::mem::replace(&mut self.root, LayoutNode::Pane(0));
         self.root = LayoutNode::Split {
@@ -298,6 +394,61 @@ impl Layout {
         };
     }
 
+    /// Add a pane to the right side of the entire layout, taking `ratio` of its width
+    pub fn add_right_pane(&mut self, new_pane_id: PaneId, ratio: f32) {
+        let old_root = std::mem::replace(&mut self.root, LayoutNode::Pane(0));
+        self.root = LayoutNode::Split {
+            direction: SplitDirection::Vertical,
+            ratio: 1.0 - ratio,
+            first: Box::new(old_root),
+            second: Box::new(LayoutNode::Pane(new_pane_id)),
+        };
+    }
+
+    /// Add a pane across the bottom of the entire layout, taking `ratio` of its height
+    pub fn add_bottom_pane(&mut self, new_pane_id: PaneId, ratio: f32) {
+        let old_root = std::mem::replace(&mut self.root, LayoutNode::Pane(0));
+        self.root = LayoutNode::Split {
+            direction: SplitDirection::Horizontal,
+            ratio: 1.0 - ratio,
+            first: Box::new(old_root),
+            second: Box::new(LayoutNode::Pane(new_pane_id)),
+        };
+    }
+
+    /// Add a pane along `edge`, see `add_left_pane` and the others
+    pub fn add_edge_pane(&mut self, new_pane_id: PaneId, edge: Edge, ratio: f32) {
+        match edge {
+            Edge::Left => self.add_left_pane(new_pane_id, ratio),
+            Edge::Right => self.add_right_pane(new_pane_id, ratio),
+            Edge::Bottom => self.add_bottom_pane(new_pane_id, ratio),
+        }
+    }
+
+    /// Resize the split enclosing the pane, see `LayoutNode::resize_pane`
+    pub fn resize_pane(&mut self, pane_id: PaneId, direction: SplitDirection, delta: f32) -> bool {
+        self.root.resize_pane(pane_id, direction, delta)
+    }
+
+    /// Exchange the positions of two panes
+    pub fn swap_panes(&mut self, a: PaneId, b: PaneId) {
+        self.root.swap_panes(a, b);
+    }
+
+    /// Rotate the split enclosing the pane, see `LayoutNode::rotate_split`
+    pub fn rotate_split(&mut self, pane_id: PaneId, pinned: Option<PaneId>) -> bool {
+        self.root.rotate_split(pane_id, pinned)
+    }
+
+    /// Drop every pane but `pane_id`, which then fills the layout
+    pub fn keep_only(&mut self, pane_id: PaneId) -> bool {
+        if !self.root.contains(pane_id) {
+            return false;
+        }
+        self.root = LayoutNode::Pane(pane_id);
+        true
+    }
+
     /// Remove a pane from the layout
     pub fn remove_pane(&mut self, pane_id: PaneId) -> bool {
         if let Some(new_root) =
@@ -310,3 +461,165 @@ impl Layout {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(node: &LayoutNode) -> f32 {
+        match node {
+            LayoutNode::Split { ratio, .. } => *ratio,
+            LayoutNode::Pane(_) => panic!("expected a split"),
+        }
+    }
+
+    #[test]
+    fn resize_adjusts_the_innermost_enclosing_split() {
+        let mut layout = Layout::new(0);
+        layout.split_pane(0, 1, SplitDirection::Vertical);
+        layout.split_pane(1, 2, SplitDirection::Horizontal);
+
+        // Pane 2 is the second child of the inner split, so growing it
+        // lowers that split's ratio and leaves the outer one alone
+        assert!(layout.resize_pane(2, SplitDirection::Horizontal, 0.1));
+        let LayoutNode::Split { second, .. } = &layout.root else {
+            panic!("expected a split");
+        };
+        assert!((ratio(second) - 0.4).abs() < 1e-6);
+        assert_eq!(ratio(&layout.root), 0.5);
+
+        // Pane 0 sits directly under the root
+        assert!(layout.resize_pane(0, SplitDirection::Vertical, 0.2));
+        assert!((ratio(&layout.root) - 0.7).abs() < 1e-6);
+    }
+
+    #[test]
+    fn resize_skips_splits_the_other_way() {
+        let mut layout = Layout::new(0);
+        layout.split_pane(0, 1, SplitDirection::Vertical);
+        layout.split_pane(1, 2, SplitDirection::Horizontal);
+
+        // Widening pane 2 moves the side-by-side split above the stacked one
+        assert!(layout.resize_pane(2, SplitDirection::Vertical, 0.1));
+        let LayoutNode::Split { second, .. } = &layout.root else {
+            panic!("expected a split");
+        };
+        assert_eq!(ratio(second), 0.5);
+        assert!((ratio(&layout.root) - 0.4).abs() < 1e-6);
+
+        // Pane 0 isn't in any stacked split
+        assert!(!layout.resize_pane(0, SplitDirection::Horizontal, 0.1));
+    }
+
+    #[test]
+    fn resize_clamps_and_needs_a_split() {
+        let mut layout = Layout::new(0);
+        assert!(!layout.resize_pane(0, SplitDirection::Vertical, 0.1));
+
+        layout.split_pane(0, 1, SplitDirection::Vertical);
+        assert!(layout.resize_pane(0, SplitDirection::Vertical, 5.0));
+        assert_eq!(ratio(&layout.root), MAX_RATIO);
+        assert!(layout.resize_pane(0, SplitDirection::Vertical, -5.0));
+        assert_eq!(ratio(&layout.root), MIN_RATIO);
+        assert!(!layout.resize_pane(7, SplitDirection::Vertical, 0.1));
+    }
+
+    #[test]
+    fn swap_exchanges_leaves() {
+        let mut layout = Layout::new(0);
+        layout.split_pane(0, 1, SplitDirection::Vertical);
+        layout.swap_panes(0, 1);
+        assert_eq!(layout.pane_ids(), vec![1, 0]);
+
+        // With three panes the nested split keeps its shape
+        layout.split_pane(0, 2, SplitDirection::Horizontal);
+        layout.swap_panes(1, 2);
+        assert_eq!(layout.pane_ids(), vec![2, 0, 1]);
+        let LayoutNode::Split { second, .. } = &layout.root else {
+            panic!("expected a split");
+        };
+        assert!(matches!(
+            **second,
+            LayoutNode::Split {
+                direction: SplitDirection::Horizontal,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn rotate_swaps_the_innermost_split() {
+        let mut layout = Layout::new(0);
+        layout.split_pane(0, 1, SplitDirection::Vertical);
+        assert!(layout.rotate_split(0, None));
+        assert_eq!(layout.pane_ids(), vec![1, 0]);
+
+        layout.split_pane(0, 2, SplitDirection::Horizontal);
+        assert!(layout.rotate_split(2, None));
+        assert_eq!(layout.pane_ids(), vec![1, 2, 0]);
+        assert!(layout.rotate_split(1, None));
+        assert_eq!(layout.pane_ids(), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn edge_panes_span_the_whole_side() {
+        let area = Rect::new(0, 0, 101, 41);
+        let rect = |layout: &Layout, id| {
+            let rects = layout.calculate_rects(area);
+            let (_, r) = rects.into_iter().find(|(i, _)| *i == id).unwrap();
+            (r.x, r.y, r.width, r.height)
+        };
+        let split = || {
+            let mut layout = Layout::new(0);
+            layout.split_pane(0, 1, SplitDirection::Horizontal);
+            layout
+        };
+
+        let mut layout = split();
+        layout.add_edge_pane(9, Edge::Left, 0.2);
+        assert_eq!(rect(&layout, 9), (0, 0, 20, 41));
+        assert_eq!(rect(&layout, 0), (21, 0, 80, 20));
+
+        let mut layout = split();
+        layout.add_edge_pane(9, Edge::Right, 0.2);
+        assert_eq!(rect(&layout, 9), (81, 0, 20, 41));
+        assert_eq!(rect(&layout, 1), (0, 21, 80, 20));
+
+        let mut layout = split();
+        layout.add_edge_pane(9, Edge::Bottom, 0.25);
+        assert_eq!(rect(&layout, 9), (0, 31, 101, 10));
+        assert_eq!(rect(&layout, 0), (0, 0, 101, 14));
+        assert_eq!(layout.pane_ids(), vec![0, 1, 9]);
+
+        // Taking it away leaves the split as it was
+        assert!(layout.remove_pane(9));
+        assert_eq!(rect(&layout, 1), (0, 21, 101, 20));
+    }
+
+    #[test]
+    fn rotate_skips_splits_holding_the_pinned_pane() {
+        let mut layout = Layout::new(0);
+        layout.add_left_pane(9, 0.2);
+        assert!(!layout.rotate_split(0, Some(9)));
+        assert_eq!(layout.pane_ids(), vec![9, 0]);
+
+        layout.split_pane(0, 1, SplitDirection::Vertical);
+        assert!(layout.rotate_split(0, Some(9)));
+        assert_eq!(layout.pane_ids(), vec![9, 1, 0]);
+    }
+
+    #[test]
+    fn keep_only_collapses_the_tree_to_one_pane() {
+        let mut layout = Layout::new(0);
+        layout.add_left_pane(9, 0.2);
+        layout.split_pane(0, 1, SplitDirection::Vertical);
+        layout.split_pane(1, 2, SplitDirection::Horizontal);
+
+        assert!(!layout.keep_only(5));
+        assert!(layout.keep_only(1));
+        assert!(matches!(layout.root, LayoutNode::Pane(1)));
+        let rects = layout.calculate_rects(Rect::new(0, 0, 80, 24));
+        assert_eq!(rects.len(), 1);
+        assert_eq!((rects[0].1.width, rects[0].1.height), (80, 24));
+    }
+}
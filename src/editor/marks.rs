@@ -0,0 +1,145 @@
+//! Marks: named positions (`ma`, `` `a ``) and the jump list (Ctrl-O, Ctrl-I)
+//!
+//! Both belong to a buffer and hold (line, col) positions, which follow the
+//! lines they're on as lines are inserted and deleted above them.
+
+use std::collections::HashMap;
+
+/// Positions the jump list keeps before dropping the oldest
+const MAX_JUMPS: usize = 100;
+
+#[derive(Debug, Clone, Default)]
+pub struct Marks {
+    named: HashMap<char, (usize, usize)>,
+    jumps: Vec<(usize, usize)>,
+    jump_index: usize, // Where Ctrl-O/Ctrl-I are in `jumps`; its length when at the newest
+}
+
+impl Marks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: char, at: (usize, usize)) {
+        self.named.insert(name, at);
+    }
+
+    /// A named mark; `` ` `` and `'` name the position before the latest jump
+    pub fn get(&self, name: char) -> Option<(usize, usize)> {
+        match name {
+            '`' | '\'' => self.jumps.last().copied(),
+            _ => self.named.get(&name).copied(),
+        }
+    }
+
+    /// Remember `from` as the place a jump left. One entry per line is kept,
+    /// and walking the list with Ctrl-O/Ctrl-I starts over from the newest.
+    pub fn push_jump(&mut self, from: (usize, usize)) {
+        self.jumps.retain(|&(line, _)| line != from.0);
+        self.jumps.push(from);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.jump_index = self.jumps.len();
+    }
+
+    /// Ctrl-O: the position before `current` in the jump list
+    pub fn jump_back(&mut self, current: (usize, usize)) -> Option<(usize, usize)> {
+        if self.jump_index == 0 {
+            return None;
+        }
+        if self.jump_index >= self.jumps.len() {
+            // Leaving the newest position: remember it so Ctrl-I can come back
+            self.push_jump(current);
+            self.jump_index = self.jumps.len() - 1;
+            if self.jump_index == 0 {
+                return None;
+            }
+        }
+        self.jump_index -= 1;
+        Some(self.jumps[self.jump_index])
+    }
+
+    /// Ctrl-I: undo a Ctrl-O
+    pub fn jump_forward(&mut self) -> Option<(usize, usize)> {
+        if self.jump_index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.jump_index += 1;
+        Some(self.jumps[self.jump_index])
+    }
+
+    /// Keep positions on their lines after lines `start..=old_end` became
+    /// `start..=new_end`. Positions on deleted lines are dropped.
+    pub fn shift(&mut self, start: usize, old_end: usize, new_end: usize) {
+        let shifted = |&(line, col): &(usize, usize)| {
+            if line > old_end {
+                Some((line + new_end - old_end, col))
+            } else if line > start && line > new_end {
+                None
+            } else {
+                Some((line, col))
+            }
+        };
+        self.named = self
+            .named
+            .iter()
+            .filter_map(|(&name, at)| shifted(at).map(|at| (name, at)))
+            .collect();
+
+        let before = self.jumps.len();
+        self.jumps = self.jumps.iter().filter_map(shifted).collect();
+        let dropped = before - self.jumps.len();
+        self.jump_index = self
+            .jump_index
+            .saturating_sub(dropped)
+            .min(self.jumps.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_follow_their_lines_and_vanish_with_them() {
+        let mut marks = Marks::new();
+        marks.set('a', (2, 3));
+        marks.set('b', (5, 0));
+        marks.set('c', (9, 1));
+
+        // Two lines inserted after line 1
+        marks.shift(1, 1, 3);
+        assert_eq!(marks.get('a'), Some((4, 3)));
+
+        // Lines 5..=7 deleted, joined into line 5
+        marks.shift(5, 7, 5);
+        assert_eq!(marks.get('a'), Some((4, 3)));
+        assert_eq!(marks.get('b'), None);
+        assert_eq!(marks.get('c'), Some((9, 1)));
+        assert_eq!(marks.get('d'), None);
+    }
+
+    #[test]
+    fn jump_list_walks_back_and_forward() {
+        let mut marks = Marks::new();
+        assert_eq!(marks.jump_back((0, 0)), None);
+
+        marks.push_jump((0, 0));
+        marks.push_jump((10, 2));
+        assert_eq!(marks.get('`'), Some((10, 2)));
+
+        // At line 20 after the last jump
+        assert_eq!(marks.jump_back((20, 0)), Some((10, 2)));
+        assert_eq!(marks.jump_back((10, 2)), Some((0, 0)));
+        assert_eq!(marks.jump_back((0, 0)), None);
+        assert_eq!(marks.jump_forward(), Some((10, 2)));
+        assert_eq!(marks.jump_forward(), Some((20, 0)));
+        assert_eq!(marks.jump_forward(), None);
+
+        // A new jump from line 10 replaces its old entry
+        marks.push_jump((10, 5));
+        assert_eq!(marks.jump_back((30, 0)), Some((10, 5)));
+        assert_eq!(marks.jump_back((10, 5)), Some((20, 0)));
+    }
+}
@@ -0,0 +1,116 @@
+//! The status-line messages and errors shown so far, for `:messages`
+//!
+//! Messages are replaced by the next key press, so this keeps the last few
+//! hundred with when they were shown and whether they were errors.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Most messages kept
+const MAX_MESSAGES: usize = 200;
+
+/// Whether a message reported a failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Error,
+}
+
+/// A message as it was shown
+#[derive(Debug, Clone)]
+pub struct LoggedMessage {
+    pub time: Instant,
+    pub severity: Severity,
+    pub text: String,
+}
+
+/// Messages shown on the status line, oldest first
+#[derive(Debug, Default)]
+pub struct MessageLog {
+    entries: VecDeque<LoggedMessage>,
+}
+
+impl MessageLog {
+    /// Remember a message; one repeating the last is only moved to now
+    pub fn add(&mut self, severity: Severity, text: &str) {
+        let time = Instant::now();
+        if let Some(last) = self.entries.back_mut()
+            && last.severity == severity
+            && last.text == text
+        {
+            last.time = time;
+            return;
+        }
+        self.entries.push_back(LoggedMessage {
+            time,
+            severity,
+            text: text.to_string(),
+        });
+        if self.entries.len() > MAX_MESSAGES {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// One line per message (more for multiline errors), each with how long
+    /// before `now` it was shown and `E` for errors
+    pub fn format(&self, now: Instant) -> String {
+        self.entries
+            .iter()
+            .map(|m| {
+                let age = format_age(now.saturating_duration_since(m.time));
+                let severity = match m.severity {
+                    Severity::Info => ' ',
+                    Severity::Error => 'E',
+                };
+                // Line up the continuation lines of multiline errors
+                let text = m.text.replace('\n', &format!("\n{:11}", ""));
+                format!("{:>8} {} {}", age, severity, text)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// `age` to the largest whole unit, e.g. "5s ago" or "3h ago"
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{}s ago", secs),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_kept_with_their_age_and_severity() {
+        let mut log = MessageLog::default();
+        log.add(Severity::Info, "Written");
+        log.add(Severity::Error, "Failed:\nno such file");
+        log.add(Severity::Error, "Failed:\nno such file");
+        log.add(Severity::Info, "Written");
+        assert_eq!(log.entries.len(), 3);
+
+        let later = log.entries[0].time + Duration::from_secs(125);
+        let text = log.format(later);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "  2m ago   Written");
+        assert!(lines[1].ends_with("ago E Failed:"));
+        assert_eq!(lines[2], "           no such file");
+
+        for n in 0..MAX_MESSAGES {
+            log.add(Severity::Info, &n.to_string());
+        }
+        assert_eq!(log.entries.len(), MAX_MESSAGES);
+        assert_eq!(log.entries[0].text, "0");
+        assert_eq!(format_age(Duration::from_secs(7200)), "2h ago");
+    }
+}
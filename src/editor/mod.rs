@@ -1,15 +1,37 @@
 mod buffer;
+mod buffer_list;
+mod clipboard;
+mod completion;
 mod cursor;
 mod file_browser;
+mod fold;
+mod formatter;
+mod git;
+mod history;
 mod layout;
+mod marks;
+mod messages;
 mod mode;
+mod outline;
 mod pane;
+mod recent;
+mod registers;
+mod sign;
 mod tab;
+mod text_object;
+mod word_completion;
 mod workspace;
 
-pub use buffer::Buffer;
+pub use buffer::{Buffer, IndentStyle, LineEnding, cell_width};
+pub use completion::Completion;
 pub use cursor::Cursor;
-pub use layout::{Direction, Rect};
-pub use mode::{Mode, SearchDirection};
+pub use history::CommandHistory;
+pub use layout::{Direction, Edge, Rect, SplitDirection};
+pub use mode::{FindKind, Mode, SearchDirection};
 pub use pane::{Pane, PaneKind};
-pub use workspace::{FinderAction, SearchState, Workspace};
+pub use recent::RecentFiles;
+pub use registers::Registers;
+pub use sign::SignKind;
+pub use text_object::TextObject;
+pub use word_completion::WordCompletion;
+pub use workspace::{EditorEvent, FinderAction, PendingKeys, PromptAction, Workspace};
@@ -3,6 +3,7 @@ pub enum Mode {
     #[default]
     Normal,
     Insert,
+    Replace, // R: typed chars overwrite the ones under the cursor
     Command,
     FileBrowser,
     MessageViewer,
@@ -16,11 +17,43 @@ pub enum SearchDirection {
     Backward,
 }
 
+/// Intra-line character find (`f`, `F`, `t`, `T`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindKind {
+    Forward,      // f: onto the next occurrence
+    Backward,     // F: onto the previous occurrence
+    TillForward,  // t: one column before the next occurrence
+    TillBackward, // T: one column after the previous occurrence
+}
+
+impl FindKind {
+    /// The same find in the opposite direction (used by `,`)
+    pub fn reversed(self) -> Self {
+        match self {
+            FindKind::Forward => FindKind::Backward,
+            FindKind::Backward => FindKind::Forward,
+            FindKind::TillForward => FindKind::TillBackward,
+            FindKind::TillBackward => FindKind::TillForward,
+        }
+    }
+
+    /// The key that starts this find
+    pub fn key(self) -> char {
+        match self {
+            FindKind::Forward => 'f',
+            FindKind::Backward => 'F',
+            FindKind::TillForward => 't',
+            FindKind::TillBackward => 'T',
+        }
+    }
+}
+
 impl Mode {
     pub fn display(&self) -> &'static str {
         match self {
             Mode::Normal => "NORMAL",
             Mode::Insert => "INSERT",
+            Mode::Replace => "REPLACE",
             Mode::Command => "COMMAND",
             Mode::FileBrowser => "FILES",
             Mode::MessageViewer => "MESSAGE",
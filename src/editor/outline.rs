@@ -0,0 +1,111 @@
+//! The outline `:symbols` lists when there's no parse tree: lines that start
+//! with a definition keyword (`fn`, `def`, `class`, ...), after any modifiers
+
+use super::Buffer;
+use crate::syntax::Symbol;
+
+/// Keywords that start a definition, and the kind of symbol each defines
+const KEYWORDS: &[(&str, &str)] = &[
+    ("fn", "function"),
+    ("def", "function"),
+    ("func", "function"),
+    ("function", "function"),
+    ("struct", "struct"),
+    ("enum", "enum"),
+    ("union", "union"),
+    ("trait", "trait"),
+    ("impl", "impl"),
+    ("mod", "mod"),
+    ("class", "class"),
+    ("interface", "interface"),
+    ("module", "module"),
+    ("type", "type"),
+];
+
+/// Words that can come before a definition keyword (`pub async fn`)
+const MODIFIERS: &[&str] = &[
+    "pub",
+    "export",
+    "default",
+    "async",
+    "unsafe",
+    "const",
+    "extern",
+    "static",
+    "public",
+    "private",
+    "protected",
+    "abstract",
+    "local",
+];
+
+/// Definitions found by keyword, in line order
+pub fn keyword_symbols(buffer: &Buffer) -> Vec<Symbol> {
+    (0..buffer.line_count())
+        .filter_map(|line| {
+            let text = buffer.line(line).to_string();
+            let (kind, name) = definition(&text)?;
+            Some(Symbol {
+                line,
+                kind: kind.to_string(),
+                name,
+            })
+        })
+        .collect()
+}
+
+/// The kind and name of what `line` defines, if it starts with a keyword
+fn definition(line: &str) -> Option<(&'static str, String)> {
+    let mut words = line.split_whitespace();
+    // Skip modifiers, including `pub(crate)` and the quoted ABI of `extern "C"`
+    let keyword = words.find(|word| {
+        !(MODIFIERS.contains(word) || word.starts_with("pub(") || word.starts_with('"'))
+    })?;
+    // Generic parameters can follow the keyword directly (`impl<T>`)
+    let keyword = keyword.split('<').next()?;
+    let &(_, kind) = KEYWORDS.iter().find(|(k, _)| *k == keyword)?;
+
+    let name: String = words
+        .next()?
+        .chars()
+        .take_while(|&c| c.is_alphanumeric() || matches!(c, '_' | '.' | '$'))
+        .collect();
+    (!name.is_empty()).then_some((kind, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn definitions_are_found_by_keyword() {
+        let buffer = Buffer::from_text(
+            "use std::fmt;\n\
+             \n\
+             pub(crate) struct Point {\n\
+             \x20   x: i32,\n\
+             }\n\
+             \n\
+             impl<T> Wrapper<T> {\n\
+             \x20   pub async fn run(&self) {}\n\
+             }\n\
+             // fn commented_out()\n\
+             def helper(x):\n\
+             \x20   return type(x)\n",
+        );
+        let symbols: Vec<_> = keyword_symbols(&buffer)
+            .into_iter()
+            .map(|s| (s.line, s.kind, s.name))
+            .collect();
+
+        assert_eq!(
+            symbols,
+            [
+                (2, "struct".to_string(), "Point".to_string()),
+                (6, "impl".to_string(), "Wrapper".to_string()),
+                (7, "function".to_string(), "run".to_string()),
+                (10, "function".to_string(), "helper".to_string()),
+            ]
+        );
+    }
+}
@@ -1,6 +1,17 @@
-use super::{Buffer, Cursor, Mode};
-use crate::syntax::{Highlighter, Language};
+use super::buffer_list::BufferView;
+use super::fold::{self, Fold, Folds};
+use super::git;
+use super::marks::Marks;
+use super::outline;
+use super::registers::Register;
+use super::sign::{self, SignKind};
+use super::{Buffer, Cursor, FindKind, IndentStyle, Mode, WordCompletion, cell_width};
+use crate::config::{Indent, Settings};
+use crate::syntax::{EditPoint, HighlightKind, Highlighter, Language, Symbol};
+use std::collections::HashMap;
+use std::io;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 /// Unique identifier for a pane
 pub type PaneId = usize;
@@ -23,6 +34,18 @@ pub struct Pane {
     pub mode: Mode,
     pub highlighter: Highlighter,
     pub language: Language,
+    pub needs_reparse: bool,    // Edits recorded since the last parse
+    edited_at: Option<Instant>, // When the first pending edit was recorded
+    pub last_find: Option<(FindKind, char)>, // Last f/F/t/T, repeated by ; and ,
+    pub alternate: Option<PathBuf>, // Previous buffer shown here, for Ctrl-^
+    pub folds: Folds,           // Closed folds
+    pub marks: Marks,           // Named marks and the jump list
+    pub signs: HashMap<usize, SignKind>, // Sign column markers by line
+    pub viewport_height: usize, // Rows shown at the last scroll adjustment, for H/M/L
+    pub extra_cursors: Vec<Cursor>, // Secondary cursors, typed at alongside `cursor`
+    pub replaced: Vec<Option<char>>, // Chars typed over in replace mode (None: added), for backspace
+    pub large_file: bool,            // Never highlighted or parsed, see `BufferView::open`
+    pub word_completion: Option<WordCompletion>, // Ctrl-N/Ctrl-P matches while inserting
 }
 
 impl Pane {
@@ -37,29 +60,18 @@ impl Pane {
             mode: Mode::Normal,
             highlighter: Highlighter::new(),
             language: Language::Unknown,
-        }
-    }
-
-    pub fn new_editor_with_file(id: PaneId, path: PathBuf) -> Self {
-        let buffer = Buffer::from_file(path.clone());
-        let mut highlighter = Highlighter::new();
-        let language = Language::from_path(&path);
-
-        // Set language and parse if grammar is available
-        if highlighter.set_language(language) {
-            highlighter.parse(&buffer.text());
-        }
-
-        Self {
-            id,
-            kind: PaneKind::Editor,
-            buffer,
-            cursor: Cursor::new(),
-            scroll_offset: 0,
-            scroll_col: 0,
-            mode: Mode::Normal,
-            highlighter,
-            language,
+            needs_reparse: false,
+            edited_at: None,
+            last_find: None,
+            alternate: None,
+            folds: Folds::new(),
+            marks: Marks::new(),
+            signs: HashMap::new(),
+            viewport_height: 0,
+            extra_cursors: Vec::new(),
+            replaced: Vec::new(),
+            large_file: false,
+            word_completion: None,
         }
     }
 
@@ -74,46 +86,1004 @@ impl Pane {
             mode: Mode::FileBrowser,
             highlighter: Highlighter::new(),
             language: Language::Unknown,
+            needs_reparse: false,
+            edited_at: None,
+            last_find: None,
+            alternate: None,
+            folds: Folds::new(),
+            marks: Marks::new(),
+            signs: HashMap::new(),
+            viewport_height: 0,
+            extra_cursors: Vec::new(),
+            replaced: Vec::new(),
+            large_file: false,
+            word_completion: None,
+        }
+    }
+
+    /// Give up this pane's buffer and view state (when the pane is closed)
+    pub fn into_view(mut self) -> BufferView {
+        if self.needs_reparse {
+            self.reparse();
+        }
+        BufferView {
+            buffer: self.buffer,
+            cursor: self.cursor,
+            scroll_offset: self.scroll_offset,
+            scroll_col: self.scroll_col,
+            highlighter: self.highlighter,
+            language: self.language,
+            folds: self.folds,
+            marks: self.marks,
+            signs: self.signs,
+            large_file: self.large_file,
+        }
+    }
+
+    /// Show another buffer in this pane, returning the one it replaces
+    pub fn replace_view(&mut self, view: BufferView) -> BufferView {
+        // Bring highlights up to date before parking the old buffer
+        if self.needs_reparse {
+            self.reparse();
+        }
+        self.extra_cursors.clear();
+        self.word_completion = None;
+        BufferView {
+            buffer: std::mem::replace(&mut self.buffer, view.buffer),
+            cursor: std::mem::replace(&mut self.cursor, view.cursor),
+            scroll_offset: std::mem::replace(&mut self.scroll_offset, view.scroll_offset),
+            scroll_col: std::mem::replace(&mut self.scroll_col, view.scroll_col),
+            highlighter: std::mem::replace(&mut self.highlighter, view.highlighter),
+            language: std::mem::replace(&mut self.language, view.language),
+            folds: std::mem::replace(&mut self.folds, view.folds),
+            marks: std::mem::replace(&mut self.marks, view.marks),
+            signs: std::mem::replace(&mut self.signs, view.signs),
+            large_file: std::mem::replace(&mut self.large_file, view.large_file),
+        }
+    }
+
+    /// How the buffer is indented: as detected in its file (or set with
+    /// `:set indent=`), else as configured for its language. Tabs take the
+    /// configured width either way.
+    pub fn indent(&self, settings: &Settings) -> Indent {
+        let configured = settings.indent(self.language);
+        match self.buffer.indent_style() {
+            Some(IndentStyle::Tabs) => Indent {
+                use_tabs: true,
+                width: configured.width,
+            },
+            Some(IndentStyle::Spaces(width)) => Indent {
+                use_tabs: false,
+                width,
+            },
+            None => configured,
+        }
+    }
+
+    /// Whether the buffer is parsed for highlighting and folds
+    fn parses(&self) -> bool {
+        self.language != Language::Unknown && !self.large_file
+    }
+
+    /// Read the buffer's file again, throwing away any changes. The cursor
+    /// stays on the same line where it can.
+    pub fn reload(&mut self) -> io::Result<()> {
+        let Some(path) = self.buffer.path().cloned() else {
+            return Err(io::Error::other("No file name"));
+        };
+        let read_only = self.buffer.is_read_only();
+        let indent_style = self.buffer.indent_style();
+        self.buffer = Buffer::load(path)?;
+        if read_only {
+            self.buffer.set_read_only(true);
         }
+        self.buffer.set_indent_style(indent_style);
+        self.extra_cursors.clear();
+        self.goto_line(self.cursor.line);
+        self.reparse();
+        Ok(())
     }
 
-    /// Re-parse the buffer for syntax highlighting
+    /// Re-parse the buffer from scratch for syntax highlighting
     pub fn reparse(&mut self) {
-        if self.language != Language::Unknown {
-            self.highlighter.parse(&self.buffer.text());
+        self.needs_reparse = false;
+        self.edited_at = None;
+        if self.parses() {
+            self.highlighter.invalidate();
+            self.highlighter.parse_chunks(self.buffer.chunks());
+        }
+    }
+
+    /// Position in the buffer as an edit point for the highlighter
+    pub fn edit_point(&self, line: usize, col: usize) -> EditPoint {
+        let byte = self.buffer.byte_offset(line, col);
+        EditPoint {
+            byte,
+            row: line,
+            column: byte - self.buffer.byte_offset(line, 0),
+        }
+    }
+
+    /// Record a buffer edit; highlights refresh on the next incremental reparse
+    pub fn record_edit(&mut self, start: EditPoint, old_end: EditPoint, new_end: EditPoint) {
+        self.folds.shift(start.row, old_end.row, new_end.row);
+        self.marks.shift(start.row, old_end.row, new_end.row);
+        sign::shift(&mut self.signs, start.row, old_end.row, new_end.row);
+        if !self.parses() {
+            return;
+        }
+        self.highlighter.edit(start, old_end, new_end);
+        if !self.needs_reparse {
+            self.needs_reparse = true;
+            self.edited_at = Some(Instant::now());
+        }
+    }
+
+    /// Delete the text between two (line, col) positions, keeping highlights in sync
+    pub fn delete_range(&mut self, start: (usize, usize), end: (usize, usize)) -> String {
+        let start_point = self.edit_point(start.0, start.1);
+        let end_point = self.edit_point(end.0, end.1);
+        let removed = self.buffer.delete_range(start, end);
+        if !removed.is_empty() {
+            self.record_edit(start_point, end_point, start_point);
+        }
+        removed
+    }
+
+    /// Insert text at a (line, col) position, keeping highlights in sync
+    pub fn insert_text(&mut self, line: usize, col: usize, text: &str) {
+        let start = self.edit_point(line, col);
+        self.buffer.insert_text(line, col, text);
+        let end_line = line + text.matches('\n').count();
+        let end_col = match text.rfind('\n') {
+            Some(i) => text[i + 1..].chars().count(),
+            None => col + text.chars().count(),
+        };
+        let new_end = self.edit_point(end_line, end_col);
+        self.record_edit(start, start, new_end);
+    }
+
+    /// Replace the whole buffer, keeping the cursor on the same line number where it can
+    pub fn replace_text(&mut self, text: &str) {
+        if self.buffer.text() == text {
+            return;
+        }
+        let last = self.buffer.line_count() - 1;
+        self.delete_range((0, 0), (last, self.buffer.line_len(last)));
+        self.insert_text(0, 0, text);
+        self.goto_line(self.cursor.line);
+    }
+
+    /// Type `ch` at the primary and every secondary cursor
+    pub fn insert_char_at_cursors(&mut self, ch: char) {
+        let positions = self.cursor_positions();
+        // Edit from the end so earlier positions stay valid
+        for &(line, col, _) in positions.iter().rev() {
+            let start = self.edit_point(line, col);
+            self.buffer.insert_char(line, col, ch);
+            let new_end = self.edit_point(line, col + 1);
+            self.record_edit(start, start, new_end);
+        }
+        // Each cursor moves past its own char and those typed before it on its line
+        let mut moved = Vec::with_capacity(positions.len());
+        let mut before = 0;
+        for (i, &(line, col, primary)) in positions.iter().enumerate() {
+            if i == 0 || positions[i - 1].0 != line {
+                before = 0;
+            }
+            before += 1;
+            moved.push((line, col + before, primary));
+        }
+        self.set_cursor_positions(moved);
+    }
+
+    /// Delete the char before the primary and every secondary cursor.
+    /// Cursors at the start of a line stay put; lines are never joined.
+    pub fn delete_char_before_cursors(&mut self) {
+        let positions = self.cursor_positions();
+        for &(line, col, _) in positions.iter().rev() {
+            if col > 0 {
+                let start = self.edit_point(line, col - 1);
+                let old_end = self.edit_point(line, col);
+                self.buffer.delete_char_backward(line, col);
+                self.record_edit(start, old_end, start);
+            }
+        }
+        let mut moved = Vec::with_capacity(positions.len());
+        let mut before = 0;
+        for (i, &(line, col, primary)) in positions.iter().enumerate() {
+            if i == 0 || positions[i - 1].0 != line {
+                before = 0;
+            }
+            if col > 0 {
+                before += 1;
+            }
+            moved.push((line, col - before, primary));
+        }
+        self.set_cursor_positions(moved);
+    }
+
+    /// Every cursor as (line, col, is_primary), in buffer order without duplicates
+    fn cursor_positions(&self) -> Vec<(usize, usize, bool)> {
+        let mut positions: Vec<_> = self
+            .extra_cursors
+            .iter()
+            .map(|c| (c.line, c.col, false))
+            .chain([(self.cursor.line, self.cursor.col, true)])
+            .collect();
+        // The primary sorts after a secondary at the same spot, and wins the dedup
+        positions.sort();
+        positions.dedup_by(|later, earlier| {
+            let same = (later.0, later.1) == (earlier.0, earlier.1);
+            if same {
+                earlier.2 |= later.2;
+            }
+            same
+        });
+        positions
+    }
+
+    fn set_cursor_positions(&mut self, positions: Vec<(usize, usize, bool)>) {
+        self.extra_cursors.clear();
+        for (line, col, primary) in positions {
+            if primary {
+                self.cursor.line = line;
+                self.cursor.col = col;
+            } else if self
+                .extra_cursors
+                .last()
+                .is_none_or(|c| (c.line, c.col) != (line, col))
+            {
+                self.extra_cursors.push(Cursor { line, col });
+            }
+        }
+        self.extra_cursors
+            .retain(|c| (c.line, c.col) != (self.cursor.line, self.cursor.col));
+    }
+
+    /// Split a line at (line, col), starting the new line with `indent`
+    pub fn insert_newline(&mut self, line: usize, col: usize, indent: &str) {
+        let start = self.edit_point(line, col);
+        self.buffer.insert_newline(line, col);
+        self.buffer.insert_text(line + 1, 0, indent);
+        let new_end = self.edit_point(line + 1, indent.chars().count());
+        self.record_edit(start, start, new_end);
+    }
+
+    /// Strip spaces and tabs from the end of every line, returning how many
+    /// lines changed. The cursor stays put, or at the end of its trimmed line.
+    pub fn trim_trailing_whitespace(&mut self) -> usize {
+        let mut trimmed = 0;
+        for line in 0..self.buffer.line_count() {
+            let len = self.buffer.line_len(line);
+            let mut end = len;
+            while end > 0 && matches!(self.buffer.char_at(line, end - 1), Some(' ' | '\t')) {
+                end -= 1;
+            }
+            if end < len {
+                self.delete_range((line, end), (line, len));
+                trimmed += 1;
+            }
+        }
+        let line_len = self.buffer.line_len(self.cursor.line);
+        self.cursor.col = self.cursor.col.min(line_len.saturating_sub(1));
+        trimmed
+    }
+
+    /// Add a line break after the last line if it has none, returning whether
+    /// one was added. An empty buffer is left empty.
+    pub fn ensure_final_newline(&mut self) -> bool {
+        let last = self.buffer.line_count() - 1;
+        let len = self.buffer.line_len(last);
+        if len == 0 {
+            return false;
+        }
+        self.insert_text(last, len, "\n");
+        true
+    }
+
+    /// Delete up to `count` characters (whole grapheme clusters) from the
+    /// cursor to the end of the line (`x`), returning the removed text
+    pub fn delete_chars(&mut self, count: usize) -> String {
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        let mut end = col;
+        for _ in 0..count {
+            end = self.buffer.next_grapheme(line, end);
+        }
+        let removed = self.delete_range((line, col), (line, end));
+        let line_len = self.buffer.line_len(line);
+        let col = col.min(line_len.saturating_sub(1));
+        self.cursor.col = self.buffer.grapheme_start(line, col);
+        removed
+    }
+
+    /// Delete up to `count` characters (whole grapheme clusters) before the
+    /// cursor on its line (`X`), returning the removed text
+    pub fn delete_chars_before(&mut self, count: usize) -> String {
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        let mut start = col;
+        for _ in 0..count {
+            start = self.buffer.prev_grapheme(line, start);
+        }
+        let removed = self.delete_range((line, start), (line, col));
+        self.cursor.col = start;
+        removed
+    }
+
+    /// Replace `count` chars from the cursor with `ch` (`r`); a newline replaces
+    /// them all with a line break. Does nothing if the line is too short.
+    pub fn replace_chars(&mut self, ch: char, count: usize) {
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        if count == 0 || col + count > self.buffer.line_len(line) {
+            return;
+        }
+        self.delete_range((line, col), (line, col + count));
+        if ch == '\n' {
+            self.insert_newline(line, col, "");
+            self.cursor.line += 1;
+            self.cursor.col = 0;
+        } else {
+            self.insert_text(line, col, &ch.to_string().repeat(count));
+            self.cursor.col = col + count - 1;
+        }
+    }
+
+    /// The text of `count` lines from the cursor's (`yy`), each ending in a newline
+    pub fn yank_lines(&self, count: usize) -> String {
+        let first = self.cursor.line;
+        let last = (first + count).min(self.buffer.line_count());
+        let mut text: String = (first..last)
+            .map(|l| self.buffer.line(l).to_string())
+            .collect();
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Put `count` copies of `register` after the cursor (`p`) or before it
+    /// (`P`). Whole lines go below or above the cursor's line.
+    pub fn paste(&mut self, register: &Register, before: bool, count: usize) {
+        let text = register.text.repeat(count);
+        let line = self.cursor.line;
+        if register.linewise {
+            let target = if before { line } else { line + 1 };
+            if target < self.buffer.line_count() {
+                self.insert_text(target, 0, &text);
+            } else {
+                // After a last line with no newline of its own
+                let end = self.buffer.line_len(line);
+                self.insert_text(line, end, &format!("\n{}", text.trim_end_matches('\n')));
+            }
+            self.cursor.line = target;
+            self.cursor.col = self.buffer.first_non_blank(target);
+            return;
+        }
+
+        let line_len = self.buffer.line_len(line);
+        let col = if before || line_len == 0 {
+            self.cursor.col.min(line_len)
+        } else {
+            (self.cursor.col + 1).min(line_len)
+        };
+        self.insert_text(line, col, &text);
+        // The cursor ends on the last char put
+        match text.rfind('\n') {
+            Some(i) => {
+                self.cursor.line = line + text.matches('\n').count();
+                self.cursor.col = text[i + 1..].chars().count().saturating_sub(1);
+            }
+            None => self.cursor.col = col + text.chars().count().saturating_sub(1),
+        }
+    }
+
+    /// Indentation for a line opened after (line, col) with auto-indent: the
+    /// line's leading whitespace, plus `unit` if the text before `col` ends with `{`
+    pub fn indent_after(&self, line: usize, col: usize, unit: &str) -> String {
+        let before: String = self.buffer.line(line).chars().take(col).collect();
+        let mut indent: String = before
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        if before.trim_end().ends_with('{') {
+            indent.push_str(unit);
+        }
+        indent
+    }
+
+    /// Add one level of indentation to a line (`>>`); blank lines are left alone
+    pub fn indent_line(&mut self, line: usize, indent: &str) {
+        if self.buffer.first_non_blank(line) < self.buffer.line_len(line) {
+            self.insert_text(line, 0, indent);
+        }
+    }
+
+    /// Remove up to one level (`width` columns) of leading whitespace (`<<`)
+    pub fn dedent_line(&mut self, line: usize, width: usize) {
+        let mut cols = 0;
+        let mut chars = 0;
+        for ch in self.buffer.line(line).chars() {
+            match ch {
+                ' ' => cols += 1,
+                '\t' => cols = width,
+                _ => break,
+            }
+            chars += 1;
+            if cols >= width {
+                break;
+            }
+        }
+        self.delete_range((line, 0), (line, chars));
+    }
+
+    /// Comment out lines `first..=last` with `token` (`gcc`), or uncomment
+    /// them if they all already are. A token with a space in it ("<!-- -->")
+    /// is an opening and a closing part. Blank lines are left alone.
+    pub fn toggle_comment(&mut self, first: usize, last: usize, token: &str) {
+        let mut parts = token.split_whitespace();
+        let open = parts.next().unwrap_or_default();
+        let close = parts.next();
+        let last = last.min(self.buffer.line_count().saturating_sub(1));
+
+        let lines: Vec<(usize, String)> = (first..=last)
+            .map(|line| {
+                let len = self.buffer.line_len(line);
+                (line, self.buffer.line(line).chars().take(len).collect())
+            })
+            .filter(|(_, text): &(usize, String)| !text.trim().is_empty())
+            .collect();
+        if lines.is_empty() || open.is_empty() {
+            return;
+        }
+
+        let commented = lines.iter().all(|(_, text)| {
+            let text = text.trim();
+            text.starts_with(open)
+                && close.is_none_or(|close| {
+                    text.len() >= open.len() + close.len() && text.ends_with(close)
+                })
+        });
+        let indent = lines
+            .iter()
+            .map(|(line, _)| self.buffer.first_non_blank(*line))
+            .min()
+            .unwrap_or(0);
+
+        for (line, text) in &lines {
+            let line = *line;
+            if commented {
+                // The closing part goes first so the opening one's columns hold
+                if let Some(close) = close {
+                    let trimmed = text.trim_end();
+                    let mut start = trimmed.chars().count() - close.chars().count();
+                    if trimmed[..trimmed.len() - close.len()].ends_with(' ') {
+                        start -= 1;
+                    }
+                    self.delete_range((line, start), (line, self.buffer.line_len(line)));
+                }
+                let start = self.buffer.first_non_blank(line);
+                let mut end = start + open.chars().count();
+                if self.buffer.line(line).chars().nth(end) == Some(' ') {
+                    end += 1;
+                }
+                self.delete_range((line, start), (line, end));
+            } else {
+                if let Some(close) = close {
+                    self.insert_text(line, self.buffer.line_len(line), &format!(" {}", close));
+                }
+                self.insert_text(line, indent, &format!("{} ", open));
+            }
+        }
+        let line_len = self.buffer.line_len(self.cursor.line);
+        self.cursor.col = self.cursor.col.min(line_len.saturating_sub(1));
+    }
+
+    /// Incrementally re-parse if edits have been pending for at least `debounce`
+    ///
+    /// Returns true if the highlights were refreshed.
+    pub fn reparse_pending(&mut self, debounce: Duration) -> bool {
+        match self.edited_at {
+            Some(at) if self.needs_reparse && at.elapsed() >= debounce => {
+                self.needs_reparse = false;
+                self.edited_at = None;
+                self.highlighter.parse_chunks(self.buffer.chunks());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Move to an occurrence of `ch` on the current line (f/F/t/T)
+    ///
+    /// When `repeat` is set (`;`, `,` or a count), till motions skip a match
+    /// right beside the cursor so they advance instead of staying put.
+    /// Returns false and leaves the cursor alone if the char isn't found.
+    pub fn find_char(&mut self, kind: FindKind, ch: char, repeat: bool) -> bool {
+        let line: Vec<char> = self.buffer.line(self.cursor.line).chars().collect();
+        let len = self.buffer.line_len(self.cursor.line);
+        let col = self.cursor.col;
+        let skip = if repeat { 1 } else { 0 };
+
+        let target = match kind {
+            FindKind::Forward => (col + 1..len).find(|&i| line[i] == ch),
+            FindKind::TillForward => (col + 1 + skip..len)
+                .find(|&i| line[i] == ch)
+                .map(|i| i - 1),
+            FindKind::Backward => (0..col.min(len)).rev().find(|&i| line[i] == ch),
+            FindKind::TillBackward => (0..col.saturating_sub(skip).min(len))
+                .rev()
+                .find(|&i| line[i] == ch)
+                .map(|i| i + 1),
+        };
+
+        match target {
+            Some(target) => {
+                self.cursor.col = target;
+                true
+            }
+            None => false,
         }
     }
 
     /// Set language and reparse
     pub fn set_language(&mut self, lang: Language) {
         self.language = lang;
-        if self.highlighter.set_language(lang) {
-            self.highlighter.parse(&self.buffer.text());
+        if !self.large_file && self.highlighter.set_language(lang) {
+            self.highlighter.parse_chunks(self.buffer.chunks());
+        }
+    }
+
+    /// Move the cursor to a line (0-based), clamped to the buffer
+    pub fn goto_line(&mut self, line: usize) {
+        self.cursor.line = line.min(self.buffer.line_count().saturating_sub(1));
+        let line_len = self.buffer.line_len(self.cursor.line);
+        self.cursor.col = self.cursor.col.min(line_len.saturating_sub(1));
+    }
+
+    /// Whether (line, col) is inside a string or comment, going by the last
+    /// parse; always false without a grammar
+    pub fn in_string_or_comment(&self, line: usize, col: usize) -> bool {
+        let Some(highlights) = self.highlighter.line_highlights(line) else {
+            return false;
+        };
+        let line_start = self.buffer.byte_offset(line, 0);
+        let kind_at =
+            |col: usize| highlights.kind_at(self.buffer.byte_offset(line, col) - line_start);
+        match col.checked_sub(1).map(kind_at) {
+            // Line comments run to the end of the line
+            Some(HighlightKind::Comment) => true,
+            // After a closing quote is outside the string
+            Some(HighlightKind::String) => kind_at(col) == HighlightKind::String,
+            _ => false,
+        }
+    }
+
+    /// Remember the cursor in the jump list before jumping away from it
+    pub fn record_jump(&mut self) {
+        self.marks.push_jump((self.cursor.line, self.cursor.col));
+    }
+
+    /// Move the cursor to a remembered (line, col), clamped to the buffer
+    pub fn jump_to(&mut self, (line, col): (usize, usize)) {
+        self.goto_line(line);
+        let line_len = self.buffer.line_len(self.cursor.line);
+        self.cursor.col = col.min(line_len.saturating_sub(1));
+    }
+
+    /// Foldable line ranges: from the parse tree if there is one, else indentation
+    pub fn fold_ranges(&mut self) -> Vec<Fold> {
+        self.reparse_pending(Duration::ZERO);
+        if self.highlighter.is_active() {
+            self.highlighter
+                .fold_ranges()
+                .into_iter()
+                .map(|(start, end)| Fold { start, end })
+                .collect()
+        } else {
+            fold::indent_ranges(&self.buffer)
+        }
+    }
+
+    /// Definitions to jump to: from the parse tree if there is one, else by keyword
+    pub fn symbols(&mut self) -> Vec<Symbol> {
+        self.reparse_pending(Duration::ZERO);
+        if self.highlighter.is_active() {
+            self.highlighter.symbols(&self.buffer.text())
+        } else {
+            outline::keyword_symbols(&self.buffer)
         }
     }
 
-    pub fn adjust_scroll(&mut self, viewport_height: usize) {
-        // Vertical scroll
-        if self.cursor.line < self.scroll_offset {
-            self.scroll_offset = self.cursor.line;
+    /// Open the fold under the cursor, or close the innermost one around it (`za`).
+    /// Returns false if there's no fold there.
+    pub fn toggle_fold(&mut self) -> bool {
+        let line = self.cursor.line;
+        if self.folds.open_at(line) {
+            return true;
+        }
+        let innermost = self
+            .fold_ranges()
+            .into_iter()
+            .filter(|f| f.contains(line))
+            .min_by_key(|f| f.end - f.start);
+        match innermost {
+            Some(fold) => {
+                self.folds.close(fold);
+                self.cursor.line = fold.start;
+                true
+            }
+            None => false,
         }
-        if self.cursor.line >= self.scroll_offset + viewport_height {
-            self.scroll_offset = self.cursor.line - viewport_height + 1;
+    }
+
+    /// Close every fold in the buffer (`zM`)
+    pub fn close_all_folds(&mut self) {
+        for fold in self.fold_ranges() {
+            self.folds.close(fold);
         }
+        self.cursor.line = self.folds.visible_line(self.cursor.line);
     }
 
-    pub fn adjust_scroll_horizontal(&mut self, viewport_width: usize) {
-        // Horizontal scroll - keep some margin
-        let margin = 5.min(viewport_width / 4);
+    /// Move the cursor down one visible line, stepping over closed folds
+    pub fn line_down(&mut self) {
+        let line_count = self.buffer.line_count();
+        if self.folds.is_empty() {
+            self.cursor.move_down(line_count);
+        } else if let Some(line) = self.folds.next_visible(self.cursor.line, line_count) {
+            self.cursor.line = line;
+        }
+    }
+
+    /// Move the cursor up one visible line, stepping over closed folds
+    pub fn line_up(&mut self) {
+        if self.folds.is_empty() {
+            self.cursor.move_up();
+        } else if let Some(line) = self.folds.prev_visible(self.cursor.line) {
+            self.cursor.line = line;
+        }
+    }
+
+    /// Cells taken by the gutter: room for the largest line number (at least 3
+    /// digits), the sign column while there are signs, and a space
+    pub fn gutter_width(&self) -> usize {
+        let digits = self.buffer.line_count().to_string().len();
+        digits.max(3) + usize::from(!self.signs.is_empty()) + 1
+    }
+
+    /// Show `kind` in the sign column on `line`, replacing any sign there
+    pub fn set_sign(&mut self, line: usize, kind: SignKind) {
+        self.signs.insert(line, kind);
+    }
+
+    /// Remove every sign, collapsing the sign column
+    pub fn clear_signs(&mut self) {
+        self.signs.clear();
+    }
+
+    /// Sign the lines changed since the file's last commit, as of the file
+    /// on disk. Large files aren't diffed.
+    pub fn update_git_signs(&mut self) {
+        let Some(path) = self.buffer.path() else {
+            return;
+        };
+        if self.large_file {
+            return;
+        }
+        let signs = git::signs(path);
+        self.clear_signs();
+        for (line, kind) in signs {
+            self.set_sign(line, kind);
+        }
+    }
+
+    /// The buffer lines on screen, top to bottom, one per row (a closed fold is one row)
+    pub fn viewport_lines(&self) -> Vec<usize> {
+        let line_count = self.buffer.line_count();
+        let mut line = self
+            .folds
+            .visible_line(self.scroll_offset.min(line_count.saturating_sub(1)));
+        let mut lines = vec![line];
+        while lines.len() < self.viewport_height {
+            match self.folds.next_visible(line, line_count) {
+                Some(next) => line = next,
+                None => break,
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// The identifier the cursor is on and the column it starts at, for `*`,
+    /// `#`, `gd` and grepping
+    pub fn word_under_cursor(&self) -> Option<(usize, String)> {
+        let chars: Vec<char> = self.buffer.line(self.cursor.line).chars().collect();
+        let col = self.cursor.col;
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        if !chars.get(col).is_some_and(|&c| is_word_char(c)) {
+            return None;
+        }
+
+        let mut start = col;
+        while start > 0 && is_word_char(chars[start - 1]) {
+            start -= 1;
+        }
+        let mut end = col;
+        while end < chars.len() && is_word_char(chars[end]) {
+            end += 1;
+        }
+        Some((start, chars[start..end].iter().collect()))
+    }
+
+    /// The bracket under the cursor (or just before it while typing) and the
+    /// one it pairs with, for highlighting
+    pub fn bracket_pair(&self) -> Option<[(usize, usize); 2]> {
+        if self.large_file {
+            return None;
+        }
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        let is_bracket = |col| {
+            self.buffer
+                .char_at(line, col)
+                .is_some_and(|c| "(){}[]".contains(c))
+        };
+        let typing = matches!(self.mode, Mode::Insert | Mode::Replace);
+        let col = if is_bracket(col) {
+            col
+        } else if typing && col > 0 && is_bracket(col - 1) {
+            col - 1
+        } else {
+            return None;
+        };
+        let other = self.buffer.matching_bracket(line, col)?;
+        Some([(line, col), other])
+    }
+
+    /// Where the view is in the buffer, as Vim's ruler shows it: `All` when
+    /// every line is on screen, `Top` or `Bot` at either end, and otherwise
+    /// the share of the lines not shown that are above the view
+    pub fn scroll_position(&self) -> String {
+        // Leave out the empty "line" after a trailing newline
+        let mut line_count = self.buffer.line_count();
+        if line_count > 1 && self.buffer.line_len(line_count - 1) == 0 {
+            line_count -= 1;
+        }
+        let shown = self.viewport_lines();
+        let above = shown.first().copied().unwrap_or(0);
+        let last = shown.last().copied().unwrap_or(0);
+        let below = match self.folds.next_visible(last, line_count) {
+            Some(next) => line_count - next,
+            None => 0,
+        };
+        match (above, below) {
+            (0, 0) => "All".to_string(),
+            (0, _) => "Top".to_string(),
+            (_, 0) => "Bot".to_string(),
+            _ => format!("{}%", above * 100 / (above + below)),
+        }
+    }
+
+    /// Move the cursor to the text drawn `row` rows down and `cell` cells right
+    /// of the pane's text area (e.g. where it was clicked)
+    pub fn move_cursor_to_cell(&mut self, row: usize, cell: usize, tab_width: usize) {
+        let line_count = self.buffer.line_count();
+        let mut line = self
+            .folds
+            .visible_line(self.scroll_offset.min(line_count.saturating_sub(1)));
+        for _ in 0..row {
+            match self.folds.next_visible(line, line_count) {
+                Some(next) => line = next,
+                None => break,
+            }
+        }
+
+        // Cells scrolled off, as the renderer counts them
+        let line_len = self.buffer.line_len(line);
+        let scrolled = self.buffer.display_col(line, self.scroll_col, tab_width)
+            + self.scroll_col.saturating_sub(line_len);
+        let col = self.buffer.col_at_display(line, scrolled + cell, tab_width);
+        let last_col = if matches!(self.mode, Mode::Insert | Mode::Replace) {
+            line_len
+        } else {
+            line_len.saturating_sub(1)
+        };
+        self.cursor.line = line;
+        self.cursor.col = col.min(last_col);
+    }
+
+    /// Scroll the view `rows` rows down (up when negative), dragging the cursor
+    /// along when it would go off screen or into the `scroll_off` rows at
+    /// either edge
+    pub fn scroll_view(&mut self, rows: isize, scroll_off: usize) {
+        let line_count = self.buffer.line_count();
+        let mut top = self
+            .folds
+            .visible_line(self.scroll_offset.min(line_count.saturating_sub(1)));
+        for _ in 0..rows.unsigned_abs() {
+            let next = if rows > 0 {
+                self.folds.next_visible(top, line_count)
+            } else {
+                self.folds.prev_visible(top)
+            };
+            match next {
+                Some(line) => top = line,
+                None => break,
+            }
+        }
+        self.scroll_offset = top;
+
+        let shown = self.viewport_lines();
+        let (first, last) = self.scroll_off_rows(&shown, scroll_off);
+        if let (Some(&first), Some(&last)) = (shown.get(first), shown.get(last)) {
+            if self.cursor.line < first {
+                self.goto_line(first);
+            } else if self.cursor.line > last {
+                self.goto_line(last);
+            }
+        }
+    }
+
+    /// The first and last rows of `shown` (the lines on screen) the cursor can
+    /// be on while keeping `scroll_off` rows beyond it. There's no need to at
+    /// the start or end of the buffer.
+    pub fn scroll_off_rows(&self, shown: &[usize], scroll_off: usize) -> (usize, usize) {
+        let last = shown.len().saturating_sub(1);
+        let (Some(&top), Some(&bottom)) = (shown.first(), shown.last()) else {
+            return (0, 0);
+        };
+        // As adjust_scroll limits it
+        let off = scroll_off.min(self.viewport_height.saturating_sub(1) / 2);
+        let first = if self.folds.prev_visible(top).is_some() {
+            off.min(last)
+        } else {
+            0
+        };
+        let more_below = self
+            .folds
+            .next_visible(bottom, self.buffer.line_count())
+            .is_some();
+        (
+            first,
+            if more_below {
+                last.saturating_sub(off)
+            } else {
+                last
+            },
+        )
+    }
+
+    /// Move the cursor and the view half a pane down (Ctrl-D) or up (Ctrl-U), so the
+    /// cursor keeps its row on screen. The view stops with the last line at the bottom.
+    pub fn scroll_half_page(&mut self, down: bool) {
+        let rows = (self.viewport_height / 2).max(1);
+        for _ in 0..rows {
+            if down {
+                self.line_down();
+            } else {
+                self.line_up();
+            }
+        }
+
+        let line_count = self.buffer.line_count();
+        let mut last_top = self.folds.visible_line(line_count - 1);
+        for _ in 1..self.viewport_height {
+            match self.folds.prev_visible(last_top) {
+                Some(line) => last_top = line,
+                None => break,
+            }
+        }
+        let mut top = self
+            .folds
+            .visible_line(self.scroll_offset.min(line_count - 1));
+        for _ in 0..rows {
+            let next = if down {
+                self.folds
+                    .next_visible(top, line_count)
+                    .filter(|_| top < last_top)
+            } else {
+                self.folds.prev_visible(top)
+            };
+            match next {
+                Some(line) => top = line,
+                None => break,
+            }
+        }
+        self.scroll_offset = top;
+    }
+
+    /// Scroll so the cursor line sits `row` rows from the top of the pane (zt, zz, zb)
+    pub fn scroll_cursor_to_row(&mut self, row: usize) {
+        let mut top = self.folds.visible_line(self.cursor.line);
+        for _ in 0..row {
+            match self.folds.prev_visible(top) {
+                Some(line) => top = line,
+                None => break,
+            }
+        }
+        self.scroll_offset = top;
+    }
+
+    /// Open any folds hiding the cursor (after a jump or search), then scroll it
+    /// into view with `scroll_off` rows above and below it, where the buffer
+    /// has that many lines
+    pub fn adjust_scroll(&mut self, viewport_height: usize, scroll_off: usize) {
+        self.viewport_height = viewport_height;
+        while self.folds.is_hidden(self.cursor.line) {
+            self.folds.open_at(self.cursor.line);
+        }
+        // At most half the rows, so the cursor can still move within the view
+        let off = scroll_off.min(viewport_height.saturating_sub(1) / 2);
+
+        // Vertical scroll, counting a closed fold as one row
+        let mut highest = self.cursor.line;
+        for _ in 0..off {
+            match self.folds.prev_visible(highest) {
+                Some(line) => highest = line,
+                None => break,
+            }
+        }
+        if highest < self.scroll_offset {
+            self.scroll_offset = highest;
+        }
+        let line_count = self.buffer.line_count();
+        let (mut below, mut line) = (0, self.cursor.line);
+        while below < off {
+            match self.folds.next_visible(line, line_count) {
+                Some(next) => (below, line) = (below + 1, next),
+                None => break,
+            }
+        }
+        let mut top = self.cursor.line;
+        for _ in 1..viewport_height - below {
+            match self.folds.prev_visible(top) {
+                Some(line) => top = line,
+                None => break,
+            }
+        }
+        if self.scroll_offset < top {
+            self.scroll_offset = top;
+        }
+        self.scroll_offset = self.folds.visible_line(self.scroll_offset);
+    }
+
+    /// Scroll sideways so the cursor is in view with `side_scroll_off` cells
+    /// (up to a quarter of the width) either side of it
+    pub fn adjust_scroll_horizontal(
+        &mut self,
+        viewport_width: usize,
+        tab_width: usize,
+        side_scroll_off: usize,
+    ) {
+        let margin = side_scroll_off.min(viewport_width / 4);
+        let line = self.cursor.line;
+        // Where each char up to just past the cursor starts on screen, found in
+        // one walk along the line; later columns clamp to the end of the line
+        let mut starts = vec![0];
+        if line < self.buffer.line_count() {
+            let end = (self.cursor.col + 1).min(self.buffer.line_len(line));
+            let mut cells = 0;
+            for ch in self.buffer.line(line).chars().take(end) {
+                cells += cell_width(ch, cells, tab_width);
+                starts.push(cells);
+            }
+        }
+        let col_at = |col: usize| starts[col.min(starts.len() - 1)];
 
         if self.cursor.col < self.scroll_col {
             self.scroll_col = self.cursor.col;
         }
-        if self.cursor.col >= self.scroll_col + viewport_width.saturating_sub(margin) {
-            self.scroll_col = self
-                .cursor
-                .col
-                .saturating_sub(viewport_width.saturating_sub(margin - 1));
+        while self.scroll_col > 0 && col_at(self.cursor.col) - col_at(self.scroll_col) < margin {
+            self.scroll_col -= 1;
+        }
+
+        // Scroll right until the whole cursor cell (wide glyphs and tabs included) fits
+        let cursor_end = if self.cursor.col < self.buffer.line_len(line) {
+            col_at(self.cursor.col + 1)
+        } else {
+            col_at(self.cursor.col) + 1
+        };
+        let limit = viewport_width.saturating_sub(margin);
+        while self.scroll_col < self.cursor.col && cursor_end - col_at(self.scroll_col) > limit {
+            self.scroll_col += 1;
         }
     }
 }
@@ -138,13 +1108,310 @@ mod tests {
         assert_eq!(pane.mode, Mode::FileBrowser);
     }
 
+    #[test]
+    fn goto_line_clamps_line_and_column() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("first line\nab\nlast\n");
+        pane.cursor.col = 8;
+
+        pane.goto_line(1);
+        assert_eq!((pane.cursor.line, pane.cursor.col), (1, 1));
+
+        pane.goto_line(99);
+        assert_eq!(pane.cursor.line, pane.buffer.line_count() - 1);
+    }
+
+    #[test]
+    fn scroll_position_reads_like_vims_ruler() {
+        let mut pane = Pane::new_editor(0);
+        let text: String = (1..=10).map(|n| format!("{}\n", n)).collect();
+        pane.buffer = Buffer::from_text(&text);
+        pane.viewport_height = 4;
+        assert_eq!(pane.scroll_position(), "Top");
+        pane.scroll_offset = 3;
+        assert_eq!(pane.scroll_position(), "50%"); // Three lines above, three below
+        pane.scroll_offset = 6;
+        assert_eq!(pane.scroll_position(), "Bot");
+
+        // A closed fold counts as one row
+        pane.scroll_offset = 0;
+        pane.folds.close(Fold { start: 2, end: 8 });
+        assert_eq!(pane.scroll_position(), "All");
+
+        pane.viewport_height = 20;
+        pane.buffer = Buffer::from_text("short\n");
+        pane.folds.open_all();
+        assert_eq!(pane.scroll_position(), "All");
+    }
+
+    #[test]
+    fn trailing_whitespace_is_trimmed_and_a_final_newline_added() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("let x = 1;  \n\t\n  keep\tinner \t\nclean\nlast \t");
+        pane.cursor.line = 2;
+        pane.cursor.col = 11;
+
+        assert_eq!(pane.trim_trailing_whitespace(), 4);
+        assert_eq!(
+            pane.buffer.text(),
+            "let x = 1;\n\n  keep\tinner\nclean\nlast"
+        );
+        assert_eq!((pane.cursor.line, pane.cursor.col), (2, 11));
+        assert_eq!(pane.trim_trailing_whitespace(), 0);
+
+        assert!(pane.ensure_final_newline());
+        assert_eq!(
+            pane.buffer.text(),
+            "let x = 1;\n\n  keep\tinner\nclean\nlast\n"
+        );
+        assert!(!pane.ensure_final_newline());
+    }
+
+    #[test]
+    fn folds_toggle_and_cursor_skips_hidden_lines() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("fn a() {\n    x\n    y\n}\nlast\n");
+        pane.cursor.line = 2;
+
+        // Without a grammar, folds come from indentation
+        assert!(pane.toggle_fold());
+        assert_eq!(pane.cursor.line, 0);
+        pane.line_down();
+        assert_eq!(pane.cursor.line, 3);
+        pane.line_up();
+        assert_eq!(pane.cursor.line, 0);
+
+        // A jump into the fold opens it again
+        pane.goto_line(1);
+        pane.adjust_scroll(10, 0);
+        assert!(pane.folds.is_empty());
+
+        pane.cursor.line = 4;
+        assert!(!pane.toggle_fold());
+    }
+
+    #[test]
+    fn indent_and_dedent_lines() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("fn main() {\n\n  x\n\tlet y;\n");
+
+        pane.indent_line(0, "    ");
+        pane.indent_line(1, "    "); // blank
+        assert_eq!(pane.buffer.line(0).to_string(), "    fn main() {\n");
+        assert_eq!(pane.buffer.line(1).to_string(), "\n");
+
+        pane.dedent_line(2, 4); // less than a full level
+        pane.dedent_line(3, 4); // a tab is a full level
+        assert_eq!(pane.buffer.line(2).to_string(), "x\n");
+        assert_eq!(pane.buffer.line(3).to_string(), "let y;\n");
+        assert_eq!(pane.buffer.first_non_blank(0), 4);
+    }
+
+    #[test]
+    fn toggle_comment_adds_and_removes_tokens() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("fn main() {\n    let x;\n\n        x += 1;\n}\n");
+
+        pane.toggle_comment(1, 3, "//");
+        assert_eq!(
+            pane.buffer.text(),
+            "fn main() {\n    // let x;\n\n    //     x += 1;\n}\n"
+        );
+        pane.toggle_comment(1, 3, "//");
+        assert_eq!(
+            pane.buffer.text(),
+            "fn main() {\n    let x;\n\n        x += 1;\n}\n"
+        );
+
+        // Mixed lines get commented, not uncommented
+        pane.buffer = Buffer::from_text("# a\nb\n");
+        pane.toggle_comment(0, 1, "#");
+        assert_eq!(pane.buffer.text(), "# # a\n# b\n");
+
+        pane.buffer = Buffer::from_text("  <p>hi</p>\n");
+        pane.toggle_comment(0, 0, "<!-- -->");
+        assert_eq!(pane.buffer.text(), "  <!-- <p>hi</p> -->\n");
+        pane.toggle_comment(0, 0, "<!-- -->");
+        assert_eq!(pane.buffer.text(), "  <p>hi</p>\n");
+    }
+
+    #[test]
+    fn paste_puts_lines_below_and_chars_after_the_cursor() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("ab\n  cd");
+        let line = Register {
+            text: pane.yank_lines(1),
+            linewise: true,
+        };
+        assert_eq!(line.text, "ab\n");
+
+        // Below a last line with no newline
+        pane.cursor.line = 1;
+        pane.paste(&line, false, 2);
+        assert_eq!(pane.buffer.text(), "ab\n  cd\nab\nab");
+        assert_eq!((pane.cursor.line, pane.cursor.col), (2, 0));
+
+        let chars = Register {
+            text: "xy".to_string(),
+            linewise: false,
+        };
+        pane.cursor.line = 0;
+        pane.cursor.col = 0;
+        pane.paste(&chars, false, 1);
+        assert_eq!(pane.buffer.line(0).to_string(), "axyb\n");
+        assert_eq!(pane.cursor.col, 2);
+        pane.paste(&chars, true, 1);
+        assert_eq!(pane.buffer.line(0).to_string(), "axxyyb\n");
+    }
+
+    #[test]
+    fn indent_after_copies_indentation_and_opens_blocks() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("    if x {\n\tfoo();\n");
+
+        assert_eq!(pane.indent_after(0, 10, "    "), "        ");
+        assert_eq!(pane.indent_after(0, 6, "    "), "    "); // before the brace
+        assert_eq!(pane.indent_after(1, 7, "    "), "\t");
+    }
+
+    #[test]
+    fn replace_chars_under_cursor() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("abcdef\n");
+        pane.cursor.col = 1;
+
+        pane.replace_chars('x', 3);
+        assert_eq!(pane.buffer.line(0).to_string(), "axxxef\n");
+        assert_eq!(pane.cursor.col, 3);
+
+        pane.replace_chars('y', 5); // past the end of the line
+        assert_eq!(pane.buffer.line(0).to_string(), "axxxef\n");
+
+        pane.replace_chars('\n', 1);
+        assert_eq!(pane.buffer.text(), "axx\nef\n");
+        assert_eq!((pane.cursor.line, pane.cursor.col), (1, 0));
+    }
+
+    #[test]
+    fn delete_chars_clamps_to_line_end() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("abc\n\nxyz\n");
+        pane.cursor.col = 1;
+
+        assert_eq!(pane.delete_chars(5), "bc");
+        assert_eq!(pane.buffer.line(0).to_string(), "a\n");
+        assert_eq!(pane.cursor.col, 0);
+
+        // At the end of the line, the last char goes and the cursor stays on the line
+        assert_eq!(pane.delete_chars(1), "a");
+        assert_eq!(pane.buffer.line(0).to_string(), "\n");
+
+        // Empty lines are left alone, newline included
+        assert_eq!(pane.delete_chars(1), "");
+        assert_eq!(pane.buffer.text(), "\n\nxyz\n");
+    }
+
+    #[test]
+    fn deleting_chars_takes_whole_graphemes() {
+        let mut pane = Pane::new_editor(0);
+        // "cafe" with a combining acute, then a flag and "!"
+        pane.buffer = Buffer::from_text("cafe\u{301}\u{1F1EF}\u{1F1F5}!\n");
+        pane.cursor.col = 3;
+
+        assert_eq!(pane.delete_chars(1), "e\u{301}");
+        assert_eq!(pane.delete_chars(1), "\u{1F1EF}\u{1F1F5}");
+        assert_eq!(pane.buffer.line(0).to_string(), "caf!\n");
+
+        pane.buffer = Buffer::from_text("a\u{1F1EF}\u{1F1F5}b\n");
+        pane.cursor.col = 3;
+        assert_eq!(pane.delete_chars_before(1), "\u{1F1EF}\u{1F1F5}");
+        assert_eq!(pane.cursor.col, 1);
+    }
+
+    #[test]
+    fn delete_chars_before_cursor() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("abcd\n\n");
+        pane.cursor.col = 3;
+
+        assert_eq!(pane.delete_chars_before(2), "bc");
+        assert_eq!(pane.buffer.line(0).to_string(), "ad\n");
+        assert_eq!(pane.cursor.col, 1);
+
+        assert_eq!(pane.delete_chars_before(5), "a");
+        assert_eq!(pane.cursor.col, 0);
+
+        // Nothing before the cursor on an empty line
+        pane.cursor.line = 1;
+        assert_eq!(pane.delete_chars_before(1), "");
+        assert_eq!(pane.buffer.text(), "d\n\n");
+    }
+
+    #[test]
+    fn half_page_scrolls_keep_the_cursor_row() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text(&"x\n".repeat(100));
+        pane.cursor.line = 5;
+        pane.adjust_scroll(20, 0);
+
+        pane.scroll_half_page(true);
+        assert_eq!((pane.cursor.line, pane.scroll_offset), (15, 10));
+        pane.scroll_half_page(false);
+        assert_eq!((pane.cursor.line, pane.scroll_offset), (5, 0));
+
+        // Near the end the view stops with the last line on the bottom row
+        pane.cursor.line = 85;
+        pane.scroll_offset = 75;
+        pane.scroll_half_page(true);
+        assert_eq!((pane.cursor.line, pane.scroll_offset), (95, 81));
+        pane.scroll_half_page(true);
+        assert_eq!((pane.cursor.line, pane.scroll_offset), (100, 81));
+
+        // A closed fold is one row
+        pane.folds.close(Fold { start: 10, end: 19 });
+        pane.cursor.line = 5;
+        pane.scroll_offset = 0;
+        pane.scroll_half_page(true);
+        assert_eq!((pane.cursor.line, pane.scroll_offset), (24, 10));
+    }
+
+    #[test]
+    fn gutter_widens_for_large_files() {
+        let mut pane = Pane::new_editor(0);
+        assert_eq!(pane.gutter_width(), 4);
+        pane.buffer = Buffer::from_text(&"x\n".repeat(9_999));
+        assert_eq!(pane.gutter_width(), 6);
+        pane.buffer = Buffer::from_text(&"x\n".repeat(100_000));
+        assert_eq!(pane.gutter_width(), 7);
+    }
+
+    #[test]
+    fn signs_widen_the_gutter_and_follow_their_lines() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("a\nb\nc\nd\n");
+        pane.set_sign(1, SignKind::Added);
+        pane.set_sign(3, SignKind::Removed);
+        assert_eq!(pane.gutter_width(), 5);
+
+        // A line inserted above moves both down; joining lines 1 and 2 drops 2's sign
+        pane.insert_text(0, 0, "new\n");
+        assert_eq!(pane.signs.get(&2), Some(&SignKind::Added));
+        assert_eq!(pane.signs.get(&4), Some(&SignKind::Removed));
+        pane.delete_range((1, 1), (2, 1));
+        assert_eq!(pane.signs.get(&2), None);
+        assert_eq!(pane.signs.get(&3), Some(&SignKind::Removed));
+
+        pane.clear_signs();
+        assert_eq!(pane.gutter_width(), 4);
+    }
+
     #[test]
     fn adjust_scroll_scrolls_down_when_cursor_below_viewport() {
         let mut pane = Pane::new_editor(0);
         pane.cursor.line = 25;
         pane.scroll_offset = 0;
 
-        pane.adjust_scroll(20); // viewport of 20 lines
+        pane.adjust_scroll(20, 0); // viewport of 20 lines
 
         // Cursor at 25 should scroll so cursor is visible
         // scroll_offset = cursor - viewport + 1 = 25 - 20 + 1 = 6
@@ -157,7 +1424,7 @@ mod tests {
         pane.cursor.line = 5;
         pane.scroll_offset = 10;
 
-        pane.adjust_scroll(20);
+        pane.adjust_scroll(20, 0);
 
         // Cursor at 5 is above scroll_offset of 10, so scroll up
         assert_eq!(pane.scroll_offset, 5);
@@ -169,9 +1436,259 @@ mod tests {
         pane.cursor.line = 10;
         pane.scroll_offset = 5;
 
-        pane.adjust_scroll(20);
+        pane.adjust_scroll(20, 0);
 
         // Cursor at 10 is within viewport (5..25), no change needed
         assert_eq!(pane.scroll_offset, 5);
     }
+
+    #[test]
+    fn scroll_off_keeps_lines_around_the_cursor_except_at_the_ends() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text(&"x\n".repeat(30)); // Lines 0..=30
+        let mut scroll_to = |line, scroll_off| {
+            pane.cursor.line = line;
+            pane.adjust_scroll(10, scroll_off);
+            pane.scroll_offset
+        };
+
+        // Nothing above the first line to keep
+        assert_eq!(scroll_to(0, 3), 0);
+        assert_eq!(scroll_to(2, 3), 0);
+        assert_eq!(scroll_to(6, 3), 0);
+        assert_eq!(scroll_to(7, 3), 1); // 8, 9 and 10 stay below
+        // Nothing below the last line to keep
+        assert_eq!(scroll_to(30, 3), 21);
+        assert_eq!(scroll_to(29, 3), 21);
+        assert_eq!(scroll_to(23, 3), 20);
+        // Never more than half the rows, so the cursor can still move
+        assert_eq!(scroll_to(15, 100), 11);
+        assert_eq!(scroll_to(16, 100), 11);
+        assert_eq!(scroll_to(17, 100), 12);
+
+        // Scrolling the view drags the cursor to keep its context
+        pane.cursor.line = 14;
+        pane.scroll_view(3, 3);
+        assert_eq!(pane.cursor.line, 18);
+        let shown = pane.viewport_lines();
+        assert_eq!(pane.scroll_off_rows(&shown, 3), (3, 6));
+        pane.scroll_view(-20, 3);
+        assert_eq!(pane.cursor.line, 6);
+        assert_eq!(pane.scroll_off_rows(&pane.viewport_lines(), 3), (0, 6));
+    }
+
+    #[test]
+    fn brackets_pair_up_at_the_cursor_or_just_behind_it_while_typing() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("f(a[0]) {\n}\n(\n");
+        let mut pair_at = |line, col, mode| {
+            pane.cursor.line = line;
+            pane.cursor.col = col;
+            pane.mode = mode;
+            pane.bracket_pair()
+        };
+
+        assert_eq!(pair_at(0, 1, Mode::Normal), Some([(0, 1), (0, 6)]));
+        assert_eq!(pair_at(0, 8, Mode::Normal), Some([(0, 8), (1, 0)]));
+        assert_eq!(pair_at(0, 0, Mode::Normal), None); // Not the next one on the line
+        assert_eq!(pair_at(0, 7, Mode::Normal), None);
+        assert_eq!(pair_at(0, 7, Mode::Insert), Some([(0, 6), (0, 1)]));
+        assert_eq!(pair_at(0, 5, Mode::Insert), Some([(0, 5), (0, 3)]));
+        assert_eq!(pair_at(2, 0, Mode::Normal), None); // Never closed
+    }
+
+    #[test]
+    fn viewport_lines_follow_scroll_and_skip_folds() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text(&"x\n".repeat(30));
+        pane.cursor.line = 12;
+        pane.scroll_offset = 10;
+        pane.adjust_scroll(5, 0);
+        assert_eq!(pane.viewport_lines(), vec![10, 11, 12, 13, 14]);
+
+        pane.folds.close(Fold { start: 11, end: 13 });
+        pane.cursor.line = 10;
+        assert_eq!(pane.viewport_lines(), vec![10, 11, 14, 15, 16]);
+
+        // Near the end of the buffer there are fewer lines than rows
+        pane.folds.open_all();
+        pane.scroll_offset = 28;
+        assert_eq!(pane.viewport_lines(), vec![28, 29, 30]);
+    }
+
+    #[test]
+    fn scroll_cursor_to_row_places_the_cursor_line() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text(&"x\n".repeat(30));
+        pane.cursor.line = 15;
+
+        pane.scroll_cursor_to_row(0);
+        assert_eq!(pane.scroll_offset, 15);
+        pane.scroll_cursor_to_row(4);
+        assert_eq!(pane.scroll_offset, 11);
+
+        // A closed fold above the cursor counts as one row
+        pane.folds.close(Fold { start: 10, end: 13 });
+        pane.scroll_cursor_to_row(4);
+        assert_eq!(pane.scroll_offset, 8);
+
+        // Can't scroll past the top of the buffer
+        pane.cursor.line = 1;
+        pane.scroll_cursor_to_row(4);
+        assert_eq!(pane.scroll_offset, 0);
+    }
+
+    #[test]
+    fn replace_text_keeps_the_cursor_line() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("fn main(){\nlet x=1;\n}\n");
+        pane.cursor.line = 1;
+        pane.cursor.col = 7;
+
+        pane.replace_text("fn main() {\n    let x = 1;\n}\n");
+        assert_eq!(pane.buffer.text(), "fn main() {\n    let x = 1;\n}\n");
+        assert_eq!((pane.cursor.line, pane.cursor.col), (1, 7));
+        assert!(pane.buffer.is_dirty());
+
+        // Shorter text pulls the cursor back inside it
+        pane.cursor.line = 3;
+        pane.replace_text("x\n");
+        assert_eq!((pane.cursor.line, pane.cursor.col), (1, 0));
+    }
+
+    #[test]
+    fn edits_apply_at_every_cursor() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("ab ab\nab\n");
+        pane.cursor = Cursor { line: 0, col: 3 };
+        pane.extra_cursors = vec![Cursor { line: 0, col: 0 }, Cursor { line: 1, col: 0 }];
+
+        pane.insert_char_at_cursors('x');
+        pane.insert_char_at_cursors('y');
+        assert_eq!(pane.buffer.text(), "xyab xyab\nxyab\n");
+        assert_eq!((pane.cursor.line, pane.cursor.col), (0, 7));
+        let extras: Vec<_> = pane.extra_cursors.iter().map(|c| (c.line, c.col)).collect();
+        assert_eq!(extras, vec![(0, 2), (1, 2)]);
+
+        pane.delete_char_before_cursors();
+        assert_eq!(pane.buffer.text(), "xab xab\nxab\n");
+        assert_eq!((pane.cursor.line, pane.cursor.col), (0, 5));
+
+        // The line start is a wall, and cursors that meet merge
+        pane.delete_char_before_cursors();
+        pane.delete_char_before_cursors();
+        assert_eq!(pane.buffer.text(), "abab\nab\n");
+        let extras: Vec<_> = pane.extra_cursors.iter().map(|c| (c.line, c.col)).collect();
+        assert_eq!(extras, vec![(0, 0), (1, 0)]);
+
+        pane.cursor = Cursor { line: 1, col: 1 };
+        pane.delete_char_before_cursors();
+        assert_eq!(pane.buffer.text(), "abab\nb\n");
+        let extras: Vec<_> = pane.extra_cursors.iter().map(|c| (c.line, c.col)).collect();
+        assert_eq!(extras, vec![(0, 0)]);
+        assert_eq!((pane.cursor.line, pane.cursor.col), (1, 0));
+    }
+
+    #[test]
+    fn find_char_moves_within_line() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("a(b, c(d))\nx\n");
+
+        assert!(pane.find_char(FindKind::Forward, '(', false));
+        assert_eq!(pane.cursor.col, 1);
+        assert!(pane.find_char(FindKind::Forward, '(', true));
+        assert_eq!(pane.cursor.col, 6);
+        assert!(pane.find_char(FindKind::Backward, ',', false));
+        assert_eq!(pane.cursor.col, 3);
+
+        // Not found (and never crosses into the next line)
+        assert!(!pane.find_char(FindKind::Forward, 'x', false));
+        assert_eq!(pane.cursor.col, 3);
+    }
+
+    #[test]
+    fn find_char_till_stops_beside_match() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("a,b,c\n");
+
+        assert!(pane.find_char(FindKind::TillForward, ',', false));
+        assert_eq!(pane.cursor.col, 0); // already beside the first ','
+        assert!(pane.find_char(FindKind::TillForward, ',', true));
+        assert_eq!(pane.cursor.col, 2);
+
+        pane.cursor.col = 4;
+        assert!(pane.find_char(FindKind::TillBackward, ',', false));
+        assert_eq!(pane.cursor.col, 4);
+        assert!(pane.find_char(FindKind::TillBackward, ',', true));
+        assert_eq!(pane.cursor.col, 2);
+    }
+
+    #[test]
+    fn adjust_scroll_horizontal_accounts_for_wide_chars() {
+        let mut pane = Pane::new_editor(0);
+        // 10 wide chars = 20 cells
+        pane.buffer = Buffer::from_text("日本語日本語日本語日\n");
+        pane.cursor.col = 5; // starts at cell 10
+
+        pane.adjust_scroll_horizontal(12, 4, 5); // margin 3, so 9 usable cells
+
+        // Cursor glyph must end within 9 cells: chars 2..=5 take 8 cells
+        assert_eq!(pane.scroll_col, 2);
+    }
+
+    #[test]
+    fn adjust_scroll_horizontal_expands_tabs() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("\t\t\tx\n");
+        pane.cursor.col = 3; // starts at cell 12
+
+        pane.adjust_scroll_horizontal(12, 4, 5); // margin 3, so 9 usable cells
+
+        assert_eq!(pane.scroll_col, 1);
+    }
+
+    #[test]
+    fn adjust_scroll_horizontal_scrolls_left_to_cursor() {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text("hello world\n");
+        pane.cursor.col = 2;
+        pane.scroll_col = 6;
+
+        pane.adjust_scroll_horizontal(20, 4, 0);
+
+        assert_eq!(pane.scroll_col, 2);
+
+        // Or short of it, to keep columns of context
+        pane.cursor.col = 6;
+        pane.scroll_col = 5;
+        pane.adjust_scroll_horizontal(20, 4, 3);
+        assert_eq!(pane.scroll_col, 3);
+    }
+
+    /// Timing for typing into a large file; run with
+    /// `cargo test --release reparse_benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn reparse_benchmark() {
+        let mut pane = Pane::new_editor(0);
+        let source = "fn f(x: u32) -> u32 {\n    let y = x * 2; // double\n    y + 1\n}\n";
+        pane.buffer = Buffer::from_text(&source.repeat(5000));
+        pane.set_language(Language::Rust);
+        if !pane.highlighter.is_active() {
+            return; // Grammar not installed
+        }
+
+        let rounds = 200;
+        let start = Instant::now();
+        for i in 0..rounds {
+            pane.insert_text(1 + (i % 100) * 4, 4, "z");
+            pane.reparse_pending(Duration::ZERO);
+        }
+        let elapsed = start.elapsed();
+        println!(
+            "{} lines: {:?} per keystroke reparse",
+            pane.buffer.line_count(),
+            elapsed / rounds as u32
+        );
+    }
 }
@@ -0,0 +1,100 @@
+//! Recently opened files, persisted to `~/.config/lark/recent`
+//!
+//! The file holds one absolute path per line, most recent first.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Most files remembered
+const MAX_RECENT: usize = 100;
+
+/// Most-recently-used file list
+#[derive(Debug, Default)]
+pub struct RecentFiles {
+    store: Option<PathBuf>, // Where the list is saved; None keeps it in memory
+    paths: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    /// The list saved in `~/.config/lark/recent`
+    pub fn load() -> Self {
+        match dirs::home_dir() {
+            Some(home) => Self::from_file(home.join(".config").join("lark").join("recent")),
+            None => Self::default(),
+        }
+    }
+
+    /// The list saved in `store`, which is created on the first `add`
+    pub fn from_file(store: PathBuf) -> Self {
+        let paths = fs::read_to_string(&store)
+            .map(|content| content.lines().map(PathBuf::from).collect())
+            .unwrap_or_default();
+        Self {
+            store: Some(store),
+            paths,
+        }
+    }
+
+    /// Move `path` to the front of the list and save it
+    pub fn add(&mut self, path: &Path) {
+        let Ok(path) = path.canonicalize() else {
+            return; // Never saved, nothing to come back to
+        };
+        self.paths.retain(|p| *p != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT);
+        self.save();
+    }
+
+    /// Remembered files that still exist, most recent first
+    pub fn existing(&self) -> Vec<PathBuf> {
+        self.paths.iter().filter(|p| p.is_file()).cloned().collect()
+    }
+
+    fn save(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        if let Some(dir) = store.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let content: String = self
+            .paths
+            .iter()
+            .filter(|p| p.exists())
+            .map(|p| format!("{}\n", p.display()))
+            .collect();
+        let _ = fs::write(store, content);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_dedupes_persists_and_skips_missing_files() {
+        let dir = std::env::temp_dir().join(format!("lark-recent-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let store = dir.join("state").join("recent");
+
+        let mut recent = RecentFiles::from_file(store.clone());
+        recent.add(&a);
+        recent.add(&b);
+        // The same file through another path counts once
+        recent.add(&dir.join(".").join("a.txt"));
+        recent.add(&dir.join("never-written.txt"));
+        fs::remove_file(&b).unwrap();
+
+        let reloaded = RecentFiles::from_file(store);
+        let existing = reloaded.existing();
+        let a = a.canonicalize().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(existing, vec![a]);
+        assert_eq!(reloaded.paths.len(), 2);
+    }
+}
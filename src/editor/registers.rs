@@ -0,0 +1,148 @@
+//! Registers: text kept by deletes and yanks, put back with `p` and `P`
+//!
+//! Every delete (`x`, `X`, `D`, `C`, `diw`, ...) and yank (`yy`) writes the
+//! unnamed register, which `p` reads when no register is named. Prefixing a
+//! command with `"a`..`"z` also writes (or reads) that named register; `"A`..`"Z`
+//! append to it instead. `"_` discards the text without touching the unnamed
+//! register. `"+` and `"*` are the system clipboard, and stand for the
+//! unnamed register with `clipboard = "system"`.
+
+use std::collections::HashMap;
+
+use super::clipboard::Clipboard;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool, // Whole lines (from `yy`), put on their own lines by `p`
+}
+
+#[derive(Default)]
+pub struct Registers {
+    unnamed: Register,
+    named: HashMap<char, Register>, // Including + and *, in case the clipboard fails
+    clipboard: Clipboard,
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `"<name>` names a register
+    pub fn is_valid(name: char) -> bool {
+        name.is_ascii_alphabetic() || matches!(name, '"' | '_' | '+' | '*')
+    }
+
+    /// Keep `text` in the unnamed register and in `name`, if given
+    pub fn write(&mut self, name: Option<char>, text: String, linewise: bool) {
+        if let Some('+' | '*') = name {
+            self.clipboard.set(&text);
+        }
+        let register = match name {
+            Some('_') => return,
+            Some(upper) if upper.is_ascii_uppercase() => {
+                let named = self.named.entry(upper.to_ascii_lowercase()).or_default();
+                // Appending lines to characters makes the whole register linewise
+                if linewise && !named.text.is_empty() && !named.text.ends_with('\n') {
+                    named.text.push('\n');
+                }
+                named.text.push_str(&text);
+                named.linewise |= linewise;
+                named.clone()
+            }
+            Some(name) if name != '"' => {
+                let register = Register { text, linewise };
+                self.named.insert(name, register.clone());
+                register
+            }
+            _ => Register { text, linewise },
+        };
+        self.unnamed = register;
+    }
+
+    /// The register `name` names, or the unnamed one. Empty registers are None.
+    pub fn read(&mut self, name: Option<char>) -> Option<Register> {
+        let register = match name {
+            Some('_') => return None,
+            Some(name @ ('+' | '*')) => {
+                let kept = self.named.get(&name);
+                match self.clipboard.get() {
+                    // Our own yank, which knows whether it was whole lines
+                    Some(text) if kept.is_some_and(|r| r.text == text) => kept.cloned()?,
+                    Some(text) => Register {
+                        linewise: text.ends_with('\n'),
+                        text,
+                    },
+                    None => kept.cloned()?,
+                }
+            }
+            Some(name) if name != '"' => self.named.get(&name.to_ascii_lowercase())?.clone(),
+            _ => self.unnamed.clone(),
+        };
+        Some(register).filter(|r| !r.text.is_empty())
+    }
+
+    /// Why the clipboard couldn't be used, the first time it fails
+    pub fn take_warning(&mut self) -> Option<String> {
+        self.clipboard.take_warning()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_registers_also_fill_the_unnamed_one() {
+        let mut registers = Registers::new();
+        registers.write(Some('a'), "one\n".to_string(), true);
+        registers.write(None, "x".to_string(), false);
+
+        assert_eq!(registers.read(Some('a')).unwrap().text, "one\n");
+        assert!(registers.read(Some('a')).unwrap().linewise);
+        assert_eq!(registers.read(None).unwrap().text, "x");
+        assert_eq!(registers.read(Some('"')).unwrap().text, "x");
+        assert_eq!(registers.read(Some('b')), None);
+
+        // The black hole keeps nothing, not even in the unnamed register
+        registers.write(Some('_'), "gone".to_string(), false);
+        assert_eq!(registers.read(None).unwrap().text, "x");
+        assert_eq!(registers.read(Some('_')), None);
+    }
+
+    #[test]
+    fn clipboard_registers_fall_back_to_internal_ones() {
+        let mut registers = Registers {
+            clipboard: Clipboard::failed("no display"),
+            ..Registers::default()
+        };
+        registers.write(Some('+'), "line\n".to_string(), true);
+
+        // Kept as yanked, in the unnamed register too, but not in "*
+        let plus = registers.read(Some('+')).unwrap();
+        assert_eq!(plus.text, "line\n");
+        assert!(plus.linewise);
+        assert_eq!(registers.read(None), Some(plus));
+        assert_eq!(registers.read(Some('*')), None);
+
+        registers.write(Some('*'), "word".to_string(), false);
+        assert_eq!(registers.read(Some('*')).unwrap().text, "word");
+        assert_eq!(registers.read(Some('+')).unwrap().text, "line\n");
+        assert!(registers.take_warning().is_some());
+    }
+
+    #[test]
+    fn uppercase_names_append() {
+        let mut registers = Registers::new();
+        registers.write(Some('a'), "foo".to_string(), false);
+        registers.write(Some('A'), "bar".to_string(), false);
+        assert_eq!(registers.read(Some('a')).unwrap().text, "foobar");
+
+        registers.write(Some('A'), "line\n".to_string(), true);
+        let a = registers.read(Some('A')).unwrap();
+        assert_eq!(a.text, "foobar\nline\n");
+        assert!(a.linewise);
+        assert_eq!(registers.read(None), Some(a));
+    }
+}
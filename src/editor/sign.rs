@@ -0,0 +1,44 @@
+//! Signs: one-cell markers (git changes) drawn between a line's
+//! number and its text
+//!
+//! A pane keeps at most one sign per line. The sign column only takes up
+//! room while the pane has signs.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignKind {
+    Added,
+    Changed,
+    Removed, // Lines deleted below this one
+}
+
+impl SignKind {
+    pub fn glyph(self) -> char {
+        match self {
+            SignKind::Added => '+',
+            SignKind::Changed => '~',
+            SignKind::Removed => '-',
+        }
+    }
+}
+
+/// Keep signs on their lines after lines `start..=old_end` became
+/// `start..=new_end`, as `Marks::shift` does. Signs on deleted lines are dropped.
+pub fn shift(signs: &mut HashMap<usize, SignKind>, start: usize, old_end: usize, new_end: usize) {
+    if signs.is_empty() {
+        return;
+    }
+    *signs = signs
+        .drain()
+        .filter_map(|(line, kind)| {
+            if line > old_end {
+                Some((line + new_end - old_end, kind))
+            } else if line > start && line > new_end {
+                None
+            } else {
+                Some((line, kind))
+            }
+        })
+        .collect();
+}
@@ -1,11 +1,8 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
 
 use super::file_browser::FileBrowser;
-use super::layout::{Layout, Rect, SplitDirection};
+use super::layout::{Edge, Layout, Rect, SplitDirection};
 use super::pane::{Pane, PaneId, PaneKind};
-use super::{Buffer, Cursor};
-use crate::syntax::Language;
 
 /// A tab contains multiple panes with their layout
 pub struct Tab {
@@ -35,27 +32,6 @@ impl Tab {
         }
     }
 
-    pub fn with_file(path: PathBuf) -> Self {
-        let name = path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "[No Name]".to_string());
-
-        let initial_pane = Pane::new_editor_with_file(0, path);
-        let mut panes = HashMap::new();
-        panes.insert(0, initial_pane);
-
-        Self {
-            panes,
-            layout: Layout::new(0),
-            focused_pane_id: 0,
-            next_pane_id: 1,
-            file_browser: FileBrowser::new(),
-            file_browser_pane_id: None,
-            name,
-        }
-    }
-
     pub fn focused_pane(&self) -> &Pane {
         self.panes
             .get(&self.focused_pane_id)
@@ -83,17 +59,15 @@ impl Tab {
     // Split operations
 
     pub fn split_vertical(&mut self) {
-        let new_id = self.next_pane_id;
-        self.next_pane_id += 1;
-
-        let new_pane = Pane::new_editor(new_id);
-
-        self.panes.insert(new_id, new_pane);
-        self.layout
-            .split_pane(self.focused_pane_id, new_id, SplitDirection::Vertical);
+        self.split(SplitDirection::Vertical);
     }
 
     pub fn split_horizontal(&mut self) {
+        self.split(SplitDirection::Horizontal);
+    }
+
+    /// Split the focused pane, returning the new (empty, unfocused) pane
+    pub fn split(&mut self, direction: SplitDirection) -> PaneId {
         let new_id = self.next_pane_id;
         self.next_pane_id += 1;
 
@@ -101,7 +75,8 @@ impl Tab {
 
         self.panes.insert(new_id, new_pane);
         self.layout
-            .split_pane(self.focused_pane_id, new_id, SplitDirection::Horizontal);
+            .split_pane(self.focused_pane_id, new_id, direction);
+        new_id
     }
 
     pub fn focus_next(&mut self) {
@@ -121,6 +96,45 @@ impl Tab {
         }
     }
 
+    /// Grow (or with a negative delta, shrink) the focused pane's nearest
+    /// split in `direction`
+    pub fn resize_focused(&mut self, direction: SplitDirection, delta: f32) -> bool {
+        self.layout
+            .resize_pane(self.focused_pane_id, direction, delta)
+    }
+
+    /// Swap the focused pane with the next editor pane, keeping focus on it
+    pub fn swap_with_next(&mut self) -> bool {
+        if Some(self.focused_pane_id) == self.file_browser_pane_id {
+            return false;
+        }
+        let ids: Vec<PaneId> = self
+            .layout
+            .pane_ids()
+            .into_iter()
+            .filter(|&id| Some(id) != self.file_browser_pane_id)
+            .collect();
+        let Some(pos) = ids.iter().position(|&id| id == self.focused_pane_id) else {
+            return false;
+        };
+        if ids.len() < 2 {
+            return false;
+        }
+        let next = ids[(pos + 1) % ids.len()];
+        self.layout.swap_panes(self.focused_pane_id, next);
+        true
+    }
+
+    /// Swap the sides of the split around the focused pane; the file browser
+    /// stays where it is
+    pub fn rotate_focused_split(&mut self) -> bool {
+        if Some(self.focused_pane_id) == self.file_browser_pane_id {
+            return false;
+        }
+        self.layout
+            .rotate_split(self.focused_pane_id, self.file_browser_pane_id)
+    }
+
     pub fn get_editor_panes_with_labels(&self) -> Vec<(char, PaneId)> {
         self.layout
             .pane_ids()
@@ -146,48 +160,49 @@ impl Tab {
         }
     }
 
-    pub fn open_file_in_pane(&mut self, path: PathBuf, label: char) -> bool {
-        let labeled = self.get_editor_panes_with_labels();
-        if let Some((_, pane_id)) = labeled.iter().find(|(l, _)| *l == label) {
-            if let Some(pane) = self.panes.get_mut(pane_id) {
-                pane.buffer = Buffer::from_file(path.clone());
-                pane.cursor = Cursor::new();
-                pane.scroll_offset = 0;
-
-                // Set up syntax highlighting
-                let lang = Language::from_path(&path);
-                pane.language = lang;
-                if pane.highlighter.set_language(lang) {
-                    pane.highlighter.parse(&pane.buffer.text());
-                }
-
-                self.focused_pane_id = *pane_id;
-                return true;
-            }
-        }
-        false
+    /// Whether any pane in this tab has unsaved changes
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.panes.values().any(|p| p.buffer.is_dirty())
     }
 
-    pub fn open_file_in_focused_pane(&mut self, path: PathBuf) {
-        if let Some(pane) = self.panes.get_mut(&self.focused_pane_id) {
-            pane.buffer = Buffer::from_file(path.clone());
-            pane.cursor = Cursor::new();
-            pane.scroll_offset = 0;
+    /// Whether a pane other than the focused one has unsaved changes
+    pub fn others_have_unsaved_changes(&self) -> bool {
+        let focused = self.focused_pane_id;
+        self.panes
+            .values()
+            .any(|p| p.id != focused && p.buffer.is_dirty())
+    }
 
-            // Set up syntax highlighting
-            let lang = Language::from_path(&path);
-            pane.language = lang;
-            if pane.highlighter.set_language(lang) {
-                pane.highlighter.parse(&pane.buffer.text());
-            }
+    /// Close every pane but the focused one (`:only`) and hand them back
+    pub fn remove_other_panes(&mut self) -> Vec<Pane> {
+        let focused = self.focused_pane_id;
+        self.layout.keep_only(focused);
+        if self.file_browser_pane_id != Some(focused) {
+            self.file_browser_pane_id = None;
         }
+        let others: Vec<PaneId> = self
+            .panes
+            .keys()
+            .copied()
+            .filter(|&id| id != focused)
+            .collect();
+        others
+            .into_iter()
+            .filter_map(|id| self.panes.remove(&id))
+            .collect()
     }
 
     /// Close the current pane. Returns true if closed, false if it was the last pane.
     pub fn close_focused_pane(&mut self) -> bool {
+        self.remove_focused_pane().is_some()
+    }
+
+    /// Close the current pane and hand it back (so its buffer can be kept).
+    /// Returns None if it was the last pane.
+    pub fn remove_focused_pane(&mut self) -> Option<Pane> {
         let pane_ids = self.layout.pane_ids();
         if pane_ids.len() <= 1 {
-            return false;
+            return None;
         }
 
         if Some(self.focused_pane_id) == self.file_browser_pane_id {
@@ -197,13 +212,13 @@ impl Tab {
         let closed_id = self.focused_pane_id;
         self.focus_next();
         self.layout.remove_pane(closed_id);
-        self.panes.remove(&closed_id);
-        true
+        self.panes.remove(&closed_id)
     }
 
     // File browser
 
-    pub fn toggle_file_browser(&mut self) {
+    /// Close the file browser, or open it along `edge` taking `ratio` of the screen
+    pub fn toggle_file_browser(&mut self, edge: Edge, ratio: f32) {
         if let Some(fb_id) = self.file_browser_pane_id {
             if self.focused_pane_id == fb_id {
                 self.focus_next();
@@ -212,54 +227,28 @@ impl Tab {
             self.panes.remove(&fb_id);
             self.file_browser_pane_id = None;
         } else {
-            self.open_file_browser();
+            self.open_file_browser(edge, ratio);
         }
     }
 
-    fn open_file_browser(&mut self) {
+    fn open_file_browser(&mut self, edge: Edge, ratio: f32) {
         let new_id = self.next_pane_id;
         self.next_pane_id += 1;
 
         let fb_pane = Pane::new_file_browser(new_id);
         self.panes.insert(new_id, fb_pane);
-        self.layout.add_left_pane(new_id, 0.2);
+        self.layout.add_edge_pane(new_id, edge, ratio);
         self.file_browser_pane_id = Some(new_id);
         self.file_browser.refresh();
         self.focused_pane_id = new_id;
     }
 
-    pub fn focus_file_browser(&mut self) {
+    /// Focus the file browser, opening it as `toggle_file_browser` does if it's closed
+    pub fn focus_file_browser(&mut self, edge: Edge, ratio: f32) {
         if let Some(fb_id) = self.file_browser_pane_id {
             self.focused_pane_id = fb_id;
         } else {
-            self.open_file_browser();
-        }
-    }
-
-    pub fn try_open_file_from_browser(&mut self) -> Option<PathBuf> {
-        let editor_panes = self.get_editor_panes_with_labels();
-
-        if editor_panes.len() <= 1 {
-            if let Some(path) = self.file_browser.select() {
-                if let Some((_, pane_id)) = editor_panes.first() {
-                    if let Some(pane) = self.panes.get_mut(pane_id) {
-                        pane.buffer = Buffer::from_file(path.clone());
-                        pane.cursor = Cursor::new();
-                        pane.scroll_offset = 0;
-
-                        // Set up syntax highlighting
-                        let lang = Language::from_path(&path);
-                        pane.language = lang;
-                        if pane.highlighter.set_language(lang) {
-                            pane.highlighter.parse(&pane.buffer.text());
-                        }
-                    }
-                    self.focused_pane_id = *pane_id;
-                }
-            }
-            None
-        } else {
-            self.file_browser.select()
+            self.open_file_browser(edge, ratio);
         }
     }
 
@@ -267,15 +256,7 @@ impl Tab {
     pub fn update_name(&mut self) {
         if let Some(pane) = self.panes.get(&self.focused_pane_id) {
             if pane.kind == PaneKind::Editor {
-                self.name = pane
-                    .buffer
-                    .path()
-                    .map(|p| {
-                        p.file_name()
-                            .map(|n| n.to_string_lossy().to_string())
-                            .unwrap_or_else(|| "[No Name]".to_string())
-                    })
-                    .unwrap_or_else(|| "[No Name]".to_string());
+                self.name = pane.buffer.display_name();
             }
         }
     }
@@ -354,6 +335,21 @@ mod tests {
         assert_eq!(tab.panes.len(), 1);
     }
 
+    #[test]
+    fn only_the_focused_pane_is_kept() {
+        let mut tab = Tab::new();
+        tab.split_vertical();
+        tab.split_horizontal();
+        tab.toggle_file_browser(Edge::Left, 0.2);
+        tab.focused_pane_id = 1;
+
+        let closed = tab.remove_other_panes();
+        assert_eq!(closed.len(), 3);
+        assert_eq!(tab.panes.len(), 1);
+        assert_eq!(tab.layout.pane_ids(), vec![1]);
+        assert_eq!(tab.file_browser_pane_id, None);
+    }
+
     #[test]
     fn get_editor_panes_with_labels_assigns_letters() {
         let mut tab = Tab::new();
@@ -393,12 +389,50 @@ mod tests {
         let mut tab = Tab::new();
         assert!(tab.file_browser_pane_id.is_none());
 
-        tab.toggle_file_browser();
+        tab.toggle_file_browser(Edge::Left, 0.2);
         assert!(tab.file_browser_pane_id.is_some());
         assert_eq!(tab.panes.len(), 2);
 
-        tab.toggle_file_browser();
+        tab.toggle_file_browser(Edge::Left, 0.2);
         assert!(tab.file_browser_pane_id.is_none());
         assert_eq!(tab.panes.len(), 1);
     }
+
+    #[test]
+    fn the_file_browser_opens_on_any_edge_and_closes_back_to_an_editor() {
+        for edge in [Edge::Left, Edge::Right, Edge::Bottom] {
+            let mut tab = Tab::new();
+            tab.split_vertical();
+            tab.toggle_file_browser(edge, 0.3);
+            let fb_id = tab.file_browser_pane_id.unwrap();
+            assert_eq!(tab.focused_pane_id, fb_id);
+            let ids = tab.layout.pane_ids();
+            let at_start = edge == Edge::Left;
+            assert_eq!(ids[if at_start { 0 } else { 2 }], fb_id);
+
+            tab.toggle_file_browser(edge, 0.3);
+            assert!(tab.file_browser_pane_id.is_none());
+            assert!(tab.panes[&tab.focused_pane_id].kind == PaneKind::Editor);
+            assert_eq!(tab.layout.pane_ids(), vec![0, 1]);
+        }
+    }
+
+    #[test]
+    fn swap_with_next_skips_the_file_browser() {
+        let mut tab = Tab::new();
+        tab.split_vertical();
+        tab.toggle_file_browser(Edge::Left, 0.2);
+        let fb_id = tab.file_browser_pane_id.unwrap();
+        assert!(!tab.swap_with_next());
+        assert!(!tab.rotate_focused_split());
+
+        tab.focused_pane_id = 1;
+        assert!(tab.swap_with_next());
+        assert_eq!(tab.layout.pane_ids(), vec![fb_id, 1, 0]);
+        // Focus follows the pane to its new position
+        assert_eq!(tab.focused_pane_id, 1);
+
+        assert!(tab.rotate_focused_split());
+        assert_eq!(tab.layout.pane_ids(), vec![fb_id, 0, 1]);
+    }
 }
@@ -0,0 +1,250 @@
+//! Text objects (`iw`, `aw`, `i"`, `a(`, ...) resolved to ranges on a line
+
+use super::Buffer;
+
+/// What a text object selects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObjectKind {
+    Word,             // w
+    Pair(char, char), // ( [ { < and their aliases b / B
+    Quote(char),      // " ' `
+}
+
+/// A text object: `inner` excludes delimiters/whitespace, `around` includes them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextObject {
+    pub kind: TextObjectKind,
+    pub around: bool,
+}
+
+impl TextObject {
+    /// Parse the key after `i`/`a` (e.g. `w`, `"`, `(`, `b`)
+    pub fn from_key(key: char, around: bool) -> Option<Self> {
+        let kind = match key {
+            'w' => TextObjectKind::Word,
+            '(' | ')' | 'b' => TextObjectKind::Pair('(', ')'),
+            '[' | ']' => TextObjectKind::Pair('[', ']'),
+            '{' | '}' | 'B' => TextObjectKind::Pair('{', '}'),
+            '<' | '>' => TextObjectKind::Pair('<', '>'),
+            '"' | '\'' | '`' => TextObjectKind::Quote(key),
+            _ => return None,
+        };
+        Some(Self { kind, around })
+    }
+
+    /// Column range `start..end` (chars) the object covers on `line` around
+    /// `col`. A count takes that many words (`2aw`) or the count-th enclosing
+    /// pair (`2i(`); quotes ignore it.
+    pub fn range(
+        &self,
+        buffer: &Buffer,
+        line: usize,
+        col: usize,
+        count: usize,
+    ) -> Option<(usize, usize)> {
+        if line >= buffer.line_count() {
+            return None;
+        }
+        let chars: Vec<char> = buffer
+            .line(line)
+            .chars()
+            .take(buffer.line_len(line))
+            .collect();
+        if chars.is_empty() {
+            return None;
+        }
+        let col = col.min(chars.len() - 1);
+
+        match self.kind {
+            TextObjectKind::Word => {
+                let (start, mut end) = word_range(&chars, col, self.around)?;
+                // Each further word starts where the last one ended
+                for _ in 1..count {
+                    if end >= chars.len() {
+                        break;
+                    }
+                    end = word_range(&chars, end, self.around)?.1;
+                }
+                Some((start, end))
+            }
+            TextObjectKind::Pair(open, close) => {
+                let (mut start, mut end) = pair_range(&chars, col, open, close, true)?;
+                for _ in 1..count {
+                    (start, end) = pair_range(&chars, start.checked_sub(1)?, open, close, true)?;
+                }
+                Some(if self.around {
+                    (start, end)
+                } else {
+                    (start + 1, end - 1)
+                })
+            }
+            TextObjectKind::Quote(quote) => quote_range(&chars, col, quote, self.around),
+        }
+    }
+}
+
+/// Character class for word objects: word chars, whitespace, or other punctuation
+fn class(c: char) -> u8 {
+    if c.is_alphanumeric() || c == '_' {
+        0
+    } else if c.is_whitespace() {
+        1
+    } else {
+        2
+    }
+}
+
+fn word_range(chars: &[char], col: usize, around: bool) -> Option<(usize, usize)> {
+    let run = |from: usize| {
+        let cls = class(chars[from]);
+        let mut start = from;
+        while start > 0 && class(chars[start - 1]) == cls {
+            start -= 1;
+        }
+        let mut end = from + 1;
+        while end < chars.len() && class(chars[end]) == cls {
+            end += 1;
+        }
+        (start, end)
+    };
+
+    let (start, end) = run(col);
+    if !around {
+        return Some((start, end));
+    }
+
+    if class(chars[col]) == 1 {
+        // On whitespace: the whitespace plus the following word
+        return Some(if end < chars.len() {
+            (start, run(end).1)
+        } else {
+            (start, end)
+        });
+    }
+    // Prefer trailing whitespace, falling back to leading whitespace
+    if end < chars.len() && class(chars[end]) == 1 {
+        Some((start, run(end).1))
+    } else if start > 0 && class(chars[start - 1]) == 1 {
+        Some((run(start - 1).0, end))
+    } else {
+        Some((start, end))
+    }
+}
+
+fn pair_range(
+    chars: &[char],
+    col: usize,
+    open: char,
+    close: char,
+    around: bool,
+) -> Option<(usize, usize)> {
+    // Unmatched open bracket at or before the cursor
+    let mut depth = 0usize;
+    let mut start = None;
+    for i in (0..=col).rev() {
+        if chars[i] == close && i != col {
+            depth += 1;
+        } else if chars[i] == open {
+            if depth == 0 {
+                start = Some(i);
+                break;
+            }
+            depth -= 1;
+        }
+    }
+    let start = start?;
+
+    // Its matching close bracket
+    depth = 0;
+    let end = (start + 1..chars.len()).find(|&i| {
+        if chars[i] == open {
+            depth += 1;
+        } else if chars[i] == close {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+
+    Some(if around {
+        (start, end + 1)
+    } else {
+        (start + 1, end)
+    })
+}
+
+fn quote_range(chars: &[char], col: usize, quote: char, around: bool) -> Option<(usize, usize)> {
+    // Quotes pair up left to right; use the pair containing the cursor,
+    // or the first one after it
+    let quotes: Vec<usize> = (0..chars.len()).filter(|&i| chars[i] == quote).collect();
+    let (start, end) = quotes
+        .chunks_exact(2)
+        .map(|p| (p[0], p[1]))
+        .find(|&(_, end)| col <= end)?;
+
+    Some(if around {
+        (start, end + 1)
+    } else {
+        (start + 1, end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(text: &str, col: usize, key: char, around: bool) -> Option<(usize, usize)> {
+        let buffer = Buffer::from_text(text);
+        TextObject::from_key(key, around)
+            .unwrap()
+            .range(&buffer, 0, col, 1)
+    }
+
+    #[test]
+    fn word_objects() {
+        let text = "let foo_bar = 1;\n";
+        assert_eq!(range(text, 5, 'w', false), Some((4, 11)));
+        assert_eq!(range(text, 5, 'w', true), Some((4, 12))); // trailing space
+        assert_eq!(range(text, 14, 'w', true), Some((13, 15))); // `1` takes leading space
+        assert_eq!(range(text, 15, 'w', false), Some((15, 16))); // punctuation run
+    }
+
+    #[test]
+    fn counts_take_more_words_or_outer_pairs() {
+        let buffer = Buffer::from_text("let foo = f(a, g(b));\n");
+        let range = |key, around, col, count| {
+            let object = TextObject::from_key(key, around).unwrap();
+            object.range(&buffer, 0, col, count)
+        };
+        assert_eq!(range('w', false, 4, 3), Some((4, 9))); // foo, space, =
+        assert_eq!(range('w', true, 4, 2), Some((4, 10))); // "foo = "
+        assert_eq!(range('w', true, 0, 99), Some((0, 21))); // Stops at the line end
+        assert_eq!(range('(', false, 17, 1), Some((17, 18)));
+        assert_eq!(range('(', false, 17, 2), Some((12, 19)));
+        assert_eq!(range('(', true, 17, 2), Some((11, 20)));
+        assert_eq!(range('(', false, 17, 3), None); // Only two levels
+    }
+
+    #[test]
+    fn bracket_objects_handle_nesting() {
+        let text = "f(a, g(b), c)\n";
+        assert_eq!(range(text, 3, '(', false), Some((2, 12)));
+        assert_eq!(range(text, 7, 'b', false), Some((7, 8)));
+        assert_eq!(range(text, 6, ')', true), Some((6, 9))); // on the open bracket
+        assert_eq!(range(text, 8, '(', false), Some((7, 8))); // on the close bracket
+        assert_eq!(range(text, 0, '(', false), None); // not inside any pair
+        assert_eq!(range("f(a\n", 2, '(', false), None); // unbalanced
+    }
+
+    #[test]
+    fn quote_objects() {
+        let text = "say(\"hi\", \"there\")\n";
+        assert_eq!(range(text, 5, '"', false), Some((5, 7)));
+        assert_eq!(range(text, 5, '"', true), Some((4, 8)));
+        assert_eq!(range(text, 0, '"', false), Some((5, 7))); // next pair on the line
+        assert_eq!(range(text, 12, '"', false), Some((11, 16)));
+        assert_eq!(range(text, 12, '\'', false), None);
+    }
+}
@@ -0,0 +1,106 @@
+//! Insert-mode completion of the word before the cursor from the words in
+//! the buffer (`Ctrl-N` and `Ctrl-P`)
+
+/// Buffer words that start with the prefix being completed, while Ctrl-N and
+/// Ctrl-P cycle through them
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordCompletion {
+    pub line: usize,
+    pub start: usize, // Column the prefix starts at
+    pub candidates: Vec<String>,
+    pub selected: usize,
+}
+
+impl WordCompletion {
+    /// The words in `words` that complete `prefix` (typed at `start` on
+    /// `line`), or None if there are none. Ctrl-P (`!forward`) starts from
+    /// the last match.
+    pub fn new(
+        words: &[String],
+        line: usize,
+        start: usize,
+        prefix: &str,
+        forward: bool,
+    ) -> Option<Self> {
+        let candidates: Vec<String> = words
+            .iter()
+            .filter(|w| w.starts_with(prefix) && w.as_str() != prefix)
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let selected = if forward { 0 } else { candidates.len() - 1 };
+        Some(Self {
+            line,
+            start,
+            candidates,
+            selected,
+        })
+    }
+
+    /// The match in the buffer
+    pub fn current(&self) -> &str {
+        &self.candidates[self.selected]
+    }
+
+    /// Move to the next match, or the previous one if `!forward`
+    pub fn step(&mut self, forward: bool) {
+        let count = self.candidates.len();
+        self.selected = if forward {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+}
+
+/// Whether `c` can be part of a completed word
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The distinct words of two or more chars in `text`, sorted
+pub fn words(text: impl Iterator<Item = char>) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in text.chain(std::iter::once(' ')) {
+        if is_word_char(c) {
+            word.push(c);
+        } else if !word.is_empty() {
+            let word = std::mem::take(&mut word);
+            if word.chars().nth(1).is_some() {
+                words.push(word);
+            }
+        }
+    }
+    words.sort_unstable();
+    words.dedup();
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_complete_to_other_words_in_the_text() {
+        let words = words("let total = to_string(total) + tot; x\n".chars());
+        assert_eq!(words, ["let", "to_string", "tot", "total"]);
+
+        let mut completion = WordCompletion::new(&words, 0, 4, "tot", true).unwrap();
+        assert_eq!(completion.current(), "total"); // Not the prefix itself
+        completion.step(true);
+        assert_eq!(completion.current(), "total");
+
+        let mut completion = WordCompletion::new(&words, 0, 4, "t", false).unwrap();
+        assert_eq!(completion.current(), "total");
+        completion.step(false);
+        assert_eq!(completion.current(), "tot");
+        completion.step(true);
+        completion.step(true);
+        assert_eq!(completion.current(), "to_string");
+
+        assert_eq!(WordCompletion::new(&words, 0, 0, "z", true), None);
+    }
+}
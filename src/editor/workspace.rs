@@ -1,16 +1,50 @@
-use std::path::PathBuf;
-
-use super::Mode;
-use super::layout::{Direction, Rect};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::buffer_list::{BufferList, BufferView};
+use super::completion::Completion;
+use super::formatter;
+use super::git;
+use super::history::CommandHistory;
+use super::layout::{Direction, Edge, Rect, SplitDirection};
+use super::messages::{MessageLog, Severity};
 use super::mode::SearchDirection;
 use super::pane::PaneId;
+use super::recent::RecentFiles;
+use super::registers::Registers;
 use super::tab::Tab;
+use super::{Buffer, Mode};
+use crate::config::Settings;
+use crate::finder::{self, GrepMatch};
+use crate::render::Picker;
+use crate::syntax::{InstallEvent, InstallResult, Language, LanguageRegistry};
+use crate::theme::{self, Theme};
 
 /// Pending finder action
 #[derive(Debug, Clone)]
 pub enum FinderAction {
     FindFile,
     Grep(String),
+    GrepWord, // The identifier under the cursor, jumping straight to a lone match
+    RecentFiles,
+}
+
+/// The grammars of a `:TSInstall all` still installing, and how the rest went
+#[derive(Debug, Default)]
+struct InstallBatch {
+    remaining: HashSet<Language>,
+    installed: usize,
+    failed: Vec<&'static str>,
+}
+
+/// Work for the script engine, which the main loop owns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditorEvent {
+    Open(PathBuf),           // A file was read from disk into a buffer
+    Save(PathBuf),           // A buffer was written
+    Command(String, String), // A custom command was run, with its arguments
 }
 
 /// State for the message viewer
@@ -21,6 +55,72 @@ pub struct MessageViewerState {
     pub title: String,
 }
 
+/// What picking each item of the picker does
+#[derive(Debug, Clone)]
+pub enum PickerTargets {
+    Locations(Vec<GrepMatch>), // Open the file at the match (grep, symbols, files)
+    Buffers(Vec<PathBuf>),     // Switch the focused pane to the buffer
+}
+
+/// What the answer to a status-line prompt is used for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptAction {
+    CreateFile,
+    CreateDir,
+    Rename,
+    ConfirmDelete,
+    // Create the missing directories above `path`, then write the focused
+    // buffer there (renaming it if `rename`); answered with y/n
+    ConfirmSaveAs { path: PathBuf, rename: bool },
+    ConfirmQuit, // Close the focused pane (or quit) without saving it; y/n
+}
+
+impl PromptAction {
+    /// Whether the prompt is a yes/no question, answered with a single key
+    pub fn is_confirmation(&self) -> bool {
+        matches!(
+            self,
+            PromptAction::ConfirmDelete
+                | PromptAction::ConfirmSaveAs { .. }
+                | PromptAction::ConfirmQuit
+        )
+    }
+}
+
+/// A question asked on the status line (e.g. a file name in the file browser)
+pub struct Prompt {
+    pub label: String,
+    pub input: String,
+    pub action: PromptAction,
+}
+
+/// A partly typed command, shown in the status line as it's typed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PendingKeys {
+    pub register: Option<char>, // Named with "x
+    pub count: Option<usize>,
+    pub keys: String, // Operator and prefix keys so far, e.g. "d", "gc", "C-w"
+    pub awaiting: Option<&'static str>, // What the next key is taken as, e.g. "text object"
+}
+
+impl PendingKeys {
+    pub fn is_empty(&self) -> bool {
+        self.register.is_none() && self.count.is_none() && self.keys.is_empty()
+    }
+}
+
+impl std::fmt::Display for PendingKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(name) = self.register {
+            write!(f, "\"{}", name)?;
+        }
+        if let Some(count) = self.count {
+            write!(f, "{}", count)?;
+        }
+        write!(f, "{}", self.keys)
+    }
+}
+
 /// A search match in a buffer
 #[derive(Debug, Clone)]
 pub struct SearchMatch {
@@ -71,19 +171,41 @@ pub struct Workspace {
     pub tabs: Vec<Tab>,
     pub active_tab: usize,
     pub command_buffer: String,
+    pub command_cursor: usize, // Byte offset of the cursor in `command_buffer`
+    pub command_history: CommandHistory,
+    pub command_completion: Option<Completion>, // Matches Tab is cycling through
     pub message: Option<String>,
     pub error: Option<String>, // Multiline error messages
     pub running: bool,
-    pub pending_keys: String,
+    pub pending_keys: PendingKeys,
+    pub recording_macro: Option<char>, // Shown in the status line while q records into it
+    pub key_hints: Vec<(String, String)>, // Keys that can follow a pending prefix, and what they do
+    pub show_key_hints: bool, // Set by the main loop once a prefix has been pending a moment
     pub selecting_pane: bool,
     pub theme_name: String,
+    pub theme: Theme, // `theme_name` loaded, so it isn't looked up every frame
     pub pending_finder: Option<FinderAction>,
     pub terminal_size: (u16, u16), // (width, height)
     pub log: Vec<String>,          // Editor log messages
+    pub messages: MessageLog,      // Status-line messages and errors, shown by :messages
     pub verbose: bool,             // Verbose logging mode
     pub message_viewer: Option<MessageViewerState>,
+    pub picker: Option<(Picker, PickerTargets)>, // Shown in place of the message viewer
+    mode_before_viewer: Mode, // Restored when the message viewer or picker closes
     pub search: SearchState,
-    pub search_buffer: String, // Input buffer for search mode
+    pub search_buffer: String,  // Input buffer for search mode
+    pub registers: Registers,   // Text from deletes and yanks, see `Registers`
+    pub buffers: BufferList,    // Open buffers, including ones not shown in a pane
+    pub prompt: Option<Prompt>, // Status-line input in progress
+    pub settings: Settings,     // Resolved settings from the config script
+    pub install_events: Option<UnboundedSender<InstallEvent>>, // Set by the main loop
+    pub installing: HashSet<Language>, // Grammars being installed in the background
+    install_batch: Option<InstallBatch>, // A `:TSInstall all` in progress
+    grammar_hints: HashSet<Language>, // Missing grammars already reported or installed
+    pub events: Vec<EditorEvent>, // Raised for scripts, drained by the main loop
+    pub reload_config: bool,    // Set by :source, handled by the main loop
+    pub script_commands: HashSet<String>, // Custom commands defined in the config
+    pub recent: RecentFiles,    // Files opened in any pane, see `track_recent_files`
 }
 
 impl Workspace {
@@ -92,19 +214,41 @@ impl Workspace {
             tabs: vec![Tab::new()],
             active_tab: 0,
             command_buffer: String::new(),
+            command_cursor: 0,
+            command_history: CommandHistory::default(),
+            command_completion: None,
             message: None,
             error: None,
             running: true,
-            pending_keys: String::new(),
+            pending_keys: PendingKeys::default(),
+            recording_macro: None,
+            key_hints: Vec::new(),
+            show_key_hints: false,
             selecting_pane: false,
             theme_name: "gruvbox-dark".to_string(),
+            theme: Theme::default(),
             pending_finder: None,
             terminal_size: (80, 24),
             log: Vec::new(),
+            messages: MessageLog::default(),
             verbose: false,
             message_viewer: None,
+            picker: None,
+            mode_before_viewer: Mode::Normal,
             search: SearchState::new(),
             search_buffer: String::new(),
+            registers: Registers::new(),
+            buffers: BufferList::new(),
+            prompt: None,
+            settings: Settings::default(),
+            install_events: None,
+            installing: HashSet::new(),
+            install_batch: None,
+            grammar_hints: HashSet::new(),
+            events: Vec::new(),
+            reload_config: false,
+            script_commands: HashSet::new(),
+            recent: RecentFiles::default(),
         }
     }
 
@@ -116,13 +260,228 @@ impl Workspace {
             scroll_col: 0,
             title: title.to_string(),
         });
-        self.focused_pane_mut().mode = super::Mode::MessageViewer;
+        self.enter_message_viewer();
+    }
+
+    /// Switch the focused pane to the message viewer's mode, remembering the
+    /// mode to go back to. Commands opening it from the `:` line go back to
+    /// normal mode.
+    fn enter_message_viewer(&mut self) {
+        let pane = self.focused_pane_mut();
+        match pane.mode {
+            Mode::MessageViewer => {}
+            Mode::Command => self.mode_before_viewer = Mode::Normal,
+            mode => self.mode_before_viewer = mode,
+        }
+        self.focused_pane_mut().mode = Mode::MessageViewer;
+    }
+
+    /// Open the picker over the panes; picking an item uses its target
+    pub fn show_picker(&mut self, picker: Picker, targets: PickerTargets) {
+        self.picker = Some((picker, targets));
+        self.enter_message_viewer();
+    }
+
+    /// Rows the picker's list has on screen (less the title, help and status lines)
+    pub fn picker_height(&self) -> usize {
+        self.terminal_size.1.saturating_sub(3) as usize
+    }
+
+    /// Close the picker and carry out what picking the item at `index` does
+    pub fn pick(&mut self, index: usize) {
+        let Some((_, targets)) = self.picker.take() else {
+            return;
+        };
+        self.close_message_viewer();
+        match targets {
+            PickerTargets::Locations(matches) => self.open_grep_match(&matches[index]),
+            PickerTargets::Buffers(paths) => {
+                self.open_file_in_focused_pane(paths[index].clone());
+            }
+        }
+    }
+
+    /// Where the finders search: the root of the focused file's project (or of
+    /// the working directory's, for unnamed buffers), or the working directory
+    /// itself outside a project or with `finder_root = "cwd"`
+    pub fn finder_root(&self) -> PathBuf {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        if self.settings.finder_from_cwd {
+            return cwd;
+        }
+        let start = match self.focused_pane().buffer.path() {
+            Some(path) => cwd
+                .join(path)
+                .parent()
+                .map_or(cwd.clone(), Path::to_path_buf),
+            None => cwd.clone(),
+        };
+        finder::find_project_root(&start).unwrap_or(cwd)
+    }
+
+    /// Show grep results in the picker (Enter opens a match)
+    pub fn show_grep_results(&mut self, pattern: &str, matches: Vec<GrepMatch>, cwd: &Path) {
+        let items = matches
+            .iter()
+            .map(|m| {
+                let file = m.file.strip_prefix(cwd).unwrap_or(&m.file);
+                format!("{}:{}:{}: {}", file.display(), m.line, m.col, m.text)
+            })
+            .collect();
+        let picker = Picker::new(&format!("Grep: {}", pattern), items);
+        self.show_picker(picker, PickerTargets::Locations(matches));
+    }
+
+    /// Open the file of a grep match with the cursor on it
+    pub fn open_grep_match(&mut self, grep_match: &GrepMatch) {
+        // No file is the focused buffer, for symbols of an unnamed one
+        if !grep_match.file.as_os_str().is_empty()
+            && !self.open_file_in_focused_pane(grep_match.file.clone())
+        {
+            return;
+        }
+        let pane = self.focused_pane_mut();
+        pane.record_jump();
+        pane.cursor.line = grep_match.line.saturating_sub(1);
+        pane.cursor.col = grep_match.col.saturating_sub(1);
+    }
+
+    /// Show files in the picker (Enter opens one)
+    pub fn show_file_picker(&mut self, title: &str, files: Vec<PathBuf>, cwd: &Path) {
+        let items = files
+            .iter()
+            .map(|f| f.strip_prefix(cwd).unwrap_or(f).display().to_string())
+            .collect();
+        let matches = files
+            .into_iter()
+            .map(|file| GrepMatch {
+                file,
+                line: 1,
+                col: 1,
+                text: String::new(),
+            })
+            .collect();
+        self.show_picker(Picker::new(title, items), PickerTargets::Locations(matches));
+    }
+
+    /// Show the open buffers in the picker, as `:ls` lists them, starting
+    /// from the focused one; Enter switches to one
+    pub fn show_buffer_picker(&mut self) {
+        let (paths, items): (Vec<PathBuf>, Vec<String>) = self.buffer_listing().into_iter().unzip();
+        if paths.is_empty() {
+            self.set_message("No buffers");
+            return;
+        }
+        let current = self.focused_pane().buffer.path();
+        let selected = paths.iter().position(|p| Some(p) == current);
+
+        let mut picker = Picker::new("Buffers", items);
+        if let Some(selected) = selected {
+            picker.select(selected, self.picker_height());
+        }
+        self.show_picker(picker, PickerTargets::Buffers(paths));
     }
 
-    /// Close the message viewer
+    /// List the focused buffer's definitions in the picker, starting from the
+    /// one the cursor is in; Enter jumps to it
+    pub fn show_symbols(&mut self) {
+        let pane = self.focused_pane_mut();
+        let symbols = pane.symbols();
+        if symbols.is_empty() {
+            self.set_message("No symbols found");
+            return;
+        }
+        let cursor_line = pane.cursor.line;
+        let title = format!("Symbols: {}", pane.buffer.display_name());
+        // An unnamed buffer's symbols open in the focused pane (see `open_grep_match`)
+        let file = pane.buffer.path().cloned().unwrap_or_default();
+        let matches: Vec<GrepMatch> = symbols
+            .iter()
+            .map(|symbol| GrepMatch {
+                file: file.clone(),
+                line: symbol.line + 1,
+                col: pane.buffer.first_non_blank(symbol.line) + 1,
+                text: symbol.name.clone(),
+            })
+            .collect();
+
+        let width = symbols.last().map_or(1, |s| (s.line + 1).to_string().len());
+        let items = symbols
+            .iter()
+            .map(|s| format!("{:>width$}: {} {}", s.line + 1, s.kind, s.name))
+            .collect();
+        let selected = symbols
+            .iter()
+            .rposition(|s| s.line <= cursor_line)
+            .unwrap_or(0);
+
+        let mut picker = Picker::new(&title, items);
+        picker.select(selected, self.picker_height());
+        self.show_picker(picker, PickerTargets::Locations(matches));
+    }
+
+    /// Close the message viewer or picker, going back to the mode it was
+    /// opened from
     pub fn close_message_viewer(&mut self) {
         self.message_viewer = None;
-        self.focused_pane_mut().mode = super::Mode::Normal;
+        self.picker = None;
+        self.focused_pane_mut().mode = self.mode_before_viewer;
+    }
+
+    /// Ask for input on the status line; `initial` pre-fills the answer
+    pub fn start_prompt(&mut self, label: impl Into<String>, initial: &str, action: PromptAction) {
+        self.prompt = Some(Prompt {
+            label: label.into(),
+            input: initial.to_string(),
+            action,
+        });
+    }
+
+    /// Ask a yes/no question on the status line; `y` carries out `action`
+    pub fn confirm(&mut self, message: &str, action: PromptAction) {
+        self.start_prompt(format!("{} (y/n) ", message), "", action);
+    }
+
+    /// Open the `:` command line, empty
+    pub fn start_command(&mut self) {
+        self.focused_pane_mut().mode = super::Mode::Command;
+        self.command_buffer.clear();
+        self.command_cursor = 0;
+        self.command_history.reset();
+        self.command_completion = None;
+    }
+
+    /// Complete the word before the command-line cursor, or replace it with
+    /// the next match (the previous one if `!forward`) when already completing
+    pub fn complete_command(&mut self, forward: bool) {
+        let cursor = self.command_cursor.min(self.command_buffer.len());
+        if let Some(completion) = &mut self.command_completion {
+            completion.step(forward);
+        } else {
+            let mut commands: Vec<String> = self.script_commands.iter().cloned().collect();
+            commands.extend(self.settings.command_aliases.keys().cloned());
+            let line = &self.command_buffer[..cursor];
+            let Some(mut completion) = Completion::new(line, &commands) else {
+                return;
+            };
+            if !forward {
+                completion.step(false);
+            }
+            self.command_completion = Some(completion);
+        }
+
+        let Some(completion) = &self.command_completion else {
+            return;
+        };
+        // The word being completed ends at the cursor
+        let word = completion.start..cursor;
+        self.command_buffer
+            .replace_range(word, completion.current());
+        self.command_cursor = completion.start + completion.current().len();
+        // A lone match is done with, so the next Tab completes from it
+        if completion.candidates.len() == 1 {
+            self.command_completion = None;
+        }
     }
 
     /// Start a search
@@ -172,10 +531,26 @@ impl Workspace {
             };
 
             self.search.current_match = match_idx;
+            self.focused_pane_mut().record_jump();
             self.jump_to_current_match();
         }
     }
 
+    /// Search for the whole word under the cursor (`*`, or `#` backwards),
+    /// leaving it as the pattern for n and N
+    pub fn search_word_under_cursor(&mut self, direction: SearchDirection) {
+        let Some((start, word)) = self.focused_pane().word_under_cursor() else {
+            self.set_error("No identifier under cursor");
+            return;
+        };
+        // From the start of the word, so `#` skips the one the cursor is on
+        self.focused_pane_mut().cursor.col = start;
+        self.search.direction = direction;
+        self.search_buffer = format!("\\b{}\\b", word);
+        self.execute_search();
+        self.search_buffer.clear();
+    }
+
     /// Cancel search input
     pub fn cancel_search(&mut self) {
         self.search.is_inputting = false;
@@ -191,27 +566,38 @@ impl Workspace {
             return;
         }
 
-        // Collect all line strings first to avoid borrow issues
-        let pane = self.focused_pane();
-        let line_count = pane.buffer.line_count();
-        let lines: Vec<String> = (0..line_count)
-            .map(|i| pane.buffer.line(i).chars().collect())
-            .collect();
-
-        // Now search through the collected lines
-        for (line_idx, line_str) in lines.iter().enumerate() {
-            let mut start = 0;
-            while let Some(pos) = line_str[start..].find(&query) {
-                let match_start = start + pos;
-                let match_end = match_start + query.len();
-                self.search.matches.push(SearchMatch {
-                    line: line_idx,
-                    start_col: match_start,
-                    end_col: match_end,
+        let ignore_case = self.search_ignores_case(&query);
+        // `\b` at either end only matches at a word boundary
+        let (word_start, rest) = match query.strip_prefix("\\b") {
+            Some(rest) => (true, rest),
+            None => (false, query.as_str()),
+        };
+        let (word_end, text) = match rest.strip_suffix("\\b") {
+            Some(text) if !text.is_empty() => (true, text),
+            _ => (false, rest),
+        };
+        let query: Vec<char> = text.chars().collect();
+        let is_word_char = |c: &char| c.is_alphanumeric() || *c == '_';
+        let buffer = &self.focused_pane().buffer;
+        let mut matches = Vec::new();
+        for line in 0..buffer.line_count() {
+            let text: Vec<char> = buffer.line(line).chars().collect();
+            let found = find_in_line(&text, &query, ignore_case).into_iter();
+            for start_col in found.filter(|&start| {
+                let before = start.checked_sub(1).map(|i| &text[i]);
+                let after = text.get(start + query.len());
+                (!word_start || !before.is_some_and(is_word_char))
+                    && (!word_end || !after.is_some_and(is_word_char))
+            }) {
+                matches.push(SearchMatch {
+                    line,
+                    start_col,
+                    end_col: start_col + query.len(),
                 });
-                start = match_start + 1;
             }
         }
+        self.search.matches = matches;
+        let query = self.search.query.clone();
 
         if self.search.matches.is_empty() {
             self.set_message(format!("Pattern not found: {}", query));
@@ -224,6 +610,51 @@ impl Workspace {
         }
     }
 
+    /// Whether searching for `query` ignores case: with `smart_case`, only
+    /// when it has no capitals, otherwise when `ignore_case` is set
+    fn search_ignores_case(&self, query: &str) -> bool {
+        if self.settings.smart_case {
+            !query.chars().any(char::is_uppercase)
+        } else {
+            self.settings.ignore_case
+        }
+    }
+
+    /// Match the last search again, e.g. after the case settings change
+    pub fn refresh_search(&mut self) {
+        if self.search.active {
+            self.find_matches();
+        }
+    }
+
+    /// Put a cursor on every match of the last search; the current match gets the
+    /// primary one. Returns how many cursors there are.
+    pub fn select_all_matches(&mut self) -> usize {
+        // Matches may be from another pane or stale after edits
+        self.find_matches();
+        if self.search.matches.is_empty() {
+            return 0;
+        }
+        self.search.current_match = self.search.current_match.min(self.search.matches.len() - 1);
+        self.jump_to_current_match();
+
+        let current = self.search.current_match;
+        let extra: Vec<_> = self
+            .search
+            .matches
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != current)
+            .map(|(_, m)| super::Cursor {
+                line: m.line,
+                col: m.start_col,
+            })
+            .collect();
+        let count = extra.len() + 1;
+        self.focused_pane_mut().extra_cursors = extra;
+        count
+    }
+
     /// Jump to the current match
     fn jump_to_current_match(&mut self) {
         let current = self.search.current_match;
@@ -236,7 +667,7 @@ impl Workspace {
         }
     }
 
-    /// Go to next search match
+    /// Go to next search match, showing the matches again after `:noh`
     pub fn search_next(&mut self) {
         if self.search.matches.is_empty() {
             // Try to re-search with the last query
@@ -258,6 +689,7 @@ impl Workspace {
             };
         }
 
+        self.search.active = true;
         self.jump_to_current_match();
         self.set_message(format!(
             "[{}/{}]",
@@ -266,7 +698,7 @@ impl Workspace {
         ));
     }
 
-    /// Go to previous search match
+    /// Go to previous search match, showing the matches again after `:noh`
     pub fn search_prev(&mut self) {
         if self.search.matches.is_empty() {
             if !self.search.query.is_empty() {
@@ -287,6 +719,7 @@ impl Workspace {
             self.search.current_match = (self.search.current_match + 1) % self.search.matches.len();
         }
 
+        self.search.active = true;
         self.jump_to_current_match();
         self.set_message(format!(
             "[{}/{}]",
@@ -300,24 +733,160 @@ impl Workspace {
         self.search.active = false;
     }
 
-    pub fn open(path: PathBuf) -> Self {
-        Self {
-            tabs: vec![Tab::with_file(path)],
-            active_tab: 0,
-            command_buffer: String::new(),
-            message: None,
-            error: None,
-            running: true,
-            pending_keys: String::new(),
-            selecting_pane: false,
-            theme_name: "gruvbox-dark".to_string(),
-            pending_finder: None,
-            terminal_size: (80, 24),
-            log: Vec::new(),
-            verbose: false,
-            message_viewer: None,
-            search: SearchState::new(),
-            search_buffer: String::new(),
+    /// A workspace showing `path`, opened under `settings` (which decide,
+    /// say, whether it's large enough to skip highlighting)
+    pub fn open(path: PathBuf, settings: Settings) -> Self {
+        let mut workspace = Self::new();
+        workspace.apply_settings(settings);
+        workspace.open_file_in_focused_pane(path);
+        workspace
+    }
+
+    /// A workspace showing `text` in an unnamed buffer called `name`
+    pub fn with_scratch(name: &str, text: &str) -> Self {
+        let mut workspace = Self::new();
+        workspace.focused_pane_mut().buffer = Buffer::scratch(name, text);
+        workspace.tab_mut().update_name();
+        workspace
+    }
+
+    // Grammar installs
+
+    /// Start installing a grammar in the background (`:TSInstall`)
+    pub fn start_install(&mut self, lang: Language) {
+        if self.installing.contains(&lang) {
+            self.set_message(format!(
+                "{} grammar is already being installed",
+                lang.name()
+            ));
+            return;
+        }
+        let Some(events) = self.install_events.clone() else {
+            self.set_error("Grammar installs are unavailable");
+            return;
+        };
+        self.installing.insert(lang);
+        self.set_message(format!("Installing {} grammar...", lang.name()));
+        crate::syntax::spawn_install(vec![lang], events);
+    }
+
+    /// Install every grammar that's missing or outdated, one at a time
+    /// (`:TSInstall all`), summing up once they've all finished
+    pub fn start_install_all(&mut self) {
+        let registry = LanguageRegistry::new();
+        let langs: Vec<_> = Language::all_installable()
+            .into_iter()
+            .filter(|&lang| !registry.is_installed(lang) || registry.needs_reinstall(lang))
+            .filter(|lang| !self.installing.contains(lang))
+            .collect();
+        if langs.is_empty() {
+            self.set_message("All grammars are installed");
+            return;
+        }
+        let Some(events) = self.install_events.clone() else {
+            self.set_error("Grammar installs are unavailable");
+            return;
+        };
+        self.installing.extend(&langs);
+        self.install_batch = Some(InstallBatch {
+            remaining: langs.iter().copied().collect(),
+            ..InstallBatch::default()
+        });
+        self.set_message(format!("Installing {} grammars...", langs.len()));
+        crate::syntax::spawn_install(langs, events);
+    }
+
+    /// Show progress from a background install; once it succeeds, re-highlight
+    /// any open buffers in that language
+    pub fn handle_install_event(&mut self, event: InstallEvent) {
+        match event {
+            InstallEvent::Progress(lang, stage) => {
+                self.log_verbose(format!("[TSInstall {}] {}", lang.name(), stage));
+                self.set_message(format!("{}: {}", lang.name(), stage));
+            }
+            InstallEvent::Finished(lang, result) => {
+                self.installing.remove(&lang);
+                let in_batch = self
+                    .install_batch
+                    .as_mut()
+                    .is_some_and(|batch| batch.remaining.remove(&lang));
+                if in_batch {
+                    self.finish_batch_install(lang, result);
+                    return;
+                }
+                match result {
+                    InstallResult::Success => {
+                        self.set_message(format!("{} grammar installed successfully!", lang.name()))
+                    }
+                    InstallResult::AlreadyInstalled => {
+                        self.set_message(format!("{} grammar is already installed", lang.name()))
+                    }
+                    InstallResult::Reinstalled => self
+                        .set_message(format!("{} grammar reinstalled (ABI updated)", lang.name())),
+                    InstallResult::Error(e) => {
+                        self.set_error(format!(
+                            "Failed to install {} grammar:\n{}",
+                            lang.name(),
+                            e
+                        ));
+                        return;
+                    }
+                }
+                self.rehighlight(lang);
+            }
+        }
+    }
+
+    /// Count one finished grammar of a `:TSInstall all`, summing up after the last
+    fn finish_batch_install(&mut self, lang: Language, result: InstallResult) {
+        let Some(batch) = self.install_batch.as_mut() else {
+            return;
+        };
+        match result {
+            InstallResult::Error(e) => {
+                batch.failed.push(lang.name());
+                self.log(format!("[TSInstall {}] Failed: {}", lang.name(), e));
+            }
+            _ => {
+                batch.installed += 1;
+                self.set_message(format!("{} grammar installed", lang.name()));
+                self.rehighlight(lang);
+            }
+        }
+
+        if self
+            .install_batch
+            .as_ref()
+            .is_some_and(|b| b.remaining.is_empty())
+        {
+            let batch = self.install_batch.take().unwrap_or_default();
+            if batch.failed.is_empty() {
+                self.set_message(format!(
+                    "Installed {} grammars successfully",
+                    batch.installed
+                ));
+            } else {
+                self.set_error(format!(
+                    "Installed {}, failed {}: {} (see :log)",
+                    batch.installed,
+                    batch.failed.len(),
+                    batch.failed.join(", ")
+                ));
+            }
+        }
+    }
+
+    /// Highlight open buffers in a language whose grammar was just installed
+    fn rehighlight(&mut self, lang: Language) {
+        let mut query_error = None;
+        for pane in self.tabs.iter_mut().flat_map(|t| t.panes.values_mut()) {
+            if pane.language == lang {
+                pane.set_language(lang);
+                query_error = pane.highlighter.take_query_error().or(query_error);
+            }
+        }
+        if let Some(err) = query_error {
+            self.set_error(err);
         }
     }
 
@@ -335,11 +904,40 @@ impl Workspace {
         }
     }
 
+    /// Add a detail to the log, only in verbose mode (`--verbose` or `:verbose`)
+    pub fn log_verbose(&mut self, msg: impl Into<String>) {
+        if self.verbose {
+            self.log(msg);
+        }
+    }
+
     /// Get the log as a formatted string
     pub fn get_log(&self) -> String {
         self.log.join("\n")
     }
 
+    // Syntax highlighting
+
+    /// Whether any pane has edits waiting to be re-parsed
+    pub fn needs_reparse(&self) -> bool {
+        self.tabs
+            .iter()
+            .any(|tab| tab.panes.values().any(|pane| pane.needs_reparse))
+    }
+
+    /// Re-parse panes whose edits have been pending for at least `debounce`
+    ///
+    /// Returns true if any highlights changed and the screen should be redrawn.
+    pub fn reparse_pending(&mut self, debounce: std::time::Duration) -> bool {
+        let mut reparsed = false;
+        for tab in &mut self.tabs {
+            for pane in tab.panes.values_mut() {
+                reparsed |= pane.reparse_pending(debounce);
+            }
+        }
+        reparsed
+    }
+
     // Tab access
 
     pub fn tab(&self) -> &Tab {
@@ -364,6 +962,42 @@ impl Workspace {
         self.tab().pane(id)
     }
 
+    /// The panes' part of the terminal
+    pub fn pane_area(&self) -> Rect {
+        let (width, height) = self.terminal_size;
+        self.content_area(width, height)
+    }
+
+    /// The panes' part of a `width` x `height` screen: below the tab bar (if
+    /// any), above the status line
+    pub fn content_area(&self, width: u16, height: u16) -> Rect {
+        let tab_bar_height = if self.tab_count() > 1 { 1 } else { 0 };
+        Rect::new(
+            0,
+            tab_bar_height,
+            width,
+            height.saturating_sub(1 + tab_bar_height),
+        )
+    }
+
+    /// The pane drawn over screen cell (x, y), and its rect
+    pub fn pane_at(&self, x: u16, y: u16) -> Option<(PaneId, Rect)> {
+        self.calculate_rects(self.pane_area())
+            .into_iter()
+            .find(|(_, rect)| rect.contains(x, y))
+    }
+
+    pub fn pane_mut(&mut self, id: PaneId) -> Option<&mut super::Pane> {
+        self.tab_mut().panes.get_mut(&id)
+    }
+
+    pub fn focus_pane(&mut self, id: PaneId) {
+        if self.tab().panes.contains_key(&id) {
+            self.tab_mut().focused_pane_id = id;
+            self.tab_mut().update_name();
+        }
+    }
+
     pub fn calculate_rects(&self, area: Rect) -> Vec<(PaneId, Rect)> {
         self.tab().calculate_rects(area)
     }
@@ -390,6 +1024,12 @@ impl Workspace {
         self.tab_mut().split_horizontal();
     }
 
+    /// Split the focused pane and focus the new, empty one
+    pub fn split_and_focus(&mut self, direction: SplitDirection) {
+        let pane_id = self.tab_mut().split(direction);
+        self.focus_pane(pane_id);
+    }
+
     pub fn focus_next(&mut self) {
         self.tab_mut().focus_next();
     }
@@ -401,6 +1041,18 @@ impl Workspace {
         self.tab_mut().focus_direction(direction, pane_area);
     }
 
+    pub fn resize_focused(&mut self, direction: SplitDirection, delta: f32) -> bool {
+        self.tab_mut().resize_focused(direction, delta)
+    }
+
+    pub fn swap_with_next(&mut self) -> bool {
+        self.tab_mut().swap_with_next()
+    }
+
+    pub fn rotate_focused_split(&mut self) -> bool {
+        self.tab_mut().rotate_focused_split()
+    }
+
     pub fn get_editor_panes_with_labels(&self) -> Vec<(char, PaneId)> {
         self.tab().get_editor_panes_with_labels()
     }
@@ -410,34 +1062,415 @@ impl Workspace {
     }
 
     pub fn open_file_in_pane(&mut self, path: PathBuf, label: char) -> bool {
-        let result = self.tab_mut().open_file_in_pane(path, label);
+        let labeled = self.get_editor_panes_with_labels();
+        if let Some(&(_, pane_id)) = labeled.iter().find(|(l, _)| *l == label) {
+            self.show_buffer_in_pane(pane_id, &path);
+            self.tab_mut().focused_pane_id = pane_id;
+            self.tab_mut().update_name();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Show `path` in the focused pane; false if its buffer can't be put
+    /// aside (see `show_buffer_in_pane`)
+    pub fn open_file_in_focused_pane(&mut self, path: PathBuf) -> bool {
+        let pane_id = self.tab().focused_pane_id;
+        let shown = self.show_buffer_in_pane(pane_id, &path);
         self.tab_mut().update_name();
-        result
+        shown
+    }
+
+    /// Record opened files in `recent` from now on, starting with the focused one
+    pub fn track_recent_files(&mut self, mut recent: RecentFiles) {
+        if let Some(path) = self.focused_pane().buffer.path() {
+            recent.add(path);
+        }
+        self.recent = recent;
+    }
+
+    /// Write the focused buffer to its file, raising a save event
+    pub fn save_focused(&mut self) -> std::io::Result<()> {
+        let buffer = &mut self.focused_pane_mut().buffer;
+        buffer.save()?;
+        if let Some(path) = buffer.path().cloned() {
+            self.events.push(EditorEvent::Save(path));
+        }
+        self.update_git_signs();
+        Ok(())
+    }
+
+    /// Open the file named under the cursor (`gf`), looking for a relative
+    /// path beside the focused file, then in the project root and the
+    /// working directory
+    pub fn goto_file(&mut self) -> Result<(), String> {
+        let pane = self.focused_pane();
+        let line: String = pane.buffer.line(pane.cursor.line).chars().collect();
+        let name = path_at(&line, pane.cursor.col).ok_or("No file name under cursor")?;
+
+        let path = match name.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir().ok_or("No home directory")?.join(rest),
+            None => PathBuf::from(&name),
+        };
+        let candidates = if path.is_absolute() {
+            vec![path]
+        } else {
+            let beside = pane
+                .buffer
+                .path()
+                .and_then(|p| p.parent())
+                .map(|dir| dir.join(&path));
+            let from_root = self.finder_root().join(&path);
+            beside.into_iter().chain([from_root, path]).collect()
+        };
+        let found = candidates
+            .into_iter()
+            .find(|p| p.is_file())
+            .ok_or_else(|| format!("Can't find file \"{}\"", name))?;
+
+        // Drop `./` parts so the same file isn't opened twice under two names
+        let found = found
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect();
+        self.open_file_in_focused_pane(found);
+        Ok(())
+    }
+
+    /// Open a file read-only in the focused pane (`:view`)
+    pub fn view_file(&mut self, path: PathBuf) -> Result<(), String> {
+        if !path.is_file() {
+            return Err(format!("No such file: {}", path.display()));
+        }
+        if self.open_file_in_focused_pane(path) {
+            self.focused_pane_mut().buffer.set_read_only(true);
+        }
+        Ok(())
     }
 
-    pub fn open_file_in_focused_pane(&mut self, path: PathBuf) {
-        self.tab_mut().open_file_in_focused_pane(path);
+    /// Write the focused buffer to `path`, saving it there from now on (`:saveas`)
+    pub fn save_focused_as(&mut self, path: PathBuf) -> std::io::Result<()> {
+        self.focused_pane_mut().buffer.save_as(path.clone())?;
+        self.buffers.register(&path);
         self.tab_mut().update_name();
+        self.events.push(EditorEvent::Save(path));
+        self.update_git_signs();
+        Ok(())
+    }
+
+    /// Recompute the focused buffer's git signs from its file, as just saved
+    fn update_git_signs(&mut self) {
+        if self.settings.git_signs {
+            self.focused_pane_mut().update_git_signs();
+        }
+    }
+
+    /// Read the focused buffer's file again, discarding its changes (`:e!`)
+    pub fn reload_focused(&mut self) -> Result<(), String> {
+        let pane = self.focused_pane_mut();
+        let name = pane.buffer.display_name();
+        pane.reload()
+            .map_err(|e| format!("Failed to reload {}: {}", name, e))?;
+        self.update_git_signs();
+        Ok(())
+    }
+
+    /// Reload the buffers in every pane whose files something else has
+    /// written, unless they have unsaved changes; those are only warned about,
+    /// once per outside write. Returns whether there was anything to say.
+    pub fn check_files_on_disk(&mut self) -> bool {
+        let git_signs = self.settings.git_signs;
+        let mut reloaded = Vec::new();
+        let mut changed = Vec::new();
+        for pane in self.tabs.iter_mut().flat_map(|t| t.panes.values_mut()) {
+            if pane.kind != super::PaneKind::Editor || !pane.buffer.changed_on_disk() {
+                continue;
+            }
+            let name = pane.buffer.display_name();
+            if pane.buffer.is_dirty() {
+                pane.buffer.acknowledge_disk_change();
+                changed.push(name);
+            } else if pane.reload().is_ok() {
+                if git_signs {
+                    pane.update_git_signs();
+                }
+                reloaded.push(name);
+            }
+        }
+
+        if !changed.is_empty() {
+            self.set_message(format!(
+                "{} changed on disk; :e! reloads it, discarding your changes",
+                changed.join(", ")
+            ));
+        } else if !reloaded.is_empty() {
+            self.set_message(format!("Reloaded {}", reloaded.join(", ")));
+        }
+        !changed.is_empty() || !reloaded.is_empty()
+    }
+
+    /// Write a copy of the focused buffer to `path` (`:w <path>`)
+    pub fn write_focused_copy(&self, path: &Path) -> std::io::Result<()> {
+        self.focused_pane().buffer.write_copy(path)
+    }
+
+    /// Pipe the focused buffer through its language's formatter (`:fmt`).
+    /// On failure the buffer is left as it was and the formatter's stderr is returned.
+    pub fn format_focused(&mut self) -> Result<(), String> {
+        if self.focused_pane().buffer.is_read_only() {
+            return Err("Buffer is read-only".to_string());
+        }
+        let language = self.focused_pane().language;
+        let Some(command) = self.settings.formatters.get(&language).cloned() else {
+            return Err(format!("No formatter configured for {}", language.name()));
+        };
+        let pane = self.focused_pane_mut();
+        let formatted = formatter::run(&command, &pane.buffer.text())?;
+        pane.replace_text(&formatted.replace("\r\n", "\n"));
+        Ok(())
+    }
+
+    /// Whether `:w` should format the focused buffer first
+    pub fn formats_on_save(&self) -> bool {
+        self.settings.format_on_save
+            && self
+                .settings
+                .formatters
+                .contains_key(&self.focused_pane().language)
     }
 
+    /// Events raised since the last call, for the main loop to run hooks on
+    pub fn take_events(&mut self) -> Vec<EditorEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Close the focused pane, keeping its buffer open in the background
     pub fn close_focused_pane(&mut self) -> bool {
+        match self.tab_mut().remove_focused_pane() {
+            Some(pane) => {
+                if pane.kind == super::PaneKind::Editor {
+                    self.buffers.park(pane.into_view());
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Close every other pane in the tab, keeping their buffers open in the
+    /// background. Returns how many were closed.
+    pub fn close_other_panes(&mut self) -> usize {
+        let closed = self.tab_mut().remove_other_panes();
+        let count = closed.len();
+        for pane in closed {
+            if pane.kind == super::PaneKind::Editor {
+                self.buffers.park(pane.into_view());
+            }
+        }
+        count
+    }
+
+    /// Close the focused pane and drop its buffer, discarding any changes
+    pub fn discard_focused_pane(&mut self) -> bool {
         self.tab_mut().close_focused_pane()
     }
 
+    // Buffers
+
+    /// Display `path` in a pane, reusing its parked buffer if it has one.
+    /// The buffer it replaces is parked (changes and all, so quitting still
+    /// asks about them) and becomes the pane's alternate. Changes to a buffer
+    /// with no file couldn't be parked, so those refuse the switch; returns
+    /// false then, if the file can't be read, or if another pane already
+    /// shows it (each pane has its own copy, so edits in one would be lost).
+    fn show_buffer_in_pane(&mut self, pane_id: PaneId, path: &Path) -> bool {
+        let Some(pane) = self.tab().pane(pane_id) else {
+            return false;
+        };
+        if pane.buffer.path().is_some_and(|p| p == path) {
+            return true;
+        }
+        if pane.buffer.path().is_none() && pane.buffer.is_dirty() {
+            let name = pane.buffer.display_name();
+            self.set_error(format!("{} has unsaved changes (:w a name first)", name));
+            return false;
+        }
+        // Pane ids are only unique within a tab
+        let active = self.active_tab;
+        let shown_elsewhere = self.tabs.iter().enumerate().any(|(i, tab)| {
+            tab.panes.values().any(|p| {
+                (i != active || p.id != pane_id) && p.buffer.path().is_some_and(|p| p == path)
+            })
+        });
+        if shown_elsewhere {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.set_error(format!("{} is already open in another pane", name));
+            return false;
+        }
+
+        let view = match self.buffers.take(path) {
+            Some(view) => view,
+            None => {
+                let mut view =
+                    match BufferView::open(path.to_path_buf(), self.settings.large_file_size) {
+                        Ok(view) => view,
+                        Err(e) => {
+                            self.set_error(format!("Can't open {}: {}", path.display(), e));
+                            return false;
+                        }
+                    };
+                self.events.push(EditorEvent::Open(path.to_path_buf()));
+                if self.settings.git_signs && !view.large_file {
+                    view.signs = git::signs(path);
+                }
+                if self.settings.detect_indent && !view.large_file {
+                    let indent_style = view.buffer.detect_indent();
+                    view.buffer.set_indent_style(indent_style);
+                }
+                view
+            }
+        };
+        self.buffers.register(path);
+        self.recent.add(path);
+
+        let Some(pane) = self.tab_mut().panes.get_mut(&pane_id) else {
+            return false;
+        };
+        let old = pane.replace_view(view);
+        pane.alternate = old.buffer.path().cloned();
+        self.buffers.park(old);
+        self.check_grammar(pane_id);
+        true
+    }
+
+    /// Point out (or install) a missing grammar for the buffer in a pane
+    pub fn check_grammar(&mut self, pane_id: PaneId) {
+        let Some(pane) = self.pane_mut(pane_id) else {
+            return;
+        };
+        if let Some(err) = pane.highlighter.take_query_error() {
+            self.set_error(err);
+            return;
+        }
+        if !pane.large_file && pane.highlighter.grammar_missing() {
+            let lang = pane.language;
+            self.hint_missing_grammar(lang);
+        }
+    }
+
+    /// Say a language's grammar isn't installed, once per session, or install
+    /// it with `auto_install_grammars`
+    fn hint_missing_grammar(&mut self, lang: Language) {
+        if self.grammar_hints.contains(&lang) {
+            return;
+        }
+        if self.settings.auto_install_grammars {
+            // A file opened at startup waits for the main loop to allow installs
+            if self.install_events.is_none() {
+                return;
+            }
+            self.start_install(lang);
+        } else {
+            self.set_message(format!(
+                "{} grammar not installed: run :TSInstall {}",
+                lang.name(),
+                lang.grammar_name().unwrap_or_default()
+            ));
+        }
+        self.grammar_hints.insert(lang);
+    }
+
+    /// Switch the focused pane to an open buffer (`:b`)
+    pub fn switch_buffer(&mut self, query: &str) -> Result<(), String> {
+        let path = self.buffers.find(query)?;
+        self.open_file_in_focused_pane(path);
+        Ok(())
+    }
+
+    /// Switch the focused pane back to the buffer it showed before (`Ctrl-^`)
+    pub fn alternate_buffer(&mut self) -> Result<(), String> {
+        let path = self
+            .focused_pane()
+            .alternate
+            .clone()
+            .ok_or("No alternate buffer")?;
+        self.open_file_in_focused_pane(path);
+        Ok(())
+    }
+
+    /// Each open buffer's path and its line of the `:ls` listing: number,
+    /// flags (`%` current, `#` alternate, `a` shown in a pane, `h` hidden),
+    /// `+` if modified, and the path
+    pub fn buffer_listing(&self) -> Vec<(PathBuf, String)> {
+        let pane = self.focused_pane();
+        let current = pane.buffer.path();
+        let alternate = pane.alternate.as_ref();
+
+        self.buffers
+            .numbered()
+            .filter_map(|(n, path)| {
+                let shown =
+                    self.tabs.iter().flat_map(|t| t.panes.values()).find(|p| {
+                        p.kind == super::PaneKind::Editor && p.buffer.path() == Some(path)
+                    });
+                let buffer = match shown {
+                    Some(p) => &p.buffer,
+                    None => &self.buffers.hidden(path)?.buffer,
+                };
+                let marker = if current == Some(path) {
+                    '%'
+                } else if alternate == Some(path) {
+                    '#'
+                } else {
+                    ' '
+                };
+                let line = format!(
+                    "{:>3} {}{} {} {}",
+                    n,
+                    marker,
+                    if shown.is_some() { 'a' } else { 'h' },
+                    if buffer.is_dirty() { '+' } else { ' ' },
+                    path.display()
+                );
+                Some((path.clone(), line))
+            })
+            .collect()
+    }
+
     // File browser (delegates to current tab)
 
     pub fn toggle_file_browser(&mut self) {
-        self.tab_mut().toggle_file_browser();
+        let (edge, ratio) = self.file_browser_placement();
+        self.tab_mut().toggle_file_browser(edge, ratio);
     }
 
     pub fn focus_file_browser(&mut self) {
-        self.tab_mut().focus_file_browser();
+        let (edge, ratio) = self.file_browser_placement();
+        self.tab_mut().focus_file_browser(edge, ratio);
+    }
+
+    /// Where the file browser opens, from the settings
+    fn file_browser_placement(&self) -> (Edge, f32) {
+        let settings = &self.settings;
+        (settings.file_browser_edge, settings.file_browser_ratio)
     }
 
     pub fn try_open_file_from_browser(&mut self) -> Option<PathBuf> {
-        let result = self.tab_mut().try_open_file_from_browser();
-        self.tab_mut().update_name();
-        result
+        let editor_panes = self.get_editor_panes_with_labels();
+        let path = self.file_browser_mut().select()?;
+
+        // With a single editor pane, open straight into it; otherwise let
+        // the caller ask which pane to use
+        if editor_panes.len() > 1 {
+            return Some(path);
+        }
+        if let Some(&(_, pane_id)) = editor_panes.first() {
+            self.show_buffer_in_pane(pane_id, &path);
+            self.tab_mut().focused_pane_id = pane_id;
+            self.tab_mut().update_name();
+        }
+        None
     }
 
     // Access file browser from current tab
@@ -452,13 +1485,25 @@ impl Workspace {
     // Tab management
 
     pub fn new_tab(&mut self) {
-        self.tabs.push(Tab::new());
+        let mut tab = Tab::new();
+        tab.file_browser
+            .set_show_hidden(self.settings.show_hidden_files);
+        self.tabs.push(tab);
         self.active_tab = self.tabs.len() - 1;
     }
 
     pub fn open_file_in_new_tab(&mut self, path: PathBuf) {
-        self.tabs.push(Tab::with_file(path));
-        self.active_tab = self.tabs.len() - 1;
+        self.new_tab();
+        self.open_file_in_focused_pane(path);
+    }
+
+    /// Use settings loaded from the config (at startup and on `:source`)
+    pub fn apply_settings(&mut self, settings: Settings) {
+        self.set_theme(&settings.theme);
+        for tab in &mut self.tabs {
+            tab.file_browser.set_show_hidden(settings.show_hidden_files);
+        }
+        self.settings = settings;
     }
 
     pub fn next_tab(&mut self) {
@@ -477,9 +1522,40 @@ impl Workspace {
         }
     }
 
+    /// Whether any open buffer has unsaved changes
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.tabs.iter().any(|t| t.has_unsaved_changes()) || self.buffers.has_unsaved_changes()
+    }
+
+    /// Whether a buffer other than the focused one has unsaved changes
+    pub fn unsaved_elsewhere(&self) -> bool {
+        let active = self.active_tab;
+        self.tab().others_have_unsaved_changes()
+            || self.buffers.has_unsaved_changes()
+            || (self.tabs.iter().enumerate()).any(|(i, t)| i != active && t.has_unsaved_changes())
+    }
+
+    /// Close the current tab, keeping its buffers open in the background.
+    /// Returns false if it's the last tab.
     pub fn close_tab(&mut self) -> bool {
+        self.remove_tab(false)
+    }
+
+    /// Close the current tab like `close_tab`, but drop the focused pane's
+    /// buffer and its changes (`:q!` on the tab's last pane)
+    pub fn discard_tab(&mut self) -> bool {
+        self.remove_tab(true)
+    }
+
+    fn remove_tab(&mut self, discard_focused: bool) -> bool {
         if self.tabs.len() > 1 {
-            self.tabs.remove(self.active_tab);
+            let tab = self.tabs.remove(self.active_tab);
+            for pane in tab.panes.into_values() {
+                let discarded = discard_focused && pane.id == tab.focused_pane_id;
+                if pane.kind == super::PaneKind::Editor && !discarded {
+                    self.buffers.park(pane.into_view());
+                }
+            }
             if self.active_tab >= self.tabs.len() {
                 self.active_tab = self.tabs.len() - 1;
             }
@@ -496,7 +1572,9 @@ impl Workspace {
     // Messages
 
     pub fn set_message(&mut self, msg: impl Into<String>) {
-        self.message = Some(msg.into());
+        let msg = msg.into();
+        self.messages.add(Severity::Info, &msg);
+        self.message = Some(msg);
     }
 
     pub fn clear_message(&mut self) {
@@ -504,7 +1582,9 @@ impl Workspace {
     }
 
     pub fn set_error(&mut self, err: impl Into<String>) {
-        self.error = Some(err.into());
+        let err = err.into();
+        self.messages.add(Severity::Error, &err);
+        self.error = Some(err);
     }
 
     pub fn clear_error(&mut self) {
@@ -517,7 +1597,46 @@ impl Workspace {
 
     pub fn set_theme(&mut self, name: &str) {
         self.theme_name = name.to_string();
+        self.theme = theme::find_theme(name).unwrap_or_default();
+    }
+}
+
+/// The file name in `line` at char column `col`, or the first one after it,
+/// as `gf` takes it: a run of path characters, less a full stop ending a
+/// sentence
+fn path_at(line: &str, col: usize) -> Option<String> {
+    let is_path_char = |c: char| c.is_alphanumeric() || "/._-~+@%$#=".contains(c);
+    let chars: Vec<char> = line.chars().collect();
+    let mut start = (col..chars.len()).find(|&i| is_path_char(chars[i]))?;
+    while start > 0 && is_path_char(chars[start - 1]) {
+        start -= 1;
+    }
+    let mut end = start;
+    while end < chars.len() && is_path_char(chars[end]) {
+        end += 1;
+    }
+    let name: String = chars[start..end].iter().collect();
+    let name = match name.trim_end_matches('.') {
+        "" => name.as_str(), // `.` or `..` itself
+        trimmed => trimmed,
+    };
+    Some(name.to_string())
+}
+
+/// The char columns where `query` starts in `line`, overlapping matches included
+fn find_in_line(line: &[char], query: &[char], ignore_case: bool) -> Vec<usize> {
+    let same = |a: char, b: char| a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()));
+    if query.is_empty() || query.len() > line.len() {
+        return Vec::new();
     }
+    (0..=line.len() - query.len())
+        .filter(|&start| {
+            line[start..start + query.len()]
+                .iter()
+                .zip(query)
+                .all(|(&a, &b)| same(a, b))
+        })
+        .collect()
 }
 
 impl Default for Workspace {
@@ -537,6 +1656,322 @@ mod tests {
         assert_eq!(ws.active_tab, 0);
     }
 
+    #[test]
+    fn the_theme_is_loaded_when_set() {
+        let mut ws = Workspace::new();
+        ws.set_theme("nord");
+        assert_eq!(ws.theme.name, Theme::nord().name);
+        ws.set_theme("no-such-theme");
+        assert_eq!(ws.theme.name, Theme::default().name);
+    }
+
+    #[test]
+    fn has_unsaved_changes_checks_every_tab() {
+        let mut ws = Workspace::new();
+        ws.new_tab();
+        assert!(!ws.has_unsaved_changes());
+
+        ws.prev_tab();
+        ws.focused_pane_mut().buffer.insert_char(0, 0, 'x');
+        ws.next_tab();
+
+        assert!(!ws.tab().has_unsaved_changes());
+        assert!(ws.has_unsaved_changes());
+    }
+
+    #[test]
+    fn buffers_survive_switching_and_closing_panes() {
+        let dir = std::env::temp_dir();
+        let first = dir.join(format!("lark-buffers-a-{}.txt", std::process::id()));
+        let second = dir.join(format!("lark-buffers-b-{}.txt", std::process::id()));
+        std::fs::write(&first, "first\n").unwrap();
+        std::fs::write(&second, "second\n").unwrap();
+
+        let mut ws = Workspace::open(first.clone(), Settings::default());
+        ws.focused_pane_mut().buffer.insert_char(0, 0, 'x');
+        ws.open_file_in_focused_pane(second.clone());
+        assert_eq!(ws.focused_pane().alternate.as_ref(), Some(&first));
+        assert!(ws.has_unsaved_changes()); // the edit is kept in the hidden buffer
+
+        ws.alternate_buffer().unwrap();
+        assert_eq!(ws.focused_pane().buffer.line(0).to_string(), "xfirst\n");
+
+        // Closing a pane keeps its buffer around for :b
+        ws.split_vertical();
+        ws.tab_mut().focus_next();
+        ws.switch_buffer("lark-buffers-b").unwrap();
+        assert!(ws.close_focused_pane());
+        assert!(ws.buffers.hidden(&second).is_some());
+        let listing = ws.buffer_listing();
+        assert!(
+            listing
+                .iter()
+                .any(|(path, line)| *path == first && line.contains("%a +"))
+        );
+
+        std::fs::remove_file(first).unwrap();
+        std::fs::remove_file(second).unwrap();
+    }
+
+    #[test]
+    fn a_file_is_only_shown_in_one_pane_so_its_edits_survive() {
+        let dir = std::env::temp_dir().join(format!("lark-twice-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let [shared, other, third] = ["shared.txt", "other.txt", "third.txt"].map(|name| {
+            let path = dir.join(name);
+            std::fs::write(&path, "text\n").unwrap();
+            path
+        });
+
+        let mut ws = Workspace::open(shared.clone(), Settings::default());
+        let first = ws.tab().focused_pane_id;
+        ws.focused_pane_mut().buffer.insert_char(0, 0, 'x');
+        ws.split_and_focus(SplitDirection::Vertical);
+        assert!(!ws.open_file_in_focused_pane(shared.clone()));
+        assert!(ws.error.take().unwrap().contains("already open"));
+        ws.new_tab();
+        assert!(!ws.open_file_in_focused_pane(shared.clone()));
+        ws.prev_tab();
+
+        // Both panes switch away; the edit is parked, not overwritten
+        assert!(ws.open_file_in_focused_pane(other));
+        ws.focus_pane(first);
+        assert!(ws.open_file_in_focused_pane(third));
+        let parked = ws
+            .buffers
+            .hidden(&shared)
+            .map(|v| v.buffer.line(0).to_string());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(parked.as_deref(), Some("xtext\n"));
+        assert!(ws.has_unsaved_changes());
+    }
+
+    #[test]
+    fn unsaved_scratch_buffers_are_not_replaced() {
+        let path = std::env::temp_dir().join(format!("lark-scratch-{}.txt", std::process::id()));
+        std::fs::write(&path, "file\n").unwrap();
+
+        let mut ws = Workspace::with_scratch("[stdin]", "text\n");
+        ws.focused_pane_mut().buffer.insert_char(0, 0, 'x');
+        assert!(!ws.open_file_in_focused_pane(path.clone()));
+        assert!(ws.error.is_some());
+        assert_eq!(ws.focused_pane().buffer.line(0).to_string(), "xtext\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn files_written_elsewhere_are_reloaded_unless_changed() {
+        let path = std::env::temp_dir().join(format!("lark-reload-{}.txt", std::process::id()));
+        // Written "later" each time, whatever the filesystem's timestamp resolution
+        let write = |text: &str, secs: u64| {
+            std::fs::write(&path, text).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            let time = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+            file.set_modified(time).unwrap();
+        };
+        write("one\ntwo\n", 1_000);
+        let mut ws = Workspace::open(path.clone(), Settings::default());
+        ws.focused_pane_mut().cursor.line = 1;
+        assert!(!ws.check_files_on_disk());
+
+        write("uno\ndos\n", 2_000);
+        assert!(ws.check_files_on_disk());
+        assert_eq!(ws.focused_pane().buffer.text(), "uno\ndos\n");
+        assert_eq!(ws.focused_pane().cursor.line, 1);
+        assert!(!ws.focused_pane().buffer.is_dirty());
+
+        // Unsaved changes are kept, with one warning
+        ws.focused_pane_mut().buffer.insert_char(0, 0, 'x');
+        write("eins", 3_000);
+        assert!(ws.check_files_on_disk());
+        assert!(ws.message.as_ref().unwrap().contains(":e!"));
+        assert_eq!(ws.focused_pane().buffer.text(), "xuno\ndos\n");
+        assert!(!ws.check_files_on_disk());
+
+        ws.reload_focused().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(ws.focused_pane().buffer.text(), "eins");
+        assert_eq!(ws.focused_pane().cursor.line, 0);
+        assert!(!ws.check_files_on_disk()); // Deleted files are left alone
+    }
+
+    #[test]
+    fn large_files_are_not_parsed() {
+        let path = std::env::temp_dir().join(format!("lark-large-{}.rs", std::process::id()));
+        std::fs::write(&path, "fn main() {}\n".repeat(10)).unwrap();
+        let settings = Settings {
+            large_file_size: 64,
+            ..Settings::default()
+        };
+
+        let mut ws = Workspace::open(path.clone(), settings);
+        std::fs::remove_file(&path).unwrap();
+
+        let pane = ws.focused_pane_mut();
+        assert!(pane.large_file);
+        assert_eq!(pane.language, Language::Rust);
+        assert_eq!(pane.highlighter.highlight_count(), 0);
+        pane.insert_text(0, 0, "// ");
+        assert!(!pane.needs_reparse);
+    }
+
+    #[test]
+    fn opening_and_saving_raise_events() {
+        let dir = std::env::temp_dir();
+        let first = dir.join(format!("lark-events-a-{}.txt", std::process::id()));
+        let second = dir.join(format!("lark-events-b-{}.txt", std::process::id()));
+        std::fs::write(&first, "first\n").unwrap();
+        std::fs::write(&second, "second\n").unwrap();
+
+        let mut ws = Workspace::open(first.clone(), Settings::default());
+        ws.open_file_in_focused_pane(second.clone());
+        ws.save_focused().unwrap();
+        assert_eq!(
+            ws.take_events(),
+            vec![
+                EditorEvent::Open(first.clone()),
+                EditorEvent::Open(second.clone()),
+                EditorEvent::Save(second.clone()),
+            ]
+        );
+
+        // Showing a buffer that's already loaded isn't an open
+        ws.alternate_buffer().unwrap();
+        assert!(ws.take_events().is_empty());
+
+        std::fs::remove_file(first).unwrap();
+        std::fs::remove_file(second).unwrap();
+    }
+
+    #[test]
+    fn concurrent_install_of_same_grammar_is_rejected() {
+        let mut ws = Workspace::new();
+        ws.installing.insert(Language::Rust);
+
+        ws.start_install(Language::Rust);
+        assert_eq!(
+            ws.message.as_deref(),
+            Some("Rust grammar is already being installed")
+        );
+
+        let result = InstallResult::Error("clone failed".to_string());
+        ws.handle_install_event(InstallEvent::Finished(Language::Rust, result));
+        assert!(ws.installing.is_empty());
+        assert!(ws.error.as_deref().unwrap().contains("clone failed"));
+    }
+
+    #[test]
+    fn install_all_sums_up_when_the_last_grammar_finishes() {
+        let mut ws = Workspace::new();
+        ws.installing.extend([Language::Go, Language::Lua]);
+        ws.install_batch = Some(InstallBatch {
+            remaining: [Language::Go, Language::Lua].into_iter().collect(),
+            ..InstallBatch::default()
+        });
+
+        let failed = InstallResult::Error("no compiler".to_string());
+        ws.handle_install_event(InstallEvent::Finished(Language::Lua, failed));
+        assert_eq!(ws.error, None);
+        ws.handle_install_event(InstallEvent::Finished(Language::Go, InstallResult::Success));
+        assert_eq!(
+            ws.error.as_deref(),
+            Some("Installed 1, failed 1: Lua (see :log)")
+        );
+        assert!(ws.installing.is_empty());
+        assert!(ws.install_batch.is_none());
+        assert!(ws.get_log().contains("no compiler"));
+    }
+
+    #[test]
+    fn missing_grammars_are_pointed_out_once() {
+        let mut ws = Workspace::new();
+        ws.hint_missing_grammar(Language::Go);
+        assert_eq!(
+            ws.message.as_deref(),
+            Some("Go grammar not installed: run :TSInstall go")
+        );
+        ws.message = None;
+        ws.hint_missing_grammar(Language::Go);
+        assert_eq!(ws.message, None);
+
+        // Auto-installs wait until the main loop can run them
+        ws.settings.auto_install_grammars = true;
+        ws.hint_missing_grammar(Language::Lua);
+        assert_eq!(ws.message, None);
+        let (events, _receiver) = tokio::sync::mpsc::unbounded_channel();
+        ws.install_events = Some(events);
+        ws.installing.insert(Language::Lua); // Don't really start one
+        ws.hint_missing_grammar(Language::Lua);
+        assert_eq!(
+            ws.message.as_deref(),
+            Some("Lua grammar is already being installed")
+        );
+    }
+
+    #[test]
+    fn file_names_are_taken_from_around_the_cursor() {
+        let line = r#"#include "../lib/util.h" // see ~/notes.txt."#;
+        assert_eq!(path_at(line, 12).as_deref(), Some("../lib/util.h"));
+        assert_eq!(path_at(line, 9).as_deref(), Some("../lib/util.h")); // On the quote
+        assert_eq!(path_at(line, 34).as_deref(), Some("~/notes.txt"));
+        assert_eq!(path_at(line, 0).as_deref(), Some("#include"));
+        assert_eq!(path_at("cd ..", 3).as_deref(), Some(".."));
+        assert_eq!(path_at("end  ", 3), None);
+    }
+
+    #[test]
+    fn gf_opens_a_file_beside_the_current_one() {
+        let dir = std::env::temp_dir().join(format!("lark-gf-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("main.txt"), "see sub/other.txt and gone.txt\n").unwrap();
+        std::fs::write(dir.join("sub").join("other.txt"), "").unwrap();
+
+        let mut ws = Workspace::open(dir.join("main.txt"), Settings::default());
+        ws.focused_pane_mut().cursor.col = 22;
+        let missing = ws.goto_file();
+        ws.focused_pane_mut().cursor.col = 6;
+        let found = ws.goto_file();
+        let opened = ws.focused_pane().buffer.path().cloned();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(missing, Err("Can't find file \"gone.txt\"".to_string()));
+        assert_eq!(found, Ok(()));
+        assert_eq!(opened, Some(dir.join("sub").join("other.txt")));
+    }
+
+    #[test]
+    fn details_are_logged_only_in_verbose_mode() {
+        let mut ws = Workspace::new();
+        ws.log_verbose("detail");
+        ws.log("failure");
+        assert_eq!(ws.get_log(), "failure");
+
+        ws.verbose = true;
+        ws.log_verbose("detail");
+        assert_eq!(ws.get_log(), "failure\ndetail");
+        assert_eq!(ws.message.as_deref(), Some("detail"));
+    }
+
+    #[test]
+    fn symbols_of_an_unnamed_buffer_jump_within_it() {
+        let mut ws = Workspace::new();
+        ws.focused_pane_mut().buffer = Buffer::from_text("fn one() {}\n\nfn two() {\n    x\n}\n");
+        ws.focused_pane_mut().cursor.line = 3;
+        ws.show_symbols();
+
+        let (picker, _) = ws.picker.as_ref().unwrap();
+        let items: Vec<&str> = picker.shown().map(|(_, item)| item).collect();
+        assert_eq!(items, ["1: function one", "3: function two"]);
+        assert_eq!(picker.selected(), Some(1)); // The one the cursor is in
+
+        ws.pick(0);
+        assert!(ws.picker.is_none());
+        assert_eq!(ws.focused_pane().cursor.line, 0);
+        assert_eq!(ws.focused_pane().buffer.line_count(), 6);
+    }
+
     #[test]
     fn new_tab_adds_and_focuses() {
         let mut ws = Workspace::new();
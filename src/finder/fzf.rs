@@ -5,16 +5,47 @@ use std::process::{Command, Stdio};
 /// Result of the file finder
 #[derive(Debug)]
 pub enum FinderResult {
-    /// User selected a file
-    Selected(PathBuf),
+    /// User selected a file, to open where the accepting key says
+    Selected(PathBuf, OpenIn),
     /// User cancelled (Esc)
     Cancelled,
     /// fzf not found or error
     Error(String),
 }
 
-/// Spawn fzf with file list and return the selected file
-pub fn find_file(cwd: &PathBuf) -> FinderResult {
+/// Where to open a picked file, from the key that accepted it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenIn {
+    Pane,            // Enter: in the focused pane
+    VerticalSplit,   // Ctrl-v
+    HorizontalSplit, // Ctrl-x
+    Tab,             // Ctrl-t
+}
+
+/// fzf arguments to report which key accepted the selection (see `accepted`),
+/// with a header listing the keys
+pub(super) const ACCEPT_ARGS: [&str; 2] = [
+    "--expect=ctrl-v,ctrl-x,ctrl-t",
+    "--header=enter: open  ctrl-v: vsplit  ctrl-x: split  ctrl-t: tab",
+];
+
+/// Where to open the selection and what it was, from fzf's output with
+/// `ACCEPT_ARGS`: the accepting key (empty for Enter), then the selected line
+pub(super) fn accepted(stdout: &str) -> Option<(OpenIn, &str)> {
+    let mut lines = stdout.lines();
+    let open_in = match lines.next()? {
+        "ctrl-v" => OpenIn::VerticalSplit,
+        "ctrl-x" => OpenIn::HorizontalSplit,
+        "ctrl-t" => OpenIn::Tab,
+        _ => OpenIn::Pane,
+    };
+    let selected = lines.next()?.trim();
+    (!selected.is_empty()).then_some((open_in, selected))
+}
+
+/// Spawn fzf with file list and return the selected file, previewed with
+/// `preview` (see `preview::args`)
+pub fn find_file(cwd: &PathBuf, preview: &str) -> FinderResult {
     // Check if fzf is available
     if Command::new("fzf").arg("--version").output().is_err() {
         return FinderResult::Error("fzf not found. Install with: brew install fzf".to_string());
@@ -22,14 +53,40 @@ pub fn find_file(cwd: &PathBuf) -> FinderResult {
 
     // Use fd if available, otherwise fall back to find
     let file_list = get_file_list(cwd);
+    pick(file_list, cwd, preview, &["--prompt=Find file: "])
+}
+
+/// Whether fzf is installed, so `pick_file` can be used
+pub fn fzf_available() -> bool {
+    Command::new("fzf").arg("--version").output().is_ok()
+}
 
+/// Let the user choose one of `files` with fzf, listing them relative to `cwd`
+/// in the order given
+pub fn pick_file(files: &[PathBuf], cwd: &PathBuf, prompt: &str, preview: &str) -> FinderResult {
+    if !fzf_available() {
+        return FinderResult::Error("fzf not found. Install with: brew install fzf".to_string());
+    }
+
+    let file_list = files
+        .iter()
+        .map(|f| f.strip_prefix(cwd).unwrap_or(f).display().to_string())
+        .collect();
+    pick(
+        file_list,
+        cwd,
+        preview,
+        &["--no-sort", &format!("--prompt={}", prompt)],
+    )
+}
+
+/// Run fzf over `file_list`, resolving the selection against `cwd`
+fn pick(file_list: Vec<String>, cwd: &PathBuf, preview: &str, args: &[&str]) -> FinderResult {
     let mut child = match Command::new("fzf")
-        .args([
-            "--height=40%",
-            "--layout=reverse",
-            "--border",
-            "--prompt=Find file: ",
-        ])
+        .args(["--height=40%", "--layout=reverse", "--border"])
+        .args(super::preview::args(preview, false))
+        .args(ACCEPT_ARGS)
+        .args(args)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit()) // Let fzf display its UI
@@ -54,14 +111,14 @@ pub fn find_file(cwd: &PathBuf) -> FinderResult {
     };
 
     if output.status.success() {
-        let selected = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if selected.is_empty() {
-            FinderResult::Cancelled
-        } else {
-            FinderResult::Selected(cwd.join(selected))
+        match accepted(&String::from_utf8_lossy(&output.stdout)) {
+            Some((open_in, selected)) => FinderResult::Selected(cwd.join(selected), open_in),
+            None => FinderResult::Cancelled,
         }
+    } else if output.status.code() == Some(2) {
+        FinderResult::Error("fzf failed".to_string())
     } else {
-        // fzf returns non-zero when user cancels with Esc
+        // fzf returns 130 when the user cancels with Esc, and 1 when nothing matched
         FinderResult::Cancelled
     }
 }
@@ -98,3 +155,24 @@ fn get_file_list(cwd: &PathBuf) -> Vec<String> {
 
     Vec::new()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_accepting_key_picks_where_to_open() {
+        assert_eq!(
+            accepted("\nsrc/main.rs\n"),
+            Some((OpenIn::Pane, "src/main.rs"))
+        );
+        assert_eq!(
+            accepted("ctrl-v\nsrc/main.rs\n"),
+            Some((OpenIn::VerticalSplit, "src/main.rs"))
+        );
+        assert_eq!(accepted("ctrl-t\nlib.rs\n"), Some((OpenIn::Tab, "lib.rs")));
+        // Accepted with nothing selected
+        assert_eq!(accepted("ctrl-x\n"), None);
+        assert_eq!(accepted(""), None);
+    }
+}
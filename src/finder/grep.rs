@@ -1,7 +1,14 @@
 use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
+use ignore::WalkBuilder;
+
+use super::fzf::{ACCEPT_ARGS, OpenIn, accepted};
+
+/// Stop the builtin grep after this many matches
+const MAX_BUILTIN_MATCHES: usize = 5000;
+
 /// A single grep match result
 #[derive(Debug, Clone)]
 pub struct GrepMatch {
@@ -13,8 +20,8 @@ pub struct GrepMatch {
 
 /// Result of grep operation
 pub enum GrepResult {
-    /// User selected a match
-    Selected(GrepMatch),
+    /// User selected a match, to open where the accepting key says
+    Selected(GrepMatch, OpenIn),
     /// User cancelled
     Cancelled,
     /// No matches found
@@ -23,8 +30,121 @@ pub enum GrepResult {
     Error(String),
 }
 
-/// Grep files with ripgrep and let user select with fzf
-pub fn grep_files(pattern: &str, cwd: &PathBuf) -> GrepResult {
+/// Whether `rg` and `fzf` are both installed, so `grep_files` can be used
+pub fn external_tools_available() -> bool {
+    ["rg", "fzf"]
+        .iter()
+        .all(|tool| Command::new(tool).arg("--version").output().is_ok())
+}
+
+/// Search files under `cwd` for a literal pattern without external tools.
+///
+/// Respects `.gitignore` and skips hidden and binary files, like ripgrep.
+/// Reports the first match on each line with 1-based line and column numbers.
+pub fn search(pattern: &str, cwd: &Path) -> Vec<GrepMatch> {
+    search_with(pattern, cwd, false)
+}
+
+/// Like `search`, but only where `word` stands alone as an identifier
+pub fn search_word(word: &str, cwd: &Path) -> Vec<GrepMatch> {
+    search_with(word, cwd, true)
+}
+
+fn search_with(pattern: &str, cwd: &Path, whole_word: bool) -> Vec<GrepMatch> {
+    let mut matches = Vec::new();
+    if pattern.is_empty() {
+        return matches;
+    }
+
+    let walker = WalkBuilder::new(cwd).require_git(false).build();
+    for entry in walker.filter_map(|e| e.ok()) {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        // Non-UTF-8 files are treated as binary and skipped
+        let Ok(content) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+
+        for (i, line) in content.lines().enumerate() {
+            let found = if whole_word {
+                find_word(line, pattern)
+            } else {
+                line.find(pattern)
+            };
+            if let Some(pos) = found {
+                matches.push(GrepMatch {
+                    file: entry.path().to_path_buf(),
+                    line: i + 1,
+                    col: line[..pos].chars().count() + 1,
+                    text: line.to_string(),
+                });
+                if matches.len() >= MAX_BUILTIN_MATCHES {
+                    return matches;
+                }
+            }
+        }
+    }
+
+    matches.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+    matches
+}
+
+/// Byte offset of the first occurrence of `word` not joined to other identifier characters
+fn find_word(line: &str, word: &str) -> Option<usize> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    line.match_indices(word).map(|(pos, _)| pos).find(|&pos| {
+        let before = line[..pos].chars().next_back();
+        let after = line[pos + word.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    })
+}
+
+/// Every whole-word occurrence of `word` under `cwd`, using ripgrep when it's
+/// installed and the builtin search otherwise
+pub fn word_matches(word: &str, cwd: &PathBuf) -> Result<Vec<GrepMatch>, String> {
+    if Command::new("rg").arg("--version").output().is_err() {
+        return Ok(search_word(word, cwd));
+    }
+
+    let output = Command::new("rg")
+        .args([
+            "--line-number",
+            "--column",
+            "--color=never",
+            "--no-heading",
+            "--word-regexp",
+            "--fixed-strings",
+            word,
+        ])
+        .current_dir(cwd)
+        .output()
+        .map_err(|e| format!("Failed to run rg: {}", e))?;
+
+    let mut matches: Vec<GrepMatch> = BufReader::new(&output.stdout[..])
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| parse_rg_line(&line, cwd))
+        .collect();
+    matches.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+    Ok(matches)
+}
+
+/// Let the user choose one of `matches` with fzf
+pub fn pick_match(matches: &[GrepMatch], cwd: &PathBuf, preview: &str) -> GrepResult {
+    let lines: Vec<String> = matches
+        .iter()
+        .map(|m| {
+            let file = m.file.strip_prefix(cwd).unwrap_or(&m.file);
+            format!("{}:{}:{}:{}", file.display(), m.line, m.col, m.text)
+        })
+        .collect();
+    pick_line(&lines, cwd, preview)
+}
+
+/// Grep files with ripgrep and let user select with fzf, previewing each
+/// match with `preview` (see `preview::args`)
+pub fn grep_files(pattern: &str, cwd: &PathBuf, preview: &str) -> GrepResult {
     // Check if rg is available
     if Command::new("rg").arg("--version").output().is_err() {
         return GrepResult::Error(
@@ -67,6 +187,11 @@ pub fn grep_files(pattern: &str, cwd: &PathBuf) -> GrepResult {
         return GrepResult::NoMatches;
     }
 
+    pick_line(&matches, cwd, preview)
+}
+
+/// Pipe `file:line:col:text` lines to fzf and parse the chosen one
+fn pick_line(matches: &[String], cwd: &PathBuf, preview: &str) -> GrepResult {
     // Pipe to fzf for selection
     let mut child = match Command::new("fzf")
         .args([
@@ -75,8 +200,9 @@ pub fn grep_files(pattern: &str, cwd: &PathBuf) -> GrepResult {
             "--border",
             "--prompt=Grep: ",
             "--delimiter=:",
-            "--preview-window=right:50%",
         ])
+        .args(super::preview::args(preview, true))
+        .args(ACCEPT_ARGS)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
@@ -89,7 +215,7 @@ pub fn grep_files(pattern: &str, cwd: &PathBuf) -> GrepResult {
 
     // Write matches to fzf
     if let Some(mut stdin) = child.stdin.take() {
-        for m in &matches {
+        for m in matches {
             let _ = writeln!(stdin, "{}", m);
         }
     }
@@ -100,18 +226,21 @@ pub fn grep_files(pattern: &str, cwd: &PathBuf) -> GrepResult {
     };
 
     if output.status.success() {
-        let selected = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if selected.is_empty() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let Some((open_in, selected)) = accepted(&stdout) else {
             return GrepResult::Cancelled;
-        }
+        };
 
         // Parse: file:line:col:text
-        if let Some(grep_match) = parse_rg_line(&selected, cwd) {
-            GrepResult::Selected(grep_match)
+        if let Some(grep_match) = parse_rg_line(selected, cwd) {
+            GrepResult::Selected(grep_match, open_in)
         } else {
-            GrepResult::Error("Failed to parse selection".to_string())
+            GrepResult::Error(format!("Failed to parse selection: {}", selected))
         }
+    } else if output.status.code() == Some(2) {
+        GrepResult::Error("fzf failed".to_string())
     } else {
+        // Esc or Ctrl-C (130), or Enter with nothing matching the query (1)
         GrepResult::Cancelled
     }
 }
@@ -148,6 +277,37 @@ mod tests {
         assert_eq!(result.text, "fn main() {");
     }
 
+    #[test]
+    fn test_builtin_search_respects_gitignore() {
+        let dir = std::env::temp_dir().join(format!("lark-grep-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(
+            dir.join("src/main.rs"),
+            "fn main() {\n    let needle = 1;\n}\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join("target/out.rs"), "needle\n").unwrap();
+
+        let matches = search("needle", &dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].file, dir.join("src/main.rs"));
+        assert_eq!(matches[0].line, 2);
+        assert_eq!(matches[0].col, 9);
+        assert_eq!(matches[0].text, "    let needle = 1;");
+    }
+
+    #[test]
+    fn test_find_word_skips_partial_identifiers() {
+        assert_eq!(find_word("needles needle", "needle"), Some(8));
+        assert_eq!(find_word("(needle)", "needle"), Some(1));
+        assert_eq!(find_word("my_needle", "needle"), None);
+        assert_eq!(find_word("needle2", "needle"), None);
+    }
+
     #[test]
     fn test_parse_rg_line_with_colons_in_text() {
         let cwd = PathBuf::from("/home/user");
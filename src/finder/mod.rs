@@ -1,5 +1,8 @@
 mod fzf;
 pub mod grep;
+mod preview;
+mod root;
 
-pub use fzf::{FinderResult, find_file};
+pub use fzf::{FinderResult, OpenIn, find_file, fzf_available, pick_file};
 pub use grep::{GrepMatch, grep_files};
+pub use root::find_project_root;
@@ -0,0 +1,74 @@
+//! The preview pane fzf shows beside the finder and grep lists
+//!
+//! A preview command names the file as `{file}` and the line to show as
+//! `{line}` (1 in the file finder), and these become the fzf field references
+//! for the list being picked from. Without one configured, files are shown
+//! with `bat` when it's installed and plain `head`/`cat` otherwise.
+
+use std::process::Command;
+
+/// The fzf arguments that preview the file on each line of the list: a whole
+/// path per line, or `file:line:col:text` for grep (scrolled to the match)
+pub fn args(command: &str, grep: bool) -> Vec<String> {
+    let template = if command.is_empty() {
+        default_command(bat(), grep)
+    } else {
+        command.to_string()
+    };
+    let (file, line) = if grep { ("{1}", "{2}") } else { ("{}", "1") };
+    let preview = template.replace("{file}", file).replace("{line}", line);
+
+    let window = if grep {
+        // Put the match in the middle of the window
+        "--preview-window=right:50%:+{2}-/2"
+    } else {
+        "--preview-window=right:50%"
+    };
+    vec![format!("--preview={}", preview), window.to_string()]
+}
+
+fn default_command(bat: Option<&str>, grep: bool) -> String {
+    match (bat, grep) {
+        (Some(bat), true) => format!(
+            "{} --color=always --style=numbers --highlight-line {{line}} {{file}}",
+            bat
+        ),
+        (Some(bat), false) => format!("{} --color=always --style=numbers {{file}}", bat),
+        // All of it, so the window can scroll to the match
+        (None, true) => "cat {file}".to_string(),
+        (None, false) => "head -n 200 {file}".to_string(),
+    }
+}
+
+/// The name `bat` is installed under, if it is (Debian calls it `batcat`)
+fn bat() -> Option<&'static str> {
+    ["bat", "batcat"]
+        .into_iter()
+        .find(|name| Command::new(name).arg("--version").output().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_become_fzf_fields() {
+        assert_eq!(
+            args("less +{line} {file}", true),
+            [
+                "--preview=less +{2} {1}",
+                "--preview-window=right:50%:+{2}-/2"
+            ]
+        );
+        assert_eq!(
+            args("less +{line} {file}", false),
+            ["--preview=less +1 {}", "--preview-window=right:50%"]
+        );
+
+        assert_eq!(
+            default_command(Some("batcat"), true),
+            "batcat --color=always --style=numbers --highlight-line {line} {file}"
+        );
+        assert_eq!(default_command(None, false), "head -n 200 {file}");
+    }
+}
@@ -0,0 +1,57 @@
+//! Project roots: where the finders search from, whichever subdirectory the
+//! open file is in
+
+use std::path::{Path, PathBuf};
+
+/// Version control directories, which mark the top of a project
+const VCS_MARKERS: &[&str] = &[".git", ".hg", ".jj"];
+
+/// Build files, which mark a project that isn't under version control
+const PROJECT_MARKERS: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "go.mod",
+    "pyproject.toml",
+    "Makefile",
+];
+
+/// The root of the project `start` is in: the nearest directory at or above it
+/// with a `.git` (or other VCS) directory, else the nearest with a build file.
+/// None outside any project.
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    let nearest = |markers: &[&str]| {
+        start
+            .ancestors()
+            .find(|dir| markers.iter().any(|marker| dir.join(marker).exists()))
+            .map(Path::to_path_buf)
+    };
+    // A crate inside a workspace has its own Cargo.toml, so VCS markers win
+    nearest(VCS_MARKERS).or_else(|| nearest(PROJECT_MARKERS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roots_are_found_above_the_start() {
+        let tmp = std::env::temp_dir().join(format!("lark-root-{}", std::process::id()));
+        let repo = tmp.join("repo");
+        let member = repo.join("crates").join("member");
+        let loose = tmp.join("loose");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(member.join("src")).unwrap();
+        std::fs::write(member.join("Cargo.toml"), "").unwrap();
+        std::fs::create_dir_all(loose.join("src")).unwrap();
+        std::fs::write(loose.join("package.json"), "").unwrap();
+
+        let from_member = find_project_root(&member.join("src"));
+        let from_loose = find_project_root(&loose.join("src"));
+        let from_loose_root = find_project_root(&loose);
+        std::fs::remove_dir_all(&tmp).unwrap();
+
+        assert_eq!(from_member, Some(repo));
+        assert_eq!(from_loose, Some(loose.clone()));
+        assert_eq!(from_loose_root, Some(loose));
+    }
+}
@@ -1,12 +1,34 @@
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
-use std::path::PathBuf;
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use super::keymap::{Action, Key, KeyResult, KeySequenceState};
-use crate::editor::{Direction, FinderAction, Mode, PaneKind, SearchDirection, Workspace};
+use super::keymap::{Action, Key, KeyResult, KeySequenceState, Operator, WordMotion};
+use crate::editor::{
+    Direction, EditorEvent, FinderAction, Mode, Pane, PaneKind, PendingKeys, PromptAction,
+    SearchDirection, SplitDirection, WordCompletion, Workspace,
+};
+use crate::render::PickerEvent;
+
+/// The last buffer change, replayed by `.`
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub action: Action,
+    pub count: usize,
+    pub inserted: Vec<KeyEvent>, // Keys typed in the insert mode the action started
+}
 
 pub struct InputState {
     pub key_seq: KeySequenceState,
     pub pending_file_path: Option<PathBuf>,
+    pub last_change: Option<Change>,
+    recording_insert: bool, // Insert-mode keys are being added to `last_change`
+    macros: HashMap<char, Vec<KeyEvent>>, // Recorded with q<char>, played with @<char>
+    recording: Option<(char, Vec<KeyEvent>)>, // The macro being recorded and its keys so far
+    last_macro: Option<char>, // Played again by @@
+    macro_depth: usize,     // Macros playing, one inside another
 }
 
 impl InputState {
@@ -14,6 +36,24 @@ impl InputState {
         Self {
             key_seq: KeySequenceState::new(),
             pending_file_path: None,
+            last_change: None,
+            recording_insert: false,
+            macros: HashMap::new(),
+            recording: None,
+            last_macro: None,
+            macro_depth: 0,
+        }
+    }
+
+    /// Add a key handled in insert mode to the change being recorded
+    fn record_insert_key(&mut self, key: KeyEvent) {
+        if !self.recording_insert {
+            return;
+        }
+        if key.code == KeyCode::Esc {
+            self.recording_insert = false;
+        } else if let Some(change) = self.last_change.as_mut() {
+            change.inserted.push(key);
         }
     }
 }
@@ -35,12 +75,104 @@ pub fn handle_event(workspace: &mut Workspace, event: Event, input_state: &mut I
             workspace.clear_message();
             handle_key(workspace, key, input_state);
         }
-        Event::Resize(_, _) => {}
+        Event::Mouse(mouse) if workspace.settings.mouse => handle_mouse(workspace, mouse),
+        Event::Resize(width, height) => workspace.terminal_size = (width, height),
+        _ => {}
+    }
+}
+
+/// Left click focuses a pane and puts the cursor (or file browser selection)
+/// under the pointer; the wheel scrolls the pane it's over
+fn handle_mouse(workspace: &mut Workspace, mouse: MouseEvent) {
+    // Prompts and overlays keep the focus while they're up
+    let overlay = matches!(workspace.mode(), Mode::Command | Mode::MessageViewer)
+        || workspace.prompt.is_some()
+        || workspace.search.is_inputting
+        || workspace.selecting_pane;
+    if overlay {
+        return;
+    }
+    let Some((pane_id, rect)) = workspace.pane_at(mouse.column, mouse.row) else {
+        return;
+    };
+    let in_browser = workspace
+        .pane(pane_id)
+        .is_some_and(|pane| pane.kind == PaneKind::FileBrowser);
+    let row = (mouse.row - rect.y) as usize;
+    let tab_width = workspace
+        .pane(pane_id)
+        .map_or(workspace.settings.tab_width, |pane| {
+            pane.indent(&workspace.settings).width
+        });
+
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            workspace.focus_pane(pane_id);
+            if in_browser {
+                // Below the title row
+                let browser = workspace.file_browser_mut();
+                let entry = browser.scroll_offset + row.saturating_sub(1);
+                if row > 0 && entry < browser.entries.len() {
+                    browser.selected = entry;
+                }
+            } else if let Some(pane) = workspace.pane_mut(pane_id) {
+                // Clicking the line number gutter lands on the start of the line
+                let gutter_width = pane.gutter_width() as u16;
+                let cell = (mouse.column - rect.x).saturating_sub(gutter_width) as usize;
+                pane.move_cursor_to_cell(row, cell, tab_width);
+                // Replace mode's backspace only undoes typing up to where the cursor is now
+                pane.replaced.clear();
+            }
+        }
+        MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+            let down = mouse.kind == MouseEventKind::ScrollDown;
+            if in_browser {
+                let browser = workspace.file_browser_mut();
+                for _ in 0..MOUSE_SCROLL_LINES {
+                    if down {
+                        browser.move_down();
+                    } else {
+                        browser.move_up();
+                    }
+                }
+            } else {
+                let scroll_off = workspace.settings.scroll_off;
+                if let Some(pane) = workspace.pane_mut(pane_id) {
+                    let rows = MOUSE_SCROLL_LINES as isize;
+                    pane.scroll_view(if down { rows } else { -rows }, scroll_off);
+                    pane.replaced.clear();
+                }
+            }
+        }
         _ => {}
     }
 }
 
 fn handle_key(workspace: &mut Workspace, key: KeyEvent, input_state: &mut InputState) {
+    // Read on every key so :source takes effect straight away
+    let settings = &workspace.settings;
+    let timeout = Duration::from_millis(settings.timeout_ms);
+    input_state.key_seq.configure(settings.leader, timeout);
+
+    // While recording, q in normal mode stops and every other key is kept.
+    // Keys a macro plays aren't; the @ that played it already was.
+    if input_state.recording.is_some() && input_state.macro_depth == 0 {
+        let stops = key.code == KeyCode::Char('q')
+            && key.modifiers == KeyModifiers::NONE
+            && workspace.mode() == Mode::Normal
+            && workspace.prompt.is_none()
+            && !workspace.search.is_inputting
+            && !workspace.selecting_pane
+            && input_state.key_seq.is_idle();
+        if stops {
+            stop_recording(workspace, input_state);
+            return;
+        }
+        if let Some((_, keys)) = input_state.recording.as_mut() {
+            keys.push(key);
+        }
+    }
+
     // Handle pane selection mode
     if workspace.selecting_pane {
         if let KeyCode::Char(c) = key.code {
@@ -66,6 +198,12 @@ fn handle_key(workspace: &mut Workspace, key: KeyEvent, input_state: &mut InputS
         return;
     }
 
+    // Status-line prompt (file browser operations)
+    if workspace.prompt.is_some() {
+        handle_prompt_input(workspace, key);
+        return;
+    }
+
     // Search input - handle typing search pattern (not a separate mode)
     if workspace.search.is_inputting {
         handle_search_input(workspace, key);
@@ -87,11 +225,8 @@ fn handle_key(workspace: &mut Workspace, key: KeyEvent, input_state: &mut InputS
         if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('w') {
             // Start Ctrl-W sequence
             let k = Key::new(key.code, key.modifiers);
-            match input_state.key_seq.process_key(k, "normal") {
-                KeyResult::Pending => {
-                    workspace.pending_keys = input_state.key_seq.pending_display();
-                }
-                _ => {}
+            if let KeyResult::Pending = input_state.key_seq.process_key(k, "normal") {
+                show_pending(workspace, &input_state.key_seq, "normal");
             }
             return;
         }
@@ -101,17 +236,15 @@ fn handle_key(workspace: &mut Workspace, key: KeyEvent, input_state: &mut InputS
             let k = Key::new(key.code, key.modifiers);
             match input_state.key_seq.process_key(k, "normal") {
                 KeyResult::Action(action, _) => {
-                    workspace.pending_keys.clear();
+                    clear_pending(workspace);
                     execute_action(workspace, action, 1, input_state);
                     return;
                 }
                 KeyResult::Pending => {
-                    workspace.pending_keys = input_state.key_seq.pending_display();
+                    show_pending(workspace, &input_state.key_seq, "normal");
                     return;
                 }
-                _ => {
-                    workspace.pending_keys.clear();
-                }
+                _ => clear_pending(workspace),
             }
         }
 
@@ -125,33 +258,113 @@ fn handle_key(workspace: &mut Workspace, key: KeyEvent, input_state: &mut InputS
         return;
     }
 
-    // Insert mode - handle text input directly
-    if workspace.focused_pane().mode == Mode::Insert {
-        if handle_insert_mode(workspace, key) {
-            return;
-        }
+    // Insert and replace mode - handle text input directly
+    if handle_typing(workspace, key) {
+        input_state.record_insert_key(key);
+        return;
+    }
+    // Once the cursor moves, backspace has nothing to put back
+    if workspace.focused_pane().mode == Mode::Replace {
+        workspace.focused_pane_mut().replaced.clear();
     }
 
     // Use key sequence system
     let mode_str = match workspace.focused_pane().mode {
         Mode::Normal => "normal",
-        Mode::Insert => "insert",
+        Mode::Insert | Mode::Replace => "insert",
         _ => "normal",
     };
 
     let k = Key::new(key.code, key.modifiers);
     match input_state.key_seq.process_key(k, mode_str) {
         KeyResult::Action(action, count) => {
-            workspace.pending_keys.clear();
+            clear_pending(workspace);
             execute_action(workspace, action, count, input_state);
+            if let Some(warning) = workspace.registers.take_warning() {
+                workspace.set_message(warning);
+            }
         }
-        KeyResult::Pending => {
-            workspace.pending_keys = input_state.key_seq.pending_display();
-        }
+        KeyResult::Pending => show_pending(workspace, &input_state.key_seq, mode_str),
         KeyResult::Unhandled | KeyResult::Cancelled => {
-            workspace.pending_keys.clear();
+            input_state.key_seq.register = None;
+            clear_pending(workspace);
+            if key.code == KeyCode::Esc {
+                workspace.focused_pane_mut().extra_cursors.clear();
+            }
+        }
+    }
+}
+
+/// Keep the macro being recorded; an uppercase name appends to its macro
+fn stop_recording(workspace: &mut Workspace, input_state: &mut InputState) {
+    workspace.recording_macro = None;
+    let Some((name, keys)) = input_state.recording.take() else {
+        return;
+    };
+    if name.is_ascii_uppercase() {
+        let recorded = input_state.macros.entry(name.to_ascii_lowercase());
+        recorded.or_default().extend(keys);
+    } else {
+        input_state.macros.insert(name, keys);
+    }
+}
+
+/// Replay the keys of macro `name` (`@`: the last one played) `count` times,
+/// stopping at the first error
+fn play_macro(workspace: &mut Workspace, name: char, count: usize, input_state: &mut InputState) {
+    let name = match (name, input_state.last_macro) {
+        ('@', Some(last)) => last,
+        ('@', None) => {
+            workspace.set_message("No macro played yet");
+            return;
+        }
+        (name, _) => name.to_ascii_lowercase(),
+    };
+    let Some(keys) = input_state
+        .macros
+        .get(&name)
+        .filter(|k| !k.is_empty())
+        .cloned()
+    else {
+        workspace.set_message(format!("Macro {} is empty", name));
+        return;
+    };
+    // A macro that plays itself would never stop
+    if input_state.macro_depth >= MAX_MACRO_DEPTH {
+        workspace.set_error(format!("Macros nested more than {} deep", MAX_MACRO_DEPTH));
+        return;
+    }
+    if keys.len().saturating_mul(count) > MAX_MACRO_KEYS {
+        workspace.set_error(format!("Macro {} played {} times is too long", name, count));
+        return;
+    }
+
+    input_state.last_macro = Some(name);
+    input_state.macro_depth += 1;
+    'replay: for _ in 0..count {
+        for &key in &keys {
+            handle_key(workspace, key, input_state);
+            if workspace.error.is_some() || !workspace.running {
+                break 'replay;
+            }
         }
     }
+    input_state.macro_depth -= 1;
+}
+
+/// Show the keys typed so far, and what can follow them for the which-key popup
+fn show_pending(workspace: &mut Workspace, key_seq: &KeySequenceState, mode: &str) {
+    workspace.pending_keys = key_seq.pending_keys();
+    workspace.key_hints = key_seq.next_keys(mode);
+    if workspace.key_hints.is_empty() {
+        workspace.show_key_hints = false;
+    }
+}
+
+fn clear_pending(workspace: &mut Workspace) {
+    workspace.pending_keys = PendingKeys::default();
+    workspace.key_hints.clear();
+    workspace.show_key_hints = false;
 }
 
 fn handle_file_browser(workspace: &mut Workspace, key: KeyEvent, input_state: &mut InputState) {
@@ -171,10 +384,35 @@ fn handle_file_browser(workspace: &mut Workspace, key: KeyEvent, input_state: &m
         }
         KeyCode::Char('j') | KeyCode::Down => workspace.file_browser_mut().move_down(),
         KeyCode::Char('k') | KeyCode::Up => workspace.file_browser_mut().move_up(),
+        KeyCode::Char('.') => {
+            let browser = workspace.file_browser_mut();
+            let show = !browser.show_hidden;
+            browser.set_show_hidden(show);
+            workspace.set_message(if show {
+                "Showing hidden files"
+            } else {
+                "Hiding hidden files"
+            });
+        }
+        KeyCode::Char('a') => workspace.start_prompt("New file: ", "", PromptAction::CreateFile),
+        KeyCode::Char('A') => {
+            workspace.start_prompt("New directory: ", "", PromptAction::CreateDir)
+        }
+        KeyCode::Char('r') => {
+            if let Some(entry) = workspace.file_browser().selected_entry() {
+                let name = entry.name.clone();
+                workspace.start_prompt("Rename to: ", &name, PromptAction::Rename);
+            }
+        }
+        KeyCode::Char('d') => {
+            if let Some(entry) = workspace.file_browser().selected_entry() {
+                let message = format!("Delete {}?", entry.name);
+                workspace.confirm(&message, PromptAction::ConfirmDelete);
+            }
+        }
         KeyCode::Char(':') => {
             // Enter command mode even from file browser
-            workspace.focused_pane_mut().mode = Mode::Command;
-            workspace.command_buffer.clear();
+            workspace.start_command();
         }
         KeyCode::Enter => {
             if let Some(path) = workspace.try_open_file_from_browser() {
@@ -192,10 +430,25 @@ fn handle_file_browser(workspace: &mut Workspace, key: KeyEvent, input_state: &m
 }
 
 fn handle_insert_mode(workspace: &mut Workspace, key: KeyEvent) -> bool {
+    let indent_unit = auto_indent_unit(workspace);
+    let auto_pairs = workspace.settings.auto_pairs;
     let pane = workspace.focused_pane_mut();
+    let multi_cursor = !pane.extra_cursors.is_empty();
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let code = match key.code {
+        KeyCode::Char('h') if ctrl => KeyCode::Backspace,
+        // At the start of a line, Ctrl-W and Ctrl-U join it to the one above
+        KeyCode::Char('w' | 'u') if ctrl && pane.cursor.col == 0 => KeyCode::Backspace,
+        code => code,
+    };
+    // Any other key ends a completion, keeping the match it's on
+    if !(ctrl && matches!(code, KeyCode::Char('n' | 'p'))) {
+        pane.word_completion = None;
+    }
 
-    match key.code {
+    match code {
         KeyCode::Esc => {
+            pane.extra_cursors.clear();
             pane.mode = Mode::Normal;
             let line_len = pane.buffer.line_len(pane.cursor.line);
             if pane.cursor.col > 0 && pane.cursor.col >= line_len {
@@ -203,59 +456,436 @@ fn handle_insert_mode(workspace: &mut Workspace, key: KeyEvent) -> bool {
             }
             true
         }
+        // Secondary cursors only take edits within their line; Ctrl chords
+        // are commands, handled below
+        KeyCode::Char(c) if multi_cursor && !ctrl => {
+            pane.insert_char_at_cursors(c);
+            true
+        }
+        KeyCode::Backspace if multi_cursor => {
+            pane.delete_char_before_cursors();
+            true
+        }
+        // Line-wide deletes go at the primary cursor alone
+        KeyCode::Char('w') if ctrl => {
+            pane.extra_cursors.clear();
+            let start = word_start_before(pane);
+            pane.delete_chars_before(pane.cursor.col - start);
+            true
+        }
+        KeyCode::Char('u') if ctrl => {
+            pane.extra_cursors.clear();
+            // Back to the indent first, then the rest of the line
+            let indent = pane.buffer.first_non_blank(pane.cursor.line);
+            let start = if pane.cursor.col > indent { indent } else { 0 };
+            pane.delete_chars_before(pane.cursor.col - start);
+            true
+        }
+        KeyCode::Char(c @ ('n' | 'p')) if ctrl => {
+            pane.extra_cursors.clear();
+            if !complete_word(pane, c == 'n') {
+                workspace.set_message("No words to complete with");
+            }
+            true
+        }
+        KeyCode::Char(c) if auto_pairs && type_pair(pane, c) => true,
+        KeyCode::Backspace if auto_pairs && delete_empty_pair(pane) => true,
         KeyCode::Char(c) => {
+            let start = pane.edit_point(pane.cursor.line, pane.cursor.col);
             pane.buffer
                 .insert_char(pane.cursor.line, pane.cursor.col, c);
             pane.cursor.col += 1;
+            let new_end = pane.edit_point(pane.cursor.line, pane.cursor.col);
+            pane.record_edit(start, start, new_end);
             true
         }
         KeyCode::Backspace => {
+            let old_end = pane.edit_point(pane.cursor.line, pane.cursor.col);
             if pane.cursor.col > 0 {
-                pane.buffer
-                    .delete_char_backward(pane.cursor.line, pane.cursor.col);
-                pane.cursor.col -= 1;
+                // The whole grapheme cluster before the cursor
+                let (line, col) = (pane.cursor.line, pane.cursor.col);
+                let prev = pane.buffer.prev_grapheme(line, col);
+                pane.delete_range((line, prev), (line, col));
+                pane.cursor.col = prev;
             } else if pane.cursor.line > 0 {
                 let prev_line_len = pane.buffer.line_len(pane.cursor.line - 1);
+                let start = pane.edit_point(pane.cursor.line - 1, prev_line_len);
                 pane.buffer
                     .delete_char_backward(pane.cursor.line, pane.cursor.col);
                 pane.cursor.line -= 1;
                 pane.cursor.col = prev_line_len;
+                pane.record_edit(start, old_end, start);
             }
             true
         }
         KeyCode::Enter => {
-            pane.buffer
-                .insert_newline(pane.cursor.line, pane.cursor.col);
+            // A line break goes in at the primary cursor alone
+            pane.extra_cursors.clear();
+            let (line, col) = (pane.cursor.line, pane.cursor.col);
+            let indent = indent_unit
+                .map(|unit| pane.indent_after(line, col, &unit))
+                .unwrap_or_default();
+            pane.insert_newline(line, col, &indent);
+            pane.cursor.line += 1;
+            pane.cursor.col = indent.chars().count();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Type `key` in insert or replace mode, whichever the focused pane is in.
+/// False if it isn't typing or the key isn't handled here.
+fn handle_typing(workspace: &mut Workspace, key: KeyEvent) -> bool {
+    match workspace.focused_pane().mode {
+        Mode::Insert => handle_insert_mode(workspace, key),
+        Mode::Replace => handle_replace_mode(workspace, key),
+        _ => false,
+    }
+}
+
+/// Replace mode (`R`): typed chars overwrite the line, and backspace puts
+/// back what they overwrote
+fn handle_replace_mode(workspace: &mut Workspace, key: KeyEvent) -> bool {
+    let pane = workspace.focused_pane_mut();
+    let (line, col) = (pane.cursor.line, pane.cursor.col);
+    match key.code {
+        KeyCode::Esc => {
+            pane.replaced.clear();
+            pane.mode = Mode::Normal;
+            let line_len = pane.buffer.line_len(line);
+            if col > 0 && col >= line_len {
+                pane.cursor.col = line_len.saturating_sub(1);
+            }
+            true
+        }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            // Past the end of the line, chars are added
+            let over = pane.buffer.char_at(line, col).filter(|&ch| ch != '\n');
+            if over.is_some() {
+                pane.delete_range((line, col), (line, col + 1));
+            }
+            pane.insert_text(line, col, &c.to_string());
+            pane.replaced.push(over);
+            pane.cursor.col += 1;
+            true
+        }
+        KeyCode::Enter => {
+            // A line break is added, not typed over anything
+            pane.insert_newline(line, col, "");
+            pane.replaced.push(None);
             pane.cursor.line += 1;
             pane.cursor.col = 0;
             true
         }
+        KeyCode::Backspace => {
+            // Before where replacing started, backspace only moves
+            let Some(over) = pane.replaced.pop() else {
+                pane.cursor.col = col.saturating_sub(1);
+                return true;
+            };
+            let (start_line, start_col) = if col > 0 {
+                (line, col - 1)
+            } else if line > 0 {
+                (line - 1, pane.buffer.line_len(line - 1))
+            } else {
+                pane.replaced.clear();
+                return true;
+            };
+            pane.delete_range((start_line, start_col), (line, col));
+            if let Some(ch) = over {
+                pane.insert_text(start_line, start_col, &ch.to_string());
+            }
+            pane.cursor.line = start_line;
+            pane.cursor.col = start_col;
+            true
+        }
         _ => false,
     }
 }
 
+/// Brackets and quotes closed as they're typed
+const AUTO_PAIRS: [(char, char); 5] =
+    [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+/// Type over a closing char that's already next, or insert an opening char
+/// with its close after the cursor. False leaves `c` to be inserted as usual.
+fn type_pair(pane: &mut Pane, c: char) -> bool {
+    let (line, col) = (pane.cursor.line, pane.cursor.col);
+    let next = pane.buffer.char_at(line, col).filter(|&ch| ch != '\n');
+    if next == Some(c) && AUTO_PAIRS.iter().any(|&(_, close)| close == c) {
+        pane.cursor.col += 1;
+        return true;
+    }
+
+    let Some(&(_, close)) = AUTO_PAIRS.iter().find(|&&(open, _)| open == c) else {
+        return false;
+    };
+    // Only before whitespace or a closing char, so wrapping existing text works
+    let before_gap = next
+        .is_none_or(|ch| ch.is_whitespace() || AUTO_PAIRS.iter().any(|&(_, close)| close == ch));
+    // Quotes after a word are apostrophes, lifetimes or closing an open string
+    let after_word = col > 0
+        && pane
+            .buffer
+            .char_at(line, col - 1)
+            .is_some_and(|ch| ch.is_alphanumeric() || ch == '_');
+    let is_quote = c == close;
+    if !before_gap || (is_quote && after_word) || pane.in_string_or_comment(line, col) {
+        return false;
+    }
+
+    pane.insert_text(line, col, &format!("{}{}", c, close));
+    pane.cursor.col += 1;
+    true
+}
+
+/// Backspace between an opening char and its close deletes both
+fn delete_empty_pair(pane: &mut Pane) -> bool {
+    let (line, col) = (pane.cursor.line, pane.cursor.col);
+    if col == 0 {
+        return false;
+    }
+    let before = pane.buffer.char_at(line, col - 1);
+    let after = pane.buffer.char_at(line, col);
+    let is_pair = AUTO_PAIRS
+        .iter()
+        .any(|&(open, close)| before == Some(open) && after == Some(close));
+    if !is_pair {
+        return false;
+    }
+    pane.delete_range((line, col - 1), (line, col + 1));
+    pane.cursor.col -= 1;
+    true
+}
+
+/// Complete the word before the cursor from the words in the buffer
+/// (Ctrl-N, or Ctrl-P from the last match), or put in the next or previous
+/// match if already completing. False if no word completes it.
+fn complete_word(pane: &mut Pane, forward: bool) -> bool {
+    let (line, col) = (pane.cursor.line, pane.cursor.col);
+    if let Some(completion) = &mut pane.word_completion {
+        completion.step(forward);
+    } else {
+        let mut start = col;
+        while start > 0
+            && pane
+                .buffer
+                .char_at(line, start - 1)
+                .is_some_and(is_word_char)
+        {
+            start -= 1;
+        }
+        let prefix: String = pane
+            .buffer
+            .line(line)
+            .chars()
+            .skip(start)
+            .take(col - start)
+            .collect();
+        let words = pane.buffer.words();
+        pane.word_completion = WordCompletion::new(words, line, start, &prefix, forward);
+    }
+    let Some(completion) = &pane.word_completion else {
+        return false;
+    };
+
+    let (start, word) = (completion.start, completion.current().to_string());
+    pane.delete_range((line, start), (line, col));
+    pane.insert_text(line, start, &word);
+    pane.cursor.col = start + word.chars().count();
+    true
+}
+
+/// Where Ctrl-W deletes back to: over spaces, then a word or a run of
+/// punctuation, staying on the cursor's line
+fn word_start_before(pane: &Pane) -> usize {
+    let (line, mut col) = (pane.cursor.line, pane.cursor.col);
+    let before = |col: usize| pane.buffer.char_at(line, col - 1);
+    while col > 0 && before(col).is_some_and(char::is_whitespace) {
+        col -= 1;
+    }
+    let Some(last) = col
+        .checked_sub(1)
+        .and_then(|c| pane.buffer.char_at(line, c))
+    else {
+        return col;
+    };
+    let same_kind = |c: char| !c.is_whitespace() && is_word_char(c) == is_word_char(last);
+    while col > 0 && before(col).is_some_and(same_kind) {
+        col -= 1;
+    }
+    col
+}
+
+/// One indent level of the focused buffer's language if `auto_indent` is
+/// enabled, for indenting new lines
+fn auto_indent_unit(workspace: &Workspace) -> Option<String> {
+    let settings = &workspace.settings;
+    let pane = workspace.focused_pane();
+    settings.auto_indent.then(|| pane.indent(settings).unit())
+}
+
 fn handle_command_mode(workspace: &mut Workspace, key: KeyEvent) {
+    let line = &mut workspace.command_buffer;
+    let cursor = workspace.command_cursor.min(line.len());
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    // Start of the character before the cursor
+    let prev = line[..cursor].char_indices().last().map_or(0, |(i, _)| i);
+    // End of the character after the cursor
+    let next = line[cursor..]
+        .chars()
+        .next()
+        .map_or(cursor, |c| cursor + c.len_utf8());
+
+    // Any key but Tab ends the completion, keeping the match it's on
+    if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+        workspace.command_completion = None;
+    }
+
     match key.code {
         KeyCode::Esc => {
             workspace.command_buffer.clear();
+            workspace.command_cursor = 0;
+            workspace.command_history.reset();
             workspace.focused_pane_mut().mode = Mode::Normal;
+            return;
         }
         KeyCode::Enter => {
+            let command = workspace.command_buffer.clone();
+            workspace.command_history.add(&command);
             execute_command(workspace);
+            return;
+        }
+        KeyCode::Up | KeyCode::Down => {
+            let history = &mut workspace.command_history;
+            let recalled = if key.code == KeyCode::Up {
+                history.older(&workspace.command_buffer)
+            } else {
+                history.newer()
+            };
+            if let Some(command) = recalled {
+                workspace.command_buffer = command.to_string();
+                workspace.command_cursor = workspace.command_buffer.len();
+            }
+            return;
+        }
+        KeyCode::Tab | KeyCode::BackTab => {
+            workspace.complete_command(key.code == KeyCode::Tab);
+        }
+        KeyCode::Left => workspace.command_cursor = prev,
+        KeyCode::Right => workspace.command_cursor = next,
+        KeyCode::Home => workspace.command_cursor = 0,
+        KeyCode::End => workspace.command_cursor = line.len(),
+        KeyCode::Char('a') if ctrl => workspace.command_cursor = 0,
+        KeyCode::Char('e') if ctrl => workspace.command_cursor = line.len(),
+        KeyCode::Char('b') if ctrl => workspace.command_cursor = 0,
+        KeyCode::Char('w') if ctrl => {
+            // Back over spaces, then a word or a run of symbols
+            let before = line[..cursor].trim_end();
+            let kind = |c: char| (c.is_alphanumeric() || c == '_', c.is_whitespace());
+            let start = match before.chars().next_back() {
+                Some(last) => before
+                    .char_indices()
+                    .rev()
+                    .find(|&(_, c)| kind(c) != kind(last))
+                    .map_or(0, |(i, c)| i + c.len_utf8()),
+                None => 0,
+            };
+            line.replace_range(start..cursor, "");
+            workspace.command_cursor = start;
+        }
+        KeyCode::Char('u') if ctrl => {
+            line.replace_range(..cursor, "");
+            workspace.command_cursor = 0;
         }
         KeyCode::Backspace => {
-            workspace.command_buffer.pop();
+            line.replace_range(prev..cursor, "");
+            workspace.command_cursor = prev;
             if workspace.command_buffer.is_empty() {
                 workspace.focused_pane_mut().mode = Mode::Normal;
             }
         }
-        KeyCode::Char(c) => {
-            workspace.command_buffer.push(c);
+        KeyCode::Delete => {
+            line.replace_range(cursor..next, "");
+        }
+        KeyCode::Char(c) if !ctrl => {
+            line.insert(cursor, c);
+            workspace.command_cursor = cursor + c.len_utf8();
+        }
+        _ => return,
+    }
+    // Editing the line starts a new walk through the history
+    workspace.command_history.reset();
+}
+
+fn handle_prompt_input(workspace: &mut Workspace, key: KeyEvent) {
+    let Some(prompt) = workspace.prompt.as_mut() else {
+        return;
+    };
+
+    // Confirmations take a single key
+    if prompt.action.is_confirmation() {
+        let action = prompt.action.clone();
+        workspace.prompt = None;
+        if key.code == KeyCode::Char('y') {
+            finish_prompt(workspace, action, "");
+        }
+        return;
+    }
+
+    match key.code {
+        KeyCode::Esc => workspace.prompt = None,
+        KeyCode::Enter => {
+            if let Some(prompt) = workspace.prompt.take()
+                && !prompt.input.is_empty()
+            {
+                finish_prompt(workspace, prompt.action, &prompt.input);
+            }
+        }
+        KeyCode::Backspace => {
+            prompt.input.pop();
         }
+        KeyCode::Char(c) => prompt.input.push(c),
         _ => {}
     }
 }
 
+/// Carry out a file browser operation (or a save or quit) once its prompt is answered
+fn finish_prompt(workspace: &mut Workspace, action: PromptAction, input: &str) {
+    if let PromptAction::ConfirmSaveAs { path, rename } = action {
+        if let Some(dir) = path.parent()
+            && let Err(e) = std::fs::create_dir_all(dir)
+        {
+            workspace.set_error(format!("Failed to create {}: {}", dir.display(), e));
+            return;
+        }
+        write_to_path(workspace, path, rename);
+        return;
+    }
+    if action == PromptAction::ConfirmQuit {
+        discard_pane_or_quit(workspace);
+        return;
+    }
+
+    let browser = workspace.file_browser_mut();
+    let result = match action {
+        PromptAction::CreateFile => browser.create_file(input).map(|p| ("Created", p)),
+        PromptAction::CreateDir => browser.create_dir(input).map(|p| ("Created", p)),
+        PromptAction::Rename => browser.rename_selected(input).map(|p| ("Renamed to", p)),
+        PromptAction::ConfirmDelete => browser.delete_selected().map(|p| ("Deleted", p)),
+        PromptAction::ConfirmSaveAs { .. } | PromptAction::ConfirmQuit => return,
+    };
+
+    match result {
+        Ok((verb, path)) => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            workspace.set_message(format!("{} {}", verb, name));
+        }
+        Err(e) => workspace.set_error(e),
+    }
+}
+
 fn handle_search_input(workspace: &mut Workspace, key: KeyEvent) {
     match key.code {
         KeyCode::Esc => {
@@ -283,6 +913,17 @@ fn handle_message_viewer_mode(workspace: &mut Workspace, key: KeyEvent) {
     let height = workspace.terminal_size.1.saturating_sub(4) as usize; // Leave room for title and help
     let width = workspace.terminal_size.0 as usize;
 
+    // Grep results, symbols, files and buffers to pick from
+    let picker_height = workspace.picker_height();
+    if let Some((picker, _)) = &mut workspace.picker {
+        match picker.handle_key(key, picker_height) {
+            PickerEvent::Pending => {}
+            PickerEvent::Picked(index) => workspace.pick(index),
+            PickerEvent::Closed => workspace.close_message_viewer(),
+        }
+        return;
+    }
+
     match key.code {
         KeyCode::Esc | KeyCode::Char('q') => {
             workspace.close_message_viewer();
@@ -304,7 +945,9 @@ fn handle_message_viewer_mode(workspace: &mut Workspace, key: KeyEvent) {
         // Horizontal scroll
         KeyCode::Char('l') | KeyCode::Right => {
             if let Some(ref mut viewer) = workspace.message_viewer {
-                viewer.scroll_col += 10;
+                let max_len = viewer.content.lines().map(|l| l.len()).max().unwrap_or(0);
+                let max_scroll_col = max_len.saturating_sub(width);
+                viewer.scroll_col = (viewer.scroll_col + 10).min(max_scroll_col);
             }
         }
         KeyCode::Char('h') | KeyCode::Left => {
@@ -355,26 +998,56 @@ fn handle_message_viewer_mode(workspace: &mut Workspace, key: KeyEvent) {
     }
 }
 
+/// The cursor's line's first non-blank column, or its last on a blank line
+fn first_non_blank_col(pane: &Pane) -> usize {
+    let line = pane.cursor.line;
+    let last = pane.buffer.line_len(line).saturating_sub(1);
+    pane.buffer.first_non_blank(line).min(last)
+}
+
 fn execute_action(
     workspace: &mut Workspace,
     action: Action,
     count: usize,
-    _input_state: &mut InputState,
+    input_state: &mut InputState,
 ) {
-    for _ in 0..count {
+    // With clipboard = "system", unnamed yanks and pastes go through "+
+    let register = input_state.key_seq.register.take();
+    let register = register.or(workspace.settings.system_clipboard.then_some('+'));
+    if action.is_change() && workspace.focused_pane().buffer.is_read_only() {
+        workspace.set_message(READ_ONLY_MESSAGE);
+        return;
+    }
+    if action.is_change() {
+        input_state.last_change = Some(Change {
+            action: action.clone(),
+            count,
+            inserted: Vec::new(),
+        });
+    }
+
+    // Secondary cursors last through `i` and the typing that follows
+    if !matches!(action, Action::EnterInsertMode | Action::SelectAllMatches) {
+        workspace.focused_pane_mut().extra_cursors.clear();
+    }
+    if action.is_jump() {
+        workspace.focused_pane_mut().record_jump();
+    }
+
+    for i in 0..count {
         match action.clone() {
             // Movement
             Action::MoveLeft => {
-                workspace.focused_pane_mut().cursor.move_left();
+                let pane = workspace.focused_pane_mut();
+                pane.cursor.move_left(&pane.buffer);
             }
             Action::MoveRight => {
                 let pane = workspace.focused_pane_mut();
-                let line_len = pane.buffer.line_len(pane.cursor.line);
-                pane.cursor.move_right(line_len);
+                pane.cursor.move_right(&pane.buffer);
             }
             Action::MoveUp => {
                 let pane = workspace.focused_pane_mut();
-                pane.cursor.move_up();
+                pane.line_up();
                 let line_len = pane.buffer.line_len(pane.cursor.line);
                 if pane.cursor.col > line_len {
                     pane.cursor.col = line_len;
@@ -382,15 +1055,26 @@ fn execute_action(
             }
             Action::MoveDown => {
                 let pane = workspace.focused_pane_mut();
-                let line_count = pane.buffer.line_count();
-                pane.cursor.move_down(line_count);
+                pane.line_down();
                 let line_len = pane.buffer.line_len(pane.cursor.line);
                 if pane.cursor.col > line_len {
                     pane.cursor.col = line_len;
                 }
             }
             Action::MoveToLineStart => {
-                workspace.focused_pane_mut().cursor.col = 0;
+                let smart_home = workspace.settings.smart_home;
+                let pane = workspace.focused_pane_mut();
+                let first = first_non_blank_col(pane);
+                // Smart home: the first non-blank, or column 0 from there
+                pane.cursor.col = if smart_home && pane.cursor.col != first {
+                    first
+                } else {
+                    0
+                };
+            }
+            Action::MoveToFirstNonBlank => {
+                let pane = workspace.focused_pane_mut();
+                pane.cursor.col = first_non_blank_col(pane);
             }
             Action::MoveToLineEnd => {
                 let pane = workspace.focused_pane_mut();
@@ -404,66 +1088,358 @@ fn execute_action(
             }
             Action::MoveToLastLine => {
                 let pane = workspace.focused_pane_mut();
-                pane.cursor.line = pane.buffer.line_count().saturating_sub(1);
+                pane.goto_line(pane.buffer.line_count());
             }
-            Action::MoveWordForward => move_word_forward(workspace.focused_pane_mut()),
-            Action::MoveWordBackward => move_word_backward(workspace.focused_pane_mut()),
-            Action::MoveWordEnd => move_word_end(workspace.focused_pane_mut()),
-            Action::PageDown => {
+            Action::GotoLine(line) => {
+                workspace
+                    .focused_pane_mut()
+                    .goto_line(line.saturating_sub(1));
+            }
+            Action::MoveWordForward => {
+                move_word_forward(workspace.focused_pane_mut(), is_word_char)
+            }
+            Action::MoveWordBackward => {
+                move_word_backward(workspace.focused_pane_mut(), is_word_char)
+            }
+            Action::MoveWordEnd => move_word_end(workspace.focused_pane_mut(), is_word_char),
+            Action::MoveBigWordForward => {
+                move_word_forward(workspace.focused_pane_mut(), is_big_word_char)
+            }
+            Action::MoveBigWordBackward => {
+                move_word_backward(workspace.focused_pane_mut(), is_big_word_char)
+            }
+            Action::MoveBigWordEnd => move_word_end(workspace.focused_pane_mut(), is_big_word_char),
+            Action::FindChar(kind, c) => {
                 let pane = workspace.focused_pane_mut();
-                let line_count = pane.buffer.line_count();
-                for _ in 0..20 {
-                    pane.cursor.move_down(line_count);
-                }
-                let line_len = pane.buffer.line_len(pane.cursor.line);
-                if pane.cursor.col > line_len {
-                    pane.cursor.col = line_len;
-                }
+                pane.last_find = Some((kind, c));
+                pane.find_char(kind, c, i > 0);
             }
-            Action::PageUp => {
+            Action::MatchBracket => {
                 let pane = workspace.focused_pane_mut();
-                for _ in 0..20 {
-                    pane.cursor.move_up();
-                }
-                let line_len = pane.buffer.line_len(pane.cursor.line);
-                if pane.cursor.col > line_len {
-                    pane.cursor.col = line_len;
+                if let Some((line, col)) = pane
+                    .buffer
+                    .matching_bracket(pane.cursor.line, pane.cursor.col)
+                {
+                    pane.cursor.line = line;
+                    pane.cursor.col = col;
                 }
             }
-
-            // Mode changes
-            Action::EnterInsertMode => {
-                workspace.focused_pane_mut().mode = Mode::Insert;
-            }
-            Action::EnterInsertModeAppend => {
+            Action::GotoPercent(percent) => {
+                // Rounded up, as Vim does, and not counting the empty line
+                // after a final newline
                 let pane = workspace.focused_pane_mut();
-                let line_len = pane.buffer.line_len(pane.cursor.line);
-                if pane.cursor.col < line_len {
-                    pane.cursor.col += 1;
+                let mut lines = pane.buffer.line_count();
+                if lines > 1 && pane.buffer.line_len(lines - 1) == 0 {
+                    lines -= 1;
                 }
-                pane.mode = Mode::Insert;
+                let line = (percent.min(100) * lines).div_ceil(100);
+                pane.goto_line(line.saturating_sub(1));
+                pane.cursor.col = first_non_blank_col(pane);
             }
-            Action::EnterInsertModeAppendLine => {
+            Action::ParagraphForward => {
                 let pane = workspace.focused_pane_mut();
-                pane.cursor.col = pane.buffer.line_len(pane.cursor.line);
-                pane.mode = Mode::Insert;
+                pane.cursor.line = pane.buffer.paragraph_forward(pane.cursor.line);
+                // Col 0 of a blank line, or the end of the last line
+                pane.cursor.col = pane.buffer.line_len(pane.cursor.line).saturating_sub(1);
             }
-            Action::EnterInsertModeOpenBelow => {
+            Action::ParagraphBackward => {
                 let pane = workspace.focused_pane_mut();
-                let line_len = pane.buffer.line_len(pane.cursor.line);
-                pane.cursor.col = line_len;
-                pane.buffer
-                    .insert_newline(pane.cursor.line, pane.cursor.col);
-                pane.cursor.line += 1;
+                pane.cursor.line = pane.buffer.paragraph_backward(pane.cursor.line);
                 pane.cursor.col = 0;
-                pane.mode = Mode::Insert;
             }
-            Action::EnterInsertModeOpenAbove => {
+            // Marks and the jump list
+            Action::SetMark(name) => {
+                if name.is_ascii_lowercase() {
+                    let pane = workspace.focused_pane_mut();
+                    let at = (pane.cursor.line, pane.cursor.col);
+                    pane.marks.set(name, at);
+                } else {
+                    workspace.set_error(format!("Invalid mark: {} (use a-z)", name));
+                }
+                break;
+            }
+            Action::JumpToMark(name) | Action::JumpToMarkLine(name) => {
                 let pane = workspace.focused_pane_mut();
-                pane.cursor.col = 0;
-                pane.buffer.insert_newline(pane.cursor.line, 0);
+                let Some(at) = pane.marks.get(name) else {
+                    workspace.set_error(format!("Mark not set: {}", name));
+                    break;
+                };
+                pane.record_jump();
+                pane.jump_to(at);
+                if let Action::JumpToMarkLine(_) = action {
+                    pane.cursor.col = pane.buffer.first_non_blank(pane.cursor.line);
+                }
+                break;
+            }
+            Action::JumpBack => {
+                let pane = workspace.focused_pane_mut();
+                let current = (pane.cursor.line, pane.cursor.col);
+                match pane.marks.jump_back(current) {
+                    Some(at) => pane.jump_to(at),
+                    None => break,
+                }
+            }
+            Action::JumpForward => {
+                let pane = workspace.focused_pane_mut();
+                match pane.marks.jump_forward() {
+                    Some(at) => pane.jump_to(at),
+                    None => break,
+                }
+            }
+            Action::RepeatFind | Action::RepeatFindReverse => {
+                let pane = workspace.focused_pane_mut();
+                if let Some((kind, c)) = pane.last_find {
+                    let kind = if action == Action::RepeatFind {
+                        kind
+                    } else {
+                        kind.reversed()
+                    };
+                    pane.find_char(kind, c, true);
+                }
+            }
+            Action::PageDown => {
+                let pane = workspace.focused_pane_mut();
+                pane.scroll_half_page(true);
+                let line_len = pane.buffer.line_len(pane.cursor.line);
+                if pane.cursor.col > line_len {
+                    pane.cursor.col = line_len;
+                }
+            }
+            Action::PageUp => {
+                let pane = workspace.focused_pane_mut();
+                pane.scroll_half_page(false);
+                let line_len = pane.buffer.line_len(pane.cursor.line);
+                if pane.cursor.col > line_len {
+                    pane.cursor.col = line_len;
+                }
+            }
+
+            Action::CursorToTop | Action::CursorToMiddle | Action::CursorToBottom => {
+                let scroll_off = workspace.settings.scroll_off;
+                let pane = workspace.focused_pane_mut();
+                let lines = pane.viewport_lines();
+                let last = lines.len() - 1;
+                // Not into the rows scrolloff keeps, which would scroll the view
+                let (first, bottom) = pane.scroll_off_rows(&lines, scroll_off);
+                let row = match action {
+                    Action::CursorToTop => (count - 1).max(first).min(bottom),
+                    Action::CursorToBottom => {
+                        last - (count - 1).max(last - bottom).min(last - first)
+                    }
+                    _ => last / 2,
+                };
+                pane.cursor.line = lines[row];
+                let line_len = pane.buffer.line_len(pane.cursor.line);
+                if pane.cursor.col > line_len {
+                    pane.cursor.col = line_len;
+                }
+                break; // The count picks the row
+            }
+
+            // Editing
+            Action::OperatorTextObject(op, object) => {
+                // The count widens the object, not a repeat
+                let pane = workspace.focused_pane_mut();
+                let line = pane.cursor.line;
+                let col = pane.cursor.col;
+                if let Some((start, end)) = object.range(&pane.buffer, line, col, count) {
+                    apply_operator(workspace, op, (line, start), (line, end), register);
+                }
+                break;
+            }
+
+            Action::OperatorWord(op, motion) => {
+                // The count is how many words, not a repeat
+                let pane = workspace.focused_pane_mut();
+                let (start, end) = word_motion_range(pane, op, motion, count);
+                apply_operator(workspace, op, start, end, register);
+                break;
+            }
+
+            Action::RecordMacro(name) => {
+                input_state.recording = Some((name, Vec::new()));
+                workspace.recording_macro = Some(name);
+                break;
+            }
+            Action::PlayMacro(name) => {
+                // The count is how many times to play it
+                play_macro(workspace, name, count, input_state);
+                break;
+            }
+
+            Action::DeleteChar | Action::DeleteCharBefore => {
+                // The count is how many chars to delete, not a repeat
+                let pane = workspace.focused_pane_mut();
+                let removed = if action == Action::DeleteChar {
+                    pane.delete_chars(count)
+                } else {
+                    pane.delete_chars_before(count)
+                };
+                if !removed.is_empty() {
+                    workspace.registers.write(register, removed, false);
+                }
+                break;
+            }
+            Action::ToggleCase => {
+                // The count is how many chars to toggle, not a repeat
+                let pane = workspace.focused_pane_mut();
+                let (line, col) = (pane.cursor.line, pane.cursor.col);
+                let end = (col + count).min(pane.buffer.line_len(line));
+                let text = pane.delete_range((line, col), (line, end));
+                let toggled = change_case(&text, Operator::ToggleCase);
+                pane.insert_text(line, col, &toggled);
+                // Onto the char after the last one toggled, staying on the line
+                let last_col = pane.buffer.line_len(line).saturating_sub(1);
+                pane.cursor.col = (col + toggled.chars().count()).min(last_col);
+                break;
+            }
+            Action::DeleteToLineEnd | Action::ChangeToLineEnd => {
+                let pane = workspace.focused_pane_mut();
+                let col = pane.cursor.col;
+                let removed = pane.delete_chars(pane.buffer.line_len(pane.cursor.line));
+                if action == Action::ChangeToLineEnd {
+                    pane.cursor.col = col;
+                    pane.mode = Mode::Insert;
+                }
+                if !removed.is_empty() {
+                    workspace.registers.write(register, removed, false);
+                }
+                break;
+            }
+            Action::YankLine => {
+                // The count is lines, not a repeat
+                let text = workspace.focused_pane().yank_lines(count);
+                workspace.registers.write(register, text, true);
+                break;
+            }
+            Action::PasteAfter | Action::PasteBefore => {
+                let Some(yanked) = workspace.registers.read(register) else {
+                    let name = register.unwrap_or('"');
+                    workspace.set_message(format!("Nothing in register {}", name));
+                    break;
+                };
+                let before = action == Action::PasteBefore;
+                workspace.focused_pane_mut().paste(&yanked, before, count);
+                break;
+            }
+            Action::RepeatChange => {
+                repeat_change(workspace, count, input_state);
+                break;
+            }
+            Action::ReplaceChar(c) => {
+                // The count is how many chars to replace, not a repeat
+                workspace.focused_pane_mut().replace_chars(c, count);
+                break;
+            }
+            Action::IndentLine | Action::DedentLine => {
+                let indent = workspace.focused_pane().indent(&workspace.settings);
+                let pane = workspace.focused_pane_mut();
+                let line = pane.cursor.line + i;
+                if line >= pane.buffer.line_count() {
+                    break;
+                }
+                if action == Action::IndentLine {
+                    pane.indent_line(line, &indent.unit());
+                } else {
+                    pane.dedent_line(line, indent.width);
+                }
+                pane.cursor.col = pane.buffer.first_non_blank(pane.cursor.line);
+            }
+            Action::ToggleComment | Action::ToggleCommentDown | Action::ToggleCommentUp => {
+                // The count is lines, not a repeat
+                let language = workspace.focused_pane().language;
+                let Some(token) = workspace.settings.comment_token(language) else {
+                    workspace.set_error(format!("No comment token for {}", language.name()));
+                    break;
+                };
+                let pane = workspace.focused_pane_mut();
+                let line = pane.cursor.line;
+                let (first, last) = match action {
+                    Action::ToggleComment => (line, line + count - 1),
+                    Action::ToggleCommentDown => (line, line + count),
+                    _ => (line.saturating_sub(count), line),
+                };
+                pane.toggle_comment(first, last, &token);
+                break;
+            }
+
+            // Folding
+            Action::ToggleFold => {
+                if !workspace.focused_pane_mut().toggle_fold() {
+                    workspace.set_error("No fold found");
+                }
+                break;
+            }
+            Action::OpenAllFolds => workspace.focused_pane_mut().folds.open_all(),
+            Action::CloseAllFolds => workspace.focused_pane_mut().close_all_folds(),
+
+            // Multiple cursors
+            Action::SelectAllMatches => {
+                match workspace.select_all_matches() {
+                    0 => workspace.set_error("No search matches"),
+                    n => workspace.set_message(format!("{} cursors", n)),
+                }
+                break;
+            }
+
+            // Scrolling
+            Action::ScrollCenter | Action::ScrollTop | Action::ScrollBottom => {
+                let pane = workspace.focused_pane_mut();
+                let last_row = pane.viewport_height.saturating_sub(1);
+                let row = match action {
+                    Action::ScrollTop => 0,
+                    Action::ScrollBottom => last_row,
+                    _ => last_row / 2,
+                };
+                pane.scroll_cursor_to_row(row);
+                break;
+            }
+
+            // Mode changes
+            Action::EnterInsertMode => {
+                workspace.focused_pane_mut().mode = Mode::Insert;
+            }
+            Action::EnterInsertModeAppend => {
+                let pane = workspace.focused_pane_mut();
+                pane.cursor.move_right(&pane.buffer);
+                pane.mode = Mode::Insert;
+            }
+            Action::EnterInsertModeAppendLine => {
+                let pane = workspace.focused_pane_mut();
+                pane.cursor.col = pane.buffer.line_len(pane.cursor.line);
+                pane.mode = Mode::Insert;
+            }
+            Action::EnterInsertModeOpenBelow => {
+                let indent_unit = auto_indent_unit(workspace);
+                let pane = workspace.focused_pane_mut();
+                let line = pane.cursor.line;
+                let line_len = pane.buffer.line_len(line);
+                let indent = indent_unit
+                    .map(|unit| pane.indent_after(line, line_len, &unit))
+                    .unwrap_or_default();
+                pane.insert_newline(line, line_len, &indent);
+                pane.cursor.line += 1;
+                pane.cursor.col = indent.chars().count();
+                pane.mode = Mode::Insert;
+            }
+            Action::EnterInsertModeOpenAbove => {
+                let indent_unit = auto_indent_unit(workspace);
+                let pane = workspace.focused_pane_mut();
+                let line = pane.cursor.line;
+                // Match the current line's indentation (no extra level above a brace)
+                let indent = indent_unit
+                    .map(|unit| pane.indent_after(line, pane.buffer.first_non_blank(line), &unit))
+                    .unwrap_or_default();
+                pane.insert_text(line, 0, &format!("{}\n", indent));
+                pane.cursor.col = indent.chars().count();
                 pane.mode = Mode::Insert;
             }
+            Action::EnterReplaceMode => {
+                let pane = workspace.focused_pane_mut();
+                pane.extra_cursors.clear();
+                pane.replaced.clear();
+                pane.mode = Mode::Replace;
+            }
             Action::EnterNormalMode => {
                 let pane = workspace.focused_pane_mut();
                 pane.mode = Mode::Normal;
@@ -472,12 +1448,10 @@ fn execute_action(
                     pane.cursor.col = line_len.saturating_sub(1);
                 }
             }
-            Action::EnterCommandMode => {
-                workspace.focused_pane_mut().mode = Mode::Command;
-                workspace.command_buffer.clear();
-            }
+            Action::EnterCommandMode => workspace.start_command(),
 
             // Window management
+            Action::OnlyPane => only_pane(workspace, false),
             Action::SplitVertical => workspace.split_vertical(),
             Action::SplitHorizontal => workspace.split_horizontal(),
             Action::FocusNext => workspace.focus_next(),
@@ -485,6 +1459,34 @@ fn execute_action(
             Action::FocusRight => workspace.focus_direction(Direction::Right),
             Action::FocusUp => workspace.focus_direction(Direction::Up),
             Action::FocusDown => workspace.focus_direction(Direction::Down),
+            Action::SwapPane => {
+                if !workspace.swap_with_next() {
+                    workspace.set_error("No pane to swap with");
+                    break;
+                }
+            }
+            Action::RotatePanes => {
+                if !workspace.rotate_focused_split() {
+                    workspace.set_error("No split to rotate");
+                    break;
+                }
+            }
+            Action::ResizeTaller
+            | Action::ResizeShorter
+            | Action::ResizeWider
+            | Action::ResizeNarrower => {
+                // Height is shared out by stacked splits, width by side-by-side ones
+                let (direction, delta) = match action {
+                    Action::ResizeTaller => (SplitDirection::Horizontal, RESIZE_STEP),
+                    Action::ResizeShorter => (SplitDirection::Horizontal, -RESIZE_STEP),
+                    Action::ResizeWider => (SplitDirection::Vertical, RESIZE_STEP),
+                    _ => (SplitDirection::Vertical, -RESIZE_STEP),
+                };
+                if !workspace.resize_focused(direction, delta) {
+                    workspace.set_error("No split to resize");
+                    break;
+                }
+            }
 
             // File browser
             Action::ToggleFileBrowser => workspace.toggle_file_browser(),
@@ -498,6 +1500,18 @@ fn execute_action(
                 // For now, grep the word under cursor (or prompt for pattern)
                 workspace.pending_finder = Some(FinderAction::Grep(String::new()));
             }
+            Action::GrepWordUnderCursor => {
+                workspace.pending_finder = Some(FinderAction::GrepWord);
+            }
+            Action::GotoFile => {
+                if let Err(e) = workspace.goto_file() {
+                    workspace.set_message(e);
+                }
+            }
+            Action::RecentFiles => {
+                workspace.pending_finder = Some(FinderAction::RecentFiles);
+            }
+            Action::Symbols => workspace.show_symbols(),
 
             // Pane selection
             Action::SelectPane(c) => {
@@ -515,7 +1529,18 @@ fn execute_action(
                 workspace.prev_tab();
             }
             Action::CloseTab => {
-                workspace.close_tab();
+                if workspace.tab().has_unsaved_changes() {
+                    workspace.set_error(NO_WRITE_MESSAGE);
+                } else {
+                    workspace.close_tab();
+                }
+            }
+
+            // Buffers
+            Action::AlternateBuffer => {
+                if let Err(e) = workspace.alternate_buffer() {
+                    workspace.set_message(e);
+                }
             }
 
             // Search
@@ -531,53 +1556,428 @@ fn execute_action(
             Action::SearchPrev => {
                 workspace.search_prev();
             }
+            Action::SearchWordForward => {
+                workspace.search_word_under_cursor(SearchDirection::Forward);
+            }
+            Action::SearchWordBackward => {
+                workspace.search_word_under_cursor(SearchDirection::Backward);
+            }
             Action::ClearSearch => {
                 workspace.clear_search();
             }
 
             // Other
-            Action::Quit => workspace.quit(),
+            Action::Quit => {
+                if workspace.has_unsaved_changes() {
+                    workspace.set_error("No write since last change (:qa! to quit anyway)");
+                } else {
+                    workspace.quit();
+                }
+            }
+        }
+    }
+
+    // Keep the cursor off the middle of a grapheme cluster, e.g. after `$`
+    // onto the accent of the last char
+    let pane = workspace.focused_pane_mut();
+    if pane.mode == Mode::Normal {
+        pane.cursor.col = pane
+            .buffer
+            .grapheme_start(pane.cursor.line, pane.cursor.col);
+    }
+
+    // Keys typed in insert or replace mode become part of the change
+    input_state.recording_insert =
+        action.is_change() && matches!(workspace.focused_pane().mode, Mode::Insert | Mode::Replace);
+}
+
+/// Replay the last change (`.`). A count replaces the change's own count,
+/// except for insertions, which are typed out `count` times.
+fn repeat_change(workspace: &mut Workspace, count: usize, input_state: &mut InputState) {
+    let Some(change) = input_state.last_change.clone() else {
+        return;
+    };
+
+    if !enters_insert(&change) {
+        let count = if count > 1 { count } else { change.count };
+        execute_action(workspace, change.action.clone(), count, input_state);
+    } else {
+        for _ in 0..count {
+            execute_action(workspace, change.action.clone(), change.count, input_state);
+            for key in &change.inserted {
+                handle_typing(workspace, *key);
+            }
+            handle_typing(workspace, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        }
+    }
+
+    // Replaying records over the change; keep the original
+    input_state.last_change = Some(change);
+    input_state.recording_insert = false;
+}
+
+/// Apply an operator to the text from `start` to `end`: delete it (into
+/// `register`), change it, or change its case
+fn apply_operator(
+    workspace: &mut Workspace,
+    op: Operator,
+    start: (usize, usize),
+    end: (usize, usize),
+    register: Option<char>,
+) {
+    let pane = workspace.focused_pane_mut();
+    let removed = pane.delete_range(start, end);
+    pane.cursor.line = start.0;
+    pane.cursor.col = start.1;
+    match op {
+        Operator::Change => pane.mode = Mode::Insert,
+        Operator::Delete => {
+            let line_len = pane.buffer.line_len(start.0);
+            pane.cursor.col = start.1.min(line_len.saturating_sub(1));
+        }
+        Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+            pane.insert_text(start.0, start.1, &change_case(&removed, op));
+            return;
+        }
+    }
+    if !removed.is_empty() {
+        workspace.registers.write(register, removed, false);
+    }
+}
+
+/// `text` in lower or upper case, or with each letter's case swapped. A char
+/// can become several (`ß` is `SS` in upper case).
+fn change_case(text: &str, op: Operator) -> String {
+    let mut changed = String::with_capacity(text.len());
+    for c in text.chars() {
+        let upper = match op {
+            Operator::Uppercase => true,
+            Operator::ToggleCase => c.is_lowercase(),
+            _ => false,
+        };
+        if upper {
+            changed.extend(c.to_uppercase());
+        } else {
+            changed.extend(c.to_lowercase());
+        }
+    }
+    changed
+}
+
+/// Whether a change starts insert mode (`i`, `o`, `C`, `ciw`, …)
+fn enters_insert(change: &Change) -> bool {
+    matches!(
+        change.action,
+        Action::OperatorTextObject(Operator::Change, _)
+            | Action::OperatorWord(Operator::Change, _)
+            | Action::ChangeToLineEnd
+            | Action::EnterInsertMode
+            | Action::EnterInsertModeAppend
+            | Action::EnterInsertModeAppendLine
+            | Action::EnterInsertModeOpenBelow
+            | Action::EnterInsertModeOpenAbove
+            | Action::EnterReplaceMode
+    )
+}
+
+/// How many macros can play inside one another (`@a` recorded into `a`)
+const MAX_MACRO_DEPTH: usize = 20;
+
+/// The most keys one `@` can replay, counting repeats (`1000@q`)
+const MAX_MACRO_KEYS: usize = 1_000_000;
+
+/// How much of a split each Ctrl-W +/- moves, as a fraction of its size
+const RESIZE_STEP: f32 = 0.05;
+
+/// Lines each notch of the mouse wheel scrolls
+const MOUSE_SCROLL_LINES: usize = 3;
+
+/// Shown when an edit is refused
+const READ_ONLY_MESSAGE: &str = "Buffer is read-only";
+
+/// Shown when writing a binary file anywhere is refused, since it was loaded lossily
+const BINARY_MESSAGE: &str = "Binary files can't be written";
+
+/// Shown when quitting or closing would discard unsaved changes
+const NO_WRITE_MESSAGE: &str = "No write since last change (add ! to override)";
+
+/// Shown when `:w <path>` or `:saveas` would overwrite another file
+const FILE_EXISTS_MESSAGE: &str = "File exists (add ! to override)";
+
+/// Close the focused pane, or quit if it's the last one
+fn close_pane_or_quit(workspace: &mut Workspace) {
+    if !workspace.close_focused_pane() && !workspace.close_tab() {
+        quit_unless_unsaved_elsewhere(workspace);
+    }
+}
+
+/// Quit from the last pane of the last tab, unless a buffer other than its
+/// own (in another tab, or in the background) has unsaved changes
+fn quit_unless_unsaved_elsewhere(workspace: &mut Workspace) {
+    if workspace.unsaved_elsewhere() {
+        workspace.set_error("Other buffers have unsaved changes (:qa! to quit anyway)");
+    } else {
+        workspace.quit();
+    }
+}
+
+/// Close every pane but the focused one (`:only`, `Ctrl-W o`), unless one
+/// has unsaved changes and this isn't `force`d
+fn only_pane(workspace: &mut Workspace, force: bool) {
+    if !force && workspace.tab().others_have_unsaved_changes() {
+        workspace.set_error(NO_WRITE_MESSAGE);
+    } else if workspace.close_other_panes() == 0 {
+        workspace.set_message("Already only one pane");
+    }
+}
+
+/// Like `close_pane_or_quit`, but throw away the buffer's changes (`:q!`)
+fn discard_pane_or_quit(workspace: &mut Workspace) {
+    if !workspace.discard_focused_pane() && !workspace.discard_tab() {
+        quit_unless_unsaved_elsewhere(workspace);
+    }
+}
+
+/// Tidy whitespace and run the formatter before a write, as the settings
+/// ask. Returns false if formatting failed; the buffer is then written
+/// unformatted.
+fn format_before_save(workspace: &mut Workspace) -> bool {
+    let (trim, final_newline) = (
+        workspace.settings.trim_trailing_whitespace,
+        workspace.settings.ensure_final_newline,
+    );
+    let pane = workspace.focused_pane_mut();
+    if !pane.buffer.is_read_only() {
+        if trim {
+            pane.trim_trailing_whitespace();
+        }
+        if final_newline {
+            pane.ensure_final_newline();
+        }
+    }
+
+    if !workspace.formats_on_save() {
+        return true;
+    }
+    match workspace.format_focused() {
+        Ok(_) => true,
+        Err(e) => {
+            workspace.set_error(e);
+            false
         }
     }
 }
 
+/// Write the focused buffer to `path` for `:w <path>` and `:saveas`, asking
+/// before creating missing directories. Another file already at `path` is
+/// only overwritten with `force` (`:w!`, `:saveas!`).
+fn save_to(workspace: &mut Workspace, path: PathBuf, rename: bool, force: bool) {
+    let own_file = workspace
+        .focused_pane()
+        .buffer
+        .path()
+        .is_some_and(|own| own == &path || same_file(own, &path));
+    if !force && !own_file && path.exists() {
+        workspace.set_error(FILE_EXISTS_MESSAGE);
+        return;
+    }
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+        && !dir.exists()
+    {
+        let message = format!("Create directory {}?", dir.display());
+        workspace.confirm(&message, PromptAction::ConfirmSaveAs { path, rename });
+        return;
+    }
+    write_to_path(workspace, path, rename);
+}
+
+/// Whether two paths name the same file on disk
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Write the focused buffer to `path`, taking that name if `rename`
+fn write_to_path(workspace: &mut Workspace, path: PathBuf, rename: bool) {
+    let result = if rename || workspace.focused_pane().buffer.path() == Some(&path) {
+        workspace.save_focused_as(path.clone())
+    } else {
+        workspace.write_focused_copy(&path)
+    };
+    match result {
+        Ok(_) => workspace.set_message(format!("Written {}", path.display())),
+        Err(e) => workspace.set_message(format!("Error: {}", e)),
+    }
+}
+
 fn execute_command(workspace: &mut Workspace) {
-    let cmd = workspace.command_buffer.trim().to_string();
+    let mut cmd = workspace.command_buffer.trim().to_string();
+    // Expand aliases defined with `lark::config::command("W", "w")`
+    let name = cmd.split(' ').next().unwrap_or("");
+    if let Some(target) = workspace.settings.command_aliases.get(name) {
+        cmd = format!("{}{}", target, &cmd[name.len()..]);
+    }
     let parts: Vec<&str> = cmd.splitn(2, ' ').collect();
     let command = parts.first().map(|s| *s).unwrap_or("");
     let args = parts.get(1).map(|s| *s);
 
     match command {
         "q" | "quit" => {
-            // Close current pane, or quit if last pane
-            if !workspace.close_focused_pane() {
+            if workspace.focused_pane().buffer.is_dirty() {
+                let name = workspace.focused_pane().buffer.display_name();
+                let message = format!("Discard changes to {}?", name);
+                workspace.confirm(&message, PromptAction::ConfirmQuit);
+            } else {
+                close_pane_or_quit(workspace);
+            }
+        }
+        "q!" | "quit!" => discard_pane_or_quit(workspace),
+        "qa" | "quitall" => {
+            if workspace.has_unsaved_changes() {
+                workspace.set_error(NO_WRITE_MESSAGE);
+            } else {
                 workspace.quit();
             }
         }
-        "qa" | "quitall" => workspace.quit(),
-        "w" | "write" => match workspace.focused_pane_mut().buffer.save() {
-            Ok(_) => workspace.set_message("Written"),
-            Err(e) => workspace.set_message(format!("Error: {}", e)),
-        },
-        "wq" => match workspace.focused_pane_mut().buffer.save() {
-            Ok(_) => {
-                if !workspace.close_focused_pane() {
-                    workspace.quit();
+        "qa!" | "quitall!" => workspace.quit(),
+        "w" | "write" | "w!" | "write!" | "wq" | "wq!" | "saveas" | "sav" | "saveas!" | "sav!"
+            if workspace.focused_pane().buffer.is_binary() =>
+        {
+            workspace.set_error(BINARY_MESSAGE);
+        }
+        "w" | "write" | "w!" | "write!" | "wq" | "wq!"
+            if args.is_none() && workspace.focused_pane().buffer.is_read_only() =>
+        {
+            workspace.set_error(READ_ONLY_MESSAGE);
+        }
+        "w" | "write" | "w!" | "write!" => {
+            format_before_save(workspace);
+            match args.map(str::trim).filter(|a| !a.is_empty()) {
+                // An unnamed buffer takes the name it's written under
+                Some(path) => {
+                    let rename = workspace.focused_pane().buffer.path().is_none();
+                    let force = command.ends_with('!');
+                    save_to(workspace, PathBuf::from(path), rename, force);
                 }
+                None => match workspace.save_focused() {
+                    Ok(_) => workspace.set_message("Written"),
+                    Err(e) => workspace.set_message(format!("Error: {}", e)),
+                },
             }
-            Err(e) => workspace.set_message(format!("Error: {}", e)),
+        }
+        "saveas" | "sav" | "saveas!" | "sav!" => match args.map(str::trim).filter(|a| !a.is_empty()) {
+            Some(path) => {
+                format_before_save(workspace);
+                save_to(workspace, PathBuf::from(path), true, command.ends_with('!'));
+            }
+            None => workspace.set_message("Usage: :saveas <path>"),
         },
-        "vs" | "vsplit" => workspace.split_vertical(),
-        "sp" | "split" => workspace.split_horizontal(),
+        "wq" | "wq!" => {
+            let formatted = format_before_save(workspace);
+            match workspace.save_focused() {
+                Ok(_) if formatted => close_pane_or_quit(workspace),
+                Ok(_) => {} // Stay open so the formatter error can be read
+                Err(e) => workspace.set_message(format!("Error: {}", e)),
+            }
+        }
+        "TrimWhitespace" => {
+            let pane = workspace.focused_pane_mut();
+            if pane.buffer.is_read_only() {
+                workspace.set_error(READ_ONLY_MESSAGE);
+            } else {
+                let trimmed = pane.trim_trailing_whitespace();
+                workspace.set_message(format!("Trimmed {} lines", trimmed));
+            }
+        }
+        "fmt" | "format" => {
+            if let Err(e) = workspace.format_focused() {
+                workspace.set_error(e);
+            }
+        }
+        // With a file name, the new pane shows that file
+        "vs" | "vsplit" | "sp" | "split" => {
+            let path = args.map(str::trim).filter(|p| !p.is_empty());
+            if let Some(path) = path
+                && !std::path::Path::new(path).exists()
+            {
+                workspace.set_message(format!("File not found: {}", path));
+            } else if let Some(path) = path
+                && std::path::Path::new(path).is_dir()
+            {
+                workspace.set_error(format!("{} is a directory", path));
+            } else if let Some(path) = path {
+                // The file goes in the new pane, which is dropped if it can't be read
+                let direction = if command.starts_with('v') {
+                    SplitDirection::Vertical
+                } else {
+                    SplitDirection::Horizontal
+                };
+                workspace.split_and_focus(direction);
+                if !workspace.open_file_in_focused_pane(PathBuf::from(path)) {
+                    workspace.discard_focused_pane();
+                }
+            } else if command.starts_with('v') {
+                workspace.split_vertical();
+            } else {
+                workspace.split_horizontal();
+            }
+        }
         "close" => {
+            if workspace.focused_pane().buffer.is_dirty() {
+                workspace.set_error(NO_WRITE_MESSAGE);
+            } else {
+                workspace.close_focused_pane();
+            }
+        }
+        "close!" => {
             workspace.close_focused_pane();
         }
+        "on" | "only" => only_pane(workspace, false),
+        "on!" | "only!" => only_pane(workspace, true),
+        // :<N> jumps to line N
+        line if line.parse::<usize>().is_ok() => {
+            let line: usize = line.parse().unwrap_or(1);
+            workspace
+                .focused_pane_mut()
+                .goto_line(line.saturating_sub(1));
+        }
+        "view" | "vie" => match args.map(str::trim).filter(|a| !a.is_empty()) {
+            Some(path) => {
+                if let Err(e) = workspace.view_file(PathBuf::from(path)) {
+                    workspace.set_error(e);
+                }
+            }
+            None => workspace.set_message("Usage: :view <path>"),
+        },
+        "recent" => workspace.pending_finder = Some(FinderAction::RecentFiles),
+        "symbols" => workspace.show_symbols(),
+        "ls" | "buffers" => workspace.show_buffer_picker(),
+        "b" | "buffer" => match args {
+            Some(name) => {
+                if let Err(e) = workspace.switch_buffer(name.trim()) {
+                    workspace.set_message(e);
+                }
+            }
+            None => workspace.show_buffer_picker(),
+        },
         "theme" => {
             if let Some(name) = args {
-                let available = crate::theme::list_builtin_themes();
-                if available.contains(&name) {
-                    workspace.set_theme(name);
-                    workspace.set_message(format!("Theme: {}", name));
+                let available = crate::theme::list_themes();
+                if available.iter().any(|t| t == name) {
+                    // Surface errors in user theme files instead of silently using the default
+                    match crate::theme::find_theme(name) {
+                        Some(_) => {
+                            workspace.set_theme(name);
+                            workspace.set_message(format!("Theme: {}", name));
+                        }
+                        None => {
+                            let err = crate::theme::load_user_theme(name).err();
+                            workspace.set_error(err.unwrap_or_default());
+                        }
+                    }
                 } else {
                     workspace.set_message(format!(
                         "Unknown theme: {}. Available: {}",
@@ -590,23 +1990,29 @@ fn execute_command(workspace: &mut Workspace) {
             }
         }
         "themes" => {
-            let themes = crate::theme::list_builtin_themes().join(", ");
+            let themes = crate::theme::list_themes().join(", ");
             workspace.set_message(format!("Available themes: {}", themes));
         }
-        "source" => {
-            // Reload config file
-            let mut script_engine = crate::scripting::ScriptEngine::new();
-            match script_engine.load_default() {
-                Ok(_) => {
-                    let settings = script_engine.settings();
-                    workspace.theme_name = settings.theme.clone();
-                    workspace.set_message("Config reloaded");
-                }
-                Err(e) => {
-                    workspace.set_message(format!("Config error: {}", e));
-                }
+        // Reloaded by the main loop, which owns the script engine and its hooks
+        "source" => workspace.reload_config = true,
+        // Edit the config file, starting it from the example if there isn't one
+        "config" if args.is_none_or(|a| a.trim() == "edit") => {
+            use crate::scripting::ScriptEngine;
+            match ScriptEngine::config_file() {
+                None => workspace.set_error("No home directory for the config file"),
+                Some(path) => match ScriptEngine::create_config_file(&path) {
+                    Ok(()) => {
+                        workspace.open_file_in_focused_pane(path);
+                        workspace
+                            .set_message("Editing the config; :source applies it once written");
+                    }
+                    Err(e) => {
+                        workspace.set_error(format!("Can't create {}: {}", path.display(), e))
+                    }
+                },
             }
         }
+        "config" => workspace.set_error("Usage: :config [edit]"),
         "TSList" => {
             // List installed and available grammars
             let registry = crate::syntax::LanguageRegistry::new();
@@ -647,6 +2053,17 @@ fn execute_command(workspace: &mut Workspace) {
                 crate::syntax::TREE_SITTER_ABI_VERSION
             )];
 
+            // Each installed grammar's pinned tag and the commit it was built from
+            let metadata = crate::syntax::GrammarMetadata::load();
+            for lang in registry.installed() {
+                let rev = lang.grammar_rev().unwrap_or("default branch");
+                let commit = metadata
+                    .commit(lang)
+                    .map(|c| &c[..c.len().min(12)])
+                    .unwrap_or("commit unknown");
+                lines.push(format!("{}: {} ({})", lang.name(), rev, commit));
+            }
+
             if outdated.is_empty() {
                 lines.push("All grammars are compatible".to_string());
             } else {
@@ -689,61 +2106,11 @@ fn execute_command(workspace: &mut Workspace) {
             // Install a grammar
             let lang_name = cmd.strip_prefix("TSInstall ").unwrap().trim();
 
-            // Find the language
-            let lang = match lang_name.to_lowercase().as_str() {
-                "rust" => Some(crate::syntax::Language::Rust),
-                "python" => Some(crate::syntax::Language::Python),
-                "javascript" | "js" => Some(crate::syntax::Language::JavaScript),
-                "typescript" | "ts" => Some(crate::syntax::Language::TypeScript),
-                "tsx" => Some(crate::syntax::Language::Tsx),
-                "go" => Some(crate::syntax::Language::Go),
-                "c" => Some(crate::syntax::Language::C),
-                "cpp" | "c++" => Some(crate::syntax::Language::Cpp),
-                "json" => Some(crate::syntax::Language::Json),
-                "toml" => Some(crate::syntax::Language::Toml),
-                "markdown" | "md" => Some(crate::syntax::Language::Markdown),
-                "bash" | "sh" => Some(crate::syntax::Language::Bash),
-                "lua" => Some(crate::syntax::Language::Lua),
-                "ruby" => Some(crate::syntax::Language::Ruby),
-                "html" => Some(crate::syntax::Language::Html),
-                "css" => Some(crate::syntax::Language::Css),
-                "yaml" | "yml" => Some(crate::syntax::Language::Yaml),
-                _ => None,
-            };
+            let lang = crate::syntax::Language::from_name(lang_name);
 
             match lang {
-                Some(lang) => {
-                    workspace.set_message(format!("Installing {} grammar...", lang.name()));
-                    // Note: This blocks the UI - ideally should be async
-                    let mut installer = crate::syntax::GrammarInstaller::new();
-                    match installer.install(lang) {
-                        crate::syntax::InstallResult::Success => {
-                            workspace.set_message(format!(
-                                "{} grammar installed successfully!",
-                                lang.name()
-                            ));
-                        }
-                        crate::syntax::InstallResult::AlreadyInstalled => {
-                            workspace.set_message(format!(
-                                "{} grammar is already installed",
-                                lang.name()
-                            ));
-                        }
-                        crate::syntax::InstallResult::Reinstalled => {
-                            workspace.set_message(format!(
-                                "{} grammar reinstalled (ABI updated)",
-                                lang.name()
-                            ));
-                        }
-                        crate::syntax::InstallResult::Error(e) => {
-                            workspace.set_error(format!(
-                                "Failed to install {} grammar:\n{}",
-                                lang.name(),
-                                e
-                            ));
-                        }
-                    }
-                }
+                _ if lang_name.eq_ignore_ascii_case("all") => workspace.start_install_all(),
+                Some(lang) => workspace.start_install(lang),
                 None => {
                     let available: Vec<_> = crate::syntax::Language::all_installable()
                         .iter()
@@ -761,19 +2128,7 @@ fn execute_command(workspace: &mut Workspace) {
             // Uninstall a grammar
             let lang_name = cmd.strip_prefix("TSUninstall ").unwrap().trim();
 
-            let lang = match lang_name.to_lowercase().as_str() {
-                "rust" => Some(crate::syntax::Language::Rust),
-                "python" => Some(crate::syntax::Language::Python),
-                "javascript" | "js" => Some(crate::syntax::Language::JavaScript),
-                "typescript" | "ts" => Some(crate::syntax::Language::TypeScript),
-                "go" => Some(crate::syntax::Language::Go),
-                "c" => Some(crate::syntax::Language::C),
-                "cpp" | "c++" => Some(crate::syntax::Language::Cpp),
-                "json" => Some(crate::syntax::Language::Json),
-                "toml" => Some(crate::syntax::Language::Toml),
-                "markdown" | "md" => Some(crate::syntax::Language::Markdown),
-                _ => None,
-            };
+            let lang = crate::syntax::Language::from_name(lang_name);
 
             match lang {
                 Some(lang) => {
@@ -792,6 +2147,16 @@ fn execute_command(workspace: &mut Workspace) {
                 }
             }
         }
+        // Hide the search highlights, keeping the pattern for n and N
+        "noh" | "nohlsearch" => workspace.clear_search(),
+        "mes" | "messages" => {
+            if workspace.messages.is_empty() {
+                workspace.set_message("No messages");
+            } else {
+                let messages = workspace.messages.format(std::time::Instant::now());
+                workspace.show_message_viewer("Messages", messages);
+            }
+        }
         "log" => {
             // Show the editor log in the message viewer
             let log = workspace.get_log();
@@ -801,6 +2166,106 @@ fn execute_command(workspace: &mut Workspace) {
                 workspace.show_message_viewer("Editor Log", log);
             }
         }
+        "set" => match args {
+            Some("ff") | Some("fileformat") => {
+                let ff = workspace.focused_pane().buffer.line_ending().name();
+                workspace.set_message(format!("fileformat={}", ff));
+            }
+            Some("indent") => {
+                let indent = workspace.focused_pane().indent(&workspace.settings);
+                workspace.set_message(format!("indent={}", indent.name()));
+            }
+            Some(arg) if arg.starts_with("indent=") => {
+                let value = arg.split_once('=').map(|(_, v)| v).unwrap_or("");
+                match crate::editor::IndentStyle::from_name(value) {
+                    Some(style) => workspace
+                        .focused_pane_mut()
+                        .buffer
+                        .set_indent_style(Some(style)),
+                    None => workspace.set_error(format!(
+                        "Invalid indent: {} (use tabs or a number of spaces)",
+                        value
+                    )),
+                }
+            }
+            Some("ro") | Some("readonly") => {
+                workspace.focused_pane_mut().buffer.set_read_only(true);
+            }
+            Some("noro") | Some("noreadonly") => {
+                let buffer = &mut workspace.focused_pane_mut().buffer;
+                if buffer.is_binary() {
+                    workspace.set_error("Binary files stay read-only");
+                } else {
+                    buffer.set_read_only(false);
+                }
+            }
+            Some(arg)
+                if (arg.starts_with("ff=") || arg.starts_with("fileformat="))
+                    && workspace.focused_pane().buffer.is_read_only() =>
+            {
+                workspace.set_error(READ_ONLY_MESSAGE);
+            }
+            Some(arg) if arg.starts_with("ff=") || arg.starts_with("fileformat=") => {
+                let value = arg.split_once('=').map(|(_, v)| v).unwrap_or("");
+                match crate::editor::LineEnding::from_name(value) {
+                    Some(ending) => {
+                        workspace.focused_pane_mut().buffer.set_line_ending(ending);
+                        workspace.set_message(format!("fileformat={}", value));
+                    }
+                    None => workspace
+                        .set_error(format!("Invalid fileformat: {} (use unix or dos)", value)),
+                }
+            }
+            Some(name @ ("ignorecase" | "ic" | "noignorecase" | "noic")) => {
+                workspace.settings.ignore_case = !name.starts_with("no");
+                workspace.refresh_search();
+            }
+            Some(name @ ("smartcase" | "scs" | "nosmartcase" | "noscs")) => {
+                workspace.settings.smart_case = !name.starts_with("no");
+                workspace.refresh_search();
+            }
+            Some("cursorline") | Some("cul") => workspace.settings.cursor_line = true,
+            Some("nocursorline") | Some("nocul") => workspace.settings.cursor_line = false,
+            Some("list") => workspace.settings.show_whitespace = true,
+            Some("nolist") => workspace.settings.show_whitespace = false,
+            Some("mouse") => workspace.settings.mouse = true,
+            Some("nomouse") => workspace.settings.mouse = false,
+            Some(arg) if arg.starts_with("clipboard=") => {
+                let value = arg.split_once('=').map(|(_, v)| v).unwrap_or("");
+                match value {
+                    "system" => workspace.settings.system_clipboard = true,
+                    "internal" => workspace.settings.system_clipboard = false,
+                    _ => workspace
+                        .set_error(format!("Invalid clipboard: {} (use system or internal)", value)),
+                }
+            }
+            Some(arg)
+                if arg.starts_with("scrolloff=")
+                    || arg.starts_with("so=")
+                    || arg.starts_with("sidescrolloff=")
+                    || arg.starts_with("siso=") =>
+            {
+                let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+                match value.parse::<usize>() {
+                    Ok(n) if matches!(name, "sidescrolloff" | "siso") => {
+                        workspace.settings.side_scroll_off = n
+                    }
+                    Ok(n) => workspace.settings.scroll_off = n,
+                    Err(_) => workspace.set_error(format!("Invalid {}: {}", name, value)),
+                }
+            }
+            Some(arg) if arg.starts_with("colorcolumn=") || arg.starts_with("cc=") => {
+                let value = arg.split_once('=').map(|(_, v)| v).unwrap_or("");
+                match value.parse::<usize>() {
+                    Ok(column) => workspace.settings.color_column = column,
+                    Err(_) => workspace.set_error(format!("Invalid colorcolumn: {}", value)),
+                }
+            }
+            Some(arg) => workspace.set_error(format!("Unknown option: {}", arg)),
+            None => workspace.set_message(
+                "Usage: :set ff=unix|dos, :set [no]cursorline, :set [no]list, :set cc=N, :set so=N, :set indent=tabs|N, :set [no]ro",
+            ),
+        },
         "syntax" => {
             // Show syntax highlighting status for the focused editor pane
             let pane = workspace.focused_pane();
@@ -814,7 +2279,11 @@ fn execute_command(workspace: &mut Workspace) {
                 crate::editor::PaneKind::FileBrowser => "FileBrowser",
             };
             let status = pane.highlighter.status();
-            workspace.set_message(format!("{} | {} | {}", pane_kind, file_info, status));
+            let ff = pane.buffer.line_ending().name();
+            workspace.set_message(format!(
+                "{} | {} | ff={} | {}",
+                pane_kind, file_info, ff, status
+            ));
         }
         "TSDebug" => {
             // Debug: dump tree-sitter node types for first few lines
@@ -830,6 +2299,15 @@ fn execute_command(workspace: &mut Workspace) {
                 if workspace.verbose { "on" } else { "off" }
             ));
         }
+        // Read the file again: :e only without unsaved changes, :e! regardless
+        "e" | "edit" if workspace.focused_pane().buffer.is_dirty() => {
+            workspace.set_error(NO_WRITE_MESSAGE);
+        }
+        "e" | "edit" | "e!" | "edit!" if args.is_none() => {
+            if let Err(e) = workspace.reload_focused() {
+                workspace.set_error(e);
+            }
+        }
         _ if cmd.starts_with("e ") || cmd.starts_with("edit ") => {
             // Open a file
             let path_str = if cmd.starts_with("e ") {
@@ -840,34 +2318,98 @@ fn execute_command(workspace: &mut Workspace) {
 
             let path = std::path::PathBuf::from(path_str);
             if path.exists() {
-                workspace.open_file_in_focused_pane(path);
-                workspace.set_message(format!("Opened: {}", path_str));
+                if workspace.open_file_in_focused_pane(path) {
+                    workspace.set_message(format!("Opened: {}", path_str));
+                }
             } else {
                 workspace.set_message(format!("File not found: {}", path_str));
             }
         }
         "" => {}
+        // Commands defined in the config run in the main loop, which owns the script engine
+        name if workspace.script_commands.contains(name) => {
+            let args = args.unwrap_or("").trim().to_string();
+            workspace
+                .events
+                .push(EditorEvent::Command(name.to_string(), args));
+        }
         _ => {
             workspace.set_message(format!("Unknown command: {}", cmd));
         }
     }
     workspace.command_buffer.clear();
+    workspace.command_cursor = 0;
+    workspace.command_completion = None;
     // Only reset mode if not in MessageViewer (some commands switch to it)
     if workspace.mode() != Mode::MessageViewer {
         workspace.focused_pane_mut().mode = Mode::Normal;
     }
 }
 
-// Word motion helpers
+// Word motion helpers. `is_word` picks out the chars words are made of:
+// letters, digits and `_` for w/b/e, anything but whitespace for W/B/E.
 fn is_word_char(c: char) -> bool {
     c.is_alphanumeric() || c == '_'
 }
 
-fn move_word_forward(pane: &mut crate::editor::Pane) {
+fn is_big_word_char(c: char) -> bool {
+    !c.is_whitespace()
+}
+
+/// The range `op` takes with `motion` repeated `count` times from the cursor.
+/// Like vim, `dw` on a line's last word stops at the end of the line, and `cw`
+/// on a word changes to its end without the space after it.
+fn word_motion_range(
+    pane: &mut Pane,
+    op: Operator,
+    motion: WordMotion,
+    count: usize,
+) -> ((usize, usize), (usize, usize)) {
+    let (is_word, motion): (fn(char) -> bool, _) = match motion {
+        WordMotion::Forward | WordMotion::End | WordMotion::Backward => (is_word_char, motion),
+        WordMotion::BigForward => (is_big_word_char, WordMotion::Forward),
+        WordMotion::BigEnd => (is_big_word_char, WordMotion::End),
+        WordMotion::BigBackward => (is_big_word_char, WordMotion::Backward),
+    };
+    let origin = (pane.cursor.line, pane.cursor.col);
+    let on_word = |col| pane.buffer.char_at(origin.0, col).is_some_and(is_word);
+    let change_word = op == Operator::Change && motion == WordMotion::Forward && on_word(origin.1);
+    // Already on the word's last char, the first `e` of `cw` stays put
+    let skip_first = change_word && !on_word(origin.1 + 1);
+    let motion = if change_word { WordMotion::End } else { motion };
+
+    for n in 0..count {
+        match motion {
+            WordMotion::Forward => move_word_forward(pane, is_word),
+            WordMotion::Backward => move_word_backward(pane, is_word),
+            _ if n == 0 && skip_first => {}
+            _ => move_word_end(pane, is_word),
+        }
+    }
+    let target = (pane.cursor.line, pane.cursor.col);
+    (pane.cursor.line, pane.cursor.col) = origin;
+
+    match motion {
+        WordMotion::Backward => (target, origin),
+        WordMotion::Forward
+            if target.0 > origin.0 && target.1 <= pane.buffer.first_non_blank(target.0) =>
+        {
+            let line = target.0 - 1;
+            (origin, (line, pane.buffer.line_len(line)))
+        }
+        WordMotion::Forward => (origin, target),
+        _ => {
+            let end = (target.1 + 1).min(pane.buffer.line_len(target.0));
+            (origin, (target.0, end))
+        }
+    }
+}
+
+fn move_word_forward(pane: &mut crate::editor::Pane, is_word: fn(char) -> bool) {
     let line_count = pane.buffer.line_count();
 
     while let Some(c) = pane.buffer.char_at(pane.cursor.line, pane.cursor.col) {
-        if !is_word_char(c) {
+        if !is_word(c) {
             break;
         }
         pane.cursor.col += 1;
@@ -875,7 +2417,7 @@ fn move_word_forward(pane: &mut crate::editor::Pane) {
 
     loop {
         match pane.buffer.char_at(pane.cursor.line, pane.cursor.col) {
-            Some(c) if is_word_char(c) => break,
+            Some(c) if is_word(c) => break,
             Some(_) => pane.cursor.col += 1,
             None => {
                 if pane.cursor.line + 1 < line_count {
@@ -889,7 +2431,7 @@ fn move_word_forward(pane: &mut crate::editor::Pane) {
     }
 }
 
-fn move_word_backward(pane: &mut crate::editor::Pane) {
+fn move_word_backward(pane: &mut crate::editor::Pane, is_word: fn(char) -> bool) {
     if pane.cursor.col > 0 {
         pane.cursor.col -= 1;
     } else if pane.cursor.line > 0 {
@@ -899,7 +2441,7 @@ fn move_word_backward(pane: &mut crate::editor::Pane) {
 
     loop {
         match pane.buffer.char_at(pane.cursor.line, pane.cursor.col) {
-            Some(c) if is_word_char(c) => break,
+            Some(c) if is_word(c) => break,
             Some(_) if pane.cursor.col > 0 => pane.cursor.col -= 1,
             _ if pane.cursor.line > 0 => {
                 pane.cursor.line -= 1;
@@ -911,7 +2453,7 @@ fn move_word_backward(pane: &mut crate::editor::Pane) {
 
     while pane.cursor.col > 0 {
         if let Some(c) = pane.buffer.char_at(pane.cursor.line, pane.cursor.col - 1) {
-            if is_word_char(c) {
+            if is_word(c) {
                 pane.cursor.col -= 1;
             } else {
                 break;
@@ -922,13 +2464,13 @@ fn move_word_backward(pane: &mut crate::editor::Pane) {
     }
 }
 
-fn move_word_end(pane: &mut crate::editor::Pane) {
+fn move_word_end(pane: &mut crate::editor::Pane, is_word: fn(char) -> bool) {
     let line_count = pane.buffer.line_count();
     pane.cursor.col += 1;
 
     loop {
         match pane.buffer.char_at(pane.cursor.line, pane.cursor.col) {
-            Some(c) if is_word_char(c) => break,
+            Some(c) if is_word(c) => break,
             Some(_) => pane.cursor.col += 1,
             None => {
                 if pane.cursor.line + 1 < line_count {
@@ -946,10 +2488,950 @@ fn move_word_end(pane: &mut crate::editor::Pane) {
     }
 
     while let Some(c) = pane.buffer.char_at(pane.cursor.line, pane.cursor.col + 1) {
-        if is_word_char(c) {
+        if is_word(c) {
             pane.cursor.col += 1;
         } else {
             break;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::{Buffer, Pane, TextObject};
+
+    fn pane_with(text: &str) -> Pane {
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = Buffer::from_text(text);
+        pane
+    }
+
+    /// Columns visited by repeating `motion` from the start of the line
+    fn stops(motion: fn(&mut Pane, fn(char) -> bool), is_word: fn(char) -> bool) -> Vec<usize> {
+        let mut pane = pane_with("a.b(c) d-e f\n");
+        (0..3)
+            .map(|_| {
+                motion(&mut pane, is_word);
+                pane.cursor.col
+            })
+            .collect()
+    }
+
+    #[test]
+    fn word_motions_stop_at_punctuation_and_big_word_motions_do_not() {
+        assert_eq!(stops(move_word_forward, is_word_char), vec![2, 4, 7]);
+        // The last W runs off the line onto the next one
+        assert_eq!(stops(move_word_forward, is_big_word_char), vec![7, 11, 0]);
+        assert_eq!(stops(move_word_end, is_word_char), vec![2, 4, 7]);
+        assert_eq!(stops(move_word_end, is_big_word_char), vec![5, 9, 11]);
+    }
+
+    #[test]
+    fn big_word_backward_skips_symbols() {
+        let mut pane = pane_with("let x = a->b; y\n");
+        pane.cursor.col = 14;
+        move_word_backward(&mut pane, is_big_word_char);
+        assert_eq!(pane.cursor.col, 8);
+        move_word_backward(&mut pane, is_big_word_char);
+        assert_eq!(pane.cursor.col, 6);
+
+        pane.cursor.col = 14;
+        move_word_backward(&mut pane, is_word_char);
+        assert_eq!(pane.cursor.col, 11);
+    }
+
+    fn run_command(workspace: &mut Workspace, command: &str) {
+        workspace.command_buffer = command.to_string();
+        execute_command(workspace);
+    }
+
+    #[test]
+    fn set_indent_shows_and_changes_how_the_buffer_is_indented() {
+        let mut ws = Workspace::with_scratch("[stdin]", "a\n");
+        let mut input_state = InputState::new();
+        run_command(&mut ws, "set indent");
+        assert_eq!(ws.message.as_deref(), Some("indent=spaces:4"));
+
+        run_command(&mut ws, "set indent=tabs");
+        execute_action(&mut ws, Action::IndentLine, 1, &mut input_state);
+        assert_eq!(ws.focused_pane().buffer.line(0).to_string(), "\ta\n");
+        run_command(&mut ws, "set indent=2");
+        execute_action(&mut ws, Action::IndentLine, 1, &mut input_state);
+        assert_eq!(ws.focused_pane().buffer.line(0).to_string(), "  \ta\n");
+        execute_action(&mut ws, Action::DedentLine, 1, &mut input_state);
+        assert_eq!(ws.focused_pane().buffer.line(0).to_string(), "\ta\n");
+
+        run_command(&mut ws, "set indent=spaces");
+        assert!(ws.error.as_deref().unwrap().starts_with("Invalid indent"));
+    }
+
+    #[test]
+    fn write_names_scratch_buffers_and_saveas_asks_for_directories() {
+        let dir = std::env::temp_dir().join(format!("lark-saveas-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut workspace = Workspace::with_scratch("[stdin]", "text\n");
+
+        // An unnamed buffer is renamed by :w, a named one only copied
+        let first = dir.join("first.txt");
+        run_command(&mut workspace, &format!("w {}", first.display()));
+        assert_eq!(workspace.focused_pane().buffer.path(), Some(&first));
+        assert_eq!(workspace.tab().name, "first.txt");
+        let copy = dir.join("copy.txt");
+        run_command(&mut workspace, &format!("w {}", copy.display()));
+        assert_eq!(workspace.focused_pane().buffer.path(), Some(&first));
+
+        let nested = dir.join("new").join("second.txt");
+        run_command(&mut workspace, &format!("saveas {}", nested.display()));
+        assert!(workspace.prompt.is_some());
+        handle_prompt_input(
+            &mut workspace,
+            KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE),
+        );
+        let written = std::fs::read_to_string(&nested).unwrap();
+        let copied = std::fs::read_to_string(&copy).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(written, "text\n");
+        assert_eq!(copied, "text\n");
+        assert_eq!(workspace.focused_pane().buffer.path(), Some(&nested));
+        assert_eq!(workspace.tab().name, "second.txt");
+    }
+
+    #[test]
+    fn writing_over_another_file_needs_a_bang() {
+        let dir = std::env::temp_dir().join(format!("lark-overwrite-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let own = dir.join("own.txt");
+        let other = dir.join("other.txt");
+        std::fs::write(&own, "own\n").unwrap();
+        std::fs::write(&other, "other\n").unwrap();
+        let mut workspace = Workspace::open(own.clone(), crate::config::Settings::default());
+
+        run_command(&mut workspace, &format!("w {}", other.display()));
+        assert_eq!(workspace.error.take().as_deref(), Some(FILE_EXISTS_MESSAGE));
+        run_command(&mut workspace, &format!("saveas {}", other.display()));
+        assert_eq!(workspace.error.take().as_deref(), Some(FILE_EXISTS_MESSAGE));
+        assert_eq!(std::fs::read_to_string(&other).unwrap(), "other\n");
+
+        // The buffer's own file needs no bang
+        run_command(&mut workspace, &format!("w {}", own.display()));
+        assert!(workspace.error.is_none());
+        run_command(&mut workspace, &format!("w! {}", other.display()));
+        let copied = std::fs::read_to_string(&other).unwrap();
+        run_command(&mut workspace, &format!("saveas! {}", other.display()));
+        let renamed = workspace.focused_pane().buffer.path().cloned();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(copied, "own\n");
+        assert_eq!(renamed, Some(other));
+    }
+
+    #[test]
+    fn the_command_line_edits_at_its_cursor_and_recalls_history() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "text\n");
+        let mut input_state = InputState::new();
+        let mut press = |workspace: &mut Workspace, code, modifiers| {
+            handle_key(workspace, KeyEvent::new(code, modifiers), &mut input_state)
+        };
+        let none = KeyModifiers::NONE;
+        let ctrl = KeyModifiers::CONTROL;
+
+        press(&mut workspace, KeyCode::Char(':'), none);
+        for c in "set wrp".chars() {
+            press(&mut workspace, KeyCode::Char(c), none);
+        }
+        press(&mut workspace, KeyCode::Left, none);
+        press(&mut workspace, KeyCode::Char('a'), none);
+        assert_eq!(workspace.command_buffer, "set wrap");
+        press(&mut workspace, KeyCode::Char('a'), ctrl);
+        press(&mut workspace, KeyCode::Delete, none);
+        press(&mut workspace, KeyCode::Char('S'), none);
+        assert_eq!(workspace.command_buffer, "Set wrap");
+        press(&mut workspace, KeyCode::End, none);
+        press(&mut workspace, KeyCode::Char('w'), ctrl);
+        assert_eq!(workspace.command_buffer, "Set ");
+        assert_eq!(workspace.command_cursor, 4);
+        press(&mut workspace, KeyCode::Char('u'), ctrl);
+        for c in "vs".chars() {
+            press(&mut workspace, KeyCode::Char(c), none);
+        }
+        press(&mut workspace, KeyCode::Enter, none);
+        assert_eq!(workspace.tab().panes.len(), 2);
+
+        press(&mut workspace, KeyCode::Char(':'), none);
+        press(&mut workspace, KeyCode::Up, none);
+        assert_eq!(workspace.command_buffer, "vs");
+        assert_eq!(workspace.command_cursor, 2);
+        press(&mut workspace, KeyCode::Down, none);
+        assert_eq!(workspace.command_buffer, "");
+    }
+
+    #[test]
+    fn replace_mode_types_over_text_and_backspace_restores_it() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "abc\ndef\n");
+        let mut input_state = InputState::new();
+        let mut press = |workspace: &mut Workspace, code| {
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            handle_key(workspace, key, &mut input_state)
+        };
+        let text = |workspace: &Workspace| workspace.focused_pane().buffer.text();
+
+        press(&mut workspace, KeyCode::Char('R'));
+        assert_eq!(workspace.focused_pane().mode, Mode::Replace);
+        for c in "XYZW".chars() {
+            press(&mut workspace, KeyCode::Char(c));
+        }
+        assert_eq!(text(&workspace), "XYZW\ndef\n");
+        press(&mut workspace, KeyCode::Backspace);
+        press(&mut workspace, KeyCode::Backspace);
+        assert_eq!(text(&workspace), "XYc\ndef\n");
+        press(&mut workspace, KeyCode::Esc);
+        assert_eq!(workspace.focused_pane().mode, Mode::Normal);
+        assert_eq!(workspace.focused_pane().cursor.col, 2);
+
+        // The typing is repeated with the change
+        press(&mut workspace, KeyCode::Char('j'));
+        press(&mut workspace, KeyCode::Char('0'));
+        press(&mut workspace, KeyCode::Char('.'));
+        assert_eq!(text(&workspace), "XYc\nXYf\n");
+    }
+
+    #[test]
+    fn quitting_a_changed_buffer_asks_first() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "text\n");
+        workspace.focused_pane_mut().buffer.insert_char(0, 0, 'x');
+        let mut input_state = InputState::new();
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        run_command(&mut workspace, "q");
+        let prompt = workspace.prompt.as_ref().unwrap();
+        assert_eq!(prompt.label, "Discard changes to [stdin]? (y/n) ");
+        handle_key(&mut workspace, key('n'), &mut input_state);
+        assert!(workspace.prompt.is_none());
+        assert!(workspace.running);
+
+        run_command(&mut workspace, "q");
+        handle_key(&mut workspace, key('y'), &mut input_state);
+        assert!(!workspace.running);
+    }
+
+    #[test]
+    fn quitting_the_last_pane_closes_its_tab_or_keeps_other_changes() {
+        let path = std::env::temp_dir().join(format!("lark-quit-{}.txt", std::process::id()));
+        std::fs::write(&path, "text\n").unwrap();
+        let mut workspace = Workspace::open(path.clone(), crate::config::Settings::default());
+        std::fs::remove_file(&path).unwrap();
+        workspace.focused_pane_mut().buffer.insert_char(0, 0, 'x');
+
+        // Only the tab goes while there are others
+        workspace.new_tab();
+        run_command(&mut workspace, "q");
+        assert!(workspace.running);
+        assert_eq!(workspace.tab_count(), 1);
+
+        // The changed file, now in the background, holds up quitting
+        workspace.new_tab();
+        workspace.prev_tab();
+        workspace.close_tab();
+        run_command(&mut workspace, "q");
+        assert!(workspace.running);
+        run_command(&mut workspace, "q!");
+        assert!(workspace.running);
+        assert!(
+            workspace
+                .error
+                .as_deref()
+                .unwrap()
+                .starts_with("Other buffers")
+        );
+        run_command(&mut workspace, "qa!");
+        assert!(!workspace.running);
+    }
+
+    #[test]
+    fn the_message_viewer_scrolls_within_its_content_and_closes() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "text\n");
+        workspace.terminal_size = (20, 8); // Four lines of content on screen
+        let mut input_state = InputState::new();
+        let mut press = |workspace: &mut Workspace, c| {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            handle_key(workspace, key, &mut input_state);
+        };
+
+        for n in 1..=6 {
+            workspace.set_message(format!("message {} {}", n, "-".repeat(21)));
+        }
+        run_command(&mut workspace, "messages");
+        assert_eq!(workspace.mode(), Mode::MessageViewer);
+        let scroll = |workspace: &Workspace| {
+            let viewer = workspace.message_viewer.as_ref().unwrap();
+            (viewer.scroll, viewer.scroll_col)
+        };
+
+        press(&mut workspace, 'G');
+        assert_eq!(scroll(&workspace), (2, 0));
+        press(&mut workspace, 'j');
+        assert_eq!(scroll(&workspace), (2, 0));
+        press(&mut workspace, 'l');
+        assert_eq!(scroll(&workspace), (2, 10));
+        press(&mut workspace, 'l');
+        press(&mut workspace, 'l');
+        // The end of the longest line, "    0s ago   message 1 ---..."
+        assert_eq!(scroll(&workspace), (2, 22));
+        press(&mut workspace, '0');
+        press(&mut workspace, 'g');
+        assert_eq!(scroll(&workspace), (0, 0));
+        press(&mut workspace, 'q');
+        assert!(workspace.message_viewer.is_none());
+        assert_eq!(workspace.mode(), Mode::Normal);
+
+        // Back to the mode it was opened from
+        workspace.focused_pane_mut().mode = Mode::Insert;
+        workspace.show_message_viewer("Log", "entry".to_string());
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        handle_key(&mut workspace, esc, &mut input_state);
+        assert_eq!(workspace.mode(), Mode::Insert);
+    }
+
+    #[test]
+    fn the_cursor_never_rests_inside_a_grapheme() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "ne\u{301}\n");
+        let mut input_state = InputState::new();
+        execute_action(&mut workspace, Action::MoveToLineEnd, 1, &mut input_state);
+        assert_eq!(workspace.focused_pane().cursor.col, 1); // On the e, not its accent
+
+        execute_action(
+            &mut workspace,
+            Action::EnterInsertModeAppend,
+            1,
+            &mut input_state,
+        );
+        assert_eq!(workspace.focused_pane().cursor.col, 3);
+        let backspace = KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE);
+        handle_key(&mut workspace, backspace, &mut input_state);
+        assert_eq!(workspace.focused_pane().buffer.text(), "n\n");
+        assert_eq!(workspace.focused_pane().cursor.col, 1);
+    }
+
+    #[test]
+    fn smartcase_ignores_case_only_without_capitals() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "Foo foo FOO\nfoo\n");
+        let search = |workspace: &mut Workspace, query: &str| {
+            workspace.search_buffer = query.to_string();
+            workspace.execute_search();
+            workspace.search.matches.len()
+        };
+
+        assert_eq!(search(&mut workspace, "foo"), 2);
+        run_command(&mut workspace, "set ic");
+        assert_eq!(workspace.search.matches.len(), 4); // Matched again at once
+        run_command(&mut workspace, "set noic");
+        run_command(&mut workspace, "set smartcase");
+        assert_eq!(search(&mut workspace, "Foo"), 1);
+        assert_eq!(search(&mut workspace, "foo"), 4);
+
+        // n steps through the same matches
+        let pane = workspace.focused_pane();
+        assert_eq!((pane.cursor.line, pane.cursor.col), (0, 4));
+        workspace.search_next();
+        assert_eq!(workspace.focused_pane().cursor.col, 8);
+    }
+
+    #[test]
+    fn star_and_hash_search_for_the_whole_word_under_the_cursor() {
+        let mut workspace =
+            Workspace::with_scratch("[stdin]", "let x = max(a, b);\nmax_len = max\n");
+        let mut input_state = InputState::new();
+        let mut press = |workspace: &mut Workspace, c: char| {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            handle_key(workspace, key, &mut input_state);
+        };
+        let position = |workspace: &Workspace| {
+            let cursor = &workspace.focused_pane().cursor;
+            (cursor.line, cursor.col)
+        };
+
+        workspace.focused_pane_mut().cursor.col = 9; // In "max"
+        press(&mut workspace, '*');
+        assert_eq!(workspace.search.matches.len(), 2); // Not max_len
+        assert_eq!(position(&workspace), (1, 10));
+        press(&mut workspace, 'n');
+        assert_eq!(position(&workspace), (0, 8));
+        press(&mut workspace, '#');
+        assert_eq!(position(&workspace), (1, 10));
+
+        run_command(&mut workspace, "noh");
+        assert!(!workspace.search.active);
+        assert_eq!(workspace.search.query, "\\bmax\\b");
+        press(&mut workspace, 'N');
+        assert!(workspace.search.active);
+        assert_eq!(position(&workspace), (0, 8));
+
+        // A typed \b only bounds the end it's at
+        workspace.search_buffer = "\\bmax".to_string();
+        workspace.execute_search();
+        assert_eq!(workspace.search.matches.len(), 3);
+
+        workspace.focused_pane_mut().cursor = crate::editor::Cursor { line: 0, col: 3 };
+        press(&mut workspace, '*');
+        assert_eq!(
+            workspace.error.as_deref(),
+            Some("No identifier under cursor")
+        );
+    }
+
+    #[test]
+    fn split_commands_open_a_named_file_in_the_new_pane() {
+        let dir = std::env::temp_dir().join(format!("lark-split-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("other.txt");
+        std::fs::write(&file, "other\n").unwrap();
+        let mut workspace = Workspace::with_scratch("[stdin]", "text\n");
+        let original = workspace.tab().focused_pane_id;
+
+        run_command(&mut workspace, &format!("vsplit {}", file.display()));
+        let new = workspace.tab().focused_pane_id;
+        assert_ne!(new, original);
+        let opened = workspace.pane(new).unwrap().buffer.path().cloned();
+        let kept = workspace.pane(original).unwrap().buffer.display_name();
+        assert_eq!(kept, "[stdin]");
+        run_command(&mut workspace, "sp missing.txt");
+        assert_eq!(
+            workspace.message.as_deref(),
+            Some("File not found: missing.txt")
+        );
+        // Directories aren't opened in a split, or by :e
+        run_command(&mut workspace, &format!("vs {}/", dir.display()));
+        assert!(workspace.error.take().unwrap().ends_with("is a directory"));
+        run_command(&mut workspace, &format!("e {}", dir.display()));
+        assert!(workspace.error.take().unwrap().starts_with("Can't open"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(workspace.tab().panes.len(), 2);
+        assert_eq!(opened, Some(file));
+    }
+
+    #[test]
+    fn only_closes_the_other_panes_unless_they_have_changes() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "text\n");
+        let mut input_state = InputState::new();
+        workspace.split_vertical();
+        workspace.split_horizontal();
+        let focused = workspace.tab().focused_pane_id;
+        let ids = workspace.tab().layout.pane_ids();
+        let other = *ids.iter().find(|&&id| id != focused).unwrap();
+        let pane = workspace.tab_mut().panes.get_mut(&other).unwrap();
+        pane.buffer.insert_text(0, 0, "edit");
+
+        run_command(&mut workspace, "only");
+        assert_eq!(workspace.tab().panes.len(), 3);
+        assert_eq!(workspace.error.as_deref(), Some(NO_WRITE_MESSAGE));
+
+        run_command(&mut workspace, "only!");
+        assert_eq!(workspace.tab().layout.pane_ids(), vec![focused]);
+        execute_action(&mut workspace, Action::OnlyPane, 1, &mut input_state);
+        assert_eq!(workspace.message.as_deref(), Some("Already only one pane"));
+    }
+
+    #[test]
+    fn read_only_buffers_refuse_edits() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "text\n");
+        let mut input_state = InputState::new();
+        run_command(&mut workspace, "set ro");
+
+        execute_action(&mut workspace, Action::DeleteChar, 1, &mut input_state);
+        execute_action(&mut workspace, Action::EnterInsertMode, 1, &mut input_state);
+        assert_eq!(workspace.focused_pane().buffer.text(), "text\n");
+        assert_eq!(workspace.mode(), Mode::Normal);
+        assert_eq!(workspace.message.as_deref(), Some(READ_ONLY_MESSAGE));
+
+        run_command(&mut workspace, "set noro");
+        execute_action(&mut workspace, Action::DeleteChar, 1, &mut input_state);
+        assert_eq!(workspace.focused_pane().buffer.text(), "ext\n");
+    }
+
+    #[test]
+    fn binary_buffers_are_never_written() {
+        let dir = std::env::temp_dir().join(format!("lark-write-binary-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join("data.bin");
+        std::fs::write(&binary, b"\x00\xff\n").unwrap();
+        let mut workspace = Workspace::open(binary.clone(), crate::config::Settings::default());
+
+        let copy = dir.join("copy.bin");
+        for command in ["w ", "w! ", "saveas ", "sav! "] {
+            run_command(&mut workspace, &format!("{}{}", command, copy.display()));
+            assert_eq!(workspace.error.take().as_deref(), Some(BINARY_MESSAGE));
+        }
+        let copied = copy.exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!copied);
+        assert_eq!(workspace.focused_pane().buffer.path(), Some(&binary));
+    }
+
+    #[test]
+    fn gc_toggles_comments_with_the_buffers_token() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "a\nb\nc\n");
+        let mut input_state = InputState::new();
+        execute_action(&mut workspace, Action::ToggleComment, 1, &mut input_state);
+        assert_eq!(
+            workspace.error.as_deref(),
+            Some("No comment token for Plain Text")
+        );
+
+        workspace.focused_pane_mut().language = crate::syntax::Language::Python;
+        workspace.focused_pane_mut().cursor.line = 1;
+        execute_action(
+            &mut workspace,
+            Action::ToggleCommentDown,
+            1,
+            &mut input_state,
+        );
+        assert_eq!(workspace.focused_pane().buffer.text(), "a\n# b\n# c\n");
+        execute_action(&mut workspace, Action::ToggleCommentUp, 1, &mut input_state);
+        assert_eq!(workspace.focused_pane().buffer.text(), "# a\n# # b\n# c\n");
+        execute_action(&mut workspace, Action::ToggleComment, 2, &mut input_state);
+        assert_eq!(workspace.focused_pane().buffer.text(), "# a\n# b\nc\n");
+    }
+
+    #[test]
+    fn resize_events_update_the_terminal_size() {
+        let mut workspace = Workspace::new();
+        workspace.set_error("still showing");
+        handle_event(
+            &mut workspace,
+            Event::Resize(120, 40),
+            &mut InputState::new(),
+        );
+
+        assert_eq!(workspace.terminal_size, (120, 40));
+        assert!(workspace.error.is_some());
+    }
+
+    #[test]
+    fn mouse_clicks_place_the_cursor_and_the_wheel_scrolls() {
+        let text: String = (0..50).map(|i| format!("line {}\n", i)).collect();
+        let mut workspace = Workspace::with_scratch("[stdin]", &text);
+        workspace.terminal_size = (80, 24);
+        workspace.focused_pane_mut().adjust_scroll(23, 0);
+        let mouse = |kind, column, row| {
+            Event::Mouse(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        let mut input_state = InputState::new();
+
+        // Past the 4-cell gutter: row 2, cell 3 is line 2, col 3
+        let click = mouse(MouseEventKind::Down(MouseButton::Left), 7, 2);
+        handle_event(&mut workspace, click, &mut input_state);
+        let pane = workspace.focused_pane();
+        assert_eq!((pane.cursor.line, pane.cursor.col), (2, 3));
+
+        // Scrolling drags the cursor down with the top of the view
+        for _ in 0..2 {
+            let scroll = mouse(MouseEventKind::ScrollDown, 10, 10);
+            handle_event(&mut workspace, scroll, &mut input_state);
+        }
+        let pane = workspace.focused_pane();
+        assert_eq!(pane.scroll_offset, 6);
+        assert_eq!(pane.cursor.line, 6);
+
+        workspace.settings.mouse = false;
+        let click = mouse(MouseEventKind::Down(MouseButton::Left), 4, 0);
+        handle_event(&mut workspace, click, &mut input_state);
+        assert_eq!(workspace.focused_pane().cursor.line, 6);
+    }
+
+    #[test]
+    fn clicking_away_in_replace_mode_forgets_what_backspace_restores() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "abc\ndef\n");
+        workspace.terminal_size = (80, 24);
+        let mut input_state = InputState::new();
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+
+        handle_event(&mut workspace, key(KeyCode::Char('R')), &mut input_state);
+        handle_event(&mut workspace, key(KeyCode::Enter), &mut input_state);
+        // Back to the start of the first line, where there's no line above to join
+        let click = Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: 4,
+            row: 0,
+            modifiers: KeyModifiers::NONE,
+        });
+        handle_event(&mut workspace, click, &mut input_state);
+        handle_event(&mut workspace, key(KeyCode::Backspace), &mut input_state);
+        let pane = workspace.focused_pane();
+        assert_eq!((pane.cursor.line, pane.cursor.col), (0, 0));
+        assert_eq!(pane.buffer.text(), "\nabc\ndef\n");
+    }
+
+    #[test]
+    fn named_registers_keep_yanks_apart() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "one\ntwo\nthree\n");
+        let mut input_state = InputState::new();
+        let mut type_keys = |workspace: &mut Workspace, keys: &str| {
+            for c in keys.chars() {
+                let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+                handle_event(workspace, Event::Key(key), &mut input_state);
+            }
+        };
+
+        // "ayy keeps line one in a; x then fills only the unnamed register
+        type_keys(&mut workspace, "\"ayyjx");
+        assert_eq!(workspace.pending_keys, PendingKeys::default());
+        type_keys(&mut workspace, "\"ap");
+        assert_eq!(
+            workspace.focused_pane().buffer.text(),
+            "one\nwo\none\nthree\n"
+        );
+        assert_eq!(workspace.focused_pane().cursor.line, 2);
+
+        type_keys(&mut workspace, "P");
+        assert_eq!(
+            workspace.focused_pane().buffer.text(),
+            "one\nwo\ntone\nthree\n"
+        );
+
+        type_keys(&mut workspace, "\"bp");
+        assert_eq!(workspace.message.as_deref(), Some("Nothing in register b"));
+    }
+
+    #[test]
+    fn counts_size_operators_and_pastes_instead_of_repeating_them() {
+        let text = "one two three four five six\n(a (b c) d)\nx\n";
+        let mut workspace = Workspace::with_scratch("[stdin]", text);
+        let mut input_state = InputState::new();
+        let mut keys = |workspace: &mut Workspace, keys: &str| {
+            for c in keys.chars() {
+                let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+                handle_key(workspace, key, &mut input_state);
+            }
+        };
+        let line = |workspace: &Workspace, n| workspace.focused_pane().buffer.line(n).to_string();
+
+        keys(&mut workspace, "2dw");
+        assert_eq!(line(&workspace, 0), "three four five six\n");
+        keys(&mut workspace, "d3w");
+        assert_eq!(line(&workspace, 0), "six\n");
+
+        // The second enclosing pair, deleted once
+        keys(&mut workspace, "jfc2di(");
+        assert_eq!(line(&workspace, 1), "()\n");
+
+        keys(&mut workspace, "jyy2p");
+        assert_eq!(workspace.focused_pane().buffer.text(), "six\n()\nx\nx\nx\n");
+    }
+
+    #[test]
+    fn ctrl_n_and_ctrl_p_complete_words_from_the_buffer() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "total total_count\nto\n");
+        let mut input_state = InputState::new();
+        let mut press = |workspace: &mut Workspace, code, modifiers| {
+            handle_key(workspace, KeyEvent::new(code, modifiers), &mut input_state);
+        };
+        let line = |workspace: &Workspace| workspace.focused_pane().buffer.line(1).to_string();
+
+        press(&mut workspace, KeyCode::Char('j'), KeyModifiers::NONE);
+        press(&mut workspace, KeyCode::Char('A'), KeyModifiers::NONE);
+        press(&mut workspace, KeyCode::Char('n'), KeyModifiers::CONTROL);
+        assert_eq!(line(&workspace), "total\n");
+        press(&mut workspace, KeyCode::Char('n'), KeyModifiers::CONTROL);
+        assert_eq!(line(&workspace), "total_count\n");
+        press(&mut workspace, KeyCode::Char('p'), KeyModifiers::CONTROL);
+        assert_eq!(line(&workspace), "total\n");
+        assert_eq!(workspace.focused_pane().cursor.col, 5);
+
+        // Typing on keeps the match
+        press(&mut workspace, KeyCode::Char('s'), KeyModifiers::NONE);
+        assert_eq!(line(&workspace), "totals\n");
+        assert!(workspace.focused_pane().word_completion.is_none());
+
+        press(&mut workspace, KeyCode::Char(' '), KeyModifiers::NONE);
+        press(&mut workspace, KeyCode::Char('z'), KeyModifiers::NONE);
+        press(&mut workspace, KeyCode::Char('p'), KeyModifiers::CONTROL);
+        assert_eq!(line(&workspace), "totals z\n");
+        assert_eq!(
+            workspace.message.as_deref(),
+            Some("No words to complete with")
+        );
+
+        // With extra cursors, Ctrl-N completes rather than typing an n at each
+        press(&mut workspace, KeyCode::Char(' '), KeyModifiers::NONE);
+        press(&mut workspace, KeyCode::Char('t'), KeyModifiers::NONE);
+        let cursor = crate::editor::Cursor { line: 0, col: 0 };
+        workspace.focused_pane_mut().extra_cursors.push(cursor);
+        press(&mut workspace, KeyCode::Char('n'), KeyModifiers::CONTROL);
+        assert!(line(&workspace).starts_with("totals z tota"));
+        let first = workspace.focused_pane().buffer.line(0).to_string();
+        assert_eq!(first, "total total_count\n");
+        assert!(workspace.focused_pane().extra_cursors.is_empty());
+    }
+
+    #[test]
+    fn macros_record_keys_and_replay_them() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "a1\nb2\nc3\nd4\ne5\n");
+        let mut input_state = InputState::new();
+        let mut keys = |workspace: &mut Workspace, keys: &str| {
+            for c in keys.chars() {
+                let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+                handle_key(workspace, key, &mut input_state);
+            }
+        };
+        let line = |workspace: &Workspace, n| workspace.focused_pane().buffer.line(n).to_string();
+
+        keys(&mut workspace, "qaxjq");
+        assert_eq!(workspace.recording_macro, None);
+        assert_eq!(line(&workspace, 0), "1\n");
+
+        keys(&mut workspace, "2@a");
+        assert_eq!(line(&workspace, 1), "2\n");
+        assert_eq!(line(&workspace, 2), "3\n");
+        keys(&mut workspace, "@@");
+        assert_eq!(line(&workspace, 3), "4\n");
+
+        // Uppercase appends: now x, j, then x again
+        keys(&mut workspace, "qAxq");
+        assert_eq!(line(&workspace, 4), "5\n");
+        assert_eq!(input_state.macros[&'a'].len(), 3);
+    }
+
+    #[test]
+    fn macros_that_play_themselves_stop() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "abc\n");
+        let mut input_state = InputState::new();
+        for c in "qa@aq".chars() {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            handle_key(&mut workspace, key, &mut input_state);
+        }
+        assert_eq!(workspace.message.as_deref(), Some("Macro a is empty"));
+
+        execute_action(&mut workspace, Action::PlayMacro('a'), 1, &mut input_state);
+        assert!(workspace.error.as_deref().unwrap().contains("nested"));
+        assert_eq!(input_state.macro_depth, 0);
+
+        workspace.clear_error();
+        execute_action(&mut workspace, Action::PlayMacro('b'), 1, &mut input_state);
+        assert_eq!(workspace.message.as_deref(), Some("Macro b is empty"));
+    }
+
+    #[test]
+    fn operators_delete_by_word() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "one two three\n  four\n");
+        let mut input_state = InputState::new();
+        let mut run = |workspace: &mut Workspace, op, motion, count| {
+            execute_action(
+                workspace,
+                Action::OperatorWord(op, motion),
+                count,
+                &mut input_state,
+            );
+        };
+        let line = |workspace: &Workspace, n| workspace.focused_pane().buffer.line(n).to_string();
+
+        run(&mut workspace, Operator::Delete, WordMotion::Forward, 1);
+        assert_eq!(line(&workspace, 0), "two three\n");
+        assert_eq!(workspace.registers.read(None).unwrap().text, "one ");
+
+        run(&mut workspace, Operator::Delete, WordMotion::End, 1);
+        assert_eq!(line(&workspace, 0), " three\n");
+
+        // The last word of a line doesn't take the next line's indent with it
+        workspace.focused_pane_mut().cursor.col = 1;
+        run(&mut workspace, Operator::Delete, WordMotion::Forward, 1);
+        assert_eq!(line(&workspace, 0), " \n");
+        assert_eq!(workspace.focused_pane().cursor.col, 0);
+
+        // db takes the word before, up to the cursor
+        workspace.focused_pane_mut().cursor = crate::editor::Cursor { line: 1, col: 6 };
+        run(&mut workspace, Operator::Delete, WordMotion::Backward, 1);
+        assert_eq!(line(&workspace, 1), "  \n");
+    }
+
+    #[test]
+    fn case_changes_keep_the_text_in_place() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "hello World straße\n");
+        let mut input_state = InputState::new();
+        let line = |workspace: &Workspace| workspace.focused_pane().buffer.line(0).to_string();
+
+        execute_action(&mut workspace, Action::ToggleCase, 2, &mut input_state);
+        assert_eq!(line(&workspace), "HEllo World straße\n");
+        assert_eq!(workspace.focused_pane().cursor.col, 2);
+
+        let g_tilde_w = Action::OperatorWord(Operator::ToggleCase, WordMotion::Forward);
+        workspace.focused_pane_mut().cursor.col = 0;
+        execute_action(&mut workspace, g_tilde_w, 2, &mut input_state);
+        assert_eq!(line(&workspace), "heLLO wORLD straße\n");
+        assert_eq!(workspace.focused_pane().cursor.col, 0);
+        assert!(workspace.registers.read(None).is_none());
+
+        // ß has no one-char upper case
+        let object = TextObject::from_key('w', false).unwrap();
+        let gu_iw = Action::OperatorTextObject(Operator::Uppercase, object);
+        workspace.focused_pane_mut().cursor.col = 14;
+        execute_action(&mut workspace, gu_iw, 1, &mut input_state);
+        assert_eq!(line(&workspace), "heLLO wORLD STRASSE\n");
+
+        assert_eq!(change_case("ÉtÉ Σ", Operator::Lowercase), "été σ");
+        assert_eq!(change_case("Ǆa", Operator::ToggleCase), "ǆA");
+    }
+
+    #[test]
+    fn change_word_keeps_the_space_after_it() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "a bc def\n");
+        let mut input_state = InputState::new();
+        let cw = Action::OperatorWord(Operator::Change, WordMotion::Forward);
+
+        // On a one-letter word, only that word
+        execute_action(&mut workspace, cw.clone(), 1, &mut input_state);
+        assert_eq!(
+            workspace.focused_pane().buffer.line(0).to_string(),
+            " bc def\n"
+        );
+        assert_eq!(workspace.focused_pane().mode, Mode::Insert);
+
+        workspace.focused_pane_mut().mode = Mode::Normal;
+        workspace.focused_pane_mut().cursor.col = 1;
+        execute_action(&mut workspace, cw, 2, &mut input_state);
+        assert_eq!(workspace.focused_pane().buffer.line(0).to_string(), " \n");
+    }
+
+    #[test]
+    fn percent_counts_go_that_far_through_the_file() {
+        let text: String = (1..=10).map(|n| format!("  line {}\n", n)).collect();
+        let mut workspace = Workspace::with_scratch("[stdin]", &text);
+        let mut input_state = InputState::new();
+        let mut goto = |workspace: &mut Workspace, percent| {
+            let action = Action::GotoPercent(percent);
+            execute_action(workspace, action, 1, &mut input_state);
+            let cursor = &workspace.focused_pane().cursor;
+            (cursor.line, cursor.col)
+        };
+
+        assert_eq!(goto(&mut workspace, 50), (4, 2));
+        assert_eq!(goto(&mut workspace, 1), (0, 2));
+        assert_eq!(goto(&mut workspace, 25), (2, 2));
+        assert_eq!(goto(&mut workspace, 100), (9, 2));
+        assert_eq!(goto(&mut workspace, 300), (9, 2));
+    }
+
+    #[test]
+    fn caret_and_smart_home_find_the_first_non_blank() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "    let x;\n   \n");
+        let mut input_state = InputState::new();
+        workspace.focused_pane_mut().cursor.col = 8;
+
+        execute_action(
+            &mut workspace,
+            Action::MoveToFirstNonBlank,
+            1,
+            &mut input_state,
+        );
+        assert_eq!(workspace.focused_pane().cursor.col, 4);
+        execute_action(&mut workspace, Action::MoveToLineStart, 1, &mut input_state);
+        assert_eq!(workspace.focused_pane().cursor.col, 0);
+
+        // Smart home goes to the first non-blank, then back and forth
+        workspace.settings.smart_home = true;
+        execute_action(&mut workspace, Action::MoveToLineStart, 1, &mut input_state);
+        assert_eq!(workspace.focused_pane().cursor.col, 4);
+        execute_action(&mut workspace, Action::MoveToLineStart, 1, &mut input_state);
+        assert_eq!(workspace.focused_pane().cursor.col, 0);
+
+        // A blank line has no non-blank; stay on it
+        workspace.focused_pane_mut().cursor.line = 1;
+        execute_action(
+            &mut workspace,
+            Action::MoveToFirstNonBlank,
+            1,
+            &mut input_state,
+        );
+        assert_eq!(workspace.focused_pane().cursor.col, 2);
+    }
+
+    #[test]
+    fn insert_mode_ctrl_keys_delete_backwards() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "one\n    foo.bar(x)  \n");
+        workspace.focused_pane_mut().mode = Mode::Insert;
+        workspace.focused_pane_mut().cursor.line = 1;
+        workspace.focused_pane_mut().cursor.col = 16;
+        let ctrl = |workspace: &mut Workspace, c: char| {
+            handle_insert_mode(
+                workspace,
+                KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL),
+            );
+        };
+        let line = |workspace: &Workspace| workspace.focused_pane().buffer.line(1).to_string();
+
+        // Spaces go with the punctuation run before them, then one word at a time
+        ctrl(&mut workspace, 'w');
+        assert_eq!(line(&workspace), "    foo.bar(x\n");
+        ctrl(&mut workspace, 'w');
+        assert_eq!(line(&workspace), "    foo.bar(\n");
+        ctrl(&mut workspace, 'h');
+        ctrl(&mut workspace, 'w');
+        assert_eq!(line(&workspace), "    foo.\n");
+
+        // Ctrl-U keeps the indent, then takes it too, then joins the lines
+        ctrl(&mut workspace, 'u');
+        assert_eq!(line(&workspace), "    \n");
+        ctrl(&mut workspace, 'u');
+        assert_eq!(line(&workspace), "\n");
+        ctrl(&mut workspace, 'u');
+        assert_eq!(workspace.focused_pane().buffer.text(), "one\n");
+        assert_eq!(workspace.focused_pane().cursor.col, 3);
+    }
+
+    #[test]
+    fn auto_pairs_close_type_over_and_delete_together() {
+        let mut workspace = Workspace::with_scratch("[stdin]", "\n");
+        workspace.focused_pane_mut().mode = Mode::Insert;
+        let type_keys = |workspace: &mut Workspace, keys: &str| {
+            for c in keys.chars() {
+                let code = match c {
+                    '\x08' => KeyCode::Backspace,
+                    c => KeyCode::Char(c),
+                };
+                handle_insert_mode(workspace, KeyEvent::new(code, KeyModifiers::NONE));
+            }
+        };
+
+        type_keys(&mut workspace, "f(a[\"x");
+        assert_eq!(workspace.focused_pane().buffer.text(), "f(a[\"x\"])\n");
+        type_keys(&mut workspace, "\"])");
+        assert_eq!(workspace.focused_pane().buffer.text(), "f(a[\"x\"])\n");
+        assert_eq!(workspace.focused_pane().cursor.col, 9);
+
+        // Apostrophes and chars typed before a word aren't paired
+        type_keys(&mut workspace, " don't {\x08");
+        assert_eq!(
+            workspace.focused_pane().buffer.text(),
+            "f(a[\"x\"]) don't \n"
+        );
+        workspace.focused_pane_mut().cursor.col = 0;
+        type_keys(&mut workspace, "(");
+        assert_eq!(
+            workspace.focused_pane().buffer.text(),
+            "(f(a[\"x\"]) don't \n"
+        );
+
+        // Backspace in an empty pair takes both
+        workspace.focused_pane_mut().cursor.col = 17;
+        type_keys(&mut workspace, "{\x08");
+        assert_eq!(
+            workspace.focused_pane().buffer.text(),
+            "(f(a[\"x\"]) don't \n"
+        );
+
+        workspace.settings.auto_pairs = false;
+        type_keys(&mut workspace, "(");
+        assert_eq!(
+            workspace.focused_pane().buffer.text(),
+            "(f(a[\"x\"]) don't (\n"
+        );
+    }
+}
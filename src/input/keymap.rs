@@ -1,6 +1,8 @@
 use crossterm::event::{KeyCode, KeyModifiers};
 use std::time::{Duration, Instant};
 
+use crate::editor::{FindKind, PendingKeys, Registers, TextObject};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Key {
     pub code: KeyCode,
@@ -8,19 +10,229 @@ pub struct Key {
 }
 
 impl Key {
-    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+    pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
         Self { code, modifiers }
     }
 
-    pub fn char(c: char) -> Self {
+    pub const fn char(c: char) -> Self {
         Self::new(KeyCode::Char(c), KeyModifiers::NONE)
     }
 
-    pub fn ctrl(c: char) -> Self {
+    pub const fn ctrl(c: char) -> Self {
         Self::new(KeyCode::Char(c), KeyModifiers::CONTROL)
     }
 }
 
+/// Stands for the configured leader key in `PREFIXES`
+const LEADER: Key = Key::new(KeyCode::Null, KeyModifiers::NONE);
+
+/// A prefix key sequence, the keys that can follow it and what they do.
+/// Followers with no description (alternate keys) are left out of the
+/// which-key popup.
+struct Prefix {
+    keys: &'static [Key],
+    mode: Option<&'static str>, // None: any mode
+    name: &'static str,         // Shown as `+name` when listed after a shorter prefix
+    next: &'static [(KeyCode, Action, &'static str)],
+}
+
+/// Every multi-key sequence. Single keys and `t<char>` (till, which shares
+/// `t` with the tab commands) are matched in `match_sequence`.
+const PREFIXES: &[Prefix] = &[
+    Prefix {
+        keys: &[Key::ctrl('w')],
+        mode: None,
+        name: "window",
+        next: &[
+            (KeyCode::Char('h'), Action::FocusLeft, "focus left"),
+            (KeyCode::Char('j'), Action::FocusDown, "focus down"),
+            (KeyCode::Char('k'), Action::FocusUp, "focus up"),
+            (KeyCode::Char('l'), Action::FocusRight, "focus right"),
+            (KeyCode::Left, Action::FocusLeft, ""),
+            (KeyCode::Down, Action::FocusDown, ""),
+            (KeyCode::Up, Action::FocusUp, ""),
+            (KeyCode::Right, Action::FocusRight, ""),
+            (KeyCode::Char('w'), Action::FocusNext, "next pane"),
+            (KeyCode::Char('v'), Action::SplitVertical, "split right"),
+            (KeyCode::Char('s'), Action::SplitHorizontal, "split below"),
+            (KeyCode::Char('x'), Action::SwapPane, "swap"),
+            (KeyCode::Char('r'), Action::RotatePanes, "rotate"),
+            (KeyCode::Char('o'), Action::OnlyPane, "close others"),
+            (KeyCode::Char('+'), Action::ResizeTaller, "taller"),
+            (KeyCode::Char('-'), Action::ResizeShorter, "shorter"),
+            (KeyCode::Char('>'), Action::ResizeWider, "wider"),
+            (KeyCode::Char('<'), Action::ResizeNarrower, "narrower"),
+        ],
+    },
+    Prefix {
+        keys: &[LEADER],
+        mode: Some("normal"),
+        name: "leader",
+        next: &[
+            (KeyCode::Char('e'), Action::FocusFileBrowser, "browser"),
+            (
+                KeyCode::Char('a'),
+                Action::SelectAllMatches,
+                "cursor on matches",
+            ),
+        ],
+    },
+    Prefix {
+        keys: &[LEADER, Key::char('f')],
+        mode: Some("normal"),
+        name: "find",
+        next: &[
+            (KeyCode::Char('f'), Action::FindFile, "files"),
+            (KeyCode::Char('g'), Action::Grep, "grep"),
+            (KeyCode::Char('r'), Action::RecentFiles, "recent files"),
+            (KeyCode::Char('s'), Action::Symbols, "symbols"),
+        ],
+    },
+    Prefix {
+        keys: &[Key::char('g')],
+        mode: Some("normal"),
+        name: "go",
+        next: &[
+            (KeyCode::Char('g'), Action::MoveToFirstLine, "first line"),
+            (KeyCode::Char('d'), Action::GrepWordUnderCursor, "grep word"),
+            (KeyCode::Char('f'), Action::GotoFile, "file under cursor"),
+        ],
+    },
+    Prefix {
+        keys: &[Key::char('g'), Key::char('c')],
+        mode: Some("normal"),
+        name: "comment",
+        next: &[
+            (KeyCode::Char('c'), Action::ToggleComment, "line"),
+            (
+                KeyCode::Char('j'),
+                Action::ToggleCommentDown,
+                "and the line below",
+            ),
+            (
+                KeyCode::Char('k'),
+                Action::ToggleCommentUp,
+                "and the line above",
+            ),
+        ],
+    },
+    Prefix {
+        keys: &[Key::char('z')],
+        mode: Some("normal"),
+        name: "fold/scroll",
+        next: &[
+            (KeyCode::Char('a'), Action::ToggleFold, "toggle fold"),
+            (KeyCode::Char('R'), Action::OpenAllFolds, "open all folds"),
+            (KeyCode::Char('M'), Action::CloseAllFolds, "close all folds"),
+            (KeyCode::Char('z'), Action::ScrollCenter, "center"),
+            (KeyCode::Char('t'), Action::ScrollTop, "to top"),
+            (KeyCode::Char('b'), Action::ScrollBottom, "to bottom"),
+        ],
+    },
+    Prefix {
+        keys: &[Key::char('y')],
+        mode: Some("normal"),
+        name: "yank",
+        next: &[(KeyCode::Char('y'), Action::YankLine, "line")],
+    },
+    Prefix {
+        keys: &[Key::char('>')],
+        mode: Some("normal"),
+        name: "indent",
+        next: &[(KeyCode::Char('>'), Action::IndentLine, "indent")],
+    },
+    Prefix {
+        keys: &[Key::char('<')],
+        mode: Some("normal"),
+        name: "dedent",
+        next: &[(KeyCode::Char('<'), Action::DedentLine, "dedent")],
+    },
+    Prefix {
+        keys: &[Key::char('t')],
+        mode: Some("normal"),
+        name: "tab",
+        next: &[
+            (KeyCode::Char('t'), Action::NewTab, "new tab"),
+            (KeyCode::Char('n'), Action::NextTab, "next tab"),
+            (KeyCode::Char('p'), Action::PrevTab, "previous tab"),
+            (KeyCode::Char('c'), Action::CloseTab, "close tab"),
+        ],
+    },
+];
+
+impl Prefix {
+    fn applies(&self, mode: &str) -> bool {
+        self.mode.is_none_or(|m| m == mode)
+    }
+
+    /// Whether `keys` begin with this prefix. After the first key modifiers
+    /// are ignored, so Ctrl can stay held (Ctrl-W Ctrl-H).
+    fn starts(&self, keys: &[Key], leader: &Key) -> bool {
+        keys.len() >= self.keys.len() && agree(keys, self.keys, leader)
+    }
+
+    /// Whether `keys` are the start of this prefix, or all of it
+    fn continues(&self, keys: &[Key], leader: &Key) -> bool {
+        keys.len() <= self.keys.len() && agree(keys, self.keys, leader)
+    }
+}
+
+/// Whether typed `keys` and `bound` keys agree as far as both go, comparing
+/// only codes after the first key
+fn agree(keys: &[Key], bound: &[Key], leader: &Key) -> bool {
+    keys.iter().zip(bound).enumerate().all(|(i, (key, bound))| {
+        let bound = if *bound == LEADER { leader } else { bound };
+        key.code == bound.code && (i > 0 || key.modifiers - KeyModifiers::SHIFT == bound.modifiers)
+    })
+}
+
+/// Operators that wait for a text object or word motion (`d`, `c`, `gU`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Lowercase,  // gu
+    Uppercase,  // gU
+    ToggleCase, // g~
+}
+
+impl Operator {
+    fn keys(self) -> &'static str {
+        match self {
+            Operator::Delete => "d",
+            Operator::Change => "c",
+            Operator::Lowercase => "gu",
+            Operator::Uppercase => "gU",
+            Operator::ToggleCase => "g~",
+        }
+    }
+}
+
+/// Word motions an operator can take (`dw`, `ce`, `db`, and `W`, `E`, `B`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordMotion {
+    Forward, // Exclusive: up to the next word's start
+    End,     // Inclusive: through the word's last char
+    Backward,
+    BigForward,
+    BigEnd,
+    BigBackward,
+}
+
+impl WordMotion {
+    fn from_key(c: char) -> Option<Self> {
+        Some(match c {
+            'w' => WordMotion::Forward,
+            'e' => WordMotion::End,
+            'b' => WordMotion::Backward,
+            'W' => WordMotion::BigForward,
+            'E' => WordMotion::BigEnd,
+            'B' => WordMotion::BigBackward,
+            _ => return None,
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Action {
     // Movement
@@ -29,14 +241,71 @@ pub enum Action {
     MoveUp,
     MoveDown,
     MoveToLineStart,
+    MoveToFirstNonBlank,
     MoveToLineEnd,
     MoveToFirstLine,
     MoveToLastLine,
+    GotoLine(usize), // 1-based, from a count before G/gg
     MoveWordForward,
     MoveWordBackward,
     MoveWordEnd,
-    PageDown,
-    PageUp,
+    MoveBigWordForward, // W, B, E: words are runs of non-whitespace
+    MoveBigWordBackward,
+    MoveBigWordEnd,
+    FindChar(FindKind, char),
+    RepeatFind,
+    RepeatFindReverse,
+    MatchBracket,       // %: without a count; with one it's GotoPercent
+    GotoPercent(usize), // N%: the line N percent of the way through the file
+    ParagraphForward,   // }: the next blank line
+    ParagraphBackward,
+    PageDown,       // Ctrl-D: half a pane, keeping the cursor's row on screen
+    PageUp,         // Ctrl-U
+    CursorToTop,    // H: first line on screen, or the count-th
+    CursorToMiddle, // M
+    CursorToBottom, // L: last line on screen, or count-th from the bottom
+
+    // Marks and the jump list
+    SetMark(char),        // m<char>
+    JumpToMark(char),     // `<char>: the marked line and column
+    JumpToMarkLine(char), // '<char>: the marked line, at its first non-blank
+    JumpBack,             // Ctrl-O
+    JumpForward,          // Ctrl-I (Tab)
+
+    // Editing. Counts are applied once, as a size, rather than by repeating
+    // the action: `2dw` is one change and `2p` pastes the register twice.
+    OperatorTextObject(Operator, TextObject), // Count words, or the count-th enclosing pair
+    OperatorWord(Operator, WordMotion),       // Count words
+    IndentLine,                               // >>: count lines from the current one
+    DedentLine,                               // <<
+    ToggleComment,                            // gcc: the current line, or count lines from it
+    ToggleCommentDown,                        // gcj: the current line and count below
+    ToggleCommentUp,                          // gck: the current line and count above
+    ReplaceChar(char),                        // r: count chars from the cursor
+    DeleteChar,                               // x: count chars from the cursor
+    DeleteCharBefore,                         // X: count chars before the cursor
+    ToggleCase,                               // ~: count chars from the cursor
+    DeleteToLineEnd,                          // D: ignores the count
+    ChangeToLineEnd,                          // C: ignores the count
+    YankLine,                                 // yy: the current line, or count lines from it
+    PasteAfter,   // p: after the cursor, or below the line for whole lines; count copies
+    PasteBefore,  // P
+    RepeatChange, // .: the count replaces the change's own
+
+    // Folding
+    ToggleFold,
+    OpenAllFolds,
+    CloseAllFolds,
+
+    // Multiple cursors: typing after `i` goes to every cursor. Only chars and
+    // Backspace within a line are mirrored; Enter, any other command, or Esc
+    // goes back to the primary cursor alone.
+    SelectAllMatches, // A cursor on every search match
+
+    // Scrolling
+    ScrollCenter,
+    ScrollTop,
+    ScrollBottom,
 
     // Mode changes
     EnterInsertMode,
@@ -44,6 +313,7 @@ pub enum Action {
     EnterInsertModeAppendLine,
     EnterInsertModeOpenBelow,
     EnterInsertModeOpenAbove,
+    EnterReplaceMode,
     EnterNormalMode,
     EnterCommandMode,
 
@@ -55,6 +325,13 @@ pub enum Action {
     FocusUp,
     FocusDown,
     FocusNext,
+    ResizeTaller,
+    ResizeShorter,
+    ResizeWider,
+    ResizeNarrower,
+    SwapPane,
+    RotatePanes,
+    OnlyPane, // Close every other pane in the tab
 
     // File browser
     ToggleFileBrowser,
@@ -63,6 +340,10 @@ pub enum Action {
     // Leader sequences
     FindFile,
     Grep,
+    GrepWordUnderCursor,
+    GotoFile, // gf: open the file named under the cursor
+    RecentFiles,
+    Symbols,
 
     // Pane selection mode
     SelectPane(char),
@@ -73,23 +354,87 @@ pub enum Action {
     PrevTab,
     CloseTab,
 
+    // Buffers
+    AlternateBuffer,
+
     // Search
     SearchForward,
     SearchBackward,
     SearchNext,
     SearchPrev,
+    SearchWordForward,  // *: the whole word under the cursor
+    SearchWordBackward, // #
     ClearSearch,
 
+    // Macros
+    RecordMacro(char), // q<char>; q again stops
+    PlayMacro(char),   // @<char>, or @@ for the last one played
+
     // Other
     Quit,
 }
 
+impl Action {
+    /// Whether this action changes the buffer, so `.` can repeat it
+    pub fn is_change(&self) -> bool {
+        matches!(
+            self,
+            Action::OperatorTextObject(..)
+                | Action::OperatorWord(..)
+                | Action::IndentLine
+                | Action::DedentLine
+                | Action::ToggleComment
+                | Action::ToggleCommentDown
+                | Action::ToggleCommentUp
+                | Action::ReplaceChar(_)
+                | Action::DeleteChar
+                | Action::DeleteCharBefore
+                | Action::ToggleCase
+                | Action::DeleteToLineEnd
+                | Action::ChangeToLineEnd
+                | Action::PasteAfter
+                | Action::PasteBefore
+                | Action::EnterInsertMode
+                | Action::EnterInsertModeAppend
+                | Action::EnterInsertModeAppendLine
+                | Action::EnterInsertModeOpenBelow
+                | Action::EnterInsertModeOpenAbove
+                | Action::EnterReplaceMode
+        )
+    }
+
+    /// Whether this action jumps, so the position it leaves goes in the jump list
+    pub fn is_jump(&self) -> bool {
+        matches!(
+            self,
+            Action::MoveToFirstLine
+                | Action::MoveToLastLine
+                | Action::GotoLine(_)
+                | Action::MatchBracket
+                | Action::GotoPercent(_)
+                | Action::ParagraphForward
+                | Action::ParagraphBackward
+                | Action::SearchNext
+                | Action::SearchPrev
+        )
+    }
+}
+
 pub struct KeySequenceState {
     pending: Vec<Key>,
     last_key_time: Instant,
-    timeout: Duration,
+    timeout: Duration, // How long a partly typed sequence waits for its next key
+    leader: Key,
     pub waiting_for_pane_select: bool,
+    pub waiting_for_find: Option<FindKind>, // f/F/T pressed, next key is the target char
+    pub waiting_for_replace: bool,          // r pressed, next key is the replacement
+    pub waiting_for_mark: Option<char>,     // m, ` or ' pressed, next key names the mark
+    pub waiting_for_register: bool,         // " pressed, next key names the register
+    pub waiting_for_macro: Option<char>,    // q or @ pressed, next key names the macro
+    pub register: Option<char>,             // Named with "x for the next command
+    pub operator: Option<Operator>, // Operator-pending: d, c, gu… waiting for a motion or object
     pub count: Option<usize>,
+    pub motion_count: Option<usize>, // Typed after the operator (`d3w`); multiplies `count`
 }
 
 impl KeySequenceState {
@@ -98,15 +443,31 @@ impl KeySequenceState {
             pending: Vec::new(),
             last_key_time: Instant::now(),
             timeout: Duration::from_millis(1000),
+            leader: Key::char(' '),
             waiting_for_pane_select: false,
+            waiting_for_find: None,
+            waiting_for_replace: false,
+            waiting_for_mark: None,
+            waiting_for_register: false,
+            waiting_for_macro: None,
+            register: None,
+            operator: None,
             count: None,
+            motion_count: None,
         }
     }
 
+    /// Use `leader` for `<leader>` sequences and wait `timeout` for each key of a sequence
+    pub fn configure(&mut self, leader: char, timeout: Duration) {
+        self.leader = Key::char(leader);
+        self.timeout = timeout;
+    }
+
     fn check_timeout(&mut self) {
         if self.last_key_time.elapsed() > self.timeout {
             self.pending.clear();
             self.count = None;
+            self.register = None;
         }
     }
 
@@ -130,6 +491,104 @@ impl KeySequenceState {
             return KeyResult::Pending;
         }
 
+        // Handle find target: the key after f/F/T is taken literally
+        if let Some(kind) = self.waiting_for_find {
+            self.waiting_for_find = None;
+            let count = self.count.take().unwrap_or(1);
+            return match key.code {
+                KeyCode::Char(c) => KeyResult::Action(Action::FindChar(kind, c), count),
+                _ => KeyResult::Cancelled,
+            };
+        }
+
+        // Handle replacement: the key after r is taken literally, Enter splits the line
+        if self.waiting_for_replace {
+            self.waiting_for_replace = false;
+            let count = self.count.take().unwrap_or(1);
+            return match key.code {
+                KeyCode::Char(c) => KeyResult::Action(Action::ReplaceChar(c), count),
+                KeyCode::Enter => KeyResult::Action(Action::ReplaceChar('\n'), count),
+                _ => KeyResult::Cancelled,
+            };
+        }
+
+        // Mark name: the key after m, ` or '
+        if let Some(prefix) = self.waiting_for_mark.take() {
+            self.count = None;
+            return match key.code {
+                KeyCode::Char(c) if prefix == 'm' => KeyResult::Action(Action::SetMark(c), 1),
+                KeyCode::Char(c) if prefix == '`' => KeyResult::Action(Action::JumpToMark(c), 1),
+                KeyCode::Char(c) => KeyResult::Action(Action::JumpToMarkLine(c), 1),
+                _ => KeyResult::Cancelled,
+            };
+        }
+
+        // Macro name: the key after q (record) or @ (play; @@ plays the last one)
+        if let Some(prefix) = self.waiting_for_macro.take() {
+            let count = self.count.take().unwrap_or(1);
+            return match key.code {
+                KeyCode::Char(c) if prefix == 'q' && is_macro_name(c) => {
+                    KeyResult::Action(Action::RecordMacro(c), 1)
+                }
+                KeyCode::Char(c) if prefix == '@' && (is_macro_name(c) || c == '@') => {
+                    KeyResult::Action(Action::PlayMacro(c), count)
+                }
+                _ => KeyResult::Cancelled,
+            };
+        }
+
+        // Register name: the key after "
+        if self.waiting_for_register {
+            self.waiting_for_register = false;
+            return match key.code {
+                KeyCode::Char(c) if Registers::is_valid(c) => {
+                    self.register = Some(c);
+                    KeyResult::Pending
+                }
+                _ => {
+                    self.register = None;
+                    self.count = None;
+                    KeyResult::Cancelled
+                }
+            };
+        }
+
+        // Operator-pending: a word motion (`dw`), or `i`/`a` and the object key
+        // (`diw`, `ca(`)
+        if let Some(op) = self.operator {
+            if let (None, KeyCode::Char(c)) = (self.pending.first(), key.code)
+                && let Some(digit) = c.to_digit(10)
+                && (digit != 0 || self.motion_count.is_some())
+            {
+                self.motion_count = Some(self.motion_count.unwrap_or(0) * 10 + digit as usize);
+                return KeyResult::Pending;
+            }
+            if let (None, KeyCode::Char(c)) = (self.pending.first(), key.code)
+                && let Some(motion) = WordMotion::from_key(c)
+            {
+                self.operator = None;
+                let count = self.take_operator_count();
+                return KeyResult::Action(Action::OperatorWord(op, motion), count);
+            }
+            let object = match (self.pending.first(), key.code) {
+                (None, KeyCode::Char('i' | 'a')) => {
+                    self.pending.push(key);
+                    return KeyResult::Pending;
+                }
+                (Some(prefix), KeyCode::Char(c)) => {
+                    TextObject::from_key(c, prefix.code == KeyCode::Char('a'))
+                }
+                _ => None,
+            };
+            self.operator = None;
+            self.pending.clear();
+            let count = self.take_operator_count();
+            return match object {
+                Some(object) => KeyResult::Action(Action::OperatorTextObject(op, object), count),
+                None => KeyResult::Cancelled,
+            };
+        }
+
         // Handle count prefix (digits at start, but not 0 as first digit)
         if let KeyCode::Char(c) = key.code {
             if c.is_ascii_digit() && key.modifiers == KeyModifiers::NONE {
@@ -141,27 +600,78 @@ impl KeySequenceState {
             }
         }
 
-        self.pending.push(key.clone());
+        // f/F/T wait for a target char without the sequence timeout.
+        // `t` is matched as a sequence instead since it's also the tab prefix.
+        if self.pending.is_empty() && mode == "normal" && key.modifiers == KeyModifiers::NONE {
+            let kind = match key.code {
+                KeyCode::Char('f') => Some(FindKind::Forward),
+                KeyCode::Char('F') => Some(FindKind::Backward),
+                KeyCode::Char('T') => Some(FindKind::TillBackward),
+                _ => None,
+            };
+            if kind.is_some() {
+                self.waiting_for_find = kind;
+                return KeyResult::Pending;
+            }
+            if key.code == KeyCode::Char('r') {
+                self.waiting_for_replace = true;
+                return KeyResult::Pending;
+            }
+            if let KeyCode::Char(prefix @ ('m' | '`' | '\'')) = key.code {
+                self.waiting_for_mark = Some(prefix);
+                return KeyResult::Pending;
+            }
+            if key.code == KeyCode::Char('"') {
+                self.waiting_for_register = true;
+                return KeyResult::Pending;
+            }
+            if let KeyCode::Char(prefix @ ('q' | '@')) = key.code {
+                self.waiting_for_macro = Some(prefix);
+                return KeyResult::Pending;
+            }
 
-        match self.match_sequence(mode) {
-            MatchResult::Complete(action) => {
-                let count = self.count.unwrap_or(1);
+            let operator = match key.code {
+                KeyCode::Char('d') => Some(Operator::Delete),
+                KeyCode::Char('c') => Some(Operator::Change),
+                _ => None,
+            };
+            if operator.is_some() {
+                self.operator = operator;
+                return KeyResult::Pending;
+            }
+        }
+
+        // gu, gU and g~ are operators too
+        if mode == "normal"
+            && self.pending == [Key::char('g')]
+            && !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            let operator = match key.code {
+                KeyCode::Char('u') => Some(Operator::Lowercase),
+                KeyCode::Char('U') => Some(Operator::Uppercase),
+                KeyCode::Char('~') => Some(Operator::ToggleCase),
+                _ => None,
+            };
+            if operator.is_some() {
                 self.pending.clear();
-                self.count = None;
-                KeyResult::Action(action, count)
+                self.operator = operator;
+                return KeyResult::Pending;
             }
+        }
+
+        self.pending.push(key.clone());
+
+        match self.match_sequence(mode) {
+            MatchResult::Complete(action) => self.complete(action),
             MatchResult::Prefix => KeyResult::Pending,
             MatchResult::NoMatch => {
                 if self.pending.len() > 1 {
                     self.pending.clear();
                     self.pending.push(key);
                     match self.match_sequence(mode) {
-                        MatchResult::Complete(action) => {
-                            let count = self.count.unwrap_or(1);
-                            self.pending.clear();
-                            self.count = None;
-                            KeyResult::Action(action, count)
-                        }
+                        MatchResult::Complete(action) => self.complete(action),
                         MatchResult::Prefix => KeyResult::Pending,
                         MatchResult::NoMatch => {
                             self.pending.clear();
@@ -181,28 +691,22 @@ impl KeySequenceState {
     fn match_sequence(&self, mode: &str) -> MatchResult {
         let pending = &self.pending;
 
-        // Ctrl-W window commands (work in any mode)
-        if !pending.is_empty() && pending[0] == Key::ctrl('w') {
-            if pending.len() == 1 {
-                return MatchResult::Prefix;
-            }
-            if pending.len() == 2 {
-                let action = match pending[1].code {
-                    KeyCode::Char('h') | KeyCode::Left => Some(Action::FocusLeft),
-                    KeyCode::Char('j') | KeyCode::Down => Some(Action::FocusDown),
-                    KeyCode::Char('k') | KeyCode::Up => Some(Action::FocusUp),
-                    KeyCode::Char('l') | KeyCode::Right => Some(Action::FocusRight),
-                    KeyCode::Char('w') => Some(Action::FocusNext),
-                    KeyCode::Char('v') => Some(Action::SplitVertical),
-                    KeyCode::Char('s') => Some(Action::SplitHorizontal),
-                    _ => None,
-                };
-                return match action {
-                    Some(a) => MatchResult::Complete(a),
-                    None => MatchResult::NoMatch,
-                };
+        // Sequences from the prefix table
+        let mut partial = false;
+        for prefix in PREFIXES.iter().filter(|p| p.applies(mode)) {
+            if prefix.continues(pending, &self.leader) {
+                partial = true;
+            } else if pending.len() == prefix.keys.len() + 1 && prefix.starts(pending, &self.leader)
+            {
+                let last = pending[pending.len() - 1].code;
+                if let Some((_, action, _)) = prefix.next.iter().find(|(code, ..)| *code == last) {
+                    return MatchResult::Complete(action.clone());
+                }
             }
         }
+        if partial {
+            return MatchResult::Prefix;
+        }
 
         // Ctrl+G toggle file browser (works in any mode)
         if pending.len() == 1 && pending[0] == Key::ctrl('g') {
@@ -222,64 +726,34 @@ impl KeySequenceState {
             }
         }
 
-        // Leader key (space) - normal mode only
-        if !pending.is_empty() && pending[0] == Key::char(' ') && mode == "normal" {
-            if pending.len() == 1 {
-                return MatchResult::Prefix;
-            }
-            // <leader>e - focus file browser
-            if pending.len() == 2 && pending[1] == Key::char('e') {
-                return MatchResult::Complete(Action::FocusFileBrowser);
-            }
-            // <leader>f prefix for file commands
-            if pending.len() == 2 && pending[1] == Key::char('f') {
-                return MatchResult::Prefix;
+        // Ctrl+^ switches to the alternate buffer (terminals send it as Ctrl+6)
+        if pending.len() == 1
+            && mode == "normal"
+            && (pending[0] == Key::ctrl('^') || pending[0] == Key::ctrl('6'))
+        {
+            return MatchResult::Complete(Action::AlternateBuffer);
+        }
+
+        // Ctrl-O / Ctrl-I walk the jump list (terminals send Ctrl-I as Tab)
+        if pending.len() == 1 && mode == "normal" {
+            if pending[0] == Key::ctrl('o') {
+                return MatchResult::Complete(Action::JumpBack);
             }
-            // <leader>ff - find file, <leader>fg - grep
-            if pending.len() == 3 && pending[1] == Key::char('f') {
-                let action = match pending[2].code {
-                    KeyCode::Char('f') => Some(Action::FindFile),
-                    KeyCode::Char('g') => Some(Action::Grep),
-                    _ => None,
-                };
-                return match action {
-                    Some(a) => MatchResult::Complete(a),
-                    None => MatchResult::NoMatch,
-                };
+            if pending[0] == Key::ctrl('i') || pending[0].code == KeyCode::Tab {
+                return MatchResult::Complete(Action::JumpForward);
             }
         }
 
         // Normal mode commands
         if mode == "normal" {
-            // gg - go to first line
-            if !pending.is_empty() && pending[0] == Key::char('g') {
-                if pending.len() == 1 {
-                    return MatchResult::Prefix;
-                }
-                if pending.len() == 2 && pending[1] == Key::char('g') {
-                    return MatchResult::Complete(Action::MoveToFirstLine);
-                }
-                return MatchResult::NoMatch;
-            }
-
-            // tt, tn, tp, tc - tab commands
-            if !pending.is_empty() && pending[0] == Key::char('t') {
-                if pending.len() == 1 {
-                    return MatchResult::Prefix;
-                }
-                if pending.len() == 2 {
-                    let action = match pending[1].code {
-                        KeyCode::Char('t') => Some(Action::NewTab),
-                        KeyCode::Char('n') => Some(Action::NextTab),
-                        KeyCode::Char('p') => Some(Action::PrevTab),
-                        KeyCode::Char('c') => Some(Action::CloseTab),
-                        _ => None,
-                    };
-                    return match action {
-                        Some(a) => MatchResult::Complete(a),
-                        None => MatchResult::NoMatch,
-                    };
-                }
+            // t<char> - till char (tt, tn, tp and tc are tab commands)
+            if pending.len() == 2 && pending[0] == Key::char('t') {
+                return match pending[1].code {
+                    KeyCode::Char(c) => {
+                        MatchResult::Complete(Action::FindChar(FindKind::TillForward, c))
+                    }
+                    _ => MatchResult::NoMatch,
+                };
             }
 
             // Single key commands
@@ -290,21 +764,44 @@ impl KeySequenceState {
                     KeyCode::Char('k') | KeyCode::Up => Some(Action::MoveUp),
                     KeyCode::Char('l') | KeyCode::Right => Some(Action::MoveRight),
                     KeyCode::Char('0') => Some(Action::MoveToLineStart),
+                    KeyCode::Char('^') => Some(Action::MoveToFirstNonBlank),
                     KeyCode::Char('$') => Some(Action::MoveToLineEnd),
                     KeyCode::Char('G') => Some(Action::MoveToLastLine),
+                    KeyCode::Char('H') => Some(Action::CursorToTop),
+                    KeyCode::Char('M') => Some(Action::CursorToMiddle),
+                    KeyCode::Char('L') => Some(Action::CursorToBottom),
                     KeyCode::Char('w') => Some(Action::MoveWordForward),
                     KeyCode::Char('b') => Some(Action::MoveWordBackward),
                     KeyCode::Char('e') => Some(Action::MoveWordEnd),
+                    KeyCode::Char('W') => Some(Action::MoveBigWordForward),
+                    KeyCode::Char('B') => Some(Action::MoveBigWordBackward),
+                    KeyCode::Char('E') => Some(Action::MoveBigWordEnd),
+                    KeyCode::Char(';') => Some(Action::RepeatFind),
+                    KeyCode::Char(',') => Some(Action::RepeatFindReverse),
+                    KeyCode::Char('%') => Some(Action::MatchBracket),
+                    KeyCode::Char('}') => Some(Action::ParagraphForward),
+                    KeyCode::Char('{') => Some(Action::ParagraphBackward),
+                    KeyCode::Char('x') => Some(Action::DeleteChar),
+                    KeyCode::Char('X') => Some(Action::DeleteCharBefore),
+                    KeyCode::Char('~') => Some(Action::ToggleCase),
+                    KeyCode::Char('D') => Some(Action::DeleteToLineEnd),
+                    KeyCode::Char('C') => Some(Action::ChangeToLineEnd),
+                    KeyCode::Char('.') => Some(Action::RepeatChange),
+                    KeyCode::Char('p') => Some(Action::PasteAfter),
+                    KeyCode::Char('P') => Some(Action::PasteBefore),
                     KeyCode::Char('i') => Some(Action::EnterInsertMode),
                     KeyCode::Char('a') => Some(Action::EnterInsertModeAppend),
                     KeyCode::Char('A') => Some(Action::EnterInsertModeAppendLine),
                     KeyCode::Char('o') => Some(Action::EnterInsertModeOpenBelow),
                     KeyCode::Char('O') => Some(Action::EnterInsertModeOpenAbove),
+                    KeyCode::Char('R') => Some(Action::EnterReplaceMode),
                     KeyCode::Char(':') => Some(Action::EnterCommandMode),
                     KeyCode::Char('/') => Some(Action::SearchForward),
                     KeyCode::Char('?') => Some(Action::SearchBackward),
                     KeyCode::Char('n') => Some(Action::SearchNext),
                     KeyCode::Char('N') => Some(Action::SearchPrev),
+                    KeyCode::Char('*') => Some(Action::SearchWordForward),
+                    KeyCode::Char('#') => Some(Action::SearchWordBackward),
                     KeyCode::Esc => Some(Action::ClearSearch),
                     _ => None,
                 };
@@ -336,16 +833,126 @@ impl KeySequenceState {
         MatchResult::NoMatch
     }
 
-    pub fn pending_display(&self) -> String {
-        let mut s = String::new();
-        if let Some(count) = self.count {
-            s.push_str(&count.to_string());
+    /// The keys that can follow a pending prefix and what each does, for
+    /// the which-key popup. Keys leading to a longer sequence show `+group`.
+    pub fn next_keys(&self, mode: &str) -> Vec<(String, String)> {
+        let pending = &self.pending;
+        let mut next = Vec::new();
+        if pending.is_empty() {
+            return next;
+        }
+        for prefix in PREFIXES.iter().filter(|p| p.applies(mode)) {
+            if prefix.keys.len() == pending.len() && prefix.starts(pending, &self.leader) {
+                next.extend(
+                    prefix
+                        .next
+                        .iter()
+                        .filter(|(_, _, description)| !description.is_empty())
+                        .map(|(code, _, description)| {
+                            let key = Key::new(*code, KeyModifiers::NONE);
+                            (key_to_string(&key), description.to_string())
+                        }),
+                );
+            } else if prefix.keys.len() == pending.len() + 1
+                && prefix.continues(pending, &self.leader)
+            {
+                let key = key_to_string(&prefix.keys[pending.len()]);
+                next.push((key, format!("+{}", prefix.name)));
+            }
+        }
+        next
+    }
+
+    /// Finish a sequence, turning a count before `G`/`gg` into a line jump
+    fn complete(&mut self, action: Action) -> KeyResult {
+        self.pending.clear();
+        match (action, self.count.take()) {
+            (Action::MoveToFirstLine | Action::MoveToLastLine, Some(line)) => {
+                KeyResult::Action(Action::GotoLine(line), 1)
+            }
+            // A count makes % a jump through the file instead of to a bracket
+            (Action::MatchBracket, Some(percent)) => {
+                KeyResult::Action(Action::GotoPercent(percent), 1)
+            }
+            (action, count) => KeyResult::Action(action, count.unwrap_or(1)),
+        }
+    }
+
+    /// The counts before and after an operator, multiplied: `2d3w` deletes
+    /// six words, as in Vim
+    fn take_operator_count(&mut self) -> usize {
+        self.count.take().unwrap_or(1) * self.motion_count.take().unwrap_or(1)
+    }
+
+    /// The command typed so far and what its next key is taken as
+    pub fn pending_keys(&self) -> PendingKeys {
+        let mut keys = String::new();
+        let mut awaiting = None;
+        if let Some(op) = self.operator {
+            keys.push_str(op.keys());
+            if let Some(count) = self.motion_count {
+                keys.push_str(&count.to_string());
+            }
+            awaiting = Some("text object");
         }
         for k in &self.pending {
-            s.push_str(&key_to_string(k));
+            keys.push_str(&key_to_string(k));
+        }
+        if let Some(kind) = self.waiting_for_find {
+            keys.push(kind.key());
+            awaiting = Some("char");
+        }
+        if self.waiting_for_replace {
+            keys.push('r');
+            awaiting = Some("replacement");
+        }
+        if let Some(prefix) = self.waiting_for_mark {
+            keys.push(prefix);
+            awaiting = Some(if prefix == 'm' { "mark to set" } else { "mark" });
+        }
+        if self.waiting_for_register {
+            keys.push('"');
+            awaiting = Some("register");
+        }
+        if let Some(prefix) = self.waiting_for_macro {
+            keys.push(prefix);
+            awaiting = Some(if prefix == 'q' {
+                "macro to record"
+            } else {
+                "macro"
+            });
+        }
+        PendingKeys {
+            register: self.register,
+            count: self.count,
+            keys,
+            awaiting,
         }
-        s
     }
+
+    /// Whether no command has been started, so a key begins a new one
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty()
+            && !self.waiting_for_pane_select
+            && self.waiting_for_find.is_none()
+            && !self.waiting_for_replace
+            && self.waiting_for_mark.is_none()
+            && !self.waiting_for_register
+            && self.waiting_for_macro.is_none()
+            && self.register.is_none()
+            && self.operator.is_none()
+            && self.count.is_none()
+            && self.motion_count.is_none()
+    }
+
+    pub fn pending_display(&self) -> String {
+        self.pending_keys().to_string()
+    }
+}
+
+/// Whether `q<c>` can record into `c`: a letter (uppercase appends) or digit
+fn is_macro_name(c: char) -> bool {
+    c.is_ascii_alphanumeric()
 }
 
 fn key_to_string(key: &Key) -> String {
@@ -357,6 +964,7 @@ fn key_to_string(key: &Key) -> String {
         s.push_str("A-");
     }
     match key.code {
+        KeyCode::Char(' ') => s.push('␣'),
         KeyCode::Char(c) => s.push(c),
         KeyCode::Esc => s.push_str("Esc"),
         KeyCode::Enter => s.push_str("Enter"),
@@ -421,6 +1029,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn gd_greps_word_under_cursor() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('g'), "normal");
+        let result = state.process_key(Key::char('d'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::GrepWordUnderCursor, 1)
+        ));
+    }
+
+    #[test]
+    fn gf_opens_the_file_under_the_cursor() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('g'), "normal");
+        let result = state.process_key(Key::char('f'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::GotoFile, 1)));
+    }
+
     #[test]
     fn count_prefix_works() {
         let mut state = KeySequenceState::new();
@@ -446,6 +1073,259 @@ mod tests {
         }
     }
 
+    #[test]
+    fn braces_move_by_paragraph() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('2'), "normal");
+        let result = state.process_key(Key::char('}'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::ParagraphForward, 2)
+        ));
+        let result = state.process_key(Key::char('{'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::ParagraphBackward, 1)
+        ));
+        assert!(Action::ParagraphForward.is_jump());
+    }
+
+    #[test]
+    fn caret_moves_to_the_first_non_blank() {
+        let mut state = KeySequenceState::new();
+        let result = state.process_key(Key::char('^'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::MoveToFirstNonBlank, 1)
+        ));
+    }
+
+    #[test]
+    fn count_before_percent_jumps_through_the_file() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('5'), "normal");
+        state.process_key(Key::char('0'), "normal");
+        let result = state.process_key(Key::char('%'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::GotoPercent(50), 1)
+        ));
+
+        let result = state.process_key(Key::char('%'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::MatchBracket, 1)));
+    }
+
+    #[test]
+    fn count_before_g_jumps_to_line() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('5'), "normal");
+        state.process_key(Key::char('0'), "normal");
+        let result = state.process_key(Key::char('G'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::GotoLine(50), 1)));
+
+        state.process_key(Key::char('1'), "normal");
+        state.process_key(Key::char('0'), "normal");
+        state.process_key(Key::char('g'), "normal");
+        let result = state.process_key(Key::char('g'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::GotoLine(10), 1)));
+
+        // Without a count, G still goes to the last line
+        let result = state.process_key(Key::char('G'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::MoveToLastLine, 1)
+        ));
+    }
+
+    #[test]
+    fn indent_sequences_take_a_count() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('3'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('>'), "normal"),
+            KeyResult::Pending
+        ));
+        let result = state.process_key(Key::char('>'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::IndentLine, 3)));
+
+        state.process_key(Key::char('<'), "normal");
+        let result = state.process_key(Key::char('<'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::DedentLine, 1)));
+    }
+
+    #[test]
+    fn next_keys_list_what_follows_a_prefix() {
+        let mut state = KeySequenceState::new();
+        assert!(state.next_keys("normal").is_empty());
+
+        state.process_key(Key::char(' '), "normal");
+        let next = state.next_keys("normal");
+        assert!(next.contains(&("e".to_string(), "browser".to_string())));
+        assert!(next.contains(&("f".to_string(), "+find".to_string())));
+
+        state.process_key(Key::char('f'), "normal");
+        let keys: Vec<String> = state
+            .next_keys("normal")
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec!["f", "g", "r", "s"]);
+
+        // Alternate keys (arrows) aren't listed twice
+        state.process_key(Key::new(KeyCode::Esc, KeyModifiers::NONE), "normal");
+        state.process_key(Key::ctrl('w'), "insert");
+        let next = state.next_keys("insert");
+        assert!(next.iter().all(|(k, _)| k != "←"));
+        assert_eq!(next[0], ("h".to_string(), "focus left".to_string()));
+
+        // Ctrl can stay held after the prefix
+        let result = state.process_key(Key::ctrl('h'), "insert");
+        assert!(matches!(result, KeyResult::Action(Action::FocusLeft, 1)));
+    }
+
+    #[test]
+    fn leader_and_timeout_are_configurable() {
+        let mut state = KeySequenceState::new();
+        state.configure('\\', Duration::from_millis(1000));
+
+        state.process_key(Key::char('\\'), "normal");
+        assert_eq!(state.pending_display(), "\\");
+        assert_eq!(state.next_keys("normal")[0].0, "e");
+        state.process_key(Key::char('f'), "normal");
+        let result = state.process_key(Key::char('f'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::FindFile, 1)));
+
+        // Space is no longer the leader
+        assert!(matches!(
+            state.process_key(Key::char(' '), "normal"),
+            KeyResult::Unhandled
+        ));
+
+        // With no timeout a pending prefix is dropped by the next key
+        state.configure(' ', Duration::ZERO);
+        state.process_key(Key::char(' '), "normal");
+        std::thread::sleep(Duration::from_millis(2));
+        let result = state.process_key(Key::char('e'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::MoveWordEnd, 1)));
+    }
+
+    #[test]
+    fn gc_sequences_toggle_comments() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('g'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('c'), "normal"),
+            KeyResult::Pending
+        ));
+        assert_eq!(state.pending_display(), "gc");
+        let result = state.process_key(Key::char('c'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::ToggleComment, 1)
+        ));
+
+        state.process_key(Key::char('2'), "normal");
+        state.process_key(Key::char('g'), "normal");
+        state.process_key(Key::char('c'), "normal");
+        let result = state.process_key(Key::char('k'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::ToggleCommentUp, 2)
+        ));
+    }
+
+    #[test]
+    fn z_sequences_fold() {
+        let mut state = KeySequenceState::new();
+        assert!(matches!(
+            state.process_key(Key::char('z'), "normal"),
+            KeyResult::Pending
+        ));
+        let result = state.process_key(Key::char('a'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::ToggleFold, 1)));
+
+        state.process_key(Key::char('z'), "normal");
+        let result = state.process_key(Key::char('M'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::CloseAllFolds, 1)
+        ));
+
+        state.process_key(Key::char('z'), "normal");
+        let result = state.process_key(Key::char('z'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::ScrollCenter, 1)));
+    }
+
+    #[test]
+    fn leader_a_selects_all_matches() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char(' '), "normal");
+        let result = state.process_key(Key::char('a'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::SelectAllMatches, 1)
+        ));
+    }
+
+    #[test]
+    fn dot_repeats_with_a_count() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('3'), "normal");
+        let result = state.process_key(Key::char('.'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::RepeatChange, 3)));
+
+        assert!(Action::DeleteChar.is_change());
+        assert!(!Action::RepeatChange.is_change());
+        assert!(!Action::MoveWordForward.is_change());
+        assert!(!Action::SplitVertical.is_change());
+    }
+
+    #[test]
+    fn screen_line_motions() {
+        let mut state = KeySequenceState::new();
+        let result = state.process_key(Key::char('H'), "normal");
+        assert!(matches!(result, KeyResult::Action(Action::CursorToTop, 1)));
+        let result = state.process_key(Key::char('M'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::CursorToMiddle, 1)
+        ));
+        state.process_key(Key::char('2'), "normal");
+        let result = state.process_key(Key::char('L'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::CursorToBottom, 2)
+        ));
+    }
+
+    #[test]
+    fn replace_consumes_next_key() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('3'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('r'), "normal"),
+            KeyResult::Pending
+        ));
+        assert_eq!(state.pending_display(), "3r");
+        let result = state.process_key(Key::char('j'), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::ReplaceChar('j'), 3)
+        ));
+
+        state.process_key(Key::char('r'), "normal");
+        let result = state.process_key(Key::new(KeyCode::Enter, KeyModifiers::NONE), "normal");
+        assert!(matches!(
+            result,
+            KeyResult::Action(Action::ReplaceChar('\n'), 1)
+        ));
+
+        state.process_key(Key::char('r'), "normal");
+        let result = state.process_key(Key::new(KeyCode::Esc, KeyModifiers::NONE), "normal");
+        assert!(matches!(result, KeyResult::Cancelled));
+        assert!(!state.waiting_for_replace);
+    }
+
     #[test]
     fn ctrl_w_sequence_works() {
         let mut state = KeySequenceState::new();
@@ -462,6 +1342,81 @@ mod tests {
         }
     }
 
+    #[test]
+    fn ctrl_w_resizes_and_moves_panes() {
+        let mut state = KeySequenceState::new();
+        for (c, expected) in [
+            ('+', Action::ResizeTaller),
+            ('>', Action::ResizeWider),
+            ('-', Action::ResizeShorter),
+            ('<', Action::ResizeNarrower),
+            ('x', Action::SwapPane),
+            ('r', Action::RotatePanes),
+            ('o', Action::OnlyPane),
+        ] {
+            state.process_key(Key::ctrl('w'), "normal");
+            match state.process_key(Key::char(c), "normal") {
+                KeyResult::Action(action, 1) => assert_eq!(action, expected),
+                _ => panic!("Expected a window action for {}", c),
+            }
+        }
+    }
+
+    #[test]
+    fn pending_keys_show_count_operator_and_what_comes_next() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('2'), "normal");
+        state.process_key(Key::char('d'), "normal");
+        let pending = state.pending_keys();
+        assert_eq!(pending.count, Some(2));
+        assert_eq!(pending.to_string(), "2d");
+        assert_eq!(pending.awaiting, Some("text object"));
+
+        state.process_key(Key::char('i'), "normal");
+        assert_eq!(state.pending_display(), "2di");
+        state.process_key(Key::char('w'), "normal");
+        assert!(state.pending_keys().is_empty());
+
+        state.process_key(Key::char(' '), "normal");
+        state.process_key(Key::char('f'), "normal");
+        assert_eq!(state.pending_display(), "␣f");
+        assert_eq!(state.pending_keys().awaiting, None);
+        state.process_key(Key::new(KeyCode::Esc, KeyModifiers::NONE), "normal");
+
+        state.process_key(Key::char('m'), "normal");
+        assert_eq!(state.pending_keys().awaiting, Some("mark to set"));
+    }
+
+    #[test]
+    fn marks_take_the_next_key_as_their_name() {
+        let mut state = KeySequenceState::new();
+        for (prefix, expected) in [
+            ('m', Action::SetMark('a')),
+            ('`', Action::JumpToMark('a')),
+            ('\'', Action::JumpToMarkLine('a')),
+        ] {
+            assert!(matches!(
+                state.process_key(Key::char(prefix), "normal"),
+                KeyResult::Pending
+            ));
+            assert_eq!(state.pending_display(), prefix.to_string());
+            match state.process_key(Key::char('a'), "normal") {
+                KeyResult::Action(action, 1) => assert_eq!(action, expected),
+                r => panic!("Expected a mark action for {}, got {:?}", prefix, r),
+            }
+        }
+
+        let tab = Key::new(KeyCode::Tab, KeyModifiers::NONE);
+        assert!(matches!(
+            state.process_key(Key::ctrl('o'), "normal"),
+            KeyResult::Action(Action::JumpBack, 1)
+        ));
+        assert!(matches!(
+            state.process_key(tab, "normal"),
+            KeyResult::Action(Action::JumpForward, 1)
+        ));
+    }
+
     #[test]
     fn tab_commands_work() {
         let mut state = KeySequenceState::new();
@@ -507,6 +1462,175 @@ mod tests {
         assert_eq!(state.pending_display(), "5g");
     }
 
+    #[test]
+    fn find_char_consumes_next_key() {
+        let mut state = KeySequenceState::new();
+
+        state.process_key(Key::char('3'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('F'), "normal"),
+            KeyResult::Pending
+        ));
+        assert_eq!(state.pending_display(), "3F");
+
+        // Even keys with their own binding are taken as the target
+        match state.process_key(Key::char('j'), "normal") {
+            KeyResult::Action(Action::FindChar(FindKind::Backward, 'j'), 3) => {}
+            r => panic!("Expected FindChar backward with count 3, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn t_is_till_unless_tab_command() {
+        let mut state = KeySequenceState::new();
+
+        state.process_key(Key::char('t'), "normal");
+        match state.process_key(Key::char('x'), "normal") {
+            KeyResult::Action(Action::FindChar(FindKind::TillForward, 'x'), 1) => {}
+            r => panic!("Expected FindChar till, got {:?}", r),
+        }
+
+        state.process_key(Key::char('t'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('n'), "normal"),
+            KeyResult::Action(Action::NextTab, 1)
+        ));
+    }
+
+    #[test]
+    fn operator_text_object_sequence() {
+        let mut state = KeySequenceState::new();
+
+        assert!(matches!(
+            state.process_key(Key::char('c'), "normal"),
+            KeyResult::Pending
+        ));
+        assert!(matches!(
+            state.process_key(Key::char('i'), "normal"),
+            KeyResult::Pending
+        ));
+        assert_eq!(state.pending_display(), "ci");
+
+        match state.process_key(Key::char('('), "normal") {
+            KeyResult::Action(Action::OperatorTextObject(Operator::Change, object), 1) => {
+                assert_eq!(object, TextObject::from_key('(', false).unwrap());
+            }
+            r => panic!("Expected change text object, got {:?}", r),
+        }
+
+        // Counts before and after the operator multiply
+        for c in "2d3a".chars() {
+            state.process_key(Key::char(c), "normal");
+        }
+        assert_eq!(state.pending_display(), "2d3a");
+        assert!(matches!(
+            state.process_key(Key::char('w'), "normal"),
+            KeyResult::Action(Action::OperatorTextObject(Operator::Delete, _), 6)
+        ));
+
+        // Unknown objects cancel the operator
+        state.process_key(Key::char('d'), "normal");
+        state.process_key(Key::char('a'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('z'), "normal"),
+            KeyResult::Cancelled
+        ));
+        assert!(state.operator.is_none());
+    }
+
+    #[test]
+    fn operators_take_word_motions() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('2'), "normal");
+        state.process_key(Key::char('d'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('w'), "normal"),
+            KeyResult::Action(
+                Action::OperatorWord(Operator::Delete, WordMotion::Forward),
+                2
+            )
+        ));
+
+        for c in "d10".chars() {
+            state.process_key(Key::char(c), "normal");
+        }
+        assert!(matches!(
+            state.process_key(Key::char('e'), "normal"),
+            KeyResult::Action(Action::OperatorWord(Operator::Delete, WordMotion::End), 10)
+        ));
+
+        state.process_key(Key::char('c'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('B'), "normal"),
+            KeyResult::Action(
+                Action::OperatorWord(Operator::Change, WordMotion::BigBackward),
+                1
+            )
+        ));
+        assert!(state.operator.is_none());
+        assert!(Action::OperatorWord(Operator::Delete, WordMotion::End).is_change());
+    }
+
+    #[test]
+    fn case_operators_follow_g() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('g'), "normal");
+        state.process_key(Key::char('U'), "normal");
+        assert_eq!(state.pending_display(), "gU");
+        state.process_key(Key::char('i'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('w'), "normal"),
+            KeyResult::Action(Action::OperatorTextObject(Operator::Uppercase, _), 1)
+        ));
+
+        state.process_key(Key::char('g'), "normal");
+        state.process_key(Key::char('~'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('e'), "normal"),
+            KeyResult::Action(
+                Action::OperatorWord(Operator::ToggleCase, WordMotion::End),
+                1
+            )
+        ));
+
+        state.process_key(Key::char('3'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('~'), "normal"),
+            KeyResult::Action(Action::ToggleCase, 3)
+        ));
+        // gg still goes to the first line
+        state.process_key(Key::char('g'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('g'), "normal"),
+            KeyResult::Action(Action::MoveToFirstLine, 1)
+        ));
+    }
+
+    #[test]
+    fn q_and_at_name_a_macro() {
+        let mut state = KeySequenceState::new();
+        state.process_key(Key::char('q'), "normal");
+        assert_eq!(state.pending_keys().awaiting, Some("macro to record"));
+        assert!(matches!(
+            state.process_key(Key::char('a'), "normal"),
+            KeyResult::Action(Action::RecordMacro('a'), 1)
+        ));
+        assert!(state.is_idle());
+
+        state.process_key(Key::char('3'), "normal");
+        state.process_key(Key::char('@'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('@'), "normal"),
+            KeyResult::Action(Action::PlayMacro('@'), 3)
+        ));
+
+        state.process_key(Key::char('q'), "normal");
+        assert!(matches!(
+            state.process_key(Key::char('!'), "normal"),
+            KeyResult::Cancelled
+        ));
+    }
+
     #[test]
     fn zero_not_treated_as_count_when_first() {
         let mut state = KeySequenceState::new();
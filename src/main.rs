@@ -1,7 +1,9 @@
 use std::env;
+use std::io::IsTerminal;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use crossterm::event::EventStream;
+use crossterm::event::{Event, EventStream};
 use futures::StreamExt;
 
 mod config;
@@ -13,11 +15,27 @@ mod scripting;
 mod syntax;
 mod theme;
 
-use editor::{FinderAction, Workspace};
-use finder::{FinderResult, GrepMatch};
+use editor::{
+    CommandHistory, EditorEvent, FinderAction, PaneKind, RecentFiles, SplitDirection, Workspace,
+};
+use finder::grep::GrepResult;
+use finder::{FinderResult, OpenIn};
 use input::InputState;
 use render::Renderer;
-use scripting::ScriptEngine;
+use scripting::{ScriptEngine, ScriptRequest};
+
+/// How long typing must pause before syntax highlights are refreshed
+const REPARSE_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// How long a prefix key must be pending before the which-key popup appears
+const KEY_HINT_DELAY: Duration = Duration::from_millis(400);
+
+/// How long lark is idle between checks for files changed on disk
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Rounds of events scripts may raise in reply to each other (a save hook that
+/// saves, say) before the rest are dropped
+const MAX_SCRIPT_ROUNDS: usize = 4;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -27,62 +45,98 @@ async fn main() -> std::io::Result<()> {
     let settings = script_engine.settings();
 
     // Parse command line args
-    let args: Vec<String> = env::args().collect();
+    let mut args = env::args().skip(1);
     let mut verbose = false;
     let mut file_path: Option<PathBuf> = None;
+    let mut position: Option<(usize, usize)> = None; // 1-based line and column
+    let mut line_arg: Option<usize> = None;
+    let mut read_stdin = false;
 
-    for arg in args.iter().skip(1) {
+    while let Some(arg) = args.next() {
         match arg.as_str() {
             "--verbose" | "-v" => verbose = true,
+            "-" => read_stdin = true,
+            "--line" | "-l" => line_arg = args.next().and_then(|n| n.parse().ok()),
             "--help" | "-h" => {
                 println!("lark - a modal terminal editor");
                 println!();
-                println!("Usage: lark [OPTIONS] [FILE]");
+                println!("Usage: lark [OPTIONS] [FILE[:LINE[:COL]]] [+LINE]");
+                println!("       command | lark [-]");
                 println!();
                 println!("Options:");
                 println!("  -v, --verbose    Enable verbose logging");
+                println!("  -l, --line N     Open FILE with the cursor on line N");
                 println!("  -h, --help       Show this help");
                 return Ok(());
             }
             _ => {
-                if !arg.starts_with('-') {
-                    file_path = Some(PathBuf::from(arg));
+                if let Some(line) = arg.strip_prefix('+')
+                    && let Ok(line) = line.parse()
+                {
+                    line_arg = Some(line);
+                } else if !arg.starts_with('-') {
+                    let (path, at) = split_file_position(&arg);
+                    file_path = Some(path);
+                    position = at;
                 }
             }
         }
     }
+    if let Some(line) = line_arg {
+        position = Some((line, 1));
+    }
 
-    let mut workspace = if let Some(path) = file_path {
-        Workspace::open(path)
+    // Piped-in text must be read before the terminal is put in raw mode;
+    // keys are then read from the terminal itself
+    let stdin = std::io::stdin();
+    let mut workspace = if read_stdin || (file_path.is_none() && !stdin.is_terminal()) {
+        Workspace::with_scratch("[stdin]", &std::io::read_to_string(stdin)?)
+    } else if let Some(path) = file_path {
+        Workspace::open(path, settings.clone())
     } else {
         Workspace::new()
     };
+    if let Some((line, col)) = position {
+        let pane = workspace.focused_pane_mut();
+        pane.cursor.col = col.saturating_sub(1);
+        pane.goto_line(line.saturating_sub(1));
+    }
 
     workspace.verbose = verbose;
+    workspace.track_recent_files(RecentFiles::load());
+    workspace.command_history = CommandHistory::load();
 
     // Apply settings from config
-    workspace.theme_name = settings.theme.clone();
+    workspace.apply_settings(settings);
+    workspace.script_commands = script_engine.command_names().into_iter().collect();
+
+    // Background grammar installs report back through this channel
+    let (install_tx, mut install_rx) = tokio::sync::mpsc::unbounded_channel();
+    workspace.install_events = Some(install_tx);
+    // A file opened above can install its grammar now, with auto_install_grammars
+    let focused = workspace.tab().focused_pane_id;
+    workspace.check_grammar(focused);
 
     // Show config error if any
     if let Some(err) = config_error {
         workspace.set_error(err);
     }
+    run_event_hooks(&mut workspace, &script_engine);
 
     // Log startup info
-    if verbose {
-        workspace.log("Lark started in verbose mode");
-        workspace.log(format!("Theme: {}", workspace.theme_name));
-    }
+    workspace.log_verbose("Lark started in verbose mode");
+    workspace.log_verbose(format!("Theme: {}", workspace.theme_name));
 
     // Set up terminal
     Renderer::setup()?;
-    let renderer = Renderer::new()?;
+    let mut renderer = Renderer::new()?;
 
     // Input state for key sequences
     let mut input_state = InputState::new();
 
-    // Initial render
-    let current_theme = theme::get_builtin_theme(&workspace.theme_name).unwrap_or_default();
+    // Initial render, scrolled to a line given on the command line
+    adjust_focused_scroll(&mut workspace, &renderer);
+    let current_theme = workspace.theme.clone();
     renderer.render(&mut workspace, &current_theme)?;
 
     // Event stream for async key reading
@@ -90,109 +144,69 @@ async fn main() -> std::io::Result<()> {
 
     // Main loop
     while workspace.running {
-        // Check for pending finder actions (need to run outside of raw mode)
+        // Finders hand the terminal to fzf, so they run outside of raw mode
         if let Some(finder_action) = workspace.pending_finder.take() {
-            let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-
-            // Teardown terminal for fzf
-            Renderer::teardown()?;
-
-            let result = match finder_action {
-                FinderAction::FindFile => {
-                    match finder::find_file(&cwd) {
-                        FinderResult::Selected(path) => Some((path, None)),
-                        FinderResult::Cancelled => None,
-                        FinderResult::Error(e) => {
-                            // Re-setup terminal first, then show error
-                            Renderer::setup()?;
-                            workspace.set_message(e);
-                            let current_theme =
-                                theme::get_builtin_theme(&workspace.theme_name).unwrap_or_default();
-                            renderer.render(&mut workspace, &current_theme)?;
-                            continue;
-                        }
-                    }
-                }
-                FinderAction::Grep(pattern) => {
-                    // If no pattern, use word under cursor
-                    let search_pattern = if pattern.is_empty() {
-                        get_word_under_cursor(&workspace)
-                    } else {
-                        pattern
-                    };
-
-                    if search_pattern.is_empty() {
-                        Renderer::setup()?;
-                        workspace.set_message("No pattern to search".to_string());
-                        let current_theme =
-                            theme::get_builtin_theme(&workspace.theme_name).unwrap_or_default();
-                        renderer.render(&mut workspace, &current_theme)?;
-                        continue;
-                    }
-
-                    match finder::grep_files(&search_pattern, &cwd) {
-                        finder::grep::GrepResult::Selected(grep_match) => {
-                            let file = grep_match.file.clone();
-                            Some((file, Some(grep_match)))
-                        }
-                        finder::grep::GrepResult::Cancelled => None,
-                        finder::grep::GrepResult::NoMatches => {
-                            Renderer::setup()?;
-                            workspace.set_message(format!("No matches for: {}", search_pattern));
-                            let current_theme =
-                                theme::get_builtin_theme(&workspace.theme_name).unwrap_or_default();
-                            renderer.render(&mut workspace, &current_theme)?;
-                            continue;
-                        }
-                        finder::grep::GrepResult::Error(e) => {
-                            Renderer::setup()?;
-                            workspace.set_message(e);
-                            let current_theme =
-                                theme::get_builtin_theme(&workspace.theme_name).unwrap_or_default();
-                            renderer.render(&mut workspace, &current_theme)?;
-                            continue;
-                        }
-                    }
-                }
-            };
-
-            // Re-setup terminal
-            Renderer::setup()?;
-
-            // Open the selected file
-            if let Some((path, grep_match)) = result {
-                workspace.open_file_in_focused_pane(path);
-
-                // If grep match, jump to line/col
-                if let Some(GrepMatch { line, col, .. }) = grep_match {
-                    let pane = workspace.focused_pane_mut();
-                    pane.cursor.line = line.saturating_sub(1);
-                    pane.cursor.col = col.saturating_sub(1);
-                }
-            }
-
-            let current_theme = theme::get_builtin_theme(&workspace.theme_name).unwrap_or_default();
+            let result = run_finder(&mut workspace, &mut renderer, finder_action);
+            // However the finder ended, don't let one queued meanwhile start another
+            workspace.pending_finder = None;
+            result?;
+
+            run_event_hooks(&mut workspace, &script_engine);
+            adjust_focused_scroll(&mut workspace, &renderer);
+            let current_theme = workspace.theme.clone();
             renderer.render(&mut workspace, &current_theme)?;
             continue;
         }
 
         tokio::select! {
             Some(Ok(event)) = event_stream.next() => {
+                if let Event::Resize(width, height) = event {
+                    renderer.resize(width, height);
+                    fit_panes_to_screen(&mut workspace, &renderer);
+                }
+                // Coming back from another window is when files tend to have changed
+                if event == Event::FocusGained && workspace.settings.auto_reload {
+                    workspace.check_files_on_disk();
+                }
                 input::handle_event(&mut workspace, event, &mut input_state);
-
-                // Adjust scroll for focused pane based on its actual dimensions
-                let pane_height = renderer.focused_pane_height(&workspace);
-                let pane_width = renderer.focused_pane_width(&workspace);
-                {
-                    let pane = workspace.focused_pane_mut();
-                    pane.adjust_scroll(pane_height);
-                    pane.adjust_scroll_horizontal(pane_width);
+                if workspace.reload_config {
+                    script_engine = reload_config(&mut workspace);
                 }
+                run_event_hooks(&mut workspace, &script_engine);
+                workspace.reparse_pending(REPARSE_DEBOUNCE);
+
+                adjust_focused_scroll(&mut workspace, &renderer);
 
                 // Get current theme (may have changed via :theme command)
-                let current_theme = theme::get_builtin_theme(&workspace.theme_name).unwrap_or_default();
+                let current_theme = workspace.theme.clone();
                 renderer.render(&mut workspace, &current_theme)?;
             }
+            Some(event) = install_rx.recv() => {
+                workspace.handle_install_event(event);
+                let current_theme = workspace.theme.clone();
+                renderer.render(&mut workspace, &current_theme)?;
+            }
+            // List the keys that can follow a prefix once typing pauses
+            _ = tokio::time::sleep(KEY_HINT_DELAY), if !workspace.key_hints.is_empty() && !workspace.show_key_hints => {
+                workspace.show_key_hints = true;
+                let current_theme = workspace.theme.clone();
+                renderer.render(&mut workspace, &current_theme)?;
+            }
+            // Pick up files changed by other programs
+            _ = tokio::time::sleep(DISK_CHECK_INTERVAL), if workspace.settings.auto_reload => {
+                if workspace.check_files_on_disk() {
+                    adjust_focused_scroll(&mut workspace, &renderer);
+                    let current_theme = workspace.theme.clone();
+                    renderer.render(&mut workspace, &current_theme)?;
+                }
+            }
+            // Refresh highlights once typing pauses
+            _ = tokio::time::sleep(REPARSE_DEBOUNCE), if workspace.needs_reparse() => {
+                if workspace.reparse_pending(REPARSE_DEBOUNCE) {
+                    let current_theme = workspace.theme.clone();
+                    renderer.render(&mut workspace, &current_theme)?;
+                }
+            }
         }
     }
 
@@ -202,33 +216,283 @@ async fn main() -> std::io::Result<()> {
     Ok(())
 }
 
-fn get_word_under_cursor(workspace: &Workspace) -> String {
-    let pane = workspace.focused_pane();
-    let line_text = pane.buffer.line(pane.cursor.line);
-    let col = pane.cursor.col;
+/// Run a finder, opening whatever the user picks. Cancelling opens nothing;
+/// errors and empty results are shown as messages.
+fn run_finder(
+    workspace: &mut Workspace,
+    renderer: &mut Renderer,
+    action: FinderAction,
+) -> std::io::Result<()> {
+    let cwd = workspace.finder_root();
+    let preview = workspace.settings.preview_command.clone();
+    match action {
+        FinderAction::FindFile => {
+            let result = outside_raw_mode(renderer, || finder::find_file(&cwd, &preview))?;
+            open_finder_result(workspace, result);
+        }
+        // Pick with fzf, or from the message viewer without it
+        FinderAction::RecentFiles => {
+            let files = workspace.recent.existing();
+            if files.is_empty() {
+                workspace.set_message("No recent files".to_string());
+            } else if finder::fzf_available() {
+                let result = outside_raw_mode(renderer, || {
+                    finder::pick_file(&files, &cwd, "Recent: ", &preview)
+                })?;
+                open_finder_result(workspace, result);
+            } else {
+                workspace.show_file_picker("Recent files", files, &cwd);
+            }
+        }
+        FinderAction::Grep(pattern) => {
+            // If no pattern, use word under cursor
+            let pattern = if pattern.is_empty() {
+                let word = workspace.focused_pane().word_under_cursor();
+                word.map(|(_, word)| word).unwrap_or_default()
+            } else {
+                pattern
+            };
 
-    let chars: Vec<char> = line_text.chars().collect();
+            if pattern.is_empty() {
+                workspace.set_message("No pattern to search".to_string());
+            } else if finder::grep::external_tools_available() {
+                let result =
+                    outside_raw_mode(renderer, || finder::grep_files(&pattern, &cwd, &preview))?;
+                open_grep_result(workspace, result, &pattern);
+            } else {
+                // Without rg/fzf, grep in-process and pick from the message viewer
+                let matches = finder::grep::search(&pattern, &cwd);
+                if matches.is_empty() {
+                    workspace.set_message(format!("No matches for: {}", pattern));
+                } else {
+                    workspace.show_grep_results(&pattern, matches, &cwd);
+                }
+            }
+        }
+        // gd: jump straight to a lone match, otherwise pick one
+        FinderAction::GrepWord => {
+            let Some((_, word)) = workspace.focused_pane().word_under_cursor() else {
+                workspace.set_message("No identifier under cursor".to_string());
+                return Ok(());
+            };
+            match finder::grep::word_matches(&word, &cwd) {
+                Ok(matches) if matches.is_empty() => {
+                    workspace.set_message(format!("No matches for: {}", word));
+                }
+                Ok(matches) if matches.len() == 1 => workspace.open_grep_match(&matches[0]),
+                Ok(matches) if finder::grep::external_tools_available() => {
+                    let result = outside_raw_mode(renderer, || {
+                        finder::grep::pick_match(&matches, &cwd, &preview)
+                    })?;
+                    open_grep_result(workspace, result, &word);
+                }
+                Ok(matches) => workspace.show_grep_results(&word, matches, &cwd),
+                Err(e) => workspace.set_message(e),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Give the terminal to `pick` (fzf), then take it back and redraw everything
+fn outside_raw_mode<T>(renderer: &mut Renderer, pick: impl FnOnce() -> T) -> std::io::Result<T> {
+    Renderer::teardown()?;
+    let result = pick();
+    Renderer::setup()?;
+    renderer.invalidate();
+    Ok(result)
+}
+
+/// Focus the pane a finder's pick opens in: the focused one, or a new split or tab
+fn focus_pane_for(workspace: &mut Workspace, open_in: OpenIn) {
+    match open_in {
+        OpenIn::Pane => {}
+        OpenIn::VerticalSplit => workspace.split_and_focus(SplitDirection::Vertical),
+        OpenIn::HorizontalSplit => workspace.split_and_focus(SplitDirection::Horizontal),
+        OpenIn::Tab => workspace.new_tab(),
+    }
+}
+
+fn open_finder_result(workspace: &mut Workspace, result: FinderResult) {
+    match result {
+        FinderResult::Selected(path, open_in) => {
+            focus_pane_for(workspace, open_in);
+            workspace.open_file_in_focused_pane(path);
+        }
+        FinderResult::Error(e) => workspace.set_message(e),
+        FinderResult::Cancelled => {}
+    }
+}
+
+/// Jump to the picked match of a search for `pattern`
+fn open_grep_result(workspace: &mut Workspace, result: GrepResult, pattern: &str) {
+    match result {
+        GrepResult::Selected(grep_match, open_in) => {
+            focus_pane_for(workspace, open_in);
+            workspace.open_grep_match(&grep_match);
+        }
+        GrepResult::NoMatches => workspace.set_message(format!("No matches for: {}", pattern)),
+        GrepResult::Error(e) => workspace.set_message(e),
+        GrepResult::Cancelled => {}
+    }
+}
+
+/// Run script hooks and commands for the events raised since the last call,
+/// then apply what the scripts asked the editor to do
+fn run_event_hooks(workspace: &mut Workspace, script_engine: &ScriptEngine) {
+    for _ in 0..MAX_SCRIPT_ROUNDS {
+        let events = workspace.take_events();
+        if events.is_empty() {
+            return;
+        }
+        for event in events {
+            // Lend the focused pane to the engine so scripts can edit it via lark::editor
+            let pane_id = workspace.tab().focused_pane_id;
+            let lent = workspace.focused_pane().kind == PaneKind::Editor;
+            if lent && let Some(pane) = workspace.tab_mut().panes.remove(&pane_id) {
+                script_engine.lend_pane(pane);
+            }
+            let result = match &event {
+                EditorEvent::Open(path) => script_engine.fire("open", path),
+                EditorEvent::Save(path) => script_engine.fire("save", path),
+                EditorEvent::Command(name, args) => script_engine.run_command(name, args),
+            };
+            if let Some(pane) = script_engine.take_pane() {
+                workspace.tab_mut().panes.insert(pane_id, pane);
+            }
+            if let Err(e) = result {
+                workspace.set_error(e);
+            }
+            for request in script_engine.take_requests() {
+                apply_script_request(workspace, request);
+            }
+        }
+    }
+    workspace.take_events();
+}
 
-    if chars.is_empty() || col >= chars.len() {
-        return String::new();
+fn apply_script_request(workspace: &mut Workspace, request: ScriptRequest) {
+    match request {
+        ScriptRequest::Message(text) => workspace.set_message(text),
+        ScriptRequest::Error(text) => workspace.set_error(text),
+        ScriptRequest::OpenFile(path) => {
+            if path.is_file() {
+                workspace.open_file_in_focused_pane(path);
+            } else {
+                workspace.set_error(format!("File not found: {}", path.display()));
+            }
+        }
+        ScriptRequest::SetTheme(name) => {
+            if theme::find_theme(&name).is_some() {
+                workspace.set_theme(&name);
+            } else {
+                workspace.set_error(format!("Unknown theme: {}", name));
+            }
+        }
     }
+}
 
-    // Find word boundaries
-    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+/// Re-run the config file (`:source`) in a fresh engine, so hooks aren't registered twice
+fn reload_config(workspace: &mut Workspace) -> ScriptEngine {
+    workspace.reload_config = false;
+    let mut script_engine = ScriptEngine::new();
+    match script_engine.load_default() {
+        Ok(_) => {
+            workspace.apply_settings(script_engine.settings());
+            workspace.script_commands = script_engine.command_names().into_iter().collect();
+            workspace.set_message("Config reloaded");
+        }
+        Err(e) => workspace.set_error(e),
+    }
+    script_engine
+}
 
-    if !is_word_char(chars[col]) {
-        return String::new();
+/// Scroll the focused pane, both ways, so the cursor is on screen
+fn adjust_focused_scroll(workspace: &mut Workspace, renderer: &Renderer) {
+    // Use the pane's actual dimensions
+    let pane_height = renderer.focused_pane_height(workspace);
+    let pane_width = renderer.focused_pane_width(workspace);
+    let tab_width = workspace.focused_pane().indent(&workspace.settings).width;
+    let scroll_off = workspace.settings.scroll_off;
+    let side_scroll_off = workspace.settings.side_scroll_off;
+    if workspace.focused_pane().kind == PaneKind::FileBrowser {
+        // The file browser's first row is its title
+        workspace
+            .file_browser_mut()
+            .adjust_scroll(pane_height.saturating_sub(1));
+    } else {
+        let pane = workspace.focused_pane_mut();
+        pane.adjust_scroll(pane_height, scroll_off);
+        pane.adjust_scroll_horizontal(pane_width, tab_width, side_scroll_off);
     }
+}
 
-    let mut start = col;
-    while start > 0 && is_word_char(chars[start - 1]) {
-        start -= 1;
+/// Scroll every editor pane of the current tab so its cursor stays visible
+/// at the pane's current size
+fn fit_panes_to_screen(workspace: &mut Workspace, renderer: &Renderer) {
+    let settings = workspace.settings.clone();
+    for (pane_id, rect) in renderer.pane_rects(workspace) {
+        if let Some(pane) = workspace.tab_mut().panes.get_mut(&pane_id)
+            && pane.kind == PaneKind::Editor
+        {
+            pane.adjust_scroll(rect.height as usize, settings.scroll_off);
+            // Text starts after the line number gutter
+            let text_width = (rect.width as usize).saturating_sub(pane.gutter_width());
+            let tab_width = pane.indent(&settings).width;
+            pane.adjust_scroll_horizontal(text_width, tab_width, settings.side_scroll_off);
+        }
     }
+}
 
-    let mut end = col;
-    while end < chars.len() && is_word_char(chars[end]) {
-        end += 1;
+/// Split a `file:line[:col]` argument, as printed by grep, into the path and a
+/// 1-based position. A path that exists as given is never split.
+fn split_file_position(arg: &str) -> (PathBuf, Option<(usize, usize)>) {
+    let whole = PathBuf::from(arg);
+    if whole.exists() {
+        return (whole, None);
     }
 
-    chars[start..end].iter().collect()
+    let mut parts = arg.rsplitn(3, ':');
+    let last = parts.next().and_then(|p| p.parse::<usize>().ok());
+    let middle = parts.next();
+    let rest = parts.next();
+    match (last, middle, rest) {
+        (Some(col), Some(line), Some(path)) if line.parse::<usize>().is_ok() => {
+            (PathBuf::from(path), Some((line.parse().unwrap_or(1), col)))
+        }
+        (Some(line), Some(middle), rest) => {
+            let path = rest.map_or(middle.to_string(), |r| format!("{}:{}", r, middle));
+            (PathBuf::from(path), Some((line, 1)))
+        }
+        _ => (whole, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_line_and_column_from_file_arguments() {
+        assert_eq!(
+            split_file_position("lark-missing.rs:42:10"),
+            (PathBuf::from("lark-missing.rs"), Some((42, 10)))
+        );
+        assert_eq!(
+            split_file_position("lark-missing.rs:42"),
+            (PathBuf::from("lark-missing.rs"), Some((42, 1)))
+        );
+        assert_eq!(
+            split_file_position("lark-missing.rs"),
+            (PathBuf::from("lark-missing.rs"), None)
+        );
+        assert_eq!(
+            split_file_position("dir:name.rs:7"),
+            (PathBuf::from("dir:name.rs"), Some((7, 1)))
+        );
+        assert_eq!(
+            split_file_position("Cargo.toml"),
+            (PathBuf::from("Cargo.toml"), None)
+        );
+    }
 }
@@ -1,3 +1,6 @@
+pub mod picker;
 mod renderer;
+mod screen;
 
+pub use picker::{Picker, PickerEvent};
 pub use renderer::Renderer;
@@ -0,0 +1,327 @@
+//! A full-screen list to choose one item from, with a title bar and a
+//! help (or filter) line at the bottom
+//!
+//! Used for grep results, symbols, recent files and open buffers. `j`/`k`
+//! move the selection, `g`/`G` go to the first and last item, `/` filters
+//! the list as you type and Enter picks the selected item. What picking does
+//! is up to the caller, which gets back the item's index.
+
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor::MoveTo,
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    queue,
+    style::{Print, SetBackgroundColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::theme::Theme;
+
+/// What a key did to the picker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerEvent {
+    Pending,       // Still choosing
+    Picked(usize), // Enter on the item at this index
+    Closed,        // Esc or q without picking
+}
+
+/// A list being picked from
+#[derive(Debug, Clone)]
+pub struct Picker {
+    pub title: String,
+    items: Vec<String>,
+    pub filter: String,
+    pub filtering: bool, // Whether keys are typed into the filter
+    shown: Vec<usize>,   // Indices of the items matching the filter
+    selected: usize,     // Position in `shown`
+    scroll: usize,       // First position in `shown` on screen
+    scroll_col: usize,   // Horizontal scroll
+}
+
+impl Picker {
+    pub fn new(title: &str, items: Vec<String>) -> Self {
+        Self {
+            title: title.to_string(),
+            shown: (0..items.len()).collect(),
+            items,
+            filter: String::new(),
+            filtering: false,
+            selected: 0,
+            scroll: 0,
+            scroll_col: 0,
+        }
+    }
+
+    /// The index of the selected item, if any match the filter
+    pub fn selected(&self) -> Option<usize> {
+        self.shown.get(self.selected).copied()
+    }
+
+    /// Select the item at `index` and scroll it into a list `height` rows tall
+    pub fn select(&mut self, index: usize, height: usize) {
+        if let Some(position) = self.shown.iter().position(|&i| i == index) {
+            self.move_to(position, height);
+        }
+    }
+
+    /// The items matching the filter, with their indices
+    pub fn shown(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.shown.iter().map(|&i| (i, self.items[i].as_str()))
+    }
+
+    /// Handle a key, given the number of rows the list has on screen
+    pub fn handle_key(&mut self, key: KeyEvent, height: usize) -> PickerEvent {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let last = self.shown.len().saturating_sub(1);
+
+        // Moving and picking work while typing into the filter too
+        match key.code {
+            KeyCode::Enter => {
+                return match self.selected() {
+                    Some(index) => PickerEvent::Picked(index),
+                    None => PickerEvent::Pending,
+                };
+            }
+            KeyCode::Down => self.move_to(self.selected + 1, height),
+            KeyCode::Up => self.move_to(self.selected.saturating_sub(1), height),
+            KeyCode::Char('n') if ctrl => self.move_to(self.selected + 1, height),
+            KeyCode::Char('p') if ctrl => self.move_to(self.selected.saturating_sub(1), height),
+            KeyCode::Char('d') if ctrl => self.move_to(self.selected + height / 2, height),
+            KeyCode::Char('u') if ctrl => {
+                self.move_to(self.selected.saturating_sub(height / 2), height)
+            }
+            _ if self.filtering => self.edit_filter(key, height),
+            KeyCode::Esc | KeyCode::Char('q') => return PickerEvent::Closed,
+            KeyCode::Char('/') => self.filtering = true,
+            KeyCode::Char('j') => self.move_to(self.selected + 1, height),
+            KeyCode::Char('k') => self.move_to(self.selected.saturating_sub(1), height),
+            KeyCode::Char('g') => self.move_to(0, height),
+            KeyCode::Char('G') => self.move_to(last, height),
+            KeyCode::Char('l') | KeyCode::Right => self.scroll_col += 10,
+            KeyCode::Char('h') | KeyCode::Left => {
+                self.scroll_col = self.scroll_col.saturating_sub(10)
+            }
+            _ => {}
+        }
+        PickerEvent::Pending
+    }
+
+    /// A key typed into the filter: Esc clears it and Backspace past the
+    /// start stops filtering
+    fn edit_filter(&mut self, key: KeyEvent, height: usize) {
+        match key.code {
+            KeyCode::Esc => {
+                self.filtering = false;
+                self.set_filter("", height);
+            }
+            KeyCode::Backspace => {
+                if self.filter.is_empty() {
+                    self.filtering = false;
+                } else {
+                    let mut filter = self.filter.clone();
+                    filter.pop();
+                    self.set_filter(&filter, height);
+                }
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let filter = format!("{}{}", self.filter, c);
+                self.set_filter(&filter, height);
+            }
+            _ => {}
+        }
+    }
+
+    /// Show only the items containing every word of `filter`, ignoring case
+    /// unless it has capitals. The selection stays on the same item if it
+    /// still matches, scrolled into a list `height` rows tall.
+    pub fn set_filter(&mut self, filter: &str, height: usize) {
+        let selected = self.selected();
+        self.filter = filter.to_string();
+        let ignore_case = !filter.chars().any(char::is_uppercase);
+        let words: Vec<String> = filter
+            .split_whitespace()
+            .map(|w| {
+                if ignore_case {
+                    w.to_lowercase()
+                } else {
+                    w.to_string()
+                }
+            })
+            .collect();
+
+        self.shown = (0..self.items.len())
+            .filter(|&i| {
+                let item = if ignore_case {
+                    self.items[i].to_lowercase()
+                } else {
+                    self.items[i].clone()
+                };
+                words.iter().all(|w| item.contains(w.as_str()))
+            })
+            .collect();
+        let position = selected
+            .and_then(|index| self.shown.iter().position(|&i| i == index))
+            .unwrap_or(0);
+        self.scroll = 0;
+        self.move_to(position, height);
+    }
+
+    /// Select the item at `position` in the list (clamped to the end),
+    /// scrolling to keep it on screen
+    fn move_to(&mut self, position: usize, height: usize) {
+        self.selected = position.min(self.shown.len().saturating_sub(1));
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + height {
+            self.scroll = self.selected + 1 - height;
+        }
+    }
+}
+
+/// Draw `picker` over the whole screen except the status line
+pub fn render(
+    stdout: &mut impl Write,
+    picker: &Picker,
+    width: u16,
+    height: u16,
+    theme: &Theme,
+) -> io::Result<()> {
+    let width = width as usize;
+    let list_height = height.saturating_sub(3) as usize; // Title + help line + status
+    let count = if picker.filter.is_empty() {
+        picker.items.len().to_string()
+    } else {
+        format!("{}/{}", picker.shown.len(), picker.items.len())
+    };
+    bar_line(
+        stdout,
+        0,
+        width,
+        theme,
+        &format!(" {} ({}) ", picker.title, count),
+    )?;
+
+    queue!(
+        stdout,
+        SetBackgroundColor(theme.background.to_crossterm()),
+        SetForegroundColor(theme.foreground.to_crossterm())
+    )?;
+    let mut items = picker.shown().skip(picker.scroll);
+    for row in 0..list_height {
+        queue!(
+            stdout,
+            MoveTo(0, row as u16 + 1),
+            Clear(ClearType::CurrentLine)
+        )?;
+        let Some((_, item)) = items.next() else {
+            continue;
+        };
+
+        let selected = picker.scroll + row == picker.selected;
+        if selected {
+            queue!(
+                stdout,
+                SetBackgroundColor(theme.selection.to_crossterm()),
+                Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        let display: String = item.chars().skip(picker.scroll_col).take(width).collect();
+        queue!(stdout, Print(display))?;
+        if selected {
+            queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
+        }
+    }
+
+    let bottom = if picker.filtering {
+        format!("/{}", picker.filter)
+    } else if !picker.filter.is_empty() {
+        format!(
+            " /{} | j/k: select | Enter: open | /: filter | q: close ",
+            picker.filter
+        )
+    } else {
+        " j/k: select | Enter: open | g/G: first/last | /: filter | h/l: pan | q: close "
+            .to_string()
+    };
+    bar_line(stdout, height.saturating_sub(2), width, theme, &bottom)?;
+
+    queue!(
+        stdout,
+        SetBackgroundColor(theme.background.to_crossterm()),
+        SetForegroundColor(theme.foreground.to_crossterm())
+    )?;
+    Ok(())
+}
+
+/// A row in the status bar's colours, `text` padded to the full width
+fn bar_line(
+    stdout: &mut impl Write,
+    row: u16,
+    width: usize,
+    theme: &Theme,
+    text: &str,
+) -> io::Result<()> {
+    queue!(
+        stdout,
+        MoveTo(0, row),
+        SetBackgroundColor(theme.status_bar_bg.to_crossterm()),
+        SetForegroundColor(theme.status_bar_fg.to_crossterm()),
+        Print(text),
+        Print(" ".repeat(width.saturating_sub(text.width())))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(picker: &mut Picker, keys: &str) -> PickerEvent {
+        let mut event = PickerEvent::Pending;
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                '\x08' => KeyCode::Backspace,
+                c => KeyCode::Char(c),
+            };
+            event = picker.handle_key(KeyEvent::new(code, KeyModifiers::NONE), 2);
+        }
+        event
+    }
+
+    #[test]
+    fn keys_move_the_selection_and_filter_the_list() {
+        let items = ["src/main.rs", "src/lib.rs", "README.md", "src/Main.rs"];
+        let mut picker = Picker::new("Files", items.map(String::from).to_vec());
+
+        assert_eq!(press(&mut picker, "jj"), PickerEvent::Pending);
+        assert_eq!(picker.selected(), Some(2));
+        assert_eq!(picker.scroll, 1); // Two rows tall
+        assert_eq!(press(&mut picker, "G\n"), PickerEvent::Picked(3));
+        assert_eq!(press(&mut picker, "gk\n"), PickerEvent::Picked(0));
+
+        // Typed keys go into the filter, which ignores case without capitals
+        press(&mut picker, "/main");
+        assert!(picker.filtering);
+        assert_eq!(picker.shown().map(|(i, _)| i).collect::<Vec<_>>(), [0, 3]);
+        press(&mut picker, "\x08\x08\x08\x08Ma");
+        assert_eq!(
+            picker.shown().map(|(_, item)| item).collect::<Vec<_>>(),
+            ["src/Main.rs"]
+        );
+        assert_eq!(press(&mut picker, "\n"), PickerEvent::Picked(3));
+
+        // Esc clears the filter, then closes
+        press(&mut picker, "\x1b");
+        assert!(!picker.filtering);
+        assert_eq!(picker.shown().count(), 4);
+        assert_eq!(picker.selected(), Some(3)); // Still on the same item
+        assert_eq!(picker.scroll, 2); // And still in view
+        press(&mut picker, "/nothing");
+        assert_eq!(picker.selected(), None);
+        assert_eq!(press(&mut picker, "\n"), PickerEvent::Pending);
+        assert_eq!(press(&mut picker, "\x1b\x1b"), PickerEvent::Closed);
+    }
+}
@@ -2,6 +2,7 @@ use std::io::{self, Write, stdout};
 
 use crossterm::{
     cursor::{Hide, MoveTo, SetCursorStyle, Show},
+    event::{DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture},
     execute, queue,
     style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
     terminal::{
@@ -9,19 +10,37 @@ use crossterm::{
         LeaveAlternateScreen,
     },
 };
+use unicode_width::UnicodeWidthStr;
 
-use crate::editor::{Mode, PaneKind, Rect, Workspace};
-use crate::theme::Theme;
+use super::screen::Screen;
+use crate::editor::{
+    Completion, Mode, Pane, PaneKind, Rect, SignKind, WordCompletion, Workspace, cell_width,
+};
+use crate::syntax::Language;
+use crate::theme::{Color, Style, Theme};
 
 pub struct Renderer {
     pub width: u16,
     pub height: u16,
+    previous: Option<Screen>, // What the terminal shows; None forces a full redraw
+    mouse_captured: bool,
 }
 
 impl Renderer {
     pub fn new() -> io::Result<Self> {
         let (width, height) = terminal::size()?;
-        Ok(Self { width, height })
+        Ok(Self {
+            width,
+            height,
+            previous: None,
+            mouse_captured: true,
+        })
+    }
+
+    /// Redraw the whole screen next time, e.g. after a resize or after
+    /// another program had the terminal
+    pub fn invalidate(&mut self) {
+        self.previous = None;
     }
 
     pub fn setup() -> io::Result<()> {
@@ -29,6 +48,8 @@ impl Renderer {
         execute!(
             stdout(),
             EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableFocusChange,
             DisableLineWrap,
             Hide,
             Clear(ClearType::All)
@@ -41,6 +62,8 @@ impl Renderer {
             stdout(),
             SetCursorStyle::DefaultUserShape,
             Show,
+            DisableMouseCapture,
+            DisableFocusChange,
             EnableLineWrap,
             LeaveAlternateScreen
         )?;
@@ -48,16 +71,26 @@ impl Renderer {
         Ok(())
     }
 
+    /// Take on the terminal's new size; the next render redraws everything
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.width = width;
+        self.height = height;
+        self.invalidate();
+    }
+
+    /// Where each pane of the current tab is drawn
+    pub fn pane_rects(&self, workspace: &Workspace) -> Vec<(usize, Rect)> {
+        workspace.calculate_rects(self.content_area(workspace))
+    }
+
+    /// The screen below the tab bar (if any) and above the status line
+    fn content_area(&self, workspace: &Workspace) -> Rect {
+        workspace.content_area(self.width, self.height)
+    }
+
     /// Calculate the height of the focused pane for scroll adjustment
     pub fn focused_pane_height(&self, workspace: &Workspace) -> usize {
-        let has_tabs = workspace.tab_count() > 1;
-        let tab_bar_height = if has_tabs { 1u16 } else { 0 };
-        let content_area = Rect::new(
-            0,
-            tab_bar_height,
-            self.width,
-            self.height.saturating_sub(1 + tab_bar_height),
-        );
+        let content_area = self.content_area(workspace);
         let pane_rects = workspace.calculate_rects(content_area);
 
         // Find the focused pane's rect
@@ -71,19 +104,12 @@ impl Renderer {
         content_area.height as usize
     }
 
-    /// Calculate the text width of the focused pane for horizontal scroll
+    /// Calculate the text width (in terminal cells) of the focused pane for horizontal scroll
     pub fn focused_pane_width(&self, workspace: &Workspace) -> usize {
-        let has_tabs = workspace.tab_count() > 1;
-        let tab_bar_height = if has_tabs { 1u16 } else { 0 };
-        let content_area = Rect::new(
-            0,
-            tab_bar_height,
-            self.width,
-            self.height.saturating_sub(1 + tab_bar_height),
-        );
+        let content_area = self.content_area(workspace);
         let pane_rects = workspace.calculate_rects(content_area);
 
-        let gutter_width = 4usize;
+        let gutter_width = workspace.focused_pane().gutter_width();
 
         // Find the focused pane's rect
         for (pane_id, rect) in &pane_rects {
@@ -96,34 +122,67 @@ impl Renderer {
         (content_area.width as usize).saturating_sub(gutter_width)
     }
 
-    pub fn render(&self, workspace: &mut Workspace, theme: &Theme) -> io::Result<()> {
-        let mut stdout = stdout();
+    /// Draw the frame, printing only the cells that changed since the last one
+    pub fn render(&mut self, workspace: &mut Workspace, theme: &Theme) -> io::Result<()> {
+        let mut frame = Vec::new();
+        let pane_rects = self.render_frame(&mut frame, workspace, theme)?;
 
-        // Update terminal size in workspace for directional navigation
-        workspace.terminal_size = (self.width, self.height);
+        let mut screen = Screen::new(self.width, self.height);
+        screen.apply(&frame);
+
+        let mut stdout = stdout();
 
         // Hide cursor during redraw to prevent flicker
         queue!(stdout, Hide)?;
 
+        // Setup captures the mouse; give it back when the setting is off
+        let mouse = workspace.settings.mouse;
+        if self.previous.is_none() || mouse != self.mouse_captured {
+            if mouse {
+                queue!(stdout, EnableMouseCapture)?;
+            } else {
+                queue!(stdout, DisableMouseCapture)?;
+            }
+            self.mouse_captured = mouse;
+        }
+
+        match &self.previous {
+            Some(previous) if previous.size() == screen.size() => {
+                screen.draw_changes(previous, &mut stdout)?
+            }
+            _ => stdout.write_all(&frame)?,
+        }
+
+        // Position cursor in focused pane
+        self.position_cursor(&mut stdout, workspace, &pane_rects, theme)?;
+
+        stdout.flush()?;
+        self.previous = Some(screen);
+        Ok(())
+    }
+
+    /// Queue a full redraw into `stdout`, returning the pane layout it used
+    fn render_frame(
+        &self,
+        stdout: &mut impl Write,
+        workspace: &mut Workspace,
+        theme: &Theme,
+    ) -> io::Result<Vec<(usize, Rect)>> {
+        // Update terminal size in workspace for directional navigation
+        workspace.terminal_size = (self.width, self.height);
+
         // Set background color (don't clear whole screen - causes flicker)
         queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
 
         let has_tabs = workspace.tab_count() > 1;
-        let tab_bar_height = if has_tabs { 1u16 } else { 0 };
 
         // Render tab bar if multiple tabs
         if has_tabs {
-            self.render_tab_bar(&mut stdout, workspace, theme)?;
+            self.render_tab_bar(stdout, workspace, theme)?;
         }
 
         // Calculate layout - reserve lines for tab bar (if any) and status
-        let content_area = Rect::new(
-            0,
-            tab_bar_height,
-            self.width,
-            self.height.saturating_sub(1 + tab_bar_height),
-        );
-        let pane_rects = workspace.calculate_rects(content_area);
+        let pane_rects = self.pane_rects(workspace);
 
         // Skip pane rendering if message viewer is active (prevents flashing)
         let in_message_viewer = workspace.mode() == Mode::MessageViewer;
@@ -134,22 +193,12 @@ impl Renderer {
                 if let Some(pane) = workspace.pane(*pane_id) {
                     match pane.kind {
                         PaneKind::Editor => {
-                            let is_focused = workspace.is_focused(*pane_id);
-                            let search_matches = if is_focused && workspace.search.active {
-                                Some(&workspace.search)
-                            } else {
-                                None
-                            };
-                            self.render_editor_pane(&mut stdout, pane, rect, theme, search_matches)?
+                            self.render_editor_pane(stdout, workspace, pane, rect, theme)?
                         }
                         PaneKind::FileBrowser => {
                             let is_focused = workspace.is_focused(*pane_id);
                             self.render_file_browser_pane(
-                                &mut stdout,
-                                workspace,
-                                rect,
-                                is_focused,
-                                theme,
+                                stdout, workspace, rect, is_focused, theme,
                             )?
                         }
                     }
@@ -160,28 +209,51 @@ impl Renderer {
         if !in_message_viewer {
             // Render pane borders (only if there are multiple panes)
             if pane_rects.len() > 1 {
-                self.render_pane_borders(&mut stdout, workspace, &pane_rects, theme)?;
+                self.render_pane_borders(stdout, workspace, &pane_rects, theme)?;
+            }
+
+            // Name the panes the global status line isn't describing
+            if workspace.settings.pane_status {
+                self.render_pane_statuses(stdout, workspace, &pane_rects, theme)?;
             }
 
             // If selecting pane, show overlay labels
             if workspace.selecting_pane {
-                self.render_pane_labels(&mut stdout, workspace, &pane_rects, theme)?;
+                self.render_pane_labels(stdout, workspace, &pane_rects, theme)?;
+            }
+
+            // Which-key popup for a pending prefix
+            if workspace.show_key_hints {
+                self.render_key_hints(stdout, workspace, theme)?;
+            }
+
+            // Command-line matches being cycled through with Tab
+            if let Some(completion) = &workspace.command_completion {
+                self.render_completion(stdout, completion, theme)?;
+            }
+
+            // Buffer words Ctrl-N/Ctrl-P are cycling through
+            let pane = workspace.focused_pane();
+            if let Some(completion) = &pane.word_completion
+                && pane.mode == Mode::Insert
+                && let Some((_, rect)) = pane_rects.iter().find(|(id, _)| *id == pane.id)
+            {
+                let tab_width = pane.indent(&workspace.settings).width;
+                self.render_word_completion(stdout, pane, completion, rect, tab_width, theme)?;
             }
         }
 
-        // Message viewer overlay (covers everything except status line)
-        if in_message_viewer {
-            self.render_message_viewer(&mut stdout, workspace, theme)?;
+        // Message viewer or picker overlay (covers everything except status line)
+        if let Some((picker, _)) = &workspace.picker {
+            super::picker::render(stdout, picker, self.width, self.height, theme)?;
+        } else if in_message_viewer {
+            self.render_message_viewer(stdout, workspace, theme)?;
         }
 
         // Render global status line
-        self.render_status_line(&mut stdout, workspace, theme)?;
-
-        // Position cursor in focused pane
-        self.position_cursor(&mut stdout, workspace, &pane_rects, theme)?;
+        self.render_status_line(stdout, workspace, theme)?;
 
-        stdout.flush()?;
-        Ok(())
+        Ok(pane_rects)
     }
 
     fn render_tab_bar(
@@ -237,31 +309,73 @@ impl Renderer {
     fn render_editor_pane(
         &self,
         stdout: &mut impl Write,
-        pane: &crate::editor::Pane,
+        workspace: &Workspace,
+        pane: &Pane,
         rect: &Rect,
         theme: &Theme,
-        search: Option<&crate::editor::SearchState>,
     ) -> io::Result<()> {
         let line_count = pane.buffer.line_count();
-        let gutter_width = 4;
-        let text_width = rect.width.saturating_sub(gutter_width) as usize;
+        let gutter_width = pane.gutter_width();
+        let text_width = (rect.width as usize).saturating_sub(gutter_width);
+
+        // Search matches and the cursor line are only shown in the focused pane
+        let is_focused = workspace.is_focused(pane.id);
+        let search = (is_focused && workspace.search.active).then_some(&workspace.search);
+        let show_cursor_line = is_focused && workspace.settings.cursor_line;
+        let color_column = workspace.settings.color_column;
+        let tab_width = pane.indent(&workspace.settings).width;
+        let list_mode = workspace.settings.show_whitespace;
+        let brackets = if is_focused {
+            pane.bracket_pair()
+        } else {
+            None
+        };
 
         queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
 
+        if pane.buffer.is_binary() && !workspace.settings.open_binary {
+            return self.render_binary_placeholder(stdout, rect, theme);
+        }
+
+        // Closed folds take a single row, so walk visible lines rather than rows
+        let mut line_idx = pane.folds.visible_line(pane.scroll_offset);
         for row in 0..rect.height {
-            let line_idx = row as usize + pane.scroll_offset;
             queue!(stdout, MoveTo(rect.x, rect.y + row))?;
 
             if line_idx < line_count {
                 let is_cursor_line = line_idx == pane.cursor.line;
 
-                // Line number
+                // Background precedence: search match, then a bracket paired with
+                // the one at the cursor, then a syntax style's own background, then
+                // the color column, then the cursor line, then the theme background
+                let line_bg = if is_cursor_line && show_cursor_line {
+                    theme.cursor_line_bg
+                } else {
+                    theme.background
+                };
+                queue!(stdout, SetBackgroundColor(line_bg.to_crossterm()))?;
+
+                // Line number, relative ones counted in screen rows
                 let line_num = if is_cursor_line {
                     line_idx + 1
+                } else if line_idx < pane.cursor.line {
+                    pane.folds.rows_between(line_idx, pane.cursor.line)
                 } else {
-                    (line_idx as isize - pane.cursor.line as isize).unsigned_abs()
+                    pane.folds.rows_between(pane.cursor.line, line_idx)
                 };
 
+                // A closed fold shows its first line followed by a marker
+                let fold_marker = pane
+                    .folds
+                    .closed_at(line_idx)
+                    .map(|fold| format!(" ⋯ {} lines", fold.hidden_lines()));
+                let content_width = text_width.saturating_sub(
+                    fold_marker
+                        .as_deref()
+                        .map(UnicodeWidthStr::width)
+                        .unwrap_or(0),
+                );
+
                 let line_num_color = if is_cursor_line {
                     theme.line_number_active
                 } else {
@@ -269,7 +383,20 @@ impl Renderer {
                 };
 
                 queue!(stdout, SetForegroundColor(line_num_color.to_crossterm()))?;
-                queue!(stdout, Print(format!("{:>3} ", line_num)))?;
+                let sign_width = usize::from(!pane.signs.is_empty());
+                let number_width = gutter_width - sign_width - 1;
+                queue!(stdout, Print(format!("{:>number_width$}", line_num)))?;
+                if sign_width > 0 {
+                    match pane.signs.get(&line_idx) {
+                        Some(&kind) => {
+                            let color = self.sign_color(kind, theme);
+                            queue!(stdout, SetForegroundColor(color.to_crossterm()))?;
+                            queue!(stdout, Print(kind.glyph()))?;
+                        }
+                        None => queue!(stdout, Print(' '))?,
+                    }
+                }
+                queue!(stdout, Print(' '))?;
 
                 // Line content with syntax highlighting
                 let line = pane.buffer.line(line_idx);
@@ -290,6 +417,26 @@ impl Renderer {
                     })
                     .unwrap_or_default();
 
+                // Secondary cursors on this line
+                let extra_cursor_cols: Vec<_> = pane
+                    .extra_cursors
+                    .iter()
+                    .filter(|c| c.line == line_idx)
+                    .map(|c| c.col)
+                    .collect();
+
+                // Text cell of the color column on this row: the cells scrolled off
+                // depend on the line's wide chars, plus a cell for each column past its end
+                let line_len = pane.buffer.line_len(line_idx);
+                let scroll_cells = pane
+                    .buffer
+                    .display_col(line_idx, pane.scroll_col, tab_width)
+                    + pane.scroll_col.saturating_sub(line_len);
+                let ruler = color_column
+                    .checked_sub(1)
+                    .and_then(|col| col.checked_sub(scroll_cells))
+                    .filter(|&cell| cell < text_width);
+
                 // Calculate byte offset for scroll_col (for highlight matching)
                 let scroll_byte_offset: usize = content
                     .chars()
@@ -297,45 +444,134 @@ impl Renderer {
                     .map(|c| c.len_utf8())
                     .sum();
 
-                // Render visible portion of the line
+                // Trailing whitespace starts here; list mode marks it
+                let trailing_start = content.trim_end_matches([' ', '\t']).chars().count();
+
+                // Render visible portion of the line, counting terminal cells
+                // so wide glyphs never get clipped mid-cell at the right edge.
+                // Tabs are expanded against the unscrolled column so stops line up.
                 let mut byte_col = scroll_byte_offset;
                 let mut char_col = pane.scroll_col;
                 let mut displayed = 0;
-                for ch in content.chars().skip(pane.scroll_col).take(text_width) {
+                for ch in content.chars().skip(pane.scroll_col) {
+                    let width = cell_width(ch, scroll_cells + displayed, tab_width);
+                    if displayed + width > content_width {
+                        break;
+                    }
+
+                    // What to draw: tabs fill their cells, and list mode shows
+                    // tabs and trailing spaces as dimmed glyphs
+                    let (glyph, is_whitespace_mark) = match ch {
+                        '\t' if list_mode => (format!("→{}", " ".repeat(width - 1)), true),
+                        '\t' => (" ".repeat(width), false),
+                        ' ' if list_mode && char_col >= trailing_start => ("·".to_string(), true),
+                        _ => (ch.to_string(), false),
+                    };
+
                     // Check if this character is in a search match
                     let in_match = line_matches
                         .iter()
                         .any(|(start, end)| char_col >= *start && char_col < *end);
 
-                    if in_match {
+                    if extra_cursor_cols.contains(&char_col) {
+                        // Secondary cursor - a block in the selection color
+                        queue!(stdout, SetBackgroundColor(theme.selection.to_crossterm()))?;
+                        queue!(stdout, SetForegroundColor(theme.foreground.to_crossterm()))?;
+                        queue!(stdout, Print(&glyph))?;
+                    } else if in_match {
                         // Search match - use inverted colors
                         queue!(stdout, SetBackgroundColor(theme.warning.to_crossterm()))?;
                         queue!(stdout, SetForegroundColor(theme.background.to_crossterm()))?;
+                        queue!(stdout, Print(&glyph))?;
                     } else {
-                        queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
-                        // Determine the color for this character
-                        let color = if let Some(hl) = highlights {
+                        // Determine the style for this character
+                        let style = if is_whitespace_mark {
+                            Style::new(theme.whitespace)
+                        } else if let Some(hl) = highlights {
                             let kind = hl.kind_at(byte_col);
-                            self.highlight_kind_to_color(kind, theme)
+                            self.highlight_kind_to_style(kind, theme)
+                        } else {
+                            Style::new(theme.foreground)
+                        };
+                        let on_ruler =
+                            ruler.is_some_and(|r| r >= displayed && r < displayed + width);
+                        let on_bracket =
+                            brackets.is_some_and(|b| b.contains(&(line_idx, char_col)));
+                        let bg = if on_bracket {
+                            theme.match_paren
                         } else {
-                            theme.foreground
+                            style.bg.unwrap_or(if on_ruler {
+                                theme.color_column
+                            } else {
+                                line_bg
+                            })
                         };
-                        queue!(stdout, SetForegroundColor(color.to_crossterm()))?;
+                        queue!(stdout, SetBackgroundColor(bg.to_crossterm()))?;
+                        queue!(stdout, SetForegroundColor(style.fg.to_crossterm()))?;
+                        if style.bold {
+                            queue!(stdout, SetAttribute(Attribute::Bold))?;
+                        }
+                        if style.italic {
+                            queue!(stdout, SetAttribute(Attribute::Italic))?;
+                        }
+
+                        queue!(stdout, Print(&glyph))?;
+
+                        // Attributes apply per cell, so clear them before the next one
+                        if style.bold {
+                            queue!(stdout, SetAttribute(Attribute::NormalIntensity))?;
+                        }
+                        if style.italic {
+                            queue!(stdout, SetAttribute(Attribute::NoItalic))?;
+                        }
                     }
 
-                    queue!(stdout, Print(ch))?;
                     byte_col += ch.len_utf8();
                     char_col += 1;
+                    displayed += width;
+                }
+
+                // A secondary cursor past the end of the line gets its own cell
+                if char_col == line_len
+                    && extra_cursor_cols.contains(&line_len)
+                    && displayed < content_width
+                {
+                    queue!(stdout, SetBackgroundColor(theme.selection.to_crossterm()))?;
+                    queue!(stdout, Print(' '))?;
                     displayed += 1;
                 }
 
                 // Reset background and pad the rest of the line
-                queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
+                queue!(stdout, SetBackgroundColor(line_bg.to_crossterm()))?;
+                if let Some(marker) = fold_marker
+                    && displayed + marker.width() <= text_width
+                {
+                    queue!(stdout, SetForegroundColor(theme.line_number.to_crossterm()))?;
+                    queue!(stdout, Print(&marker))?;
+                    displayed += marker.width();
+                }
                 if displayed < text_width {
                     queue!(stdout, SetForegroundColor(theme.foreground.to_crossterm()))?;
-                    let padding = " ".repeat(text_width - displayed);
-                    queue!(stdout, Print(&padding))?;
+                    match ruler {
+                        Some(r) if r >= displayed => {
+                            queue!(stdout, Print(" ".repeat(r - displayed)))?;
+                            queue!(
+                                stdout,
+                                SetBackgroundColor(theme.color_column.to_crossterm())
+                            )?;
+                            queue!(stdout, Print(' '))?;
+                            queue!(stdout, SetBackgroundColor(line_bg.to_crossterm()))?;
+                            queue!(stdout, Print(" ".repeat(text_width - r - 1)))?;
+                        }
+                        _ => queue!(stdout, Print(" ".repeat(text_width - displayed)))?,
+                    }
                 }
+
+                line_idx = pane
+                    .folds
+                    .next_visible(line_idx, line_count)
+                    .unwrap_or(line_count);
+                queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
             } else {
                 // Empty line indicator
                 queue!(stdout, SetForegroundColor(theme.line_number.to_crossterm()))?;
@@ -349,30 +585,60 @@ impl Renderer {
         Ok(())
     }
 
-    /// Map a highlight kind to a theme color
-    fn highlight_kind_to_color(
+    /// Map a highlight kind to a theme style
+    fn highlight_kind_to_style(&self, kind: crate::syntax::HighlightKind, theme: &Theme) -> Style {
+        use crate::syntax::HighlightKind;
+
+        match kind {
+            HighlightKind::Keyword => theme.syntax_keyword,
+            HighlightKind::String => theme.syntax_string,
+            HighlightKind::Number => theme.syntax_number,
+            HighlightKind::Comment => theme.syntax_comment,
+            HighlightKind::Function => theme.syntax_function,
+            HighlightKind::Type => theme.syntax_type,
+            HighlightKind::Variable => theme.syntax_variable,
+            HighlightKind::Operator => theme.syntax_operator,
+            HighlightKind::Punctuation => theme.syntax_punctuation,
+            HighlightKind::Property => theme.syntax_property,
+            HighlightKind::Constant => theme.syntax_constant,
+            HighlightKind::Namespace => theme.syntax_namespace,
+            HighlightKind::Parameter => theme.syntax_parameter,
+            HighlightKind::Label => theme.syntax_label,
+            HighlightKind::Default => Style::new(theme.foreground),
+        }
+    }
+
+    /// Stand-in for a binary file's contents
+    fn render_binary_placeholder(
         &self,
-        kind: crate::syntax::HighlightKind,
+        stdout: &mut impl Write,
+        rect: &Rect,
         theme: &Theme,
-    ) -> crate::theme::Color {
-        use crate::syntax::HighlightKind;
+    ) -> io::Result<()> {
+        let width = rect.width as usize;
+        let blank = " ".repeat(width);
+        for row in 0..rect.height {
+            queue!(stdout, MoveTo(rect.x, rect.y + row), Print(&blank))?;
+        }
 
+        let text = "[binary] file not shown (lark::config::set_open_binary to view)";
+        let text: String = text.chars().take(width).collect();
+        let x = rect.x + (width.saturating_sub(text.width()) / 2) as u16;
+        queue!(
+            stdout,
+            MoveTo(x, rect.y + rect.height / 2),
+            SetForegroundColor(theme.line_number.to_crossterm()),
+            Print(text),
+            SetForegroundColor(theme.foreground.to_crossterm())
+        )?;
+        Ok(())
+    }
+
+    fn sign_color(&self, kind: SignKind, theme: &Theme) -> Color {
         match kind {
-            HighlightKind::Keyword => theme.syntax_keyword.fg,
-            HighlightKind::String => theme.syntax_string.fg,
-            HighlightKind::Number => theme.syntax_number.fg,
-            HighlightKind::Comment => theme.syntax_comment.fg,
-            HighlightKind::Function => theme.syntax_function.fg,
-            HighlightKind::Type => theme.syntax_type.fg,
-            HighlightKind::Variable => theme.syntax_variable.fg,
-            HighlightKind::Operator => theme.syntax_operator.fg,
-            HighlightKind::Punctuation => theme.syntax_punctuation.fg,
-            HighlightKind::Property => theme.syntax_variable.fg,
-            HighlightKind::Constant => theme.syntax_number.fg,
-            HighlightKind::Namespace => theme.syntax_type.fg,
-            HighlightKind::Parameter => theme.syntax_variable.fg,
-            HighlightKind::Label => theme.syntax_keyword.fg,
-            HighlightKind::Default => theme.foreground,
+            SignKind::Added => theme.info,
+            SignKind::Changed => theme.warning,
+            SignKind::Removed => theme.error,
         }
     }
 
@@ -405,7 +671,7 @@ impl Renderer {
 
         // File list
         for row in 1..rect.height {
-            let idx = row as usize - 1;
+            let idx = file_browser.scroll_offset + row as usize - 1;
             queue!(stdout, MoveTo(rect.x, rect.y + row))?;
             queue!(
                 stdout,
@@ -643,6 +909,15 @@ impl Renderer {
             return Ok(());
         }
 
+        // Prompt - show the question and the answer so far
+        if let Some(ref prompt) = workspace.prompt {
+            queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
+            queue!(stdout, SetForegroundColor(theme.foreground.to_crossterm()))?;
+            queue!(stdout, Clear(ClearType::CurrentLine))?;
+            queue!(stdout, Print(format!("{}{}", prompt.label, prompt.input)))?;
+            return Ok(());
+        }
+
         // Search input mode - show search pattern
         if workspace.search.is_inputting {
             queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
@@ -673,15 +948,14 @@ impl Renderer {
                 queue!(stdout, Clear(ClearType::CurrentLine))?;
 
                 // Prefix first line with "Error: "
-                if i == 0 {
-                    let display = format!("Error: {}", line);
-                    queue!(
-                        stdout,
-                        Print(&display[..display.len().min(self.width as usize)])
-                    )?;
+                let display = if i == 0 {
+                    format!("Error: {}", line)
                 } else {
-                    queue!(stdout, Print(&line[..line.len().min(self.width as usize)]))?;
-                }
+                    line.to_string()
+                };
+                // Source lines quoted in config errors may not be ASCII
+                let display: String = display.chars().take(self.width as usize).collect();
+                queue!(stdout, Print(display))?;
             }
 
             // Show hint to dismiss
@@ -719,28 +993,78 @@ impl Renderer {
 
         let pane = workspace.focused_pane();
         let mode = pane.mode.display();
-        let filename = pane
-            .buffer
-            .path()
-            .map(|p| {
-                p.file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "[No Name]".to_string())
-            })
-            .unwrap_or_else(|| "[No Name]".to_string());
+        let filename = pane.buffer.display_name();
+        let modified = match (pane.buffer.is_binary(), pane.buffer.is_dirty()) {
+            (true, _) => " [binary]",
+            (false, true) => " [+]",
+            (false, false) => "",
+        };
+        let read_only = if pane.buffer.is_read_only() {
+            " [RO]"
+        } else {
+            ""
+        };
         let position = format!("{}:{}", pane.cursor.line + 1, pane.cursor.col + 1);
 
-        let pending = if !workspace.pending_keys.is_empty() {
-            format!(" [{}]", workspace.pending_keys)
+        let pending = &workspace.pending_keys;
+        let pending = match pending.awaiting {
+            _ if pending.is_empty() => String::new(),
+            Some(awaiting) => format!(" [{} → {}]", pending, awaiting),
+            None => format!(" [{}]", pending),
+        };
+
+        let recording = match workspace.recording_macro {
+            Some(name) => format!(" recording @{}", name),
+            None => String::new(),
+        };
+
+        let language = pane.highlighter.language();
+        let language = if pane.large_file {
+            format!("{} (large file)", language.name())
+        } else if pane.highlighter.is_active() || language == Language::Unknown {
+            language.name().to_string()
         } else {
-            String::new()
+            format!("{} (no grammar)", language.name())
         };
+        // Don't count the empty "line" after a trailing newline
+        let last_line = pane.buffer.line_count().saturating_sub(1);
+        let line_count = if last_line > 0 && pane.buffer.line_len(last_line) == 0 {
+            last_line
+        } else {
+            last_line + 1
+        };
+
+        let left = format!(
+            " {} | {}{}{}{}{} ",
+            mode, filename, modified, read_only, pending, recording
+        );
 
-        let left = format!(" {} | {}{} ", mode, filename, pending);
-        let right = format!(" {} ", position);
+        // Right-hand segments with their importance (0 = always shown). When the
+        // terminal is narrow, the least important are dropped first.
+        let mut segments = vec![
+            (1, language),
+            (2, pane.indent(&workspace.settings).name()),
+            (2, pane.buffer.line_ending().name().to_string()),
+            (0, position),
+        ];
+        if workspace.settings.scroll_indicator {
+            segments.push((3, pane.scroll_position()));
+        }
+        segments.push((3, format!("{}L", line_count)));
+        let width = self.width as usize;
+        let right = loop {
+            let texts: Vec<&str> = segments.iter().map(|(_, text)| text.as_str()).collect();
+            let right = format!(" {} ", texts.join(" | "));
+            if left.width() + right.width() <= width || segments.len() == 1 {
+                break right;
+            }
+            if let Some(least) = (0..segments.len()).max_by_key(|&i| segments[i].0) {
+                segments.remove(least);
+            }
+        };
 
-        let padding = self.width as usize - left.len() - right.len();
-        let middle = " ".repeat(padding.max(0));
+        let padding = width.saturating_sub(left.width() + right.width());
+        let middle = " ".repeat(padding);
 
         let status = format!("{}{}{}", left, middle, right);
         let status: String = status.chars().take(self.width as usize).collect();
@@ -751,6 +1075,211 @@ impl Renderer {
         Ok(())
     }
 
+    /// List the keys that can follow the pending prefix in columns above the
+    /// status line, e.g. `f  +find   e  browser`
+    fn render_key_hints(
+        &self,
+        stdout: &mut impl Write,
+        workspace: &Workspace,
+        theme: &Theme,
+    ) -> io::Result<()> {
+        let hints = &workspace.key_hints;
+        let key_width = hints.iter().map(|(k, _)| k.width()).max().unwrap_or(0);
+        let cell_width = hints
+            .iter()
+            .map(|(_, d)| key_width + 2 + d.width() + 3)
+            .max()
+            .unwrap_or(1);
+        let width = self.width as usize;
+        let columns = (width.saturating_sub(1) / cell_width).max(1);
+        let max_rows = self.height.saturating_sub(2) as usize;
+        let rows = hints.len().div_ceil(columns).min(max_rows);
+        let top = self.height.saturating_sub(1 + rows as u16);
+
+        let bg = theme.status_bar_bg.blend(theme.background, 0.5);
+        queue!(stdout, SetBackgroundColor(bg.to_crossterm()))?;
+        for row in 0..rows {
+            queue!(stdout, MoveTo(0, top + row as u16), Print(" "))?;
+            let mut used = 1;
+            for (key, description) in hints.iter().skip(row * columns).take(columns) {
+                let key = format!("{:<w$}  ", key, w = key_width);
+                let description: String = description
+                    .chars()
+                    .take(cell_width.saturating_sub(key.width()))
+                    .collect();
+                let description = format!("{:<w$}", description, w = cell_width - key.width());
+                if used + key.width() + description.width() > width {
+                    break;
+                }
+                queue!(stdout, SetForegroundColor(theme.warning.to_crossterm()))?;
+                queue!(stdout, Print(&key))?;
+                queue!(
+                    stdout,
+                    SetForegroundColor(theme.status_bar_fg.to_crossterm())
+                )?;
+                queue!(stdout, Print(&description))?;
+                used += key.width() + description.width();
+            }
+            queue!(stdout, Print(" ".repeat(width.saturating_sub(used))))?;
+        }
+
+        queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
+        queue!(stdout, SetForegroundColor(theme.foreground.to_crossterm()))?;
+        Ok(())
+    }
+
+    /// List the matches on the row above the command line, scrolled so the
+    /// selected one shows
+    fn render_completion(
+        &self,
+        stdout: &mut impl Write,
+        completion: &Completion,
+        theme: &Theme,
+    ) -> io::Result<()> {
+        let width = self.width as usize;
+        let items: Vec<String> = completion
+            .candidates
+            .iter()
+            .map(|c| format!(" {} ", c))
+            .collect();
+        // Start as late as needed for the selected match to fit
+        let mut first = completion.selected;
+        let mut used = items[first].width();
+        while first > 0 && used + items[first - 1].width() <= width {
+            first -= 1;
+            used += items[first].width();
+        }
+
+        let row = self.height.saturating_sub(2);
+        let bg = theme.status_bar_bg.blend(theme.background, 0.5);
+        queue!(
+            stdout,
+            MoveTo(0, row),
+            SetBackgroundColor(bg.to_crossterm())
+        )?;
+        queue!(
+            stdout,
+            SetForegroundColor(theme.status_bar_fg.to_crossterm())
+        )?;
+        let mut used = 0;
+        for (i, item) in items.iter().enumerate().skip(first) {
+            if used + item.width() > width {
+                break;
+            }
+            if i == completion.selected {
+                queue!(stdout, SetBackgroundColor(theme.selection.to_crossterm()))?;
+                queue!(stdout, Print(item))?;
+                queue!(stdout, SetBackgroundColor(bg.to_crossterm()))?;
+            } else {
+                queue!(stdout, Print(item))?;
+            }
+            used += item.width();
+        }
+        queue!(stdout, Print(" ".repeat(width.saturating_sub(used))))?;
+
+        queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
+        queue!(stdout, SetForegroundColor(theme.foreground.to_crossterm()))?;
+        Ok(())
+    }
+
+    /// List the words being completed in insert mode under the start of the
+    /// word, or above it if they don't fit below
+    fn render_word_completion(
+        &self,
+        stdout: &mut impl Write,
+        pane: &Pane,
+        completion: &WordCompletion,
+        rect: &Rect,
+        tab_width: usize,
+        theme: &Theme,
+    ) -> io::Result<()> {
+        const MAX_ROWS: usize = 8;
+        let rows = completion.candidates.len().min(MAX_ROWS);
+        // Scrolled so the selected word shows
+        let first = completion.selected.saturating_sub(rows - 1);
+        let longest = completion.candidates.iter().map(|w| w.width()).max();
+        let width = (longest.unwrap_or(0) + 2).min(self.width as usize);
+
+        let col = pane.buffer.display_width(
+            completion.line,
+            pane.scroll_col,
+            completion.start,
+            tab_width,
+        );
+        let x = (rect.x as usize + pane.gutter_width() + col).min(self.width as usize - width);
+        let line_row =
+            rect.y as usize + pane.folds.rows_between(pane.scroll_offset, completion.line);
+        let y = if line_row + 1 + rows <= (rect.y + rect.height) as usize {
+            line_row + 1
+        } else {
+            line_row.saturating_sub(rows)
+        };
+
+        let bg = theme.status_bar_bg.blend(theme.background, 0.5);
+        queue!(
+            stdout,
+            SetForegroundColor(theme.status_bar_fg.to_crossterm())
+        )?;
+        let shown = completion.candidates.iter().enumerate().skip(first);
+        for (row, (i, word)) in shown.take(rows).enumerate() {
+            let bg = if i == completion.selected {
+                theme.selection
+            } else {
+                bg
+            };
+            queue!(
+                stdout,
+                MoveTo(x as u16, (y + row) as u16),
+                SetBackgroundColor(bg.to_crossterm()),
+                Print(format!(" {}", word)),
+                Print(" ".repeat(width.saturating_sub(word.width() + 1)))
+            )?;
+        }
+
+        queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
+        queue!(stdout, SetForegroundColor(theme.foreground.to_crossterm()))?;
+        Ok(())
+    }
+
+    /// Overlay a filename bar on the last row of each unfocused editor pane
+    fn render_pane_statuses(
+        &self,
+        stdout: &mut impl Write,
+        workspace: &Workspace,
+        pane_rects: &[(usize, Rect)],
+        theme: &Theme,
+    ) -> io::Result<()> {
+        let bg = theme.status_bar_bg.blend(theme.background, 0.5);
+        let fg = theme.status_bar_fg.blend(theme.background, 0.3);
+
+        for (pane_id, rect) in pane_rects {
+            if workspace.is_focused(*pane_id) || rect.height < 2 {
+                continue;
+            }
+            let Some(pane) = workspace.pane(*pane_id) else {
+                continue;
+            };
+            if pane.kind != PaneKind::Editor {
+                continue;
+            }
+
+            let modified = if pane.buffer.is_dirty() { " [+]" } else { "" };
+            let text = format!(" {}{}", pane.buffer.display_name(), modified);
+            let width = rect.width as usize;
+            let mut bar: String = text.chars().take(width).collect();
+            bar.push_str(&" ".repeat(width.saturating_sub(bar.width())));
+
+            queue!(stdout, MoveTo(rect.x, rect.y + rect.height - 1))?;
+            queue!(stdout, SetBackgroundColor(bg.to_crossterm()))?;
+            queue!(stdout, SetForegroundColor(fg.to_crossterm()))?;
+            queue!(stdout, Print(bar))?;
+        }
+
+        queue!(stdout, SetBackgroundColor(theme.background.to_crossterm()))?;
+        queue!(stdout, SetForegroundColor(theme.foreground.to_crossterm()))?;
+        Ok(())
+    }
+
     fn render_message_viewer(
         &self,
         stdout: &mut impl Write,
@@ -839,6 +1368,15 @@ impl Renderer {
         pane_rects: &[(usize, Rect)],
         _theme: &Theme,
     ) -> io::Result<()> {
+        // The picker's filter line has the cursor while it's typed into
+        if let Some((picker, _)) = &workspace.picker
+            && picker.filtering
+        {
+            let col = 1 + picker.filter.width() as u16;
+            queue!(stdout, MoveTo(col, self.height.saturating_sub(2)), Show)?;
+            return Ok(());
+        }
+
         // Hide cursor for message viewer
         if workspace.mode() == Mode::MessageViewer {
             queue!(stdout, Hide)?;
@@ -848,11 +1386,24 @@ impl Renderer {
         let focused_pane = workspace.focused_pane();
         if let Some((_, rect)) = pane_rects.iter().find(|(id, _)| *id == focused_pane.id) {
             if workspace.mode() == Mode::Command {
-                let cmd_col = 1 + workspace.command_buffer.len() as u16;
+                let typed = workspace
+                    .command_buffer
+                    .get(..workspace.command_cursor)
+                    .unwrap_or(&workspace.command_buffer);
+                let cmd_col = 1 + typed.width() as u16;
                 let cmd_row = self.height.saturating_sub(1);
                 queue!(stdout, MoveTo(cmd_col, cmd_row))?;
                 queue!(stdout, SetCursorStyle::BlinkingBar)?;
                 queue!(stdout, Show)?;
+            } else if let Some(ref prompt) = workspace.prompt {
+                // Prompt input - cursor at end of the answer
+                let prompt_col = prompt.label.width() + prompt.input.width();
+                queue!(
+                    stdout,
+                    MoveTo(prompt_col as u16, self.height.saturating_sub(1))
+                )?;
+                queue!(stdout, SetCursorStyle::BlinkingBar)?;
+                queue!(stdout, Show)?;
             } else if workspace.search.is_inputting {
                 // Search input - cursor at end of search buffer
                 let search_col = 1 + workspace.search_buffer.len() as u16;
@@ -861,19 +1412,24 @@ impl Renderer {
                 queue!(stdout, SetCursorStyle::BlinkingBar)?;
                 queue!(stdout, Show)?;
             } else if focused_pane.kind == PaneKind::Editor {
-                let gutter_width = 4u16;
-                // Account for horizontal scroll
-                let visible_col = focused_pane
-                    .cursor
-                    .col
-                    .saturating_sub(focused_pane.scroll_col);
+                let gutter_width = focused_pane.gutter_width() as u16;
+                // Account for horizontal scroll and wide characters
+                let visible_col = focused_pane.buffer.display_width(
+                    focused_pane.cursor.line,
+                    focused_pane.scroll_col,
+                    focused_pane.cursor.col,
+                    focused_pane.indent(&workspace.settings).width,
+                );
                 let cursor_x = rect.x + gutter_width + visible_col as u16;
-                let cursor_y =
-                    rect.y + (focused_pane.cursor.line - focused_pane.scroll_offset) as u16;
+                let cursor_row = focused_pane
+                    .folds
+                    .rows_between(focused_pane.scroll_offset, focused_pane.cursor.line);
+                let cursor_y = rect.y + cursor_row as u16;
                 queue!(stdout, MoveTo(cursor_x, cursor_y))?;
 
                 let cursor_style = match focused_pane.mode {
                     Mode::Insert => SetCursorStyle::BlinkingBar,
+                    Mode::Replace => SetCursorStyle::SteadyUnderScore,
                     _ => SetCursorStyle::SteadyBlock,
                 };
                 queue!(stdout, cursor_style)?;
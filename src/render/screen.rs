@@ -0,0 +1,356 @@
+//! Cell grid for redrawing only what changed between frames
+//!
+//! The renderer still queues ordinary crossterm commands; a frame's output is
+//! replayed into a `Screen` by interpreting the few escape sequences those
+//! commands produce (cursor moves, colors and attributes, line and screen
+//! clears). Comparing that grid with the previous frame's gives the cells
+//! that actually need printing.
+
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{
+        Attribute, Attributes, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor,
+    },
+};
+use unicode_width::UnicodeWidthChar;
+
+/// Colors and attributes of a cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CellStyle {
+    fg: Color,
+    bg: Color,
+    attributes: Attributes,
+}
+
+impl Default for CellStyle {
+    fn default() -> Self {
+        Self {
+            fg: Color::Reset,
+            bg: Color::Reset,
+            attributes: Attributes::none(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Cell {
+    symbol: String, // Empty for the right half of a wide glyph
+    style: CellStyle,
+}
+
+impl Cell {
+    fn blank(style: CellStyle) -> Self {
+        Self {
+            symbol: " ".to_string(),
+            style,
+        }
+    }
+}
+
+/// What a terminal of `width` x `height` shows after a frame's output
+#[derive(Debug, Clone)]
+pub struct Screen {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    cursor: (u16, u16),
+    style: CellStyle,
+}
+
+impl Screen {
+    /// A blank screen
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::blank(CellStyle::default()); width as usize * height as usize],
+            cursor: (0, 0),
+            style: CellStyle::default(),
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// Replay terminal output onto the grid
+    pub fn apply(&mut self, output: &[u8]) {
+        let text = String::from_utf8_lossy(output);
+        let mut chars = text.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\x1b' {
+                self.put(ch);
+                continue;
+            }
+            // Only CSI sequences draw anything; others (e.g. ESC 7) are skipped
+            if chars.next() != Some('[') {
+                continue;
+            }
+            let mut params = String::new();
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    self.csi(&params, c);
+                    break;
+                }
+                params.push(c);
+            }
+        }
+    }
+
+    /// Queue the commands that turn `previous` into this screen
+    pub fn draw_changes(&self, previous: &Screen, out: &mut impl Write) -> io::Result<()> {
+        let mut style: Option<CellStyle> = None; // Unknown until first set
+        let mut cursor: Option<(u16, u16)> = None;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = self.index(x, y);
+                let cell = &self.cells[i];
+                if cell.symbol.is_empty() || *cell == previous.cells[i] {
+                    continue;
+                }
+                if cursor != Some((x, y)) {
+                    queue!(out, MoveTo(x, y))?;
+                }
+                if style != Some(cell.style) {
+                    set_style(out, style, cell.style)?;
+                    style = Some(cell.style);
+                }
+                queue!(out, Print(&cell.symbol))?;
+
+                let width = cell
+                    .symbol
+                    .chars()
+                    .map(|c| c.width().unwrap_or(0))
+                    .sum::<usize>();
+                cursor = Some((x + width.max(1) as u16, y));
+            }
+        }
+        Ok(())
+    }
+
+    fn index(&self, x: u16, y: u16) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Draw a character at the cursor and move past it, clipping at the right edge
+    fn put(&mut self, ch: char) {
+        let Some(width) = ch.width() else {
+            return; // Control characters take no cells
+        };
+        let (x, y) = self.cursor;
+        if width == 0 {
+            // Combining marks join the glyph before them
+            if x > 0 && y < self.height && x <= self.width {
+                let i = self.index(x - 1, y);
+                self.cells[i].symbol.push(ch);
+            }
+            return;
+        }
+        if y < self.height && x as usize + width <= self.width as usize {
+            let i = self.index(x, y);
+            self.cells[i] = Cell {
+                symbol: ch.to_string(),
+                style: self.style,
+            };
+            if width == 2 {
+                self.cells[i + 1] = Cell {
+                    symbol: String::new(),
+                    style: self.style,
+                };
+            }
+        }
+        self.cursor.0 = x.saturating_add(width as u16);
+    }
+
+    /// Blank `from..to` on row `y` the way an erase does: background only
+    fn erase(&mut self, y: u16, from: u16, to: u16) {
+        if y >= self.height {
+            return;
+        }
+        let style = CellStyle {
+            bg: self.style.bg,
+            ..CellStyle::default()
+        };
+        for x in from..to.min(self.width) {
+            let i = self.index(x, y);
+            self.cells[i] = Cell::blank(style);
+        }
+    }
+
+    fn csi(&mut self, params: &str, command: char) {
+        match (command, params) {
+            ('H', _) => {
+                let mut parts = params.split(';').map(|p| p.parse::<u16>().unwrap_or(1));
+                let row = parts.next().unwrap_or(1).max(1) - 1;
+                let col = parts.next().unwrap_or(1).max(1) - 1;
+                self.cursor = (col, row);
+            }
+            ('m', _) => self.sgr(params),
+            ('K', "" | "0") => self.erase(self.cursor.1, self.cursor.0, self.width),
+            ('K', "1") => self.erase(self.cursor.1, 0, self.cursor.0 + 1),
+            ('K', "2") => self.erase(self.cursor.1, 0, self.width),
+            ('J', "2") => {
+                for y in 0..self.height {
+                    self.erase(y, 0, self.width);
+                }
+            }
+            _ => {} // Cursor visibility and shape don't touch cells
+        }
+    }
+
+    /// Apply a Select Graphic Rendition sequence to the current style
+    fn sgr(&mut self, params: &str) {
+        let mut codes = params.split(';').map(|p| p.parse::<u8>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            let attributes = &mut self.style.attributes;
+            match code {
+                0 => self.style = CellStyle::default(),
+                1 => attributes.set(Attribute::Bold),
+                2 => attributes.set(Attribute::Dim),
+                3 => attributes.set(Attribute::Italic),
+                4 => attributes.set(Attribute::Underlined),
+                7 => attributes.set(Attribute::Reverse),
+                9 => attributes.set(Attribute::CrossedOut),
+                22 => {
+                    attributes.unset(Attribute::Bold);
+                    attributes.unset(Attribute::Dim);
+                }
+                23 => attributes.unset(Attribute::Italic),
+                24 => attributes.unset(Attribute::Underlined),
+                27 => attributes.unset(Attribute::Reverse),
+                29 => attributes.unset(Attribute::CrossedOut),
+                30..=37 => self.style.fg = Color::AnsiValue(code - 30),
+                90..=97 => self.style.fg = Color::AnsiValue(code - 90 + 8),
+                40..=47 => self.style.bg = Color::AnsiValue(code - 40),
+                100..=107 => self.style.bg = Color::AnsiValue(code - 100 + 8),
+                38 => self.style.fg = extended_color(&mut codes),
+                48 => self.style.bg = extended_color(&mut codes),
+                39 => self.style.fg = Color::Reset,
+                49 => self.style.bg = Color::Reset,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The color after a 38 or 48: `5;n` or `2;r;g;b`
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Color {
+    match codes.next() {
+        Some(5) => Color::AnsiValue(codes.next().unwrap_or(0)),
+        Some(2) => Color::Rgb {
+            r: codes.next().unwrap_or(0),
+            g: codes.next().unwrap_or(0),
+            b: codes.next().unwrap_or(0),
+        },
+        _ => Color::Reset,
+    }
+}
+
+/// Queue the commands to go from style `from` (None when unknown) to `to`
+fn set_style(out: &mut impl Write, from: Option<CellStyle>, to: CellStyle) -> io::Result<()> {
+    let from = match from {
+        Some(from) if from.attributes == to.attributes => from,
+        _ => {
+            // Turning attributes off piecemeal is unreliable; start over
+            queue!(out, SetAttribute(Attribute::Reset))?;
+            for attribute in Attribute::iterator().filter(|a| to.attributes.has(*a)) {
+                queue!(out, SetAttribute(attribute))?;
+            }
+            CellStyle {
+                attributes: to.attributes,
+                ..CellStyle::default()
+            }
+        }
+    };
+    if from.fg != to.fg {
+        queue!(out, SetForegroundColor(to.fg))?;
+    }
+    if from.bg != to.bg {
+        queue!(out, SetBackgroundColor(to.bg))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_with(output: &str) -> Screen {
+        let mut screen = Screen::new(10, 3);
+        screen.apply(output.as_bytes());
+        screen
+    }
+
+    fn row(screen: &Screen, y: u16) -> String {
+        (0..screen.width)
+            .map(|x| screen.cells[screen.index(x, y)].symbol.as_str())
+            .collect()
+    }
+
+    fn changes(previous: &Screen, next: &Screen) -> String {
+        let mut out = Vec::new();
+        next.draw_changes(previous, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn replays_moves_text_and_clears() {
+        let mut out = Vec::new();
+        queue!(out, MoveTo(2, 1), Print("hello world")).unwrap();
+        queue!(out, MoveTo(0, 2), Print("ab中c"), MoveTo(1, 2)).unwrap();
+        queue!(
+            out,
+            crossterm::terminal::Clear(crossterm::terminal::ClearType::UntilNewLine)
+        )
+        .unwrap();
+        let mut screen = Screen::new(10, 3);
+        screen.apply(&out);
+
+        assert_eq!(row(&screen, 0), " ".repeat(10));
+        assert_eq!(row(&screen, 1), "  hello wo");
+        assert_eq!(row(&screen, 2), "a         ");
+    }
+
+    #[test]
+    fn tracks_colors_and_attributes() {
+        let mut out = Vec::new();
+        let red = Color::Rgb { r: 255, g: 0, b: 0 };
+        queue!(
+            out,
+            SetForegroundColor(red),
+            SetAttribute(Attribute::Bold),
+            Print("a")
+        )
+        .unwrap();
+        queue!(out, SetAttribute(Attribute::NormalIntensity), Print("b")).unwrap();
+        let mut screen = Screen::new(10, 3);
+        screen.apply(&out);
+
+        let (a, b) = (&screen.cells[0].style, &screen.cells[1].style);
+        assert_eq!(a.fg, red);
+        assert!(a.attributes.has(Attribute::Bold));
+        assert_eq!(b.fg, red);
+        assert!(b.attributes.is_empty());
+    }
+
+    #[test]
+    fn only_changed_cells_are_drawn() {
+        let before = screen_with("\x1b[1;1Hhello\x1b[2;1Hworld");
+        let after = screen_with("\x1b[1;1Hhelp!\x1b[2;1Hworld");
+
+        let drawn = changes(&before, &after);
+        assert!(drawn.starts_with("\x1b[1;4H"));
+        assert!(drawn.ends_with("p!"));
+        assert!(!drawn.contains("world"));
+        assert_eq!(changes(&after, &after), "");
+
+        // Replaying the changes over the old frame gives the new one
+        let mut replayed = before.clone();
+        replayed.apply(drawn.as_bytes());
+        assert_eq!(replayed.cells, after.cells);
+    }
+}
@@ -5,15 +5,33 @@
 //! lark::config::set_theme("nord");
 //! lark::config::set_tab_width(4);
 //! lark::config::bind("<leader>w", "save");
+//! lark::config::set_formatter("rust", "rustfmt --edition 2024");
 //! ```
+//!
+//! Custom `:` commands run a callback, or stand in for a builtin command:
+//! ```rhai
+//! lark::config::command("greet", |args| lark::ui::message(`Hello, ${args}!`));
+//! lark::config::command("W", "w");
+//! ```
+//! A callback gets the text after the command name (`:greet World` passes
+//! `"World"`, or `""` with no arguments); it may also take no parameters.
 
+use rhai::FnPtr;
 use rhai::plugin::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 
-use crate::config::Settings;
+use crate::config::{Indent, Settings};
+use crate::editor::Edge;
+use crate::syntax::Language;
+
+/// Command callbacks defined with `command(name, callback)`
+pub type Commands = Rc<RefCell<HashMap<String, FnPtr>>>;
 
 /// Create the config module with access to settings
-pub fn create_module(settings: Arc<RwLock<Settings>>) -> rhai::Module {
+pub fn create_module(settings: Arc<RwLock<Settings>>, commands: Commands) -> rhai::Module {
     let mut module = rhai::Module::new();
 
     // set_theme(name: &str)
@@ -82,6 +100,178 @@ pub fn create_module(settings: Arc<RwLock<Settings>>) -> rhai::Module {
         });
     }
 
+    // set_cursor_line(enabled: bool)
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_cursor_line", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.cursor_line = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_show_whitespace(enabled: bool) - list mode: mark tabs and trailing spaces
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_show_whitespace", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.show_whitespace = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_color_column(column: i64) - 0 disables the ruler
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_color_column", move |column: i64| {
+            if let Ok(mut settings) = s.write() {
+                settings.color_column = column.max(0) as usize;
+            }
+            Ok(())
+        });
+    }
+
+    // set_pane_status(enabled: bool) - filename bar on unfocused split panes
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_pane_status", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.pane_status = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_git_signs(enabled: bool) - mark lines changed since git HEAD
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_git_signs", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.git_signs = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_auto_reload(enabled: bool) - reload files changed outside the editor
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_auto_reload", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.auto_reload = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_scroll_indicator(enabled: bool) - Top/Bot/All or a percentage in the status line
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_scroll_indicator", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.scroll_indicator = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_scroll_off(lines: i64) - lines kept above and below the cursor
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_scroll_off", move |lines: i64| {
+            if let Ok(mut settings) = s.write() {
+                settings.scroll_off = lines.max(0) as usize;
+            }
+            Ok(())
+        });
+    }
+
+    // set_side_scroll_off(columns: i64) - columns kept either side of the cursor
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_side_scroll_off", move |columns: i64| {
+            if let Ok(mut settings) = s.write() {
+                settings.side_scroll_off = columns.max(0) as usize;
+            }
+            Ok(())
+        });
+    }
+
+    // set_open_binary(enabled: bool) - show binary files (still read-only)
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_open_binary", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.open_binary = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_large_file_size(bytes: i64) - skip highlighting files this big, 0 for no limit
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_large_file_size", move |bytes: i64| {
+            if let Ok(mut settings) = s.write() {
+                settings.large_file_size = bytes.max(0) as usize;
+            }
+            Ok(())
+        });
+    }
+
+    // set_auto_install_grammars(enabled: bool) - install missing grammars on open
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_auto_install_grammars", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.auto_install_grammars = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_leader(key: &str) - a single key, e.g. "\\" or "," (default " ")
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn(
+            "set_leader",
+            move |key: &str| -> Result<(), Box<EvalAltResult>> {
+                let mut chars = key.chars();
+                let (Some(leader), None) = (chars.next(), chars.next()) else {
+                    return Err(format!("Leader must be a single key: {:?}", key).into());
+                };
+                if let Ok(mut settings) = s.write() {
+                    settings.leader = leader;
+                }
+                Ok(())
+            },
+        );
+    }
+
+    // set_timeout_ms(ms: i64) - how long a key sequence waits for its next key
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_timeout_ms", move |ms: i64| {
+            if let Ok(mut settings) = s.write() {
+                settings.timeout_ms = ms.max(0) as u64;
+            }
+            Ok(())
+        });
+    }
+
+    // set_mouse(enabled: bool) - off leaves the mouse to the terminal for selecting text
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_mouse", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.mouse = enabled;
+            }
+            Ok(())
+        });
+    }
+
     // set_auto_indent(enabled: bool)
     {
         let s = Arc::clone(&settings);
@@ -93,6 +283,28 @@ pub fn create_module(settings: Arc<RwLock<Settings>>) -> rhai::Module {
         });
     }
 
+    // set_detect_indent(enabled: bool)
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_detect_indent", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.detect_indent = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_auto_pairs(enabled: bool)
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_auto_pairs", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.auto_pairs = enabled;
+            }
+            Ok(())
+        });
+    }
+
     // set_insert_spaces(enabled: bool)
     {
         let s = Arc::clone(&settings);
@@ -104,6 +316,150 @@ pub fn create_module(settings: Arc<RwLock<Settings>>) -> rhai::Module {
         });
     }
 
+    // set_formatter(lang: &str, command: &str) - e.g. ("rust", "rustfmt --edition 2024")
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn(
+            "set_formatter",
+            move |lang: &str, command: &str| -> Result<(), Box<EvalAltResult>> {
+                let language = Language::from_name(lang)
+                    .ok_or_else(|| format!("Unknown language: {}", lang))?;
+                if let Ok(mut settings) = s.write() {
+                    settings.formatters.insert(language, command.to_string());
+                }
+                Ok(())
+            },
+        );
+    }
+
+    // set_ignore_case(enabled: bool) - searches match either case
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_ignore_case", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.ignore_case = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_smart_case(enabled: bool) - searches match either case unless they have capitals
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_smart_case", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.smart_case = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_smart_home(enabled: bool) - 0 toggles between the first non-blank and column 0
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_smart_home", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.smart_home = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_clipboard(mode: &str) - "system" to yank and paste with the system clipboard, or "internal"
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn(
+            "set_clipboard",
+            move |mode: &str| -> Result<(), Box<EvalAltResult>> {
+                let system = match mode {
+                    "system" => true,
+                    "internal" => false,
+                    _ => {
+                        return Err(format!(
+                            "Invalid clipboard: {} (use system or internal)",
+                            mode
+                        )
+                        .into());
+                    }
+                };
+                if let Ok(mut settings) = s.write() {
+                    settings.system_clipboard = system;
+                }
+                Ok(())
+            },
+        );
+    }
+
+    // set_comment_token(lang: &str, token: &str) - e.g. ("c", "/* */") for block comments
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn(
+            "set_comment_token",
+            move |lang: &str, token: &str| -> Result<(), Box<EvalAltResult>> {
+                let language = Language::from_name(lang)
+                    .ok_or_else(|| format!("Unknown language: {}", lang))?;
+                if token.trim().is_empty() {
+                    return Err("Comment token can't be empty".into());
+                }
+                if let Ok(mut settings) = s.write() {
+                    settings.comment_tokens.insert(language, token.to_string());
+                }
+                Ok(())
+            },
+        );
+    }
+
+    // set_indent(lang: &str, indent: Map) - e.g. ("go", #{ use_tabs: true, width: 4 });
+    // fields left out keep the language's current indentation
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn(
+            "set_indent",
+            move |lang: &str, fields: rhai::Map| -> Result<(), Box<EvalAltResult>> {
+                let language = Language::from_name(lang)
+                    .ok_or_else(|| format!("Unknown language: {}", lang))?;
+                if let Ok(mut settings) = s.write() {
+                    let indent = build_indent(settings.indent(language), &fields)?;
+                    settings.indents.insert(language, indent);
+                }
+                Ok(())
+            },
+        );
+    }
+
+    // set_format_on_save(enabled: bool)
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_format_on_save", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.format_on_save = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_trim_trailing_whitespace(enabled: bool) - strip line-end spaces and tabs on :w
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_trim_trailing_whitespace", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.trim_trailing_whitespace = enabled;
+            }
+            Ok(())
+        });
+    }
+
+    // set_ensure_final_newline(enabled: bool) - end the file with a line break on :w
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_ensure_final_newline", move |enabled: bool| {
+            if let Ok(mut settings) = s.write() {
+                settings.ensure_final_newline = enabled;
+            }
+            Ok(())
+        });
+    }
+
     // set_show_hidden_files(enabled: bool)
     {
         let s = Arc::clone(&settings);
@@ -115,6 +471,68 @@ pub fn create_module(settings: Arc<RwLock<Settings>>) -> rhai::Module {
         });
     }
 
+    // set_file_browser(side: &str, ratio: f64) - "left", "right" or "bottom", and the
+    // share of the screen it takes there
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn(
+            "set_file_browser",
+            move |side: &str, ratio: f64| -> Result<(), Box<EvalAltResult>> {
+                let edge = match side {
+                    "left" => Edge::Left,
+                    "right" => Edge::Right,
+                    "bottom" => Edge::Bottom,
+                    _ => {
+                        return Err(format!(
+                            "Invalid file browser side: {} (use left, right or bottom)",
+                            side
+                        )
+                        .into());
+                    }
+                };
+                if let Ok(mut settings) = s.write() {
+                    settings.file_browser_edge = edge;
+                    settings.file_browser_ratio = ratio.clamp(0.1, 0.9) as f32;
+                }
+                Ok(())
+            },
+        );
+    }
+
+    // set_preview_command(command: &str) - finder preview, e.g. "bat --highlight-line {line} {file}"
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn("set_preview_command", move |command: &str| {
+            if let Ok(mut settings) = s.write() {
+                settings.preview_command = command.to_string();
+            }
+            Ok(())
+        });
+    }
+
+    // set_finder_root(root: &str) - "project" to search from the file's project root, or "cwd"
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn(
+            "set_finder_root",
+            move |root: &str| -> Result<(), Box<EvalAltResult>> {
+                let from_cwd = match root {
+                    "project" => false,
+                    "cwd" => true,
+                    _ => {
+                        return Err(
+                            format!("Invalid finder root: {} (use project or cwd)", root).into(),
+                        );
+                    }
+                };
+                if let Ok(mut settings) = s.write() {
+                    settings.finder_from_cwd = from_cwd;
+                }
+                Ok(())
+            },
+        );
+    }
+
     // bind(key: &str, action: &str)
     {
         let s = Arc::clone(&settings);
@@ -128,17 +546,71 @@ pub fn create_module(settings: Arc<RwLock<Settings>>) -> rhai::Module {
         });
     }
 
+    // command(name: &str, callback: Fn) - a custom :name command
+    module.set_native_fn(
+        "command",
+        move |name: &str, callback: FnPtr| -> Result<(), Box<EvalAltResult>> {
+            check_command_name(name)?;
+            commands.borrow_mut().insert(name.to_string(), callback);
+            Ok(())
+        },
+    );
+
+    // command(name: &str, target: &str) - :name runs the builtin :target
+    {
+        let s = Arc::clone(&settings);
+        module.set_native_fn(
+            "command",
+            move |name: &str, target: &str| -> Result<(), Box<EvalAltResult>> {
+                check_command_name(name)?;
+                if let Ok(mut settings) = s.write() {
+                    settings
+                        .command_aliases
+                        .insert(name.to_string(), target.to_string());
+                }
+                Ok(())
+            },
+        );
+    }
+
     // list_themes() -> Array
     module.set_native_fn(
         "list_themes",
         || -> Result<rhai::Array, Box<EvalAltResult>> {
-            let themes = crate::theme::list_builtin_themes();
-            Ok(themes
-                .into_iter()
-                .map(|s| rhai::Dynamic::from(s.to_string()))
-                .collect())
+            let themes = crate::theme::list_themes();
+            Ok(themes.into_iter().map(rhai::Dynamic::from).collect())
         },
     );
 
     module
 }
+
+/// `base` with the fields set in a Rhai map
+fn build_indent(base: Indent, fields: &rhai::Map) -> Result<Indent, String> {
+    let mut indent = base;
+    for (key, value) in fields {
+        match key.as_str() {
+            "use_tabs" => {
+                indent.use_tabs = value
+                    .as_bool()
+                    .map_err(|_| "`use_tabs` must be a bool".to_string())?
+            }
+            "width" => {
+                let width = value
+                    .as_int()
+                    .map_err(|_| "`width` must be a number".to_string())?;
+                indent.width = width.clamp(1, 16) as usize;
+            }
+            _ => return Err(format!("Unknown indent field: {}", key)),
+        }
+    }
+    Ok(indent)
+}
+
+/// A command name is one word, so `:name args` can be split on the first space
+fn check_command_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("Invalid command name: {:?}", name));
+    }
+    Ok(())
+}
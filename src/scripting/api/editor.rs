@@ -0,0 +1,146 @@
+//! lark::editor - Editor operations for command callbacks and hooks
+//!
+//! Usage in Rhai:
+//! ```rhai
+//! lark::editor::open("notes.md");
+//! lark::editor::set_theme("nord");
+//!
+//! let line = lark::editor::cursor_line();
+//! lark::editor::set_line(line, lark::editor::get_line(line).to_upper());
+//! ```
+//!
+//! `open` and `set_theme` are queued and applied once the script returns.
+//! The buffer functions work on the focused pane while a command or hook
+//! runs; lines and columns count from 0.
+
+use std::path::PathBuf;
+
+use rhai::EvalAltResult;
+
+use super::{EditorHandle, Requests, ScriptRequest};
+use crate::editor::Pane;
+
+/// Create the editor module, queueing operations into `requests` and editing
+/// the pane lent through `pane`
+pub fn create_module(requests: Requests, pane: EditorHandle) -> rhai::Module {
+    let mut module = rhai::Module::new();
+
+    // open(path: &str)
+    {
+        let r = requests.clone();
+        module.set_native_fn("open", move |path: &str| {
+            r.borrow_mut()
+                .push(ScriptRequest::OpenFile(PathBuf::from(path)));
+            Ok(())
+        });
+    }
+
+    // set_theme(name: &str)
+    {
+        let r = requests.clone();
+        module.set_native_fn("set_theme", move |name: &str| {
+            r.borrow_mut()
+                .push(ScriptRequest::SetTheme(name.to_string()));
+            Ok(())
+        });
+    }
+
+    // line_count() -> i64
+    {
+        let p = pane.clone();
+        module.set_native_fn("line_count", move || {
+            with_pane(&p, |pane| Ok(pane.buffer.line_count() as i64))
+        });
+    }
+
+    // get_line(n: i64) -> String, without the line break
+    {
+        let p = pane.clone();
+        module.set_native_fn("get_line", move |n: i64| {
+            with_pane(&p, |pane| {
+                let line = line_index(pane, n)?;
+                let text = pane.buffer.line(line).to_string();
+                Ok(text.trim_end_matches('\n').to_string())
+            })
+        });
+    }
+
+    // set_line(n: i64, text: &str)
+    {
+        let p = pane.clone();
+        module.set_native_fn("set_line", move |n: i64, text: &str| {
+            with_pane(&p, |pane| {
+                writable(pane)?;
+                let line = line_index(pane, n)?;
+                pane.delete_range((line, 0), (line, pane.buffer.line_len(line)));
+                pane.insert_text(line, 0, text);
+                pane.goto_line(pane.cursor.line);
+                Ok(())
+            })
+        });
+    }
+
+    // cursor_line() -> i64
+    {
+        let p = pane.clone();
+        module.set_native_fn("cursor_line", move || {
+            with_pane(&p, |pane| Ok(pane.cursor.line as i64))
+        });
+    }
+
+    // cursor_col() -> i64
+    {
+        let p = pane.clone();
+        module.set_native_fn("cursor_col", move || {
+            with_pane(&p, |pane| Ok(pane.cursor.col as i64))
+        });
+    }
+
+    // insert_text(text: &str) - at the cursor, which moves past it
+    {
+        let p = pane.clone();
+        module.set_native_fn("insert_text", move |text: &str| {
+            with_pane(&p, |pane| {
+                writable(pane)?;
+                let (line, col) = (pane.cursor.line, pane.cursor.col);
+                pane.insert_text(line, col, text);
+                match text.rfind('\n') {
+                    Some(i) => {
+                        pane.cursor.line = line + text.matches('\n').count();
+                        pane.cursor.col = text[i + 1..].chars().count();
+                    }
+                    None => pane.cursor.col = col + text.chars().count(),
+                }
+                Ok(())
+            })
+        });
+    }
+
+    module
+}
+
+/// Run `f` on the lent pane, failing when no script command or hook is running
+fn with_pane<T>(
+    handle: &EditorHandle,
+    f: impl FnOnce(&mut Pane) -> Result<T, String>,
+) -> Result<T, Box<EvalAltResult>> {
+    match handle.borrow_mut().as_mut() {
+        Some(pane) => f(pane).map_err(Into::into),
+        None => Err("No buffer: editor functions work in commands and hooks".into()),
+    }
+}
+
+fn writable(pane: &Pane) -> Result<(), String> {
+    if pane.buffer.is_read_only() {
+        return Err("Buffer is read-only".to_string());
+    }
+    Ok(())
+}
+
+fn line_index(pane: &Pane, n: i64) -> Result<usize, String> {
+    let count = pane.buffer.line_count();
+    usize::try_from(n)
+        .ok()
+        .filter(|&line| line < count)
+        .ok_or_else(|| format!("Line {} out of range (0..{})", n, count))
+}
@@ -0,0 +1,47 @@
+//! lark::events - Callbacks for editor lifecycle events
+//!
+//! Usage in Rhai:
+//! ```rhai
+//! lark::events::on("save", |path| {
+//!     print(`wrote ${path}`);
+//! });
+//! ```
+//!
+//! Events, each passing the file path to the callback:
+//! - `open` - a file was read from disk into a buffer
+//! - `save` - a buffer was written with `:w`
+
+use rhai::FnPtr;
+use rhai::plugin::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Event names accepted by `on`
+pub const EVENTS: &[&str] = &["open", "save"];
+
+/// Registered callbacks, in registration order
+pub type Hooks = Rc<RefCell<Vec<(String, FnPtr)>>>;
+
+/// Create the events module, registering callbacks into `hooks`
+pub fn create_module(hooks: Hooks) -> rhai::Module {
+    let mut module = rhai::Module::new();
+
+    // on(event: &str, callback: Fn)
+    module.set_native_fn(
+        "on",
+        move |event: &str, callback: FnPtr| -> Result<(), Box<EvalAltResult>> {
+            if !EVENTS.contains(&event) {
+                return Err(format!(
+                    "Unknown event: {} (expected one of: {})",
+                    event,
+                    EVENTS.join(", ")
+                )
+                .into());
+            }
+            hooks.borrow_mut().push((event.to_string(), callback));
+            Ok(())
+        },
+    );
+
+    module
+}
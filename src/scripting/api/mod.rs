@@ -2,10 +2,32 @@
 //!
 //! Each submodule provides functions under `lark::<module>::*`
 
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::editor::Pane;
+
 pub mod config;
+pub mod editor;
+pub mod events;
+pub mod theme;
+pub mod ui;
 // Future modules:
-// pub mod editor;
-// pub mod ui;
 // pub mod fs;
 // pub mod process;
-// pub mod events;
+
+/// Something a script asked the editor to do, applied after the script returns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptRequest {
+    Message(String),
+    Error(String),
+    OpenFile(PathBuf),
+    SetTheme(String),
+}
+
+/// Requests queued by the running script
+pub type Requests = Rc<RefCell<Vec<ScriptRequest>>>;
+
+/// The focused pane, lent to the engine while a command or hook runs
+pub type EditorHandle = Rc<RefCell<Option<Pane>>>;
@@ -0,0 +1,126 @@
+//! lark::theme - Define custom themes from scripts
+//!
+//! Usage in Rhai:
+//! ```rhai
+//! lark::theme::define("mytheme", #{
+//!     base: "nord",
+//!     background: "#101010",
+//!     keyword: #{ fg: "#ff0000", bold: true },
+//!     comment: "#606060",
+//! });
+//! lark::config::set_theme("mytheme");
+//! ```
+//!
+//! Keys not given are inherited from `base` (default gruvbox-dark).
+//!
+//! Color keys (hex strings), one per `Theme` field:
+//! - editor: `background`, `foreground`, `cursor`, `selection`
+//! - gutter: `line_number`, `line_number_active`
+//! - bars: `status_bar_bg`, `status_bar_fg`, `tab_bar_bg`, `tab_bar_fg`,
+//!   `tab_active_bg`, `tab_active_fg`
+//! - file browser: `file_browser_bg`, `file_browser_dir`, `file_browser_file`,
+//!   `file_browser_selected`
+//! - borders: `pane_border`, `pane_border_active`
+//! - diagnostics: `error`, `warning`, `info`, `hint`
+//!
+//! Style keys map to the `syntax_*` fields (`keyword` -> `syntax_keyword`):
+//! `keyword`, `string`, `number`, `comment`, `function`, `type`, `variable`,
+//! `operator`, `punctuation`, `property`, `constant`, `namespace`, `parameter`,
+//! `label`. A style is a hex string (foreground only) or a
+//! map with any of `fg`, `bg`, `bold`, `italic`.
+
+use rhai::plugin::*;
+
+use crate::theme::{self, Color, Style, Theme};
+
+/// Create the theme module
+pub fn create_module() -> rhai::Module {
+    let mut module = rhai::Module::new();
+
+    // define(name: &str, fields: Map)
+    module.set_native_fn(
+        "define",
+        |name: &str, fields: rhai::Map| -> Result<(), Box<EvalAltResult>> {
+            let theme = build_theme(name, &fields)?;
+            theme::register_theme(theme);
+            Ok(())
+        },
+    );
+
+    // list() -> Array
+    module.set_native_fn("list", || -> Result<rhai::Array, Box<EvalAltResult>> {
+        Ok(theme::list_themes()
+            .into_iter()
+            .map(rhai::Dynamic::from)
+            .collect())
+    });
+
+    module
+}
+
+/// Build a theme from a Rhai map, filling unset fields from the base theme
+fn build_theme(name: &str, fields: &rhai::Map) -> Result<Theme, String> {
+    let base_name = match fields.get("base") {
+        Some(base) => base
+            .clone()
+            .into_string()
+            .map_err(|_| "`base` must be a string".to_string())?,
+        None => "gruvbox-dark".to_string(),
+    };
+    let mut theme = theme::base_theme(&base_name)
+        .ok_or_else(|| format!("Unknown base theme: {}", base_name))?;
+    theme.name = name.to_string();
+
+    for (key, value) in fields {
+        let key = key.as_str();
+        if key == "base" {
+            continue;
+        }
+        if let Some(color) = theme.color_mut(key) {
+            *color = parse_color(key, value)?;
+        } else if let Some(style) = theme.style_mut(key) {
+            *style = parse_style(key, value, *style)?;
+        } else {
+            return Err(format!("Unknown theme key: {}", key));
+        }
+    }
+
+    Ok(theme)
+}
+
+fn parse_color(key: &str, value: &Dynamic) -> Result<Color, String> {
+    value
+        .read_lock::<rhai::ImmutableString>()
+        .and_then(|s| Color::from_hex(&s))
+        .ok_or_else(|| format!("`{}` must be a hex color like \"#rrggbb\"", key))
+}
+
+fn parse_style(key: &str, value: &Dynamic, base: Style) -> Result<Style, String> {
+    if value.is_string() {
+        return Ok(Style::new(parse_color(key, value)?));
+    }
+    let map = value
+        .read_lock::<rhai::Map>()
+        .ok_or_else(|| format!("`{}` must be a hex color or a map", key))?;
+
+    let mut style = base;
+    for (attr, v) in map.iter() {
+        let field = format!("{}.{}", key, attr);
+        match attr.as_str() {
+            "fg" => style.fg = parse_color(&field, v)?,
+            "bg" => style.bg = Some(parse_color(&field, v)?),
+            "bold" => {
+                style.bold = v
+                    .as_bool()
+                    .map_err(|_| format!("`{}` must be a bool", field))?
+            }
+            "italic" => {
+                style.italic = v
+                    .as_bool()
+                    .map_err(|_| format!("`{}` must be a bool", field))?
+            }
+            _ => return Err(format!("Unknown style attribute: {}", field)),
+        }
+    }
+    Ok(style)
+}
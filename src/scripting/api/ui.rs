@@ -0,0 +1,38 @@
+//! lark::ui - Status line output from scripts
+//!
+//! Usage in Rhai:
+//! ```rhai
+//! lark::ui::message("Hello!");
+//! lark::ui::error("Something went wrong");
+//! ```
+//!
+//! Output shows once the script returns and goes away on the next keypress,
+//! like the editor's own messages. Errors can span several lines.
+
+use super::{Requests, ScriptRequest};
+
+/// Create the ui module, queueing output into `requests`
+pub fn create_module(requests: Requests) -> rhai::Module {
+    let mut module = rhai::Module::new();
+
+    // message(text: &str)
+    {
+        let r = requests.clone();
+        module.set_native_fn("message", move |text: &str| {
+            r.borrow_mut()
+                .push(ScriptRequest::Message(text.to_string()));
+            Ok(())
+        });
+    }
+
+    // error(text: &str)
+    {
+        let r = requests.clone();
+        module.set_native_fn("error", move |text: &str| {
+            r.borrow_mut().push(ScriptRequest::Error(text.to_string()));
+            Ok(())
+        });
+    }
+
+    module
+}
@@ -2,21 +2,33 @@
 //!
 //! Provides the `lark` namespace with all editor APIs:
 //! - `lark::config::*` - configuration and settings
-//! - `lark::editor::*` - buffer/cursor operations (future)
-//! - `lark::ui::*` - UI elements like popups (future)
+//! - `lark::theme::*` - custom theme definitions
+//! - `lark::events::*` - lifecycle callbacks (`on("save", |path| ...)`)
+//! - `lark::editor::*` - buffer and cursor access, opening files, the theme
+//! - `lark::ui::*` - status line messages
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
-use rhai::{AST, Engine, Scope};
+use rhai::{AST, Dynamic, Engine, EvalAltResult, FnPtr, FuncArgs, Scope};
 
-use super::api;
+use super::api::config::Commands;
+use super::api::events::Hooks;
+use super::api::{self, EditorHandle, Requests, ScriptRequest};
 use crate::config::Settings;
+use crate::editor::Pane;
+
+/// What `:config` starts a new config file with: the documented example
+const CONFIG_TEMPLATE: &str = include_str!("../../examples/init.rhai");
 
 /// The main scripting engine for Lark
 pub struct ScriptEngine {
     engine: Engine,
     settings: Arc<RwLock<Settings>>,
+    hooks: Hooks,
+    commands: Commands,
+    requests: Requests,
+    pane: EditorHandle,
     ast: Option<AST>,
 }
 
@@ -24,17 +36,37 @@ impl ScriptEngine {
     /// Create a new script engine with fresh settings
     pub fn new() -> Self {
         let settings = Arc::new(RwLock::new(Settings::default()));
-        let engine = Self::create_engine(Arc::clone(&settings));
+        let hooks = Hooks::default();
+        let commands = Commands::default();
+        let requests = Requests::default();
+        let pane = EditorHandle::default();
+        let engine = Self::create_engine(
+            Arc::clone(&settings),
+            hooks.clone(),
+            commands.clone(),
+            requests.clone(),
+            pane.clone(),
+        );
 
         Self {
             engine,
             settings,
+            hooks,
+            commands,
+            requests,
+            pane,
             ast: None,
         }
     }
 
     /// Create the Rhai engine with the `lark` namespace
-    fn create_engine(settings: Arc<RwLock<Settings>>) -> Engine {
+    fn create_engine(
+        settings: Arc<RwLock<Settings>>,
+        hooks: Hooks,
+        commands: Commands,
+        requests: Requests,
+        pane: EditorHandle,
+    ) -> Engine {
         let mut engine = Engine::new();
 
         // Safety limits
@@ -45,12 +77,21 @@ impl ScriptEngine {
         let mut lark_module = rhai::Module::new();
 
         // Register lark::config submodule
-        let config_module = api::config::create_module(Arc::clone(&settings));
+        let config_module = api::config::create_module(Arc::clone(&settings), commands);
         lark_module.set_sub_module("config", config_module);
 
+        // Register lark::theme submodule
+        lark_module.set_sub_module("theme", api::theme::create_module());
+
+        // Register lark::events submodule
+        lark_module.set_sub_module("events", api::events::create_module(hooks));
+
+        // Register lark::editor and lark::ui submodules
+        let editor_module = api::editor::create_module(requests.clone(), pane);
+        lark_module.set_sub_module("editor", editor_module);
+        lark_module.set_sub_module("ui", api::ui::create_module(requests));
+
         // Future: Register other submodules
-        // lark_module.set_sub_module("editor", api::editor::create_module(...));
-        // lark_module.set_sub_module("ui", api::ui::create_module(...));
         // lark_module.set_sub_module("fs", api::fs::create_module(...));
 
         // Register `lark` as a static module (accessible as lark::*)
@@ -75,20 +116,102 @@ impl ScriptEngine {
 
     /// Evaluate a Rhai script string
     pub fn eval(&mut self, script: &str) -> Result<(), String> {
-        let ast = self
-            .engine
-            .compile(script)
-            .map_err(|e| format!("Script parse error: {}", e))?;
+        let ast = self.engine.compile(script).map_err(|e| {
+            let error = EvalAltResult::from(e);
+            format!("Script parse error{}", describe_error(script, error))
+        })?;
 
         let mut scope = Scope::new();
         self.engine
             .run_ast_with_scope(&mut scope, &ast)
-            .map_err(|e| format!("Script error: {}", e))?;
+            .map_err(|e| format!("Script error{}", describe_error(script, *e)))?;
 
         self.ast = Some(ast);
         Ok(())
     }
 
+    /// Run the callbacks registered for `event` with the file path.
+    /// Every callback runs; the first error is returned.
+    pub fn fire(&self, event: &str, path: &Path) -> Result<(), String> {
+        // Collect first so a callback registering another can't hit a borrowed RefCell
+        let callbacks: Vec<_> = self
+            .hooks
+            .borrow()
+            .iter()
+            .filter(|(name, _)| name == event)
+            .map(|(_, callback)| callback.clone())
+            .collect();
+
+        let path = path.to_string_lossy().to_string();
+        let mut result = Ok(());
+        for callback in callbacks {
+            if let Err(e) = self.call(&callback, (path.clone(),))
+                && result.is_ok()
+            {
+                result = Err(format!("Error in {} hook: {}", event, e));
+            }
+        }
+        result
+    }
+
+    /// Names of the `:` commands defined with `lark::config::command`
+    pub fn command_names(&self) -> Vec<String> {
+        self.commands.borrow().keys().cloned().collect()
+    }
+
+    /// Run a custom command, passing `args` if its callback takes a parameter
+    pub fn run_command(&self, name: &str, args: &str) -> Result<(), String> {
+        let Some(callback) = self.commands.borrow().get(name).cloned() else {
+            return Err(format!("Unknown command: {}", name));
+        };
+        let result = if self.takes_no_args(&callback) {
+            self.call(&callback, ())
+        } else {
+            self.call(&callback, (args.to_string(),))
+        };
+        result.map_err(|e| format!("Error in :{}: {}", name, e))
+    }
+
+    /// Whether `callback` is a function in the loaded config with no
+    /// parameters besides those curried into it. Decided before calling, so a
+    /// callback is never run twice to find out.
+    fn takes_no_args(&self, callback: &FnPtr) -> bool {
+        let Some(ast) = self.ast.as_ref() else {
+            return false;
+        };
+        let curried = callback.curry().len();
+        let arities: Vec<usize> = ast
+            .iter_functions()
+            .filter(|f| f.name == callback.fn_name())
+            .map(|f| f.params.len().saturating_sub(curried))
+            .collect();
+        !arities.is_empty() && !arities.contains(&1)
+    }
+
+    /// Operations the scripts run so far asked the editor to do
+    pub fn take_requests(&self) -> Vec<ScriptRequest> {
+        std::mem::take(&mut *self.requests.borrow_mut())
+    }
+
+    /// Give scripts the focused pane to work on through `lark::editor`
+    pub fn lend_pane(&self, pane: Pane) {
+        *self.pane.borrow_mut() = Some(pane);
+    }
+
+    /// Take back the pane given to `lend_pane`
+    pub fn take_pane(&self) -> Option<Pane> {
+        self.pane.borrow_mut().take()
+    }
+
+    /// Call a script callback with the functions of the loaded config in scope
+    fn call(&self, callback: &FnPtr, args: impl FuncArgs) -> Result<(), Box<EvalAltResult>> {
+        let empty = AST::empty();
+        let ast = self.ast.as_ref().unwrap_or(&empty);
+        callback
+            .call::<Dynamic>(&self.engine, ast, args)
+            .map(|_| ())
+    }
+
     /// Get the current settings (cloned)
     pub fn settings(&self) -> Settings {
         self.settings.read().map(|s| s.clone()).unwrap_or_default()
@@ -110,6 +233,18 @@ impl ScriptEngine {
         Self::config_dir().map(|p| p.join("init.rhai"))
     }
 
+    /// Write the example config to `path` unless there's a file there
+    /// already, making its directory if need be
+    pub fn create_config_file(path: &Path) -> std::io::Result<()> {
+        if path.exists() {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, CONFIG_TEMPLATE)
+    }
+
     /// Load the default config file if it exists
     pub fn load_default(&mut self) -> Result<(), String> {
         if let Some(config_file) = Self::config_file() {
@@ -121,6 +256,35 @@ impl ScriptEngine {
     }
 }
 
+/// `error` with the line it's on and that line of `script`, a caret under
+/// the column, to follow "Script error", as in:
+///
+/// ```text
+///  on line 3: Function not found: set_tabwidth (i64)
+///  3 | lark::config::set_tabwidth(4);
+///    |               ^
+/// ```
+fn describe_error(script: &str, mut error: EvalAltResult) -> String {
+    let position = error.take_position();
+    let Some(line) = position.line() else {
+        return format!(": {}", error);
+    };
+    let source = script.lines().nth(line - 1).unwrap_or("");
+    let number = line.to_string();
+    let mut text = format!(" on line {}: {}\n {} | {}", line, error, number, source);
+    if let Some(col) = position.position() {
+        // Keep tabs so the caret lines up under them
+        let indent: String = source
+            .chars()
+            .take(col - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let gutter = " ".repeat(number.len());
+        text.push_str(&format!("\n {} | {}^", gutter, indent));
+    }
+    text
+}
+
 impl Default for ScriptEngine {
     fn default() -> Self {
         Self::new()
@@ -168,6 +332,29 @@ mod tests {
                 lark::config::set_tab_width(4);
                 lark::config::set_relative_line_numbers(false);
                 lark::config::set_auto_indent(true);
+                lark::config::set_detect_indent(false);
+                lark::config::set_cursor_line(true);
+                lark::config::set_color_column(100);
+                lark::config::set_pane_status(false);
+                lark::config::set_git_signs(false);
+                lark::config::set_auto_reload(false);
+                lark::config::set_scroll_indicator(false);
+                lark::config::set_scroll_off(3);
+                lark::config::set_file_browser("bottom", 0.3);
+                lark::config::set_side_scroll_off(-1);
+                lark::config::set_open_binary(true);
+                lark::config::set_large_file_size(1024);
+                lark::config::set_auto_install_grammars(true);
+                lark::config::set_mouse(false);
+                lark::config::set_auto_pairs(false);
+                lark::config::set_smart_home(true);
+                lark::config::set_ignore_case(true);
+                lark::config::set_smart_case(true);
+                lark::config::set_leader(",");
+                lark::config::set_timeout_ms(500);
+                lark::config::set_clipboard("system");
+                lark::config::set_preview_command("head {file}");
+                lark::config::set_finder_root("cwd");
             "#,
             )
             .unwrap();
@@ -177,6 +364,348 @@ mod tests {
         assert_eq!(settings.tab_width, 4);
         assert!(!settings.relative_line_numbers);
         assert!(settings.auto_indent);
+        assert!(!settings.detect_indent);
+        assert!(settings.cursor_line);
+        assert_eq!(settings.color_column, 100);
+        assert!(!settings.pane_status);
+        assert!(!settings.git_signs);
+        assert!(!settings.auto_reload);
+        assert!(!settings.scroll_indicator);
+        assert_eq!(settings.scroll_off, 3);
+        assert_eq!(settings.file_browser_edge, crate::editor::Edge::Bottom);
+        assert_eq!(settings.file_browser_ratio, 0.3);
+        assert_eq!(settings.side_scroll_off, 0);
+        assert!(settings.open_binary);
+        assert_eq!(settings.large_file_size, 1024);
+        assert!(settings.auto_install_grammars);
+        assert!(!settings.mouse);
+        assert!(!settings.auto_pairs);
+        assert!(settings.smart_home);
+        assert!(settings.ignore_case);
+        assert!(settings.smart_case);
+        assert_eq!(settings.leader, ',');
+        assert_eq!(settings.timeout_ms, 500);
+
+        assert!(settings.system_clipboard);
+        assert_eq!(settings.preview_command, "head {file}");
+        assert!(settings.finder_from_cwd);
+
+        assert!(engine.eval(r#"lark::config::set_leader("ab");"#).is_err());
+        assert!(
+            engine
+                .eval(r#"lark::config::set_clipboard("x11");"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_lark_theme_define() {
+        let mut engine = ScriptEngine::new();
+        engine
+            .eval(
+                r##"
+                lark::theme::define("script-test", #{
+                    base: "nord",
+                    background: "#101010",
+                    keyword: #{ fg: "#ff0000", italic: true },
+                });
+                lark::config::set_theme("script-test");
+            "##,
+            )
+            .unwrap();
+
+        let theme = crate::theme::find_theme("script-test").unwrap();
+        let nord = crate::theme::Theme::nord();
+        assert_eq!(theme.background, crate::theme::Color::rgb(16, 16, 16));
+        assert_eq!(theme.syntax_keyword.fg, crate::theme::Color::RED);
+        assert!(theme.syntax_keyword.italic);
+        assert_eq!(theme.foreground, nord.foreground);
+        assert!(crate::theme::list_themes().contains(&"script-test".to_string()));
+    }
+
+    #[test]
+    fn test_lark_theme_define_rejects_unknown_keys() {
+        let mut engine = ScriptEngine::new();
+        let result = engine.eval(r##"lark::theme::define("bad", #{ backgrond: "#000000" });"##);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_lark_config_set_formatter() {
+        let mut engine = ScriptEngine::new();
+        engine
+            .eval(
+                r#"
+                lark::config::set_formatter("rust", "rustfmt --edition 2024");
+                lark::config::set_format_on_save(true);
+                lark::config::set_trim_trailing_whitespace(true);
+                lark::config::set_ensure_final_newline(true);
+            "#,
+            )
+            .unwrap();
+        let settings = engine.settings();
+        assert_eq!(
+            settings.formatters.get(&crate::syntax::Language::Rust),
+            Some(&"rustfmt --edition 2024".to_string())
+        );
+        assert!(settings.format_on_save);
+        assert!(settings.trim_trailing_whitespace);
+        assert!(settings.ensure_final_newline);
+
+        let result = engine.eval(r#"lark::config::set_formatter("cobol", "fmt");"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_lark_config_set_comment_token() {
+        use crate::syntax::Language;
+
+        let mut engine = ScriptEngine::new();
+        engine
+            .eval(r#"lark::config::set_comment_token("c", "/* */");"#)
+            .unwrap();
+        let settings = engine.settings();
+        assert_eq!(
+            settings.comment_token(Language::C),
+            Some("/* */".to_string())
+        );
+        assert_eq!(
+            settings.comment_token(Language::Lua),
+            Some("--".to_string())
+        );
+        assert_eq!(settings.comment_token(Language::Json), None);
+
+        assert!(
+            engine
+                .eval(r#"lark::config::set_comment_token("cobol", "*");"#)
+                .is_err()
+        );
+        assert!(
+            engine
+                .eval(r#"lark::config::set_comment_token("c", " ");"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn config_files_are_created_from_the_example_once() {
+        let dir = std::env::temp_dir().join(format!("lark-config-{}", std::process::id()));
+        let path = dir.join("lark").join("init.rhai");
+        ScriptEngine::create_config_file(&path).unwrap();
+        let template = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, "// mine\n").unwrap();
+        ScriptEngine::create_config_file(&path).unwrap();
+        let kept = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(template, CONFIG_TEMPLATE);
+        assert_eq!(kept, "// mine\n");
+        // The template is a config that loads
+        assert!(ScriptEngine::new().eval(CONFIG_TEMPLATE).is_ok());
+    }
+
+    #[test]
+    fn errors_point_at_the_line_they_are_on() {
+        let mut engine = ScriptEngine::new();
+        let error = engine
+            .eval("lark::config::set_theme(\"nord\");\n\tlark::config::set_tabwidth(4);\n")
+            .unwrap_err();
+        assert_eq!(
+            error,
+            [
+                "Script error on line 2: Function not found: lark::config::set_tabwidth (i64)",
+                " 2 | \tlark::config::set_tabwidth(4);",
+                "   | \t              ^", // Under the function name
+            ]
+            .join("\n")
+        );
+
+        let error = engine.eval("let x = ;").unwrap_err();
+        assert!(error.starts_with("Script parse error on line 1: "));
+        assert!(error.ends_with("\n 1 | let x = ;\n   |         ^"));
+    }
+
+    #[test]
+    fn test_lark_config_set_indent() {
+        use crate::syntax::Language;
+
+        let mut engine = ScriptEngine::new();
+        engine
+            .eval(r#"lark::config::set_indent("go", #{ width: 8 });"#)
+            .unwrap();
+        engine
+            .eval(r#"lark::config::set_indent("rust", #{ use_tabs: true, width: 2 });"#)
+            .unwrap();
+        let settings = engine.settings();
+        let go = settings.indent(Language::Go);
+        assert!(go.use_tabs); // Kept from Go's own convention
+        assert_eq!(go.width, 8);
+        assert_eq!(settings.indent(Language::Rust).unit(), "\t");
+        assert_eq!(settings.indent(Language::Rust).width, 2);
+        assert_eq!(settings.indent(Language::C).unit(), "    ");
+
+        assert!(
+            engine
+                .eval(r#"lark::config::set_indent("go", #{ tabs: true });"#)
+                .is_err()
+        );
+        assert!(
+            engine
+                .eval(r#"lark::config::set_indent("go", #{ width: "4" });"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_lark_events_fire_hooks() {
+        let mut engine = ScriptEngine::new();
+        engine
+            .eval(
+                r#"
+                fn on_open(path) {
+                    lark::config::set_theme(`opened ${path}`);
+                }
+                lark::events::on("save", |path| lark::config::set_theme(`saved ${path}`));
+                lark::events::on("open", Fn("on_open"));
+            "#,
+            )
+            .unwrap();
+
+        engine.fire("save", Path::new("a.rs")).unwrap();
+        assert_eq!(engine.settings().theme, "saved a.rs");
+        engine.fire("open", Path::new("b.rs")).unwrap();
+        assert_eq!(engine.settings().theme, "opened b.rs");
+    }
+
+    #[test]
+    fn test_lark_events_errors() {
+        let mut engine = ScriptEngine::new();
+        assert!(engine.eval(r#"lark::events::on("sav", |p| 1);"#).is_err());
+
+        engine
+            .eval(r#"lark::events::on("save", |path| throw "boom");"#)
+            .unwrap();
+        let err = engine.fire("save", Path::new("a.rs")).unwrap_err();
+        assert!(err.contains("save hook") && err.contains("boom"));
+        assert!(engine.fire("open", Path::new("a.rs")).is_ok());
+    }
+
+    #[test]
+    fn test_lark_config_command() {
+        let mut engine = ScriptEngine::new();
+        engine
+            .eval(
+                r#"
+                lark::config::command("greet", |args| lark::ui::message(`Hello, ${args}!`));
+                lark::config::command("notes", || lark::editor::open("notes.md"));
+                lark::config::command("W", "w");
+            "#,
+            )
+            .unwrap();
+
+        let mut names = engine.command_names();
+        names.sort();
+        assert_eq!(names, vec!["greet", "notes"]);
+        assert_eq!(
+            engine.settings().command_aliases.get("W"),
+            Some(&"w".to_string())
+        );
+
+        engine.run_command("greet", "World").unwrap();
+        engine.run_command("notes", "").unwrap();
+        assert_eq!(
+            engine.take_requests(),
+            vec![
+                ScriptRequest::Message("Hello, World!".to_string()),
+                ScriptRequest::OpenFile(PathBuf::from("notes.md")),
+            ]
+        );
+        assert!(engine.take_requests().is_empty());
+
+        // Whether args are passed is decided up front, counting captured
+        // variables out, so a failing callback isn't run again without them
+        engine
+            .eval(
+                r#"
+                let suffix = "!";
+                lark::config::command("shout", |args| lark::ui::message(args + suffix));
+                fn tally(args) { lark::ui::message(args); missing(); }
+                fn tally() { lark::ui::message("again"); }
+                lark::config::command("tally", Fn("tally"));
+            "#,
+            )
+            .unwrap();
+        engine.run_command("shout", "hey").unwrap();
+        assert!(engine.run_command("tally", "once").is_err());
+        assert_eq!(
+            engine.take_requests(),
+            vec![
+                ScriptRequest::Message("hey!".to_string()),
+                ScriptRequest::Message("once".to_string())
+            ]
+        );
+
+        assert!(engine.run_command("nope", "").is_err());
+        assert!(
+            engine
+                .eval(r#"lark::config::command("two words", "w");"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_lark_ui_output_is_queued() {
+        let mut engine = ScriptEngine::new();
+        engine
+            .eval(
+                r#"
+                lark::events::on("save", |path| {
+                    lark::ui::message(`Saved ${path}`);
+                    lark::ui::error("line 1\nline 2");
+                });
+            "#,
+            )
+            .unwrap();
+        assert!(engine.take_requests().is_empty());
+
+        engine.fire("save", Path::new("a.rs")).unwrap();
+        assert_eq!(
+            engine.take_requests(),
+            vec![
+                ScriptRequest::Message("Saved a.rs".to_string()),
+                ScriptRequest::Error("line 1\nline 2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lark_editor_edits_the_lent_pane() {
+        let mut engine = ScriptEngine::new();
+        engine
+            .eval(
+                r#"
+                lark::config::command("shout", || {
+                    let line = lark::editor::cursor_line();
+                    lark::editor::set_line(line, lark::editor::get_line(line).to_upper());
+                    lark::editor::insert_text(`${lark::editor::line_count()}:`);
+                });
+            "#,
+            )
+            .unwrap();
+
+        // Editor functions need a pane
+        assert!(engine.eval("lark::editor::line_count();").is_err());
+
+        let mut pane = Pane::new_editor(0);
+        pane.buffer = crate::editor::Buffer::from_text("one\ntwo\n");
+        pane.cursor.line = 1;
+        engine.lend_pane(pane);
+        engine.run_command("shout", "").unwrap();
+        let pane = engine.take_pane().unwrap();
+
+        assert_eq!(pane.buffer.text(), "one\n3:TWO\n");
+        assert_eq!((pane.cursor.line, pane.cursor.col), (1, 2));
+        assert!(pane.buffer.is_dirty());
     }
 
     #[test]
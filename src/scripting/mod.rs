@@ -2,12 +2,15 @@
 //!
 //! All editor functions are exposed under the `lark` namespace:
 //! - `lark::config::*` - settings, themes, keybinds
-//! - `lark::editor::*` - buffer operations, cursor, mode (future)
-//! - `lark::ui::*` - popups, windows, messages (future)
+//! - `lark::theme::*` - custom theme definitions
+//! - `lark::events::*` - callbacks for open/save
+//! - `lark::editor::*` - editor operations (open a file, set the theme)
+//! - `lark::ui::*` - status line messages
 //! - `lark::fs::*` - file operations (future)
 //! - `lark::process::*` - spawn commands (future)
 
 mod api;
 mod engine;
 
+pub use api::ScriptRequest;
 pub use engine::ScriptEngine;
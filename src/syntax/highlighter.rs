@@ -1,18 +1,35 @@
 //! Syntax highlighter using Tree-sitter
 
+use std::collections::HashSet;
 use std::path::Path;
-use tree_sitter::{Parser, Tree};
+
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{InputEdit, Parser, Point, Query, QueryCursor, Tree};
 
 use super::languages::{Language, LanguageRegistry};
 
 /// A highlight span within a line
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Highlight {
     pub start: usize, // Column start (byte offset within line)
     pub end: usize,   // Column end (byte offset within line)
     pub kind: HighlightKind,
 }
 
+/// A position in the source used to describe edits for incremental parsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditPoint {
+    pub byte: usize,   // Byte offset in the whole source
+    pub row: usize,    // Line number
+    pub column: usize, // Byte offset within the line
+}
+
+impl EditPoint {
+    fn point(&self) -> Point {
+        Point::new(self.row, self.column)
+    }
+}
+
 /// Types of syntax elements for highlighting
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HighlightKind {
@@ -34,6 +51,35 @@ pub enum HighlightKind {
 }
 
 impl HighlightKind {
+    /// Map a highlight query capture name (`keyword`, `function.method`, …) to a highlight kind
+    pub fn from_capture_name(name: &str) -> Self {
+        match name {
+            "variable.parameter" | "parameter" => return HighlightKind::Parameter,
+            "variable.builtin" => return HighlightKind::Keyword,
+            _ => {}
+        }
+
+        // Otherwise only the top-level group matters
+        match name.split('.').next().unwrap_or(name) {
+            "keyword" | "include" | "conditional" | "repeat" | "exception" | "storageclass" => {
+                HighlightKind::Keyword
+            }
+            "string" | "character" | "escape" => HighlightKind::String,
+            "number" | "float" => HighlightKind::Number,
+            "comment" => HighlightKind::Comment,
+            "function" | "method" => HighlightKind::Function,
+            "type" | "constructor" | "tag" | "attribute" => HighlightKind::Type,
+            "variable" => HighlightKind::Variable,
+            "operator" => HighlightKind::Operator,
+            "punctuation" => HighlightKind::Punctuation,
+            "property" | "field" => HighlightKind::Property,
+            "constant" | "boolean" => HighlightKind::Constant,
+            "module" | "namespace" => HighlightKind::Namespace,
+            "label" => HighlightKind::Label,
+            _ => HighlightKind::Default,
+        }
+    }
+
     /// Map a Tree-sitter node type to a highlight kind
     pub fn from_node_type(node_type: &str, lang: Language) -> Self {
         // Try language-specific patterns first (they're more accurate)
@@ -267,8 +313,16 @@ impl HighlightKind {
     }
 }
 
+/// A definition in the parse tree (or found by keyword), for `:symbols`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub line: usize,
+    pub kind: String, // "function", "struct", "class", ...
+    pub name: String,
+}
+
 /// A line with its syntax highlights
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HighlightedLine {
     pub highlights: Vec<Highlight>,
 }
@@ -297,14 +351,27 @@ impl Default for HighlightedLine {
     }
 }
 
+/// Byte offsets of each `\n` in `source`
+fn newline_offsets(source: &str) -> impl Iterator<Item = usize> + '_ {
+    source
+        .bytes()
+        .enumerate()
+        .filter(|&(_, b)| b == b'\n')
+        .map(|(i, _)| i)
+}
+
 /// Syntax highlighter for a buffer
 pub struct Highlighter {
     parser: Parser,
     tree: Option<Tree>,
     language: Language,
     registry: LanguageRegistry,
+    query: Option<Query>, // The grammar's highlights.scm, if installed
     line_highlights: Vec<HighlightedLine>,
+    line_starts: Vec<usize>, // Byte offset of each line in the last parsed source
+    source: String,          // Reused by `parse_chunks` to avoid an allocation per parse
     load_error: Option<String>,
+    query_error: Option<String>, // Why the highlights query was ignored, until taken
 }
 
 impl Highlighter {
@@ -315,8 +382,12 @@ impl Highlighter {
             tree: None,
             language: Language::Unknown,
             registry: LanguageRegistry::new(),
+            query: None,
             line_highlights: Vec::new(),
+            line_starts: Vec::new(),
+            source: String::new(),
             load_error: None,
+            query_error: None,
         }
     }
 
@@ -325,15 +396,26 @@ impl Highlighter {
     pub fn set_language(&mut self, lang: Language) -> bool {
         if lang == Language::Unknown {
             self.tree = None;
+            self.query = None;
             self.language = lang;
             self.line_highlights.clear();
             self.load_error = None;
+            self.query_error = None;
             return true;
         }
 
-        match self.registry.load(lang) {
+        match self.registry.load(lang).cloned() {
             Some(ts_lang) => {
-                if self.parser.set_language(ts_lang).is_ok() {
+                if self.parser.set_language(&ts_lang).is_ok() {
+                    let query = self
+                        .registry
+                        .highlights_query(lang)
+                        .map(|source| Self::compile_query(&ts_lang, &source, lang))
+                        .transpose();
+                    (self.query, self.query_error) = match query {
+                        Ok(query) => (query, None),
+                        Err(e) => (None, Some(e)),
+                    };
                     self.language = lang;
                     self.tree = None;
                     self.line_highlights.clear();
@@ -345,6 +427,7 @@ impl Highlighter {
             }
             None => {
                 // Store the fact that we tried but couldn't load
+                self.query = None;
                 self.language = lang; // Set the language even if we can't load grammar
                 self.load_error = Some(format!("Grammar for {} not loaded", lang.name()));
             }
@@ -352,12 +435,34 @@ impl Highlighter {
         false
     }
 
+    /// Compile a highlights query; on error highlighting falls back to node-type heuristics
+    fn compile_query(
+        ts_lang: &tree_sitter::Language,
+        source: &str,
+        lang: Language,
+    ) -> Result<Query, String> {
+        Query::new(ts_lang, source)
+            .map_err(|e| format!("Ignoring highlights query for {}: {}", lang.name(), e))
+    }
+
+    /// Why the language's highlights query didn't compile, if it didn't (once)
+    pub fn take_query_error(&mut self) -> Option<String> {
+        self.query_error.take()
+    }
+
     /// Set language from file path
     pub fn set_language_from_path(&mut self, path: &Path) -> bool {
         let lang = Language::from_path(path);
         self.set_language(lang)
     }
 
+    /// Whether highlighting failed because the language's grammar isn't installed
+    pub fn grammar_missing(&self) -> bool {
+        self.language != Language::Unknown
+            && self.load_error.is_some()
+            && !self.registry.is_installed(self.language)
+    }
+
     /// Get the current language
     pub fn language(&self) -> Language {
         self.language
@@ -389,14 +494,22 @@ impl Highlighter {
             }
         } else {
             format!(
-                "Language: {}, {} lines highlighted",
+                "Language: {}, {} lines highlighted ({})",
                 self.language.name(),
-                self.line_highlights.len()
+                self.line_highlights.len(),
+                if self.query.is_some() {
+                    "highlights.scm"
+                } else {
+                    "heuristics"
+                }
             )
         }
     }
 
     /// Parse the given source code
+    ///
+    /// Reuses the previous tree when edits have been recorded on it with
+    /// [`Highlighter::edit`], so only the changed regions are re-parsed.
     pub fn parse(&mut self, source: &str) {
         if self.language == Language::Unknown {
             self.line_highlights.clear();
@@ -405,27 +518,45 @@ impl Highlighter {
 
         self.tree = self.parser.parse(source, self.tree.as_ref());
 
-        // Clone the tree to avoid borrow checker issues
-        if let Some(tree) = self.tree.clone() {
+        // Lend the tree out rather than cloning it, so `self` can be mutated
+        if let Some(tree) = self.tree.take() {
             self.build_highlights(source, &tree);
+            self.tree = Some(tree);
         }
     }
 
-    /// Update highlights after an edit (incremental parsing)
-    pub fn update(
-        &mut self,
-        source: &str,
-        _start_byte: usize,
-        _old_end_byte: usize,
-        _new_end_byte: usize,
-    ) {
-        if self.language == Language::Unknown {
-            return;
-        }
+    /// Parse source given in pieces (a rope's chunks), copying it into a buffer
+    /// that's kept between parses
+    pub fn parse_chunks<'a>(&mut self, chunks: impl Iterator<Item = &'a str>) {
+        let mut source = std::mem::take(&mut self.source);
+        source.clear();
+        chunks.for_each(|chunk| source.push_str(chunk));
+        self.parse(&source);
+        self.source = source;
+    }
 
-        // For now, just do a full reparse
-        // TODO: Implement proper incremental parsing with tree.edit()
-        self.parse(source);
+    /// Forget the previous tree so the next parse starts from scratch
+    ///
+    /// Needed after buffer changes that weren't recorded with [`Highlighter::edit`].
+    pub fn invalidate(&mut self) {
+        self.tree = None;
+    }
+
+    /// Record an edit on the current tree (incremental parsing)
+    ///
+    /// `start..old_end` in the old text was replaced by `start..new_end`.
+    /// Highlights are refreshed on the next call to [`Highlighter::parse`].
+    pub fn edit(&mut self, start: EditPoint, old_end: EditPoint, new_end: EditPoint) {
+        if let Some(ref mut tree) = self.tree {
+            tree.edit(&InputEdit {
+                start_byte: start.byte,
+                old_end_byte: old_end.byte,
+                new_end_byte: new_end.byte,
+                start_position: start.point(),
+                old_end_position: old_end.point(),
+                new_end_position: new_end.point(),
+            });
+        }
     }
 
     /// Get highlights for a specific line
@@ -433,6 +564,87 @@ impl Highlighter {
         self.line_highlights.get(line)
     }
 
+    /// Line ranges of multi-line nodes worth folding (functions, blocks, …),
+    /// one per start line, ordered by start line
+    pub fn fold_ranges(&self) -> Vec<(usize, usize)> {
+        let Some(ref tree) = self.tree else {
+            return Vec::new();
+        };
+
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            let start = node.start_position().row;
+            // A node ending at column 0 only reaches the previous line
+            let end = match node.end_position() {
+                p if p.column == 0 => p.row.saturating_sub(1),
+                p => p.row,
+            };
+            if end > start && node.is_named() && is_foldable_node(node.kind()) {
+                // Nodes are visited in start order, so a range on the same line is the last one
+                match ranges.last_mut() {
+                    Some(range) if range.0 == start => range.1 = range.1.max(end),
+                    _ => ranges.push((start, end)),
+                }
+            }
+
+            // Depth-first walk without recursion
+            if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                continue;
+            }
+            loop {
+                if !cursor.goto_parent() {
+                    return ranges;
+                }
+                if cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// The language's definitions (`Language::definition_kinds`) in `source`,
+    /// the text the tree was parsed from, in the order they appear
+    pub fn symbols(&self, source: &str) -> Vec<Symbol> {
+        let Some(ref tree) = self.tree else {
+            return Vec::new();
+        };
+        let kinds = self.language.definition_kinds();
+
+        let mut symbols = Vec::new();
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            // `struct foo x;` in C only uses the struct; its definition has a body
+            let declares =
+                !node.kind().ends_with("_specifier") || node.child_by_field_name("body").is_some();
+            if kinds.contains(&node.kind())
+                && declares
+                && let Some(name) = definition_name(node, source)
+            {
+                symbols.push(Symbol {
+                    line: node.start_position().row,
+                    kind: symbol_kind(node.kind()).to_string(),
+                    name,
+                });
+            }
+
+            // Depth-first walk without recursion
+            if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                continue;
+            }
+            loop {
+                if !cursor.goto_parent() {
+                    return symbols;
+                }
+                if cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+        }
+    }
+
     /// Debug: dump node types for the first N lines
     pub fn debug_tree(&self, max_lines: usize) -> String {
         let Some(ref tree) = self.tree else {
@@ -523,21 +735,106 @@ impl Highlighter {
 
     /// Build highlights from the parse tree
     fn build_highlights(&mut self, source: &str, tree: &Tree) {
-        // Count lines
-        let line_count = source.lines().count().max(1);
-        self.line_highlights = vec![HighlightedLine::new(); line_count];
-
         // Calculate line start offsets
-        let mut line_starts: Vec<usize> = vec![0];
-        for (i, c) in source.char_indices() {
-            if c == '\n' {
-                line_starts.push(i + 1);
+        let mut line_starts = std::mem::take(&mut self.line_starts);
+        line_starts.clear();
+        line_starts.push(0);
+        line_starts.extend(newline_offsets(source).map(|i| i + 1));
+
+        // One entry per buffer line, including the empty line after a trailing
+        // newline; lines are cleared in place so their allocations are reused
+        self.line_highlights
+            .resize_with(line_starts.len(), HighlightedLine::new);
+        for line in &mut self.line_highlights {
+            line.highlights.clear();
+        }
+
+        if self.query.is_some() {
+            self.collect_query_highlights(source, tree, &line_starts);
+        } else {
+            // Walk the tree and collect highlights
+            let mut cursor = tree.walk();
+            self.walk_tree_with_parent(&mut cursor, source, &line_starts, None);
+        }
+        self.line_starts = line_starts;
+    }
+
+    /// Collect highlights from the captures of the grammar's highlights query
+    fn collect_query_highlights(&mut self, source: &str, tree: &Tree, line_starts: &[usize]) {
+        let Some(query) = self.query.take() else {
+            return;
+        };
+        let kinds: Vec<HighlightKind> = query
+            .capture_names()
+            .iter()
+            .map(|name| HighlightKind::from_capture_name(name))
+            .collect();
+
+        // When several patterns capture the same node, the first one wins
+        let mut seen: HashSet<(usize, usize)> = HashSet::new();
+        let mut cursor = QueryCursor::new();
+        let mut captures = cursor.captures(&query, tree.root_node(), source.as_bytes());
+        while let Some((m, index)) = captures.next() {
+            let capture = m.captures[*index];
+            let node = capture.node;
+            let kind = kinds[capture.index as usize];
+            if kind == HighlightKind::Default || !seen.insert((node.start_byte(), node.end_byte()))
+            {
+                continue;
             }
+            self.push_span(&node, kind, source, line_starts);
         }
 
-        // Walk the tree and collect highlights
-        let mut cursor = tree.walk();
-        self.walk_tree_with_parent(&mut cursor, source, &line_starts, None);
+        // Nested captures (an escape inside a string) should win over their parent,
+        // and `kind_at` takes the first span that contains a column
+        for line in &mut self.line_highlights {
+            line.highlights.sort_by_key(|h| h.end - h.start);
+        }
+
+        self.query = Some(query);
+    }
+
+    /// Add a highlight for a node to each line it spans
+    fn push_span(
+        &mut self,
+        node: &tree_sitter::Node,
+        kind: HighlightKind,
+        source: &str,
+        line_starts: &[usize],
+    ) {
+        let start_byte = node.start_byte();
+        let end_byte = node.end_byte();
+        let start_line = node.start_position().row;
+        let end_line = node.end_position().row;
+
+        for line in start_line..=end_line {
+            if line >= self.line_highlights.len() {
+                break;
+            }
+
+            let line_start = line_starts.get(line).copied().unwrap_or(0);
+            let line_end = line_starts.get(line + 1).copied().unwrap_or(source.len());
+
+            let highlight_start = if line == start_line {
+                start_byte.saturating_sub(line_start)
+            } else {
+                0
+            };
+
+            let highlight_end = if line == end_line {
+                end_byte.saturating_sub(line_start)
+            } else {
+                line_end.saturating_sub(line_start)
+            };
+
+            if highlight_start < highlight_end {
+                self.line_highlights[line].highlights.push(Highlight {
+                    start: highlight_start,
+                    end: highlight_end,
+                    kind,
+                });
+            }
+        }
     }
 
     /// Determine highlight kind considering parent context
@@ -675,40 +972,7 @@ impl Highlighter {
             if kind != HighlightKind::Default
                 && (node.child_count() == 0 || is_highlightable_parent(node_kind))
             {
-                let start_byte = node.start_byte();
-                let end_byte = node.end_byte();
-                let start_line = node.start_position().row;
-                let end_line = node.end_position().row;
-
-                // Add highlight to each line the node spans
-                for line in start_line..=end_line {
-                    if line >= self.line_highlights.len() {
-                        break;
-                    }
-
-                    let line_start = line_starts.get(line).copied().unwrap_or(0);
-                    let line_end = line_starts.get(line + 1).copied().unwrap_or(source.len());
-
-                    let highlight_start = if line == start_line {
-                        start_byte.saturating_sub(line_start)
-                    } else {
-                        0
-                    };
-
-                    let highlight_end = if line == end_line {
-                        end_byte.saturating_sub(line_start)
-                    } else {
-                        line_end.saturating_sub(line_start)
-                    };
-
-                    if highlight_start < highlight_end {
-                        self.line_highlights[line].highlights.push(Highlight {
-                            start: highlight_start,
-                            end: highlight_end,
-                            kind,
-                        });
-                    }
-                }
+                self.push_span(&node, kind, source, line_starts);
             }
 
             // Recurse into children with current node as parent
@@ -731,6 +995,76 @@ impl Default for Highlighter {
     }
 }
 
+/// A definition's name: its `name` field, the innermost `declarator` (C's
+/// `int *main(void)`), or for Rust impls the type (and trait)
+fn definition_name(node: tree_sitter::Node, source: &str) -> Option<String> {
+    let text = |node: tree_sitter::Node| source.get(node.byte_range()).map(str::to_string);
+    if let Some(name) = node.child_by_field_name("name") {
+        return text(name);
+    }
+    if let Some(mut declarator) = node.child_by_field_name("declarator") {
+        while let Some(inner) = declarator.child_by_field_name("declarator") {
+            declarator = inner;
+        }
+        return text(declarator);
+    }
+    let ty = text(node.child_by_field_name("type")?)?;
+    match node.child_by_field_name("trait").and_then(text) {
+        Some(name) => Some(format!("{} for {}", name, ty)),
+        None => Some(ty),
+    }
+}
+
+/// What a definition node is, without its grammar's suffix: `function_item`
+/// is a function, `class_declaration` a class
+fn symbol_kind(node_type: &str) -> &str {
+    let kind = [
+        "_item",
+        "_definition",
+        "_declaration",
+        "_specifier",
+        "_spec",
+    ]
+    .iter()
+    .find_map(|suffix| node_type.strip_suffix(suffix))
+    .unwrap_or(node_type);
+    match kind {
+        "function_signature" => "function",
+        "type_alias" => "type",
+        "singleton_method" => "method",
+        "generator_function" => "function",
+        "abstract_class" => "class",
+        kind => kind,
+    }
+}
+
+/// Check if a multi-line node of this type should be foldable
+fn is_foldable_node(node_type: &str) -> bool {
+    [
+        "function",
+        "method",
+        "block",
+        "body",
+        "class",
+        "impl",
+        "struct",
+        "enum",
+        "trait",
+        "mod",
+        "interface",
+        "object",
+        "array",
+        "table",
+        "comment",
+        "match",
+        "switch",
+        "element",
+        "section",
+    ]
+    .iter()
+    .any(|kind| node_type.contains(kind))
+}
+
 /// Check if a parent node type should be highlighted as a whole
 fn is_highlightable_parent(node_type: &str) -> bool {
     matches!(
@@ -754,6 +1088,35 @@ fn is_highlightable_parent(node_type: &str) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_symbols_rust() {
+        let mut highlighter = Highlighter::new();
+        // Grammar may not be installed, so set_language may return false
+        let set_ok = highlighter.set_language(Language::Rust);
+
+        let source = "struct Point;\n\nimpl Display for Point {\n    fn fmt(&self) {}\n}\n";
+        highlighter.parse(source);
+
+        if set_ok {
+            let symbols: Vec<_> = highlighter
+                .symbols(source)
+                .into_iter()
+                .map(|s| (s.line, s.kind, s.name))
+                .collect();
+            assert_eq!(
+                symbols,
+                [
+                    (0, "struct".to_string(), "Point".to_string()),
+                    (2, "impl".to_string(), "Display for Point".to_string()),
+                    (3, "function".to_string(), "fmt".to_string()),
+                ]
+            );
+        }
+        assert_eq!(symbol_kind("function_signature_item"), "function");
+        assert_eq!(symbol_kind("class_declaration"), "class");
+        assert_eq!(symbol_kind("method"), "method");
+    }
+
     #[test]
     fn test_highlighter_rust() {
         let mut highlighter = Highlighter::new();
@@ -774,6 +1137,77 @@ mod tests {
         }
     }
 
+    /// Point at a byte offset of `source` (test helper)
+    fn point_at(source: &str, byte: usize) -> EditPoint {
+        let before = &source[..byte];
+        let row = before.matches('\n').count();
+        let column = byte - before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        EditPoint { byte, row, column }
+    }
+
+    #[test]
+    fn test_incremental_edits_match_full_parse() {
+        let mut highlighter = Highlighter::new();
+        if !highlighter.set_language(Language::Rust) {
+            return; // Grammar not installed
+        }
+
+        let mut source = String::from("fn main() {\n    let x = 42;\n}\n");
+        highlighter.parse(&source);
+
+        // Type a new line of code one character at a time
+        let insert_at = source.find('}').unwrap();
+        for (i, ch) in "let s = \"hi\";\n".chars().enumerate() {
+            let start = point_at(&source, insert_at + i);
+            source.insert(start.byte, ch);
+            let new_end = point_at(&source, start.byte + ch.len_utf8());
+            highlighter.edit(start, start, new_end);
+            highlighter.parse(&source);
+        }
+
+        // Then delete the `42` literal, batching the edit before one parse
+        let byte = source.find("42").unwrap();
+        let (start, old_end) = (point_at(&source, byte), point_at(&source, byte + 2));
+        source.replace_range(byte..byte + 2, "");
+        highlighter.edit(start, old_end, start);
+        highlighter.parse(&source);
+
+        let mut fresh = Highlighter::new();
+        fresh.set_language(Language::Rust);
+        fresh.parse(&source);
+
+        assert_eq!(highlighter.line_highlights, fresh.line_highlights);
+        let line2 = highlighter.line_highlights(2).unwrap();
+        assert_eq!(line2.kind_at(0), HighlightKind::Keyword); // `let`
+        assert_eq!(line2.kind_at(8), HighlightKind::String); // `"hi"`
+    }
+
+    #[test]
+    fn test_line_highlights_track_line_count() {
+        let mut highlighter = Highlighter::new();
+        if !highlighter.set_language(Language::Rust) {
+            return; // Grammar not installed
+        }
+
+        let mut source = String::from("fn main() {}\n");
+        highlighter.parse(&source);
+        assert_eq!(highlighter.highlight_count(), 2);
+
+        // Insert two newlines inside the braces
+        for _ in 0..2 {
+            let start = point_at(&source, 11);
+            source.insert(11, '\n');
+            highlighter.edit(start, start, point_at(&source, 12));
+        }
+        highlighter.parse(&source);
+
+        assert_eq!(highlighter.highlight_count(), 4);
+        assert_eq!(
+            highlighter.line_highlights(2).unwrap().kind_at(0),
+            HighlightKind::Punctuation
+        );
+    }
+
     #[test]
     fn test_highlighter_unknown_language() {
         let mut highlighter = Highlighter::new();
@@ -807,4 +1241,74 @@ mod tests {
             HighlightKind::Number
         );
     }
+
+    #[test]
+    fn test_fold_ranges_cover_multiline_items() {
+        let mut highlighter = Highlighter::new();
+        if !highlighter.set_language(Language::Rust) {
+            return; // Grammar not installed
+        }
+
+        highlighter.parse("fn main() {\n    if x {\n        y();\n    }\n}\nstruct S;\n");
+        assert_eq!(highlighter.fold_ranges(), vec![(0, 4), (1, 3)]);
+    }
+
+    #[test]
+    fn test_highlight_kind_from_capture_name() {
+        assert_eq!(
+            HighlightKind::from_capture_name("keyword"),
+            HighlightKind::Keyword
+        );
+        assert_eq!(
+            HighlightKind::from_capture_name("function.method"),
+            HighlightKind::Function
+        );
+        assert_eq!(
+            HighlightKind::from_capture_name("variable.parameter"),
+            HighlightKind::Parameter
+        );
+        assert_eq!(
+            HighlightKind::from_capture_name("_private"),
+            HighlightKind::Default
+        );
+    }
+
+    #[test]
+    fn test_query_highlights_prefer_first_pattern_and_inner_captures() {
+        let mut highlighter = Highlighter::new();
+        if !highlighter.set_language(Language::Rust) {
+            return; // Grammar not installed
+        }
+        let ts_lang = highlighter.registry.load(Language::Rust).cloned().unwrap();
+        let query = r#"
+            "let" @keyword
+            (escape_sequence) @string.escape
+            (string_literal) @string
+            (identifier) @variable.parameter
+            (identifier) @function
+        "#;
+        highlighter.query = Highlighter::compile_query(&ts_lang, query, Language::Rust).ok();
+        assert!(highlighter.query.is_some());
+
+        highlighter.parse("let x = \"a\\n\";\n");
+        let line = highlighter.line_highlights(0).unwrap();
+        assert_eq!(line.kind_at(0), HighlightKind::Keyword);
+        assert_eq!(line.kind_at(4), HighlightKind::Parameter); // first pattern for `x`
+        assert_eq!(line.kind_at(8), HighlightKind::String);
+        assert_eq!(line.kind_at(10), HighlightKind::String); // the `\n` escape
+        assert_eq!(line.kind_at(13), HighlightKind::Default); // `;` isn't captured
+    }
+
+    #[test]
+    fn test_broken_query_is_reported_not_printed() {
+        let mut highlighter = Highlighter::new();
+        if !highlighter.set_language(Language::Rust) {
+            return; // Grammar not installed
+        }
+        let ts_lang = highlighter.registry.load(Language::Rust).cloned().unwrap();
+        let err = Highlighter::compile_query(&ts_lang, "(no_such_node) @keyword", Language::Rust)
+            .err()
+            .unwrap();
+        assert!(err.starts_with("Ignoring highlights query for Rust: "));
+    }
 }
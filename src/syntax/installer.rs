@@ -5,10 +5,99 @@
 
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::OnceLock;
+use std::sync::mpsc::Sender;
+
+use tokio::sync::mpsc::UnboundedSender;
 
 use super::languages::Language;
 use super::metadata::GrammarMetadata;
 
+/// Progress from a background grammar install (see `spawn_install`)
+#[derive(Debug)]
+pub enum InstallEvent {
+    Progress(Language, String),
+    Finished(Language, InstallResult),
+}
+
+/// A batch of grammars to install, and where to report on them
+type InstallJob = (Vec<Language>, UnboundedSender<InstallEvent>);
+
+/// Queue grammars to install one after another on the install worker, reporting
+/// each one's progress and result on `events`
+///
+/// All installs share a single worker thread, so two batches never write
+/// `metadata.json` or check out the same repository (TypeScript and TSX share
+/// one) at the same time.
+pub fn spawn_install(langs: Vec<Language>, events: UnboundedSender<InstallEvent>) {
+    static QUEUE: OnceLock<Sender<InstallJob>> = OnceLock::new();
+    let queue = QUEUE.get_or_init(|| {
+        let (tx, rx) = std::sync::mpsc::channel::<InstallJob>();
+        std::thread::spawn(move || {
+            for (langs, events) in rx {
+                let mut installer = GrammarInstaller::new();
+                for lang in langs {
+                    let result = installer.install_with_progress(lang, &|stage| {
+                        let _ = events.send(InstallEvent::Progress(lang, stage.to_string()));
+                    });
+                    let _ = events.send(InstallEvent::Finished(lang, result));
+                }
+            }
+        });
+        tx
+    });
+    let _ = queue.send((langs, events));
+}
+
+/// A git command's failure as an install error, with what it printed
+fn git_result(command: &str, output: &std::process::Output) -> Result<(), String> {
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(format!(
+        "{} failed with exit code {:?}: {}",
+        command,
+        output.status.code(),
+        stderr.trim()
+    ))
+}
+
+/// Run the C compiler, failing with exactly what it printed
+#[cfg(not(target_os = "windows"))]
+fn run_compiler(mut command: Command) -> Result<(), String> {
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run compiler: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(format!(
+        "Compilation failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+/// Directories to search for a grammar's headers. `tree_sitter/parser.h` and
+/// scanner helpers live next to `parser.c`, but grammars that share a scanner
+/// between dialects (TypeScript's `common/`) or generate files into the repo
+/// root include from outside `src`.
+fn include_dirs(repo_dir: &Path, src_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![src_dir.to_path_buf()];
+    let candidates = [
+        src_dir.parent().map(Path::to_path_buf),
+        Some(repo_dir.join("src")),
+        Some(repo_dir.join("common")),
+        Some(repo_dir.to_path_buf()),
+    ];
+    for dir in candidates.into_iter().flatten() {
+        if dir.is_dir() && !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
 /// Result of a grammar installation
 #[derive(Debug)]
 pub enum InstallResult {
@@ -62,7 +151,7 @@ impl GrammarInstaller {
                 let lib_path = self.library_path(name);
                 let _ = std::fs::remove_file(&lib_path);
             }
-            match self.install_internal(lang, true) {
+            match self.install_internal(lang, true, &|_| {}) {
                 InstallResult::Success => InstallResult::Reinstalled,
                 other => other,
             }
@@ -71,13 +160,22 @@ impl GrammarInstaller {
         }
     }
 
-    /// Install a grammar
-    pub fn install(&mut self, lang: Language) -> InstallResult {
-        self.install_internal(lang, false)
+    /// Install a grammar, calling `progress` as each stage starts
+    pub fn install_with_progress(
+        &mut self,
+        lang: Language,
+        progress: &dyn Fn(&str),
+    ) -> InstallResult {
+        self.install_internal(lang, false, progress)
     }
 
     /// Internal install implementation
-    fn install_internal(&mut self, lang: Language, force: bool) -> InstallResult {
+    fn install_internal(
+        &mut self,
+        lang: Language,
+        force: bool,
+        progress: &dyn Fn(&str),
+    ) -> InstallResult {
         let grammar_name = match lang.grammar_name() {
             Some(name) => name,
             None => return InstallResult::Error("Unknown language".to_string()),
@@ -102,44 +200,62 @@ impl GrammarInstaller {
             return InstallResult::Error(format!("Failed to create cache directory: {}", e));
         }
 
-        // Clone or update the repository
+        // Clone or update the repository, at the pinned tag if there is one
         let repo_dir = self.cache_dir.join(grammar_name);
+        let rev = lang.grammar_rev();
+        // Output is captured rather than inherited so it can't draw over the editor
         if repo_dir.exists() {
-            // Pull latest
-            let status = Command::new("git")
-                .args(["pull", "--depth=1"])
+            progress(&format!("Updating {}…", grammar_name));
+            // Fetch and check out the revision, so a clone made at another tag
+            // (or on a branch) moves to this one
+            let fetched = Command::new("git")
+                .args(["fetch", "--depth=1", "origin", rev.unwrap_or("HEAD")])
                 .current_dir(&repo_dir)
-                .status();
-
-            if let Err(e) = status {
-                return InstallResult::Error(format!("Failed to update repository: {}", e));
+                .output()
+                .map_err(|e| format!("Failed to update repository: {}", e))
+                .and_then(|o| git_result("git fetch", &o));
+            let checked_out = fetched.and_then(|_| {
+                Command::new("git")
+                    .args(["checkout", "--force", "FETCH_HEAD"])
+                    .current_dir(&repo_dir)
+                    .output()
+                    .map_err(|e| format!("Failed to update repository: {}", e))
+                    .and_then(|o| git_result("git checkout", &o))
+            });
+            if let Err(e) = checked_out {
+                return InstallResult::Error(e);
             }
         } else {
-            // Clone
             let url = format!("https://github.com/{}.git", repo);
-            let status = Command::new("git")
-                .args(["clone", "--depth=1", &url])
+            progress(&format!("Cloning {}…", grammar_name));
+            let mut clone = Command::new("git");
+            clone.args(["clone", "--depth=1"]);
+            if let Some(rev) = rev {
+                clone.args(["--branch", rev]);
+            }
+            let cloned = clone
+                .arg(&url)
                 .arg(&repo_dir)
-                .status();
-
-            match status {
-                Ok(s) if s.success() => {}
-                Ok(s) => {
-                    return InstallResult::Error(format!(
-                        "git clone failed with exit code: {:?}",
-                        s.code()
-                    ));
-                }
-                Err(e) => {
-                    return InstallResult::Error(format!("Failed to clone repository: {}", e));
-                }
+                .output()
+                .map_err(|e| format!("Failed to clone repository: {}", e))
+                .and_then(|o| git_result("git clone", &o));
+            if let Err(e) = cloned {
+                return InstallResult::Error(e);
             }
         }
+        let commit = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(&repo_dir)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
 
         // Regenerate the grammar to ensure ABI compatibility
+        progress("Generating…");
         if let Err(e) = self.regenerate_grammar(&repo_dir, lang) {
             // Not fatal - try to compile with existing files
-            eprintln!("[syntax] Warning: Could not regenerate grammar: {}", e);
+            progress(&format!("Warning: Could not regenerate grammar: {}", e));
         }
 
         // Find the source directory (some repos have src/ in root, some in subdirs)
@@ -152,10 +268,18 @@ impl GrammarInstaller {
         }
 
         // Compile the grammar
-        match self.compile_grammar(grammar_name, &src_dir) {
+        progress("Compiling…");
+        match self.compile_grammar(grammar_name, &repo_dir, &src_dir) {
             Ok(_) => {
-                // Record in metadata
-                self.metadata.record_install(lang);
+                // Highlighting falls back to heuristics without the query, so don't fail over it
+                if let Err(e) = self.install_highlights_query(grammar_name, &repo_dir, lang) {
+                    progress(&format!("Warning: {}", e));
+                }
+
+                // Record in metadata, re-read first so installs made elsewhere since
+                // this installer loaded it aren't overwritten
+                self.metadata = GrammarMetadata::load();
+                self.metadata.record_install(lang, commit);
                 if let Err(e) = self.metadata.save() {
                     eprintln!("[syntax] Warning: Failed to save metadata: {}", e);
                 }
@@ -165,6 +289,34 @@ impl GrammarInstaller {
         }
     }
 
+    /// Copy the grammar's `queries/highlights.scm` next to the compiled library
+    fn install_highlights_query(
+        &self,
+        name: &str,
+        repo_dir: &Path,
+        lang: Language,
+    ) -> Result<(), String> {
+        // The TypeScript queries only extend the JavaScript ones, so they're
+        // no use on their own; those languages keep the heuristic highlighting
+        if matches!(lang, Language::TypeScript | Language::Tsx) {
+            return Ok(());
+        }
+
+        let source = repo_dir.join("queries").join("highlights.scm");
+        if !source.exists() {
+            return Ok(());
+        }
+
+        let dest = self.highlights_query_path(name);
+        if let Some(dir) = dest.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create queries directory: {}", e))?;
+        }
+        std::fs::copy(&source, &dest)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to copy highlights query: {}", e))
+    }
+
     /// Regenerate the grammar using tree-sitter CLI
     fn regenerate_grammar(&self, repo_dir: &Path, lang: Language) -> Result<(), String> {
         // Check if tree-sitter CLI is available
@@ -225,11 +377,33 @@ impl GrammarInstaller {
             return grammar_subdir;
         }
 
-        standard
+        // Or in one named after the language (tree-sitter-markdown/src, not
+        // the tree-sitter-markdown-inline grammar beside it)
+        if let Some(name) = lang.grammar_name() {
+            for dir in [format!("tree-sitter-{}", name), name.to_string()] {
+                let named = repo_dir.join(dir).join("src");
+                if named.join("parser.c").exists() {
+                    return named;
+                }
+            }
+        }
+
+        // Failing that, the first subdirectory with a parser, in name order
+        let mut subdirs: Vec<PathBuf> = std::fs::read_dir(repo_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path().join("src"))
+            .collect();
+        subdirs.sort();
+        subdirs
+            .into_iter()
+            .find(|dir| dir.join("parser.c").exists())
+            .unwrap_or(standard)
     }
 
     /// Compile a grammar to a dynamic library
-    fn compile_grammar(&self, name: &str, src_dir: &Path) -> Result<(), String> {
+    fn compile_grammar(&self, name: &str, repo_dir: &Path, src_dir: &Path) -> Result<(), String> {
         let parser_c = src_dir.join("parser.c");
         let scanner_c = src_dir.join("scanner.c");
         let scanner_cc = src_dir.join("scanner.cc");
@@ -239,128 +413,102 @@ impl GrammarInstaller {
         }
 
         let lib_path = self.library_path(name);
+        let includes = include_dirs(repo_dir, src_dir);
 
         // Compile using cc
         #[cfg(target_os = "macos")]
-        let compile_result =
-            self.compile_macos(name, &parser_c, &scanner_c, &scanner_cc, &lib_path);
+        let compile_result = self.compile_macos(
+            name,
+            &includes,
+            &parser_c,
+            &scanner_c,
+            &scanner_cc,
+            &lib_path,
+        );
 
         #[cfg(target_os = "linux")]
-        let compile_result =
-            self.compile_linux(name, &parser_c, &scanner_c, &scanner_cc, &lib_path);
+        let compile_result = self.compile_linux(
+            name,
+            &includes,
+            &parser_c,
+            &scanner_c,
+            &scanner_cc,
+            &lib_path,
+        );
 
         #[cfg(target_os = "windows")]
-        let compile_result =
-            self.compile_windows(name, &parser_c, &scanner_c, &scanner_cc, &lib_path);
-
-        compile_result
+        let compile_result = self.compile_windows(
+            name,
+            &includes,
+            &parser_c,
+            &scanner_c,
+            &scanner_cc,
+            &lib_path,
+        );
+
+        compile_result.map_err(|e| format!("{} (in {})", e, repo_dir.display()))
     }
 
     #[cfg(target_os = "macos")]
     fn compile_macos(
         &self,
         _name: &str,
+        includes: &[PathBuf],
         parser_c: &Path,
         scanner_c: &Path,
         scanner_cc: &Path,
         lib_path: &Path,
     ) -> Result<(), String> {
-        let mut args = vec![
-            "-shared",
-            "-fPIC",
-            "-O2",
-            "-I",
-            parser_c.parent().unwrap().to_str().unwrap(),
-        ];
-
-        let parser_c_str = parser_c.to_str().unwrap();
-        args.push(parser_c_str);
+        let mut command = Command::new("cc");
+        command.args(["-shared", "-fPIC", "-O2"]);
+        for dir in includes {
+            command.arg("-I").arg(dir);
+        }
+        command.arg(parser_c);
 
         // Add scanner if it exists
-        let scanner_c_str;
-        let scanner_cc_str;
         if scanner_c.exists() {
-            scanner_c_str = scanner_c.to_str().unwrap().to_string();
-            args.push(&scanner_c_str);
+            command.arg(scanner_c);
         } else if scanner_cc.exists() {
-            scanner_cc_str = scanner_cc.to_str().unwrap().to_string();
-            args.push(&scanner_cc_str);
-            args.push("-lstdc++");
+            command.arg(scanner_cc).arg("-lstdc++");
         }
 
-        args.push("-o");
-        let lib_path_str = lib_path.to_str().unwrap();
-        args.push(lib_path_str);
-
-        let output = Command::new("cc")
-            .args(&args)
-            .output()
-            .map_err(|e| format!("Failed to run compiler: {}", e))?;
-
-        if output.status.success() {
-            Ok(())
-        } else {
-            Err(format!(
-                "Compilation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ))
-        }
+        command.arg("-o").arg(lib_path);
+        run_compiler(command)
     }
 
     #[cfg(target_os = "linux")]
     fn compile_linux(
         &self,
         _name: &str,
+        includes: &[PathBuf],
         parser_c: &Path,
         scanner_c: &Path,
         scanner_cc: &Path,
         lib_path: &Path,
     ) -> Result<(), String> {
-        let mut args = vec![
-            "-shared",
-            "-fPIC",
-            "-O2",
-            "-I",
-            parser_c.parent().unwrap().to_str().unwrap(),
-        ];
-
-        let parser_c_str = parser_c.to_str().unwrap();
-        args.push(parser_c_str);
-
-        let scanner_c_str;
-        let scanner_cc_str;
+        let mut command = Command::new("cc");
+        command.args(["-shared", "-fPIC", "-O2"]);
+        for dir in includes {
+            command.arg("-I").arg(dir);
+        }
+        command.arg(parser_c);
+
         if scanner_c.exists() {
-            scanner_c_str = scanner_c.to_str().unwrap().to_string();
-            args.push(&scanner_c_str);
+            command.arg(scanner_c);
         } else if scanner_cc.exists() {
-            scanner_cc_str = scanner_cc.to_str().unwrap().to_string();
-            args.push(&scanner_cc_str);
-            args.push("-lstdc++");
+            command.arg(scanner_cc).arg("-lstdc++");
         }
 
-        args.push("-o");
-        let lib_path_str = lib_path.to_str().unwrap();
-        args.push(lib_path_str);
-
-        let output = Command::new("cc")
-            .args(&args)
-            .output()
-            .map_err(|e| format!("Failed to run compiler: {}", e))?;
-
-        if output.status.success() {
-            Ok(())
-        } else {
-            Err(format!(
-                "Compilation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ))
-        }
+        command.arg("-o").arg(lib_path);
+        run_compiler(command)
     }
 
     #[cfg(target_os = "windows")]
     fn compile_windows(
         &self,
         _name: &str,
+        _includes: &[PathBuf],
         _parser_c: &Path,
         _scanner_c: &Path,
         _scanner_cc: &Path,
@@ -381,6 +529,14 @@ impl GrammarInstaller {
         self.grammars_dir.join(format!("lib{}.{}", name, ext))
     }
 
+    /// Get the highlights query path for a grammar
+    fn highlights_query_path(&self, name: &str) -> PathBuf {
+        self.grammars_dir
+            .join("queries")
+            .join(name)
+            .join("highlights.scm")
+    }
+
     /// Uninstall a grammar
     pub fn uninstall(&mut self, lang: Language) -> Result<(), String> {
         let grammar_name = lang
@@ -393,6 +549,12 @@ impl GrammarInstaller {
                 .map_err(|e| format!("Failed to remove grammar: {}", e))?;
         }
 
+        let query_dir = self.grammars_dir.join("queries").join(grammar_name);
+        if query_dir.exists() {
+            std::fs::remove_dir_all(&query_dir)
+                .map_err(|e| format!("Failed to remove highlights query: {}", e))?;
+        }
+
         // Also remove cached source
         let cache_dir = self.cache_dir.join(grammar_name);
         if cache_dir.exists() {
@@ -401,6 +563,7 @@ impl GrammarInstaller {
         }
 
         // Remove from metadata
+        self.metadata = GrammarMetadata::load();
         self.metadata.record_uninstall(lang);
         if let Err(e) = self.metadata.save() {
             eprintln!("[syntax] Warning: Failed to save metadata: {}", e);
@@ -431,3 +594,49 @@ impl Default for GrammarInstaller {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_grammar_named_after_the_language_is_built() {
+        let repo = std::env::temp_dir().join(format!("lark-src-dir-{}", std::process::id()));
+        for grammar in ["tree-sitter-markdown-inline", "tree-sitter-markdown"] {
+            let src = repo.join(grammar).join("src");
+            std::fs::create_dir_all(&src).unwrap();
+            std::fs::write(src.join("parser.c"), "").unwrap();
+        }
+
+        let installer = GrammarInstaller::new();
+        let markdown = installer.find_src_dir(&repo, Language::Markdown);
+        // Anything else takes the first in name order
+        let other = installer.find_src_dir(&repo, Language::Yaml);
+        std::fs::remove_dir_all(&repo).unwrap();
+
+        assert_eq!(markdown, repo.join("tree-sitter-markdown").join("src"));
+        assert_eq!(other, repo.join("tree-sitter-markdown").join("src"));
+    }
+
+    #[test]
+    fn headers_outside_src_are_on_the_include_path() {
+        let repo = std::env::temp_dir().join(format!("lark-include-{}", std::process::id()));
+        let src = repo.join("typescript").join("src");
+        std::fs::create_dir_all(src.join("tree_sitter")).unwrap();
+        std::fs::create_dir_all(repo.join("common")).unwrap();
+
+        let dirs = include_dirs(&repo, &src);
+        std::fs::remove_dir_all(&repo).unwrap();
+
+        // No repo-level src/ here, so it isn't passed
+        assert_eq!(
+            dirs,
+            [
+                src.clone(),
+                repo.join("typescript"),
+                repo.join("common"),
+                repo.clone()
+            ]
+        );
+    }
+}
@@ -58,6 +58,30 @@ impl Language {
         }
     }
 
+    /// Look up a language by the name a user types (`rust`, `js`, `c++`, ...)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "rust" => Some(Language::Rust),
+            "python" => Some(Language::Python),
+            "javascript" | "js" => Some(Language::JavaScript),
+            "typescript" | "ts" => Some(Language::TypeScript),
+            "tsx" => Some(Language::Tsx),
+            "go" => Some(Language::Go),
+            "c" => Some(Language::C),
+            "cpp" | "c++" => Some(Language::Cpp),
+            "json" => Some(Language::Json),
+            "toml" => Some(Language::Toml),
+            "markdown" | "md" => Some(Language::Markdown),
+            "bash" | "sh" => Some(Language::Bash),
+            "lua" => Some(Language::Lua),
+            "ruby" => Some(Language::Ruby),
+            "html" => Some(Language::Html),
+            "css" => Some(Language::Css),
+            "yaml" | "yml" => Some(Language::Yaml),
+            _ => None,
+        }
+    }
+
     /// Detect language from file path
     pub fn from_path(path: &Path) -> Self {
         // Check special filenames first
@@ -102,6 +126,82 @@ impl Language {
         }
     }
 
+    /// Get the line comment token; block-only languages give "open close"
+    pub fn comment_token(&self) -> Option<&'static str> {
+        match self {
+            Language::Rust
+            | Language::JavaScript
+            | Language::TypeScript
+            | Language::Tsx
+            | Language::Go
+            | Language::C
+            | Language::Cpp => Some("//"),
+            Language::Python
+            | Language::Toml
+            | Language::Bash
+            | Language::Ruby
+            | Language::Yaml => Some("#"),
+            Language::Lua => Some("--"),
+            Language::Html | Language::Markdown => Some("<!-- -->"),
+            Language::Css => Some("/* */"),
+            Language::Json | Language::Unknown => None,
+        }
+    }
+
+    /// Parse tree node kinds that define something worth listing in `:symbols`
+    pub fn definition_kinds(&self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &[
+                "function_item",
+                "function_signature_item",
+                "struct_item",
+                "enum_item",
+                "union_item",
+                "trait_item",
+                "impl_item",
+                "mod_item",
+                "type_item",
+                "macro_definition",
+            ],
+            Language::Python => &["function_definition", "class_definition"],
+            Language::JavaScript => &[
+                "function_declaration",
+                "generator_function_declaration",
+                "class_declaration",
+                "method_definition",
+            ],
+            Language::TypeScript | Language::Tsx => &[
+                "function_declaration",
+                "generator_function_declaration",
+                "class_declaration",
+                "abstract_class_declaration",
+                "method_definition",
+                "interface_declaration",
+                "type_alias_declaration",
+                "enum_declaration",
+            ],
+            Language::Go => &["function_declaration", "method_declaration", "type_spec"],
+            Language::C => &["function_definition", "struct_specifier", "enum_specifier"],
+            Language::Cpp => &[
+                "function_definition",
+                "struct_specifier",
+                "enum_specifier",
+                "class_specifier",
+                "namespace_definition",
+            ],
+            Language::Bash => &["function_definition"],
+            Language::Lua => &["function_declaration"],
+            Language::Ruby => &["method", "singleton_method", "class", "module"],
+            Language::Json
+            | Language::Toml
+            | Language::Markdown
+            | Language::Html
+            | Language::Css
+            | Language::Yaml
+            | Language::Unknown => &[],
+        }
+    }
+
     /// Get the grammar name (used for library loading)
     pub fn grammar_name(&self) -> Option<&'static str> {
         match self {
@@ -150,6 +250,30 @@ impl Language {
         }
     }
 
+    /// The release tag a grammar is pinned to, checked out instead of the
+    /// default branch so installs are reproducible. None follows the default branch.
+    pub fn grammar_rev(&self) -> Option<&'static str> {
+        match self {
+            Language::Rust => Some("v0.23.2"),
+            Language::Python => Some("v0.23.6"),
+            Language::JavaScript => Some("v0.23.1"),
+            Language::TypeScript | Language::Tsx => Some("v0.23.2"),
+            Language::Go => Some("v0.23.4"),
+            Language::Cpp => Some("v0.23.4"),
+            Language::Json => Some("v0.24.8"),
+            Language::Bash => Some("v0.23.3"),
+            Language::Ruby => Some("v0.23.1"),
+            Language::Html => Some("v0.23.2"),
+            Language::C
+            | Language::Toml
+            | Language::Markdown
+            | Language::Lua
+            | Language::Css
+            | Language::Yaml
+            | Language::Unknown => None,
+        }
+    }
+
     /// List all installable languages
     pub fn all_installable() -> Vec<Language> {
         vec![
@@ -157,6 +281,7 @@ impl Language {
             Language::Python,
             Language::JavaScript,
             Language::TypeScript,
+            Language::Tsx,
             Language::Go,
             Language::C,
             Language::Cpp,
@@ -240,6 +365,20 @@ impl LanguageRegistry {
         self.grammars_dir.join(format!("lib{}.{}", name, ext))
     }
 
+    /// Get the highlights query path for a grammar
+    fn highlights_query_path(&self, name: &str) -> PathBuf {
+        self.grammars_dir
+            .join("queries")
+            .join(name)
+            .join("highlights.scm")
+    }
+
+    /// Read the grammar's highlights query, if one was installed with it
+    pub fn highlights_query(&self, lang: Language) -> Option<String> {
+        let name = lang.grammar_name()?;
+        std::fs::read_to_string(self.highlights_query_path(name)).ok()
+    }
+
     /// Load a grammar if installed, auto-reinstalling if ABI is outdated
     pub fn load(&mut self, lang: Language) -> Option<&tree_sitter::Language> {
         // Already loaded?
@@ -346,6 +485,25 @@ mod tests {
         assert_eq!(Language::from_extension("xyz"), Language::Unknown);
     }
 
+    #[test]
+    fn test_language_from_name() {
+        assert_eq!(Language::from_name("Rust"), Some(Language::Rust));
+        assert_eq!(Language::from_name("c++"), Some(Language::Cpp));
+        assert_eq!(Language::from_name("yml"), Some(Language::Yaml));
+        assert_eq!(Language::from_name("cobol"), None);
+    }
+
+    #[test]
+    fn installable_languages_are_found_by_name() {
+        for lang in Language::all_installable() {
+            assert_eq!(Language::from_name(lang.name()), Some(lang));
+            assert_eq!(
+                Language::from_name(lang.grammar_name().unwrap()),
+                Some(lang)
+            );
+        }
+    }
+
     #[test]
     fn test_language_from_path() {
         assert_eq!(
@@ -74,20 +74,25 @@ impl GrammarMetadata {
             .unwrap_or_else(|| PathBuf::from("grammars/metadata.json"))
     }
 
-    /// Record that a grammar was installed
-    pub fn record_install(&mut self, lang: Language) {
+    /// Record that a grammar was installed, built from `commit` if known
+    pub fn record_install(&mut self, lang: Language, commit: Option<String>) {
         if let Some(name) = lang.grammar_name() {
             self.grammars.insert(
                 name.to_string(),
                 GrammarInfo {
                     abi_version: TREE_SITTER_ABI_VERSION,
                     installed_at: chrono_lite_now(),
-                    commit: None,
+                    commit,
                 },
             );
         }
     }
 
+    /// The commit an installed grammar was built from, if it was recorded
+    pub fn commit(&self, lang: Language) -> Option<&str> {
+        self.grammars.get(lang.grammar_name()?)?.commit.as_deref()
+    }
+
     /// Record that a grammar was uninstalled
     pub fn record_uninstall(&mut self, lang: Language) {
         if let Some(name) = lang.grammar_name() {
@@ -140,18 +145,21 @@ mod tests {
     #[test]
     fn test_metadata_serialization() {
         let mut metadata = GrammarMetadata::default();
-        metadata.record_install(Language::Rust);
+        metadata.record_install(Language::Rust, Some("0123abc".to_string()));
+        metadata.record_install(Language::Lua, None);
 
         let json = serde_json::to_string(&metadata).unwrap();
         let loaded: GrammarMetadata = serde_json::from_str(&json).unwrap();
 
         assert!(loaded.is_installed(Language::Rust));
+        assert_eq!(loaded.commit(Language::Rust), Some("0123abc"));
+        assert_eq!(loaded.commit(Language::Lua), None);
     }
 
     #[test]
     fn test_needs_reinstall() {
         let mut metadata = GrammarMetadata::default();
-        metadata.record_install(Language::Rust);
+        metadata.record_install(Language::Rust, None);
 
         // Current version should not need reinstall
         assert!(!metadata.needs_reinstall(Language::Rust));
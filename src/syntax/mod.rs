@@ -9,8 +9,8 @@ mod languages;
 mod metadata;
 
 #[allow(unused_imports)] // Will be used when rendering integrates highlighting
-pub use highlighter::{Highlight, HighlightKind, HighlightedLine, Highlighter};
-pub use installer::{GrammarInstaller, InstallResult};
+pub use highlighter::{EditPoint, Highlight, HighlightKind, HighlightedLine, Highlighter, Symbol};
+pub use installer::{GrammarInstaller, InstallEvent, InstallResult, spawn_install};
 pub use languages::{Language, LanguageRegistry};
 #[allow(unused_imports)]
 // GrammarMetadata used internally, TREE_SITTER_ABI_VERSION for :TSStatus
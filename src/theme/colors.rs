@@ -26,6 +26,16 @@ impl Color {
         Some(Self { r, g, b })
     }
 
+    /// Mix towards `other`, where `amount` 0.0 keeps this color and 1.0 gives `other`
+    pub fn blend(&self, other: Color, amount: f32) -> Color {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+        Color::rgb(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+
     /// Convert to crossterm Color
     pub fn to_crossterm(&self) -> crossterm::style::Color {
         crossterm::style::Color::Rgb {
@@ -75,4 +85,11 @@ mod tests {
         assert!(Color::from_hex("fff").is_none());
         assert!(Color::from_hex("gggggg").is_none());
     }
+
+    #[test]
+    fn blend_mixes_channels() {
+        assert_eq!(Color::BLACK.blend(Color::WHITE, 0.0), Color::BLACK);
+        assert_eq!(Color::BLACK.blend(Color::WHITE, 1.0), Color::WHITE);
+        assert_eq!(Color::RED.blend(Color::BLUE, 0.5), Color::rgb(128, 0, 128));
+    }
 }
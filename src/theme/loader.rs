@@ -0,0 +1,169 @@
+//! User themes loaded from `~/.config/lark/themes/<name>.toml`
+//!
+//! A theme file sets any subset of the `Theme` fields; everything else is
+//! inherited from a base theme (`base = "nord"`, default gruvbox-dark).
+//! Syntax styles may drop the `syntax_` prefix (`keyword` = `syntax_keyword`).
+//!
+//! ```toml
+//! base = "nord"
+//! background = "#1e1e2e"
+//! syntax_keyword = { fg = "#cba6f7", bold = true }
+//! syntax_string = "#a6e3a1"
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use super::theme::Style;
+use super::{Color, Theme};
+
+/// Directory user themes are loaded from
+pub fn themes_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".config").join("lark").join("themes"))
+}
+
+/// Names of the themes found in the themes directory, sorted
+pub fn list_user_themes() -> Vec<String> {
+    let Some(dir) = themes_dir() else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("toml"))
+        .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(String::from))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Load a user theme by name from the themes directory
+pub fn load_user_theme(name: &str) -> Result<Theme, String> {
+    let dir = themes_dir().ok_or("Could not find home directory")?;
+    load_from_file(&dir.join(format!("{}.toml", name)))
+}
+
+/// Load a theme from a TOML file; the theme is named after the file stem
+pub fn load_from_file(path: &Path) -> Result<Theme, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("custom");
+
+    parse_theme(name, &content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Parse theme TOML, filling unset fields from the base theme
+pub fn parse_theme(name: &str, content: &str) -> Result<Theme, String> {
+    let table: toml::Table = content.parse().map_err(|e| format!("{}", e))?;
+
+    let base_name = match table.get("base") {
+        Some(toml::Value::String(s)) => s.as_str(),
+        Some(_) => return Err("`base` must be a string".to_string()),
+        None => "gruvbox-dark",
+    };
+    let mut theme =
+        super::base_theme(base_name).ok_or_else(|| format!("Unknown base theme: {}", base_name))?;
+    theme.name = name.to_string();
+
+    for (key, value) in &table {
+        if key == "base" {
+            continue;
+        }
+        apply_field(&mut theme, key, value)?;
+    }
+
+    Ok(theme)
+}
+
+/// Set a single theme field from its TOML value
+fn apply_field(theme: &mut Theme, key: &str, value: &toml::Value) -> Result<(), String> {
+    if let Some(color) = theme.color_mut(key) {
+        *color = parse_color(key, value)?;
+    } else if let Some(style) = theme.style_mut(key) {
+        *style = parse_style(key, value, *style)?;
+    } else {
+        return Err(format!("Unknown theme field: {}", key));
+    }
+    Ok(())
+}
+
+fn parse_color(key: &str, value: &toml::Value) -> Result<Color, String> {
+    value
+        .as_str()
+        .and_then(Color::from_hex)
+        .ok_or_else(|| format!("`{}` must be a hex color like \"#rrggbb\"", key))
+}
+
+/// A style is either a hex color or a table `{ fg, bg, bold, italic }`;
+/// keys missing from the table keep the base theme's value
+fn parse_style(key: &str, value: &toml::Value, base: Style) -> Result<Style, String> {
+    let table = match value {
+        toml::Value::String(_) => return Ok(Style::new(parse_color(key, value)?)),
+        toml::Value::Table(table) => table,
+        _ => return Err(format!("`{}` must be a hex color or a table", key)),
+    };
+
+    let mut style = base;
+    for (attr, v) in table {
+        let field = format!("{}.{}", key, attr);
+        match attr.as_str() {
+            "fg" => style.fg = parse_color(&field, v)?,
+            "bg" => style.bg = Some(parse_color(&field, v)?),
+            "bold" => style.bold = parse_bool(&field, v)?,
+            "italic" => style.italic = parse_bool(&field, v)?,
+            _ => return Err(format!("Unknown style attribute: {}", field)),
+        }
+    }
+    Ok(style)
+}
+
+fn parse_bool(key: &str, value: &toml::Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("`{}` must be true or false", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_inherit_from_base() {
+        let theme = parse_theme("mine", "base = \"nord\"\nbackground = \"#101010\"").unwrap();
+        let nord = Theme::nord();
+
+        assert_eq!(theme.name, "mine");
+        assert_eq!(theme.background, Color::rgb(16, 16, 16));
+        assert_eq!(theme.foreground, nord.foreground);
+        assert_eq!(theme.syntax_keyword, nord.syntax_keyword);
+    }
+
+    #[test]
+    fn styles_accept_colors_and_tables() {
+        let theme = parse_theme(
+            "mine",
+            "syntax_string = \"#00ff00\"\nsyntax_keyword = { fg = \"#ff0000\", italic = true }",
+        )
+        .unwrap();
+
+        assert_eq!(theme.syntax_string, Style::new(Color::GREEN));
+        assert_eq!(theme.syntax_keyword.fg, Color::RED);
+        assert!(theme.syntax_keyword.italic);
+        // Unset attributes keep the base style (gruvbox keywords are bold)
+        assert!(theme.syntax_keyword.bold);
+    }
+
+    #[test]
+    fn invalid_values_are_reported() {
+        assert!(parse_theme("t", "background = \"red\"").is_err());
+        assert!(parse_theme("t", "backgrond = \"#000000\"").is_err());
+        assert!(parse_theme("t", "base = \"nope\"").is_err());
+        assert!(parse_theme("t", "not toml").is_err());
+    }
+}
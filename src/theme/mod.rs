@@ -1,8 +1,15 @@
 mod colors;
+mod loader;
 mod theme;
 
+use std::sync::RwLock;
+
 pub use colors::Color;
-pub use theme::Theme;
+pub use loader::{list_user_themes, load_user_theme};
+pub use theme::{Style, Theme};
+
+/// Themes defined from scripts with `lark::theme::define`
+static SCRIPT_THEMES: RwLock<Vec<Theme>> = RwLock::new(Vec::new());
 
 /// Built-in themes
 pub fn default_theme() -> Theme {
@@ -19,7 +26,48 @@ pub fn list_builtin_themes() -> Vec<&'static str> {
     ]
 }
 
-pub fn get_builtin_theme(name: &str) -> Option<Theme> {
+/// Register a script-defined theme, replacing any earlier one with the same name
+pub fn register_theme(theme: Theme) {
+    if let Ok(mut themes) = SCRIPT_THEMES.write() {
+        themes.retain(|t| t.name != theme.name);
+        themes.push(theme);
+    }
+}
+
+fn script_theme(name: &str) -> Option<Theme> {
+    let themes = SCRIPT_THEMES.read().ok()?;
+    themes.iter().find(|t| t.name == name).cloned()
+}
+
+/// All available theme names: builtins, then script-defined, then user theme files
+pub fn list_themes() -> Vec<String> {
+    let mut themes: Vec<String> = list_builtin_themes()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let script_themes: Vec<String> = SCRIPT_THEMES
+        .read()
+        .map(|t| t.iter().map(|t| t.name.clone()).collect())
+        .unwrap_or_default();
+    for name in script_themes.into_iter().chain(list_user_themes()) {
+        if !themes.contains(&name) {
+            themes.push(name);
+        }
+    }
+    themes
+}
+
+/// Look up a theme by name: builtins, then script-defined themes, then theme files
+pub fn find_theme(name: &str) -> Option<Theme> {
+    base_theme(name).or_else(|| load_user_theme(name).ok())
+}
+
+/// Themes other definitions can inherit from (builtin or script-defined)
+pub fn base_theme(name: &str) -> Option<Theme> {
+    builtin_theme(name).or_else(|| script_theme(name))
+}
+
+fn builtin_theme(name: &str) -> Option<Theme> {
     match name {
         "gruvbox-dark" => Some(Theme::gruvbox_dark()),
         "gruvbox-light" => Some(Theme::gruvbox_light()),
@@ -46,6 +46,10 @@ pub struct Theme {
     pub foreground: Color,
     pub cursor: Color,
     pub selection: Color,
+    pub cursor_line_bg: Color, // Background of the cursor's line, when enabled
+    pub color_column: Color,   // Background of the ruler column, when enabled
+    pub whitespace: Color,     // Tab and trailing space marks in list mode
+    pub match_paren: Color,    // Background of the bracket at the cursor and its match
 
     // UI elements
     pub line_number: Color,
@@ -77,6 +81,11 @@ pub struct Theme {
     pub syntax_variable: Style,
     pub syntax_operator: Style,
     pub syntax_punctuation: Style,
+    pub syntax_property: Style,
+    pub syntax_constant: Style,
+    pub syntax_namespace: Style,
+    pub syntax_parameter: Style,
+    pub syntax_label: Style,
 
     // Diagnostics
     pub error: Color,
@@ -86,6 +95,60 @@ pub struct Theme {
 }
 
 impl Theme {
+    /// Mutable access to a color field by name (e.g. "background")
+    pub fn color_mut(&mut self, key: &str) -> Option<&mut Color> {
+        Some(match key {
+            "background" => &mut self.background,
+            "foreground" => &mut self.foreground,
+            "cursor" => &mut self.cursor,
+            "selection" => &mut self.selection,
+            "cursor_line_bg" => &mut self.cursor_line_bg,
+            "color_column" => &mut self.color_column,
+            "whitespace" => &mut self.whitespace,
+            "match_paren" => &mut self.match_paren,
+            "line_number" => &mut self.line_number,
+            "line_number_active" => &mut self.line_number_active,
+            "status_bar_bg" => &mut self.status_bar_bg,
+            "status_bar_fg" => &mut self.status_bar_fg,
+            "tab_bar_bg" => &mut self.tab_bar_bg,
+            "tab_bar_fg" => &mut self.tab_bar_fg,
+            "tab_active_bg" => &mut self.tab_active_bg,
+            "tab_active_fg" => &mut self.tab_active_fg,
+            "file_browser_bg" => &mut self.file_browser_bg,
+            "file_browser_dir" => &mut self.file_browser_dir,
+            "file_browser_file" => &mut self.file_browser_file,
+            "file_browser_selected" => &mut self.file_browser_selected,
+            "pane_border" => &mut self.pane_border,
+            "pane_border_active" => &mut self.pane_border_active,
+            "error" => &mut self.error,
+            "warning" => &mut self.warning,
+            "info" => &mut self.info,
+            "hint" => &mut self.hint,
+            _ => return None,
+        })
+    }
+
+    /// Mutable access to a syntax style by name; the `syntax_` prefix is optional
+    pub fn style_mut(&mut self, key: &str) -> Option<&mut Style> {
+        Some(match key.strip_prefix("syntax_").unwrap_or(key) {
+            "keyword" => &mut self.syntax_keyword,
+            "string" => &mut self.syntax_string,
+            "number" => &mut self.syntax_number,
+            "comment" => &mut self.syntax_comment,
+            "function" => &mut self.syntax_function,
+            "type" => &mut self.syntax_type,
+            "variable" => &mut self.syntax_variable,
+            "operator" => &mut self.syntax_operator,
+            "punctuation" => &mut self.syntax_punctuation,
+            "property" => &mut self.syntax_property,
+            "constant" => &mut self.syntax_constant,
+            "namespace" => &mut self.syntax_namespace,
+            "parameter" => &mut self.syntax_parameter,
+            "label" => &mut self.syntax_label,
+            _ => return None,
+        })
+    }
+
     /// Gruvbox Dark - warm retro theme
     pub fn gruvbox_dark() -> Self {
         Self {
@@ -94,6 +157,10 @@ impl Theme {
             foreground: Color::from_hex("#ebdbb2").unwrap(),
             cursor: Color::from_hex("#fe8019").unwrap(),
             selection: Color::from_hex("#504945").unwrap(),
+            cursor_line_bg: Color::from_hex("#3c3836").unwrap(),
+            color_column: Color::from_hex("#32302f").unwrap(),
+            whitespace: Color::from_hex("#665c54").unwrap(),
+            match_paren: Color::from_hex("#665c54").unwrap(),
 
             line_number: Color::from_hex("#665c54").unwrap(),
             line_number_active: Color::from_hex("#fabd2f").unwrap(),
@@ -121,6 +188,11 @@ impl Theme {
             syntax_variable: Color::from_hex("#ebdbb2").unwrap().into(),
             syntax_operator: Color::from_hex("#fe8019").unwrap().into(),
             syntax_punctuation: Color::from_hex("#ebdbb2").unwrap().into(),
+            syntax_property: Color::from_hex("#8ec07c").unwrap().into(),
+            syntax_constant: Style::new(Color::from_hex("#d3869b").unwrap()),
+            syntax_namespace: Color::from_hex("#83a598").unwrap().into(),
+            syntax_parameter: Style::new(Color::from_hex("#ebdbb2").unwrap()).italic(),
+            syntax_label: Style::new(Color::from_hex("#fe8019").unwrap()).italic(),
 
             error: Color::from_hex("#fb4934").unwrap(),
             warning: Color::from_hex("#fabd2f").unwrap(),
@@ -137,6 +209,10 @@ impl Theme {
             foreground: Color::from_hex("#3c3836").unwrap(),
             cursor: Color::from_hex("#d65d0e").unwrap(),
             selection: Color::from_hex("#ebdbb2").unwrap(),
+            cursor_line_bg: Color::from_hex("#f2e5bc").unwrap(),
+            color_column: Color::from_hex("#f5e9c3").unwrap(),
+            whitespace: Color::from_hex("#a89984").unwrap(),
+            match_paren: Color::from_hex("#d5c4a1").unwrap(),
 
             line_number: Color::from_hex("#a89984").unwrap(),
             line_number_active: Color::from_hex("#b57614").unwrap(),
@@ -164,6 +240,11 @@ impl Theme {
             syntax_variable: Color::from_hex("#3c3836").unwrap().into(),
             syntax_operator: Color::from_hex("#d65d0e").unwrap().into(),
             syntax_punctuation: Color::from_hex("#3c3836").unwrap().into(),
+            syntax_property: Color::from_hex("#427b58").unwrap().into(),
+            syntax_constant: Style::new(Color::from_hex("#8f3f71").unwrap()),
+            syntax_namespace: Color::from_hex("#076678").unwrap().into(),
+            syntax_parameter: Style::new(Color::from_hex("#3c3836").unwrap()).italic(),
+            syntax_label: Style::new(Color::from_hex("#af3a03").unwrap()).italic(),
 
             error: Color::from_hex("#9d0006").unwrap(),
             warning: Color::from_hex("#b57614").unwrap(),
@@ -180,6 +261,10 @@ impl Theme {
             foreground: Color::from_hex("#d8dee9").unwrap(),
             cursor: Color::from_hex("#88c0d0").unwrap(),
             selection: Color::from_hex("#434c5e").unwrap(),
+            cursor_line_bg: Color::from_hex("#3b4252").unwrap(),
+            color_column: Color::from_hex("#353c4a").unwrap(),
+            whitespace: Color::from_hex("#4c566a").unwrap(),
+            match_paren: Color::from_hex("#434c5e").unwrap(),
 
             line_number: Color::from_hex("#4c566a").unwrap(),
             line_number_active: Color::from_hex("#d8dee9").unwrap(),
@@ -207,6 +292,11 @@ impl Theme {
             syntax_variable: Color::from_hex("#d8dee9").unwrap().into(),
             syntax_operator: Color::from_hex("#81a1c1").unwrap().into(),
             syntax_punctuation: Color::from_hex("#eceff4").unwrap().into(),
+            syntax_property: Color::from_hex("#8fbcbb").unwrap().into(),
+            syntax_constant: Style::new(Color::from_hex("#b48ead").unwrap()),
+            syntax_namespace: Color::from_hex("#8fbcbb").unwrap().into(),
+            syntax_parameter: Style::new(Color::from_hex("#d8dee9").unwrap()).italic(),
+            syntax_label: Style::new(Color::from_hex("#d08770").unwrap()).italic(),
 
             error: Color::from_hex("#bf616a").unwrap(),
             warning: Color::from_hex("#ebcb8b").unwrap(),
@@ -223,6 +313,10 @@ impl Theme {
             foreground: Color::from_hex("#f8f8f2").unwrap(),
             cursor: Color::from_hex("#f8f8f2").unwrap(),
             selection: Color::from_hex("#44475a").unwrap(),
+            cursor_line_bg: Color::from_hex("#343746").unwrap(),
+            color_column: Color::from_hex("#2e303e").unwrap(),
+            whitespace: Color::from_hex("#6272a4").unwrap(),
+            match_paren: Color::from_hex("#44475a").unwrap(),
 
             line_number: Color::from_hex("#6272a4").unwrap(),
             line_number_active: Color::from_hex("#f8f8f2").unwrap(),
@@ -250,6 +344,11 @@ impl Theme {
             syntax_variable: Color::from_hex("#f8f8f2").unwrap().into(),
             syntax_operator: Color::from_hex("#ff79c6").unwrap().into(),
             syntax_punctuation: Color::from_hex("#f8f8f2").unwrap().into(),
+            syntax_property: Color::from_hex("#8be9fd").unwrap().into(),
+            syntax_constant: Style::new(Color::from_hex("#bd93f9").unwrap()),
+            syntax_namespace: Color::from_hex("#8be9fd").unwrap().into(),
+            syntax_parameter: Style::new(Color::from_hex("#ffb86c").unwrap()).italic(),
+            syntax_label: Style::new(Color::from_hex("#ff79c6").unwrap()).italic(),
 
             error: Color::from_hex("#ff5555").unwrap(),
             warning: Color::from_hex("#ffb86c").unwrap(),
@@ -266,6 +365,10 @@ impl Theme {
             foreground: Color::from_hex("#839496").unwrap(),
             cursor: Color::from_hex("#268bd2").unwrap(),
             selection: Color::from_hex("#073642").unwrap(),
+            cursor_line_bg: Color::from_hex("#073642").unwrap(),
+            color_column: Color::from_hex("#04313c").unwrap(),
+            whitespace: Color::from_hex("#586e75").unwrap(),
+            match_paren: Color::from_hex("#586e75").unwrap(),
 
             line_number: Color::from_hex("#586e75").unwrap(),
             line_number_active: Color::from_hex("#93a1a1").unwrap(),
@@ -293,6 +396,11 @@ impl Theme {
             syntax_variable: Color::from_hex("#839496").unwrap().into(),
             syntax_operator: Color::from_hex("#859900").unwrap().into(),
             syntax_punctuation: Color::from_hex("#839496").unwrap().into(),
+            syntax_property: Color::from_hex("#268bd2").unwrap().into(),
+            syntax_constant: Style::new(Color::from_hex("#cb4b16").unwrap()),
+            syntax_namespace: Color::from_hex("#b58900").unwrap().into(),
+            syntax_parameter: Style::new(Color::from_hex("#839496").unwrap()).italic(),
+            syntax_label: Style::new(Color::from_hex("#6c71c4").unwrap()).italic(),
 
             error: Color::from_hex("#dc322f").unwrap(),
             warning: Color::from_hex("#cb4b16").unwrap(),